@@ -0,0 +1,271 @@
+//! Batch shortening for the cutl CLI
+//!
+//! Shortens a list of URLs read from a file or stdin, one per line. URLs are
+//! sent to `POST /api/shorten/batch` in chunks; servers without that endpoint
+//! get one `POST /shorten` per URL, a few at a time.
+
+use crate::client::{ApiClient, BatchItemResult, ShortenRequest, ShortenResponse};
+use crate::validation;
+use anyhow::Result;
+use serde::Serialize;
+use std::sync::Arc;
+use tokio::{sync::Semaphore, task::JoinSet};
+
+/// URLs sent per batch request, well under the server's default `MAX_BATCH_SIZE`
+const BATCH_CHUNK_SIZE: usize = 100;
+
+/// Requests in flight at once when falling back to single shorten calls
+const SEQUENTIAL_CONCURRENCY: usize = 4;
+
+/// A URL to shorten and the input line it came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputLine {
+    /// 1-based line number in the input
+    pub line: usize,
+    pub url: String,
+}
+
+/// The result of shortening one input line
+#[derive(Debug, Serialize)]
+pub struct LineOutcome {
+    pub line: usize,
+    pub url: String,
+    #[serde(flatten)]
+    pub result: LineResult,
+}
+
+/// Either the created link or why it failed
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum LineResult {
+    Created(ShortenResponse),
+    Failed { error: String },
+}
+
+/// Splits input into URLs, one per line, skipping blank lines and `#` comments
+pub fn parse_lines(input: &str) -> Vec<InputLine> {
+    input
+        .lines()
+        .enumerate()
+        .filter_map(|(i, line)| {
+            let url = line.trim();
+            (!url.is_empty() && !url.starts_with('#')).then(|| InputLine {
+                line: i + 1,
+                url: url.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Shortens every line with the same TTL, returning one outcome per line in input order
+///
+/// Invalid URLs are reported without contacting the server. A failure on one
+/// line never stops the others.
+///
+/// # Errors
+/// Returns an error only if a batch request fails as a whole (e.g. the server
+/// is unreachable or rejects the token).
+pub async fn shorten_all(
+    client: &ApiClient,
+    lines: Vec<InputLine>,
+    ttl: Option<String>,
+) -> Result<Vec<LineOutcome>> {
+    let mut outcomes = Vec::with_capacity(lines.len());
+    let mut pending = Vec::new();
+
+    for line in lines {
+        match validation::validate_url(&line.url) {
+            Ok(()) => pending.push(line),
+            Err(e) => outcomes.push(failed(line, e.to_string())),
+        }
+    }
+
+    let mut batch_supported = true;
+    let mut chunks = pending.chunks(BATCH_CHUNK_SIZE);
+
+    for chunk in chunks.by_ref() {
+        let requests = chunk.iter().map(|l| request(l, &ttl)).collect();
+
+        match client.shorten_batch(requests).await? {
+            Some(results) => outcomes.extend(merge_batch_results(chunk, results)),
+            None => {
+                // Older server without the batch endpoint: redo this chunk one by one
+                outcomes.extend(shorten_each(client, chunk.to_vec(), &ttl).await);
+                batch_supported = false;
+                break;
+            }
+        }
+    }
+
+    if !batch_supported {
+        let rest: Vec<InputLine> = chunks.flatten().cloned().collect();
+        outcomes.extend(shorten_each(client, rest, &ttl).await);
+    }
+
+    outcomes.sort_by_key(|o| o.line);
+    Ok(outcomes)
+}
+
+/// Shortens lines with single requests, at most `SEQUENTIAL_CONCURRENCY` at a time
+async fn shorten_each(
+    client: &ApiClient,
+    lines: Vec<InputLine>,
+    ttl: &Option<String>,
+) -> Vec<LineOutcome> {
+    let permits = Arc::new(Semaphore::new(SEQUENTIAL_CONCURRENCY));
+    let mut tasks = JoinSet::new();
+
+    for line in lines {
+        let client = client.clone();
+        let permits = permits.clone();
+        let request = request(&line, ttl);
+
+        tasks.spawn(async move {
+            let _permit = permits.acquire_owned().await;
+            match client.shorten(request).await {
+                Ok(response) => LineOutcome {
+                    line: line.line,
+                    url: line.url,
+                    result: LineResult::Created(response),
+                },
+                Err(e) => failed(line, e.to_string()),
+            }
+        });
+    }
+
+    let mut outcomes = Vec::new();
+    while let Some(outcome) = tasks.join_next().await {
+        match outcome {
+            Ok(outcome) => outcomes.push(outcome),
+            Err(e) => std::panic::resume_unwind(e.into_panic()),
+        }
+    }
+    outcomes
+}
+
+/// Pairs each line of a chunk with its entry in the batch response
+fn merge_batch_results(chunk: &[InputLine], results: Vec<BatchItemResult>) -> Vec<LineOutcome> {
+    let mut results = results.into_iter();
+
+    chunk
+        .iter()
+        .map(|line| match results.next() {
+            Some(BatchItemResult::Created(response)) => LineOutcome {
+                line: line.line,
+                url: line.url.clone(),
+                result: LineResult::Created(response),
+            },
+            Some(BatchItemResult::Failed { error }) => failed(line.clone(), error),
+            None => failed(line.clone(), "Missing from server response".to_string()),
+        })
+        .collect()
+}
+
+/// Returns the outcomes that failed
+pub fn failures(outcomes: &[LineOutcome]) -> Vec<&LineOutcome> {
+    outcomes
+        .iter()
+        .filter(|o| matches!(o.result, LineResult::Failed { .. }))
+        .collect()
+}
+
+/// Builds the shorten request for one line
+fn request(line: &InputLine, ttl: &Option<String>) -> ShortenRequest {
+    ShortenRequest {
+        url: line.url.clone(),
+        code: None,
+        ttl: ttl.clone(),
+    }
+}
+
+/// Records a failed line
+fn failed(line: InputLine, error: String) -> LineOutcome {
+    LineOutcome {
+        line: line.line,
+        url: line.url,
+        result: LineResult::Failed { error },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(line: usize, url: &str) -> InputLine {
+        InputLine {
+            line,
+            url: url.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_lines() {
+        let input =
+            "https://example.com\n\n  https://example.org  \n# comment\r\nhttps://example.net\r\n";
+        assert_eq!(
+            parse_lines(input),
+            vec![
+                line(1, "https://example.com"),
+                line(3, "https://example.org"),
+                line(5, "https://example.net"),
+            ]
+        );
+        assert!(parse_lines("\n  \n").is_empty());
+    }
+
+    #[test]
+    fn test_merge_batch_results() {
+        let chunk = vec![
+            line(1, "https://example.com"),
+            line(2, "https://example.org"),
+            line(4, "https://example.net"),
+        ];
+        let results = vec![
+            BatchItemResult::Created(ShortenResponse {
+                code: "abc".to_string(),
+                short_url: "https://cutl.my.id/abc".to_string(),
+                expires_at: 1760000000,
+            }),
+            BatchItemResult::Failed {
+                error: "Code already exists".to_string(),
+            },
+        ];
+
+        let outcomes = merge_batch_results(&chunk, results);
+        assert_eq!(outcomes.len(), 3);
+        assert!(matches!(outcomes[0].result, LineResult::Created(ref r) if r.code == "abc"));
+
+        // Every failure is kept with its line, including items the server left out
+        let failed = failures(&outcomes);
+        assert_eq!(failed.len(), 2);
+        assert_eq!(failed[0].line, 2);
+        assert_eq!(failed[1].line, 4);
+        assert!(
+            matches!(failed[1].result, LineResult::Failed { ref error } if error.contains("Missing"))
+        );
+    }
+
+    #[test]
+    fn test_line_outcome_serialize() {
+        let outcome = failed(line(3, "ftp://x"), "Invalid URL".to_string());
+        let json = serde_json::to_value(&outcome).unwrap();
+        assert_eq!(json["line"], 3);
+        assert_eq!(json["url"], "ftp://x");
+        assert_eq!(json["error"], "Invalid URL");
+    }
+
+    #[tokio::test]
+    async fn test_shorten_all_reports_invalid_urls_locally() {
+        // Nothing is listening here, so any request would fail the whole run
+        let client = ApiClient::new(
+            "http://127.0.0.1:9".to_string(),
+            None,
+            crate::client::ClientOptions::default(),
+        )
+        .unwrap();
+        let lines = vec![line(1, "not a url"), line(2, "https://localhost/")];
+
+        let outcomes = shorten_all(&client, lines, None).await.unwrap();
+        assert_eq!(failures(&outcomes).len(), 2);
+    }
+}
@@ -3,9 +3,42 @@
 //! Handles communication with the cutl server API.
 
 use anyhow::{Context, Result};
-use reqwest::Client;
+use reqwest::{header::HeaderMap, Client, RequestBuilder, Response, StatusCode};
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+/// Default request timeout in seconds
+pub const DEFAULT_TIMEOUT_SECS: u64 = 30;
+
+/// Default number of retries after a transient failure
+pub const DEFAULT_RETRIES: u32 = 2;
+
+/// Delay before the first retry; each further retry waits twice as long
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+
+/// Longest wait between retries, including a server's `Retry-After`
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Timeout and retry settings for `ApiClient`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientOptions {
+    /// Timeout for each individual request
+    pub timeout: Duration,
+    /// Retries after transient failures; see `Retry` for which ones
+    pub retries: u32,
+}
+
+impl Default for ClientOptions {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(DEFAULT_TIMEOUT_SECS),
+            retries: DEFAULT_RETRIES,
+        }
+    }
+}
 
 /// API request to shorten a URL
 #[derive(Serialize)]
@@ -16,24 +49,130 @@ pub struct ShortenRequest {
 }
 
 /// API response from the server
-#[derive(Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct ShortenResponse {
     pub code: String,
     pub short_url: String,
     pub expires_at: i64,
 }
 
+/// Request body for `POST /api/shorten/batch`
+#[derive(Serialize)]
+struct BatchShortenRequest {
+    links: Vec<ShortenRequest>,
+}
+
+/// Per-item outcome from `POST /api/shorten/batch`
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum BatchItemResult {
+    Created(ShortenResponse),
+    Failed { error: String },
+}
+
+/// Response body from `POST /api/shorten/batch`
+#[derive(Deserialize)]
+struct BatchShortenResponse {
+    results: Vec<BatchItemResult>,
+}
+
+/// Link metadata, as returned by `GET /api/info/{code}`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LinkInfoResponse {
+    pub code: String,
+    pub original_url: String,
+    pub created_at: i64,
+    pub expires_at: i64,
+    /// Whether the link is past its expiration time
+    #[serde(default)]
+    pub expired: bool,
+}
+
+/// Analytics for a short link, as returned by `GET /analytics/{code}`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AnalyticsResponse {
+    pub code: String,
+    pub original_url: String,
+    pub created_at: i64,
+    pub expires_at: i64,
+    pub total_visits: i64,
+    /// Missing from servers that predate unique visitor counts
+    #[serde(default)]
+    pub unique_visitors: i64,
+    pub countries: Vec<CountStat>,
+    pub referers: Vec<CountStat>,
+    pub daily: Vec<DailyStat>,
+    pub recent_visits: Vec<VisitRow>,
+}
+
+/// A visit count grouped by a value (country or referer)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CountStat {
+    pub value: Option<String>,
+    pub count: i64,
+}
+
+/// Visit count for one day ("YYYY-MM-DD")
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DailyStat {
+    pub date: String,
+    pub count: i64,
+}
+
+/// A single recorded visit
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VisitRow {
+    pub visited_at: i64,
+    pub ip: Option<String>,
+    pub country: Option<String>,
+    pub city: Option<String>,
+    pub user_agent: Option<String>,
+    pub referer: Option<String>,
+}
+
 /// API error response
 #[derive(Deserialize)]
 pub struct ErrorResponse {
     pub error: String,
 }
 
+/// A request the server answered with an error status
+///
+/// Carried inside `anyhow::Error` so callers can recover the real status with
+/// `downcast_ref` instead of parsing the message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub message: String,
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+/// Which failures `ApiClient::send` may retry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Retry {
+    /// Any transient failure: connection errors, timeouts, 5xx, and 429.
+    /// Only for requests that are safe to repeat.
+    Transient,
+    /// Only failures where the server can't have acted on the request: a
+    /// connection that never opened, or a 429 or 503 with `Retry-After`.
+    /// For requests such as `POST /shorten` that would create a second link.
+    Refused,
+}
+
 /// HTTP client for the cutl API
+#[derive(Clone)]
 pub struct ApiClient {
     client: Client,
     server_url: String,
     auth_token: Option<String>,
+    retries: u32,
 }
 
 impl ApiClient {
@@ -42,16 +181,60 @@ impl ApiClient {
     /// # Arguments
     /// * `server_url` - Base URL of the cutl server
     /// * `auth_token` - Optional bearer token for authentication
-    pub fn new(server_url: String, auth_token: Option<String>) -> Result<Self> {
-        let client = Client::builder().timeout(Duration::from_secs(30)).build()?;
+    /// * `options` - Request timeout and retry count
+    pub fn new(
+        server_url: String,
+        auth_token: Option<String>,
+        options: ClientOptions,
+    ) -> Result<Self> {
+        let client = Client::builder().timeout(options.timeout).build()?;
 
         Ok(Self {
             client,
             server_url,
             auth_token,
+            retries: options.retries,
         })
     }
 
+    /// Sends a request with the auth token, retrying failures `retry` allows
+    ///
+    /// Retries happen up to `retries` times with exponential backoff, honoring
+    /// `Retry-After`. Other responses, including 4xx errors, are returned as-is.
+    async fn send(&self, retry: Retry, build: impl Fn() -> RequestBuilder) -> Result<Response> {
+        let mut attempt = 0;
+
+        loop {
+            let mut req_builder = build();
+
+            // Add auth token if available
+            if let Some(ref token) = self.auth_token {
+                req_builder = req_builder.bearer_auth(token);
+            }
+
+            let delay = match req_builder.send().await {
+                Ok(response) if attempt < self.retries && retryable(retry, &response) => {
+                    match retry_after(response.headers()) {
+                        Some(delay) if delay > MAX_RETRY_DELAY => return Ok(response),
+                        Some(delay) => delay,
+                        None => backoff_delay(attempt),
+                    }
+                }
+                Ok(response) => return Ok(response),
+                Err(e)
+                    if attempt < self.retries
+                        && (e.is_connect() || (retry == Retry::Transient && e.is_timeout())) =>
+                {
+                    backoff_delay(attempt)
+                }
+                Err(e) => return Err(e).context("Failed to connect to server"),
+            };
+
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
     /// Sends a request to shorten a URL
     ///
     /// # Arguments
@@ -62,17 +245,97 @@ impl ApiClient {
     pub async fn shorten(&self, request: ShortenRequest) -> Result<ShortenResponse> {
         let api_url = format!("{}/shorten", self.server_url.trim_end_matches('/'));
 
-        let mut req_builder = self.client.post(&api_url).json(&request);
+        let response = self
+            .send(Retry::Refused, || self.client.post(&api_url).json(&request))
+            .await?;
 
-        // Add auth token if available
-        if let Some(ref token) = self.auth_token {
-            req_builder = req_builder.bearer_auth(token);
+        let status = response.status();
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            return Err(ApiError {
+                status,
+                message: rate_limit_message(retry_after(response.headers())),
+            }
+            .into());
         }
 
-        let response = req_builder
-            .send()
-            .await
-            .context("Failed to connect to server")?;
+        let response_text = response.text().await?;
+
+        if status.is_success() {
+            serde_json::from_str(&response_text).context("Failed to parse server response")
+        } else {
+            Err(server_error(status, &response_text).into())
+        }
+    }
+
+    /// Shortens several URLs with one `POST /api/shorten/batch` request
+    ///
+    /// # Returns
+    /// One result per request, in order, or `None` if the server has no batch endpoint
+    pub async fn shorten_batch(
+        &self,
+        requests: Vec<ShortenRequest>,
+    ) -> Result<Option<Vec<BatchItemResult>>> {
+        let api_url = format!(
+            "{}/api/shorten/batch",
+            self.server_url.trim_end_matches('/')
+        );
+        let body = BatchShortenRequest { links: requests };
+
+        let response = self
+            .send(Retry::Refused, || self.client.post(&api_url).json(&body))
+            .await?;
+
+        let status = response.status();
+        let response_text = response.text().await?;
+
+        if status == StatusCode::NOT_FOUND || status == StatusCode::METHOD_NOT_ALLOWED {
+            return Ok(None);
+        }
+
+        if status.is_success() {
+            let response: BatchShortenResponse =
+                serde_json::from_str(&response_text).context("Failed to parse server response")?;
+            Ok(Some(response.results))
+        } else {
+            Err(server_error(status, &response_text).into())
+        }
+    }
+
+    /// Deletes a short link
+    ///
+    /// # Arguments
+    /// * `code` - The short code to delete
+    pub async fn delete(&self, code: &str) -> Result<()> {
+        let api_url = self.link_url(code);
+
+        let response = self
+            .send(Retry::Transient, || self.client.delete(&api_url))
+            .await?;
+
+        let status = response.status();
+        let response_text = response.text().await?;
+
+        if status.is_success() {
+            Ok(())
+        } else {
+            Err(link_error(code, status, &response_text).into())
+        }
+    }
+
+    /// Fetches visit analytics for a short link
+    ///
+    /// # Arguments
+    /// * `code` - The short code to fetch analytics for
+    pub async fn analytics(&self, code: &str) -> Result<AnalyticsResponse> {
+        let api_url = format!(
+            "{}/analytics/{}",
+            self.server_url.trim_end_matches('/'),
+            code
+        );
+
+        let response = self
+            .send(Retry::Transient, || self.client.get(&api_url))
+            .await?;
 
         let status = response.status();
         let response_text = response.text().await?;
@@ -80,20 +343,187 @@ impl ApiClient {
         if status.is_success() {
             serde_json::from_str(&response_text).context("Failed to parse server response")
         } else {
-            let error_msg = if let Ok(err) = serde_json::from_str::<ErrorResponse>(&response_text) {
-                err.error
-            } else {
-                format!("Server returned HTTP {}", status.as_u16())
-            };
+            Err(link_error(code, status, &response_text).into())
+        }
+    }
+
+    /// Looks up where a short link points without following it
+    ///
+    /// # Arguments
+    /// * `code` - The short code to look up
+    pub async fn info(&self, code: &str) -> Result<LinkInfoResponse> {
+        let api_url = format!(
+            "{}/api/info/{}",
+            self.server_url.trim_end_matches('/'),
+            code
+        );
+
+        let response = self
+            .send(Retry::Transient, || self.client.get(&api_url))
+            .await?;
+
+        let status = response.status();
+        let response_text = response.text().await?;
+
+        if status.is_success() {
+            serde_json::from_str(&response_text).context("Failed to parse server response")
+        } else {
+            Err(link_error(code, status, &response_text).into())
+        }
+    }
+
+    /// Checks that the server and its database are up with `GET /healthz`
+    ///
+    /// # Returns
+    /// How long the server took to answer, including any retries
+    pub async fn health(&self) -> Result<Duration> {
+        let api_url = self.health_url();
+
+        let started = Instant::now();
+        let response = self
+            .send(Retry::Transient, || self.client.get(&api_url))
+            .await?;
+        let latency = started.elapsed();
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(latency);
+        }
+
+        // Servers without the endpoint treat "healthz" as a short code
+        if status == StatusCode::NOT_FOUND {
+            return Err(ApiError {
+                status,
+                message: "Server has no /healthz endpoint (404)".to_string(),
+            }
+            .into());
+        }
+
+        let response_text = response.text().await?;
+        Err(server_error(status, &response_text).into())
+    }
+
+    /// Builds the URL of the server's health check
+    fn health_url(&self) -> String {
+        format!("{}/healthz", self.server_url.trim_end_matches('/'))
+    }
+
+    /// Builds the URL of a short link on the server
+    fn link_url(&self, code: &str) -> String {
+        format!("{}/{}", self.server_url.trim_end_matches('/'), code)
+    }
+}
+
+/// Whether a response status is worth retrying: rate limiting and server errors
+///
+/// Client errors such as 400, 401, 404, and 409 won't change on retry.
+pub fn should_retry(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS
+        || status == StatusCode::REQUEST_TIMEOUT
+        || (status.is_server_error() && status != StatusCode::NOT_IMPLEMENTED)
+}
 
-            anyhow::bail!("{}", error_msg);
+/// Whether `response` may be retried under `retry`
+fn retryable(retry: Retry, response: &Response) -> bool {
+    match retry {
+        Retry::Transient => should_retry(response.status()),
+        Retry::Refused => {
+            matches!(
+                response.status(),
+                StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE
+            ) && retry_after(response.headers()).is_some()
         }
     }
 }
 
+/// Delay before retry number `attempt` (0-based): 0.5s, 1s, 2s, ... capped at 30s
+pub fn backoff_delay(attempt: u32) -> Duration {
+    RETRY_BASE_DELAY
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(MAX_RETRY_DELAY)
+}
+
+/// Reads a `Retry-After` header given in seconds
+///
+/// The HTTP-date form isn't used by the cutl server and is ignored.
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    headers
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+/// Describes a 429 response, including how long to wait when the server says
+pub fn rate_limit_message(retry_after: Option<Duration>) -> String {
+    match retry_after {
+        Some(delay) => format!("Rate limited, retry in {}s", delay.as_secs()),
+        None => "Rate limited, try again later".to_string(),
+    }
+}
+
+/// Builds the error for a failed response, using the server's message when it sent one
+fn server_error(status: StatusCode, response_text: &str) -> ApiError {
+    let message = match serde_json::from_str::<ErrorResponse>(response_text) {
+        Ok(err) => err.error,
+        Err(_) => format!("Server returned HTTP {}", status.as_u16()),
+    };
+
+    ApiError { status, message }
+}
+
+/// Builds the error for a failed response about an existing short link
+fn link_error(code: &str, status: StatusCode, response_text: &str) -> ApiError {
+    match status {
+        StatusCode::UNAUTHORIZED => ApiError {
+            status,
+            message: "Unauthorized (401)".to_string(),
+        },
+        StatusCode::NOT_FOUND => ApiError {
+            status,
+            message: format!("Short link '{}' not found (404)", code),
+        },
+        _ => server_error(status, response_text),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Serves one canned HTTP response on a local port
+    ///
+    /// Returns the server URL and a handle that yields the raw request head.
+    async fn serve_once(
+        status: &'static str,
+        body: &'static str,
+    ) -> (String, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        let handle = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0u8; 4096];
+            let n = socket.read(&mut buf).await.unwrap();
+
+            let response = format!(
+                "HTTP/1.1 {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+
+            String::from_utf8_lossy(&buf[..n]).into_owned()
+        });
+
+        (url, handle)
+    }
 
     #[test]
     fn test_shorten_request_serialization() {
@@ -125,7 +555,11 @@ mod tests {
 
     #[test]
     fn test_api_client_new() {
-        let client = ApiClient::new("http://localhost:3000".to_string(), None);
+        let client = ApiClient::new(
+            "http://localhost:3000".to_string(),
+            None,
+            ClientOptions::default(),
+        );
         assert!(client.is_ok());
         let client = client.unwrap();
         assert_eq!(client.server_url, "http://localhost:3000");
@@ -137,6 +571,7 @@ mod tests {
         let client = ApiClient::new(
             "http://localhost:3000".to_string(),
             Some("secret-token".to_string()),
+            ClientOptions::default(),
         );
         assert!(client.is_ok());
         let client = client.unwrap();
@@ -145,7 +580,12 @@ mod tests {
 
     #[test]
     fn test_api_client_trims_trailing_slash() {
-        let client = ApiClient::new("http://localhost:3000/".to_string(), None).unwrap();
+        let client = ApiClient::new(
+            "http://localhost:3000/".to_string(),
+            None,
+            ClientOptions::default(),
+        )
+        .unwrap();
         assert_eq!(client.server_url, "http://localhost:3000/");
     }
 
@@ -164,4 +604,417 @@ mod tests {
         assert_eq!(response.short_url, "http://localhost:3000/abc123");
         assert_eq!(response.expires_at, 1234567890);
     }
+
+    #[test]
+    fn test_link_url() {
+        let client = ApiClient::new(
+            "http://localhost:3000/".to_string(),
+            None,
+            ClientOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(client.link_url("abc123"), "http://localhost:3000/abc123");
+
+        let client = ApiClient::new(
+            "https://cutl.my.id".to_string(),
+            None,
+            ClientOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(client.link_url("docs"), "https://cutl.my.id/docs");
+    }
+
+    #[test]
+    fn test_health_url() {
+        let client = ApiClient::new(
+            "https://cutl.my.id/".to_string(),
+            None,
+            ClientOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(client.health_url(), "https://cutl.my.id/healthz");
+    }
+
+    #[tokio::test]
+    async fn test_health_ok() {
+        let (url, request) = serve_once("200 OK", r#"{"status":"ok"}"#).await;
+
+        let client = ApiClient::new(url, None, ClientOptions::default()).unwrap();
+        let latency = client.health().await.unwrap();
+        assert!(latency < Duration::from_secs(DEFAULT_TIMEOUT_SECS));
+
+        let request = request.await.unwrap();
+        assert!(request.starts_with("GET /healthz HTTP/1.1"));
+    }
+
+    #[tokio::test]
+    async fn test_health_failures() {
+        let options = ClientOptions {
+            retries: 0,
+            ..ClientOptions::default()
+        };
+
+        let (url, _) = serve_once(
+            "503 Service Unavailable",
+            r#"{"error":"Database unavailable: pool closed","code":"database_unavailable"}"#,
+        )
+        .await;
+        let client = ApiClient::new(url, None, options).unwrap();
+        let err = client.health().await.unwrap_err();
+        let err = err.downcast_ref::<ApiError>().unwrap();
+        assert_eq!(err.status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(err.message, "Database unavailable: pool closed");
+
+        let (url, _) = serve_once("404 Not Found", r#"{"error":"Short link not found"}"#).await;
+        let client = ApiClient::new(url, None, options).unwrap();
+        let err = client.health().await.unwrap_err();
+        assert_eq!(err.to_string(), "Server has no /healthz endpoint (404)");
+
+        // Nothing listening at all
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+        let client = ApiClient::new(url, None, options).unwrap();
+        let err = client.health().await.unwrap_err();
+        assert_eq!(err.to_string(), "Failed to connect to server");
+    }
+
+    #[test]
+    fn test_link_error() {
+        let body = r#"{"error":"Invalid or missing authorization token"}"#;
+        let err = link_error("abc", StatusCode::UNAUTHORIZED, body);
+        assert_eq!(err.status, StatusCode::UNAUTHORIZED);
+        assert_eq!(err.message, "Unauthorized (401)");
+
+        let err = link_error(
+            "abc",
+            StatusCode::NOT_FOUND,
+            r#"{"error":"Short link not found"}"#,
+        );
+        assert_eq!(err.status, StatusCode::NOT_FOUND);
+        assert_eq!(err.message, "Short link 'abc' not found (404)");
+
+        let err = link_error(
+            "abc",
+            StatusCode::INTERNAL_SERVER_ERROR,
+            r#"{"error":"Database error"}"#,
+        );
+        assert_eq!(err.message, "Database error");
+        assert_eq!(
+            link_error("abc", StatusCode::BAD_GATEWAY, "<html>").to_string(),
+            "Server returned HTTP 502"
+        );
+    }
+
+    #[test]
+    fn test_server_error() {
+        let err = server_error(StatusCode::CONFLICT, r#"{"error":"Code already exists"}"#);
+        assert_eq!(
+            err,
+            ApiError {
+                status: StatusCode::CONFLICT,
+                message: "Code already exists".to_string(),
+            }
+        );
+
+        let err = server_error(StatusCode::SERVICE_UNAVAILABLE, "");
+        assert_eq!(err.status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(err.message, "Server returned HTTP 503");
+    }
+
+    #[test]
+    fn test_analytics_response_deserialization() {
+        let json = r#"{
+            "code": "abc123",
+            "original_url": "https://example.com",
+            "created_at": 1739000000,
+            "expires_at": 1760000000,
+            "total_visits": 42,
+            "countries": [
+                { "value": "ID", "count": 30 },
+                { "value": null, "count": 12 }
+            ],
+            "referers": [
+                { "value": "https://twitter.com/", "count": 15 }
+            ],
+            "daily": [
+                { "date": "2026-02-18", "count": 10 },
+                { "date": "2026-02-17", "count": 32 }
+            ],
+            "recent_visits": [
+                {
+                    "visited_at": 1739900000,
+                    "ip": "1.2.3.4",
+                    "country": "ID",
+                    "city": "Jakarta",
+                    "user_agent": "Mozilla/5.0",
+                    "referer": null
+                }
+            ]
+        }"#;
+
+        let response: AnalyticsResponse = serde_json::from_str(json).unwrap();
+        assert_eq!(response.code, "abc123");
+        assert_eq!(response.total_visits, 42);
+        assert_eq!(response.countries[0].value, Some("ID".to_string()));
+        assert!(response.countries[1].value.is_none());
+        assert_eq!(response.referers[0].count, 15);
+        assert_eq!(response.daily[1].date, "2026-02-17");
+        assert_eq!(response.recent_visits[0].city, Some("Jakarta".to_string()));
+        assert!(response.recent_visits[0].referer.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_info_request() {
+        let body = r#"{"code":"docs","original_url":"https://example.com","created_at":1739000000,"expires_at":1760000000,"expired":false}"#;
+        let (url, request) = serve_once("200 OK", body).await;
+
+        let client = ApiClient::new(
+            format!("{}/", url),
+            Some("secret".to_string()),
+            ClientOptions::default(),
+        )
+        .unwrap();
+        let info = client.info("docs").await.unwrap();
+        assert_eq!(info.original_url, "https://example.com");
+        assert_eq!(info.expires_at, 1760000000);
+        assert!(!info.expired);
+
+        let request = request.await.unwrap().to_lowercase();
+        assert!(request.starts_with("get /api/info/docs http/1.1"));
+        assert!(request.contains("authorization: bearer secret"));
+    }
+
+    #[tokio::test]
+    async fn test_info_not_found() {
+        let (url, _) = serve_once("404 Not Found", r#"{"error":"Short link not found"}"#).await;
+
+        let client = ApiClient::new(url, None, ClientOptions::default()).unwrap();
+        let err = client.info("gone").await.unwrap_err();
+        assert_eq!(err.to_string(), "Short link 'gone' not found (404)");
+    }
+
+    #[tokio::test]
+    async fn test_shorten_batch_request() {
+        let body = r#"{"created":1,"results":[{"code":"abc","short_url":"http://x/abc","expires_at":1760000000},{"index":1,"code":"invalid_url","error":"Invalid URL"}]}"#;
+        let (url, request) = serve_once("200 OK", body).await;
+
+        let client = ApiClient::new(url, None, ClientOptions::default()).unwrap();
+        let results = client
+            .shorten_batch(vec![
+                ShortenRequest {
+                    url: "https://example.com".to_string(),
+                    code: None,
+                    ttl: Some("1h".to_string()),
+                },
+                ShortenRequest {
+                    url: "https://example.org".to_string(),
+                    code: None,
+                    ttl: Some("1h".to_string()),
+                },
+            ])
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert!(matches!(results[0], BatchItemResult::Created(ref r) if r.code == "abc"));
+        assert!(
+            matches!(results[1], BatchItemResult::Failed { ref error } if error == "Invalid URL")
+        );
+
+        let request = request.await.unwrap();
+        assert!(request.starts_with("POST /api/shorten/batch HTTP/1.1"));
+        assert!(request.contains(r#""links":[{"url":"https://example.com""#));
+    }
+
+    #[tokio::test]
+    async fn test_shorten_batch_unsupported() {
+        let (url, _) = serve_once("404 Not Found", "").await;
+
+        let client = ApiClient::new(url, None, ClientOptions::default()).unwrap();
+        let results = client.shorten_batch(Vec::new()).await.unwrap();
+        assert!(results.is_none());
+    }
+
+    #[test]
+    fn test_should_retry() {
+        for status in [429, 408, 500, 502, 503, 504] {
+            assert!(
+                should_retry(StatusCode::from_u16(status).unwrap()),
+                "{}",
+                status
+            );
+        }
+        for status in [200, 400, 401, 403, 404, 409, 410, 501] {
+            assert!(
+                !should_retry(StatusCode::from_u16(status).unwrap()),
+                "{}",
+                status
+            );
+        }
+    }
+
+    #[test]
+    fn test_backoff_delay() {
+        assert_eq!(backoff_delay(0), Duration::from_millis(500));
+        assert_eq!(backoff_delay(1), Duration::from_secs(1));
+        assert_eq!(backoff_delay(3), Duration::from_secs(4));
+        assert_eq!(backoff_delay(10), MAX_RETRY_DELAY);
+        assert_eq!(backoff_delay(u32::MAX), MAX_RETRY_DELAY);
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+
+        headers.insert(reqwest::header::RETRY_AFTER, "7".parse().unwrap());
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(7)));
+
+        headers.insert(
+            reqwest::header::RETRY_AFTER,
+            "Wed, 21 Oct 2015 07:28:00 GMT".parse().unwrap(),
+        );
+        assert_eq!(retry_after(&headers), None);
+    }
+
+    #[test]
+    fn test_rate_limit_message() {
+        let mut headers = HeaderMap::new();
+        headers.insert(reqwest::header::RETRY_AFTER, " 12 ".parse().unwrap());
+        assert_eq!(
+            rate_limit_message(retry_after(&headers)),
+            "Rate limited, retry in 12s"
+        );
+        assert_eq!(rate_limit_message(None), "Rate limited, try again later");
+    }
+
+    #[tokio::test]
+    async fn test_shorten_rate_limited() {
+        let (url, _) =
+            serve_once("429 Too Many Requests", r#"{"error":"Too many requests"}"#).await;
+
+        let options = ClientOptions {
+            retries: 0,
+            ..ClientOptions::default()
+        };
+        let client = ApiClient::new(url, None, options).unwrap();
+        let err = client
+            .shorten(ShortenRequest {
+                url: "https://example.com".to_string(),
+                code: None,
+                ttl: None,
+            })
+            .await
+            .unwrap_err();
+        let err = err.downcast_ref::<ApiError>().unwrap();
+        assert_eq!(err.status, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(err.message, "Rate limited, try again later");
+    }
+
+    #[tokio::test]
+    async fn test_send_retries_transient_errors() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let body = r#"{"code":"docs","original_url":"https://example.com","created_at":1739000000,"expires_at":1760000000,"expired":false}"#;
+
+        let server = tokio::spawn(async move {
+            let responses = [
+                "HTTP/1.1 503 Service Unavailable\r\ncontent-length: 0\r\nconnection: close\r\n\r\n".to_string(),
+                "HTTP/1.1 429 Too Many Requests\r\nretry-after: 0\r\ncontent-length: 0\r\nconnection: close\r\n\r\n".to_string(),
+                format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                ),
+            ];
+            for response in responses {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buf = vec![0u8; 4096];
+                let _ = socket.read(&mut buf).await.unwrap();
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        let client = ApiClient::new(url, None, ClientOptions::default()).unwrap();
+        let info = client.info("docs").await.unwrap();
+        assert_eq!(info.code, "docs");
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_shorten_retries_only_refused_requests() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let body =
+            r#"{"code":"docs","short_url":"https://cutl.my.id/docs","expires_at":1760000000}"#;
+
+        let server = tokio::spawn(async move {
+            let responses = [
+                "HTTP/1.1 429 Too Many Requests\r\nretry-after: 0\r\ncontent-length: 0\r\nconnection: close\r\n\r\n".to_string(),
+                format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                ),
+            ];
+            for response in responses {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buf = vec![0u8; 4096];
+                let _ = socket.read(&mut buf).await.unwrap();
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        // A 429 with Retry-After means the link wasn't created, so it's safe to resend
+        let client = ApiClient::new(url, None, ClientOptions::default()).unwrap();
+        let response = client
+            .shorten(ShortenRequest {
+                url: "https://example.com".to_string(),
+                code: None,
+                ttl: None,
+            })
+            .await
+            .unwrap();
+        assert_eq!(response.code, "docs");
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_shorten_does_not_retry_server_errors() {
+        let (url, _) = serve_once("502 Bad Gateway", r#"{"error":"Bad gateway"}"#).await;
+
+        // The link may have been created before the error, so a retry could
+        // make a second one; serve_once would refuse it anyway
+        let client = ApiClient::new(url, None, ClientOptions::default()).unwrap();
+        let err = client
+            .shorten(ShortenRequest {
+                url: "https://example.com".to_string(),
+                code: None,
+                ttl: None,
+            })
+            .await
+            .unwrap_err();
+        let err = err.downcast_ref::<ApiError>().unwrap();
+        assert_eq!(err.status, StatusCode::BAD_GATEWAY);
+    }
+
+    #[tokio::test]
+    async fn test_send_does_not_retry_client_errors() {
+        let (url, _) = serve_once("409 Conflict", r#"{"error":"Code already exists"}"#).await;
+
+        // serve_once accepts a single connection, so a retry would fail to connect
+        let client = ApiClient::new(url, None, ClientOptions::default()).unwrap();
+        let err = client
+            .shorten(ShortenRequest {
+                url: "https://example.com".to_string(),
+                code: Some("docs".to_string()),
+                ttl: None,
+            })
+            .await
+            .unwrap_err();
+        let err = err.downcast_ref::<ApiError>().unwrap();
+        assert_eq!(err.status, StatusCode::CONFLICT);
+        assert_eq!(err.message, "Code already exists");
+    }
 }
@@ -1,8 +1,22 @@
 //! Configuration management for the cutl CLI
 //!
-//! Loads configuration from command-line arguments and environment variables.
+//! Settings come from, in order of precedence: command-line arguments,
+//! environment variables, the config file (`~/.config/cutl/config.toml` on
+//! Linux), and built-in defaults.
 
-use std::env;
+use crate::validation::validate_ttl_format;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+/// Server used when none is configured anywhere
+pub const DEFAULT_SERVER_URL: &str = "https://cutl.my.id";
+
+/// Name of the config file inside the cutl config directory
+const CONFIG_FILE_NAME: &str = "config.toml";
 
 /// CLI configuration
 #[derive(Debug, Clone)]
@@ -13,7 +27,7 @@ pub struct Config {
     /// Optional custom short code
     pub code: Option<String>,
 
-    /// Optional time-to-live
+    /// Time-to-live: `--ttl`, else the configured default TTL
     pub ttl: Option<String>,
 
     /// Server API URL
@@ -24,42 +38,196 @@ pub struct Config {
 }
 
 impl Config {
-    /// Load configuration from arguments and environment variables
+    /// Load configuration from arguments, environment variables, and the config file
+    ///
+    /// When `ttl` is `None`, `CUTL_DEFAULT_TTL` or the config file's `default_ttl` is used.
     ///
     /// # Arguments
     /// * `url` - The URL to shorten
     /// * `code` - Optional custom short code
     /// * `ttl` - Optional time-to-live
     /// * `server` - Optional server URL override
+    ///
+    /// # Errors
+    /// Returns an error if the config file can't be parsed or the default TTL is malformed.
     pub fn new(
         url: String,
         code: Option<String>,
         ttl: Option<String>,
         server: Option<String>,
+    ) -> Result<Self> {
+        Self::from_sources(
+            url,
+            code,
+            ttl,
+            server,
+            |name| env::var(name).ok(),
+            &FileConfig::load()?,
+        )
+    }
+
+    /// Resolves settings from arguments, an environment lookup, and the config file
+    fn from_sources(
+        url: String,
+        code: Option<String>,
+        ttl: Option<String>,
+        server: Option<String>,
+        env: impl Fn(&str) -> Option<String>,
+        file: &FileConfig,
+    ) -> Result<Self> {
+        let ServerConfig {
+            server_url,
+            auth_token,
+        } = ServerConfig::from_sources(server, &env, file);
+
+        // Check the default even when --ttl is given, so a bad setting surfaces early
+        let default_ttl = env("CUTL_DEFAULT_TTL").or_else(|| file.default_ttl.clone());
+        if let Some(ref default_ttl) = default_ttl {
+            validate_ttl_format(default_ttl)
+                .with_context(|| format!("Invalid default TTL '{}'", default_ttl))?;
+        }
+
+        Ok(Self {
+            url,
+            code,
+            ttl: ttl.or(default_ttl),
+            server_url,
+            auth_token,
+        })
+    }
+}
+
+/// Server connection settings shared by all subcommands
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// Server API URL
+    pub server_url: String,
+
+    /// Optional auth token
+    pub auth_token: Option<String>,
+}
+
+impl ServerConfig {
+    /// Load server settings from an optional override, environment variables,
+    /// and the config file
+    ///
+    /// # Arguments
+    /// * `server` - Optional server URL override
+    ///
+    /// # Errors
+    /// Returns an error if the config file exists but can't be parsed.
+    pub fn new(server: Option<String>) -> Result<Self> {
+        Ok(Self::from_sources(
+            server,
+            |name| env::var(name).ok(),
+            &FileConfig::load()?,
+        ))
+    }
+
+    /// Resolves server settings from an override, an environment lookup, and the config file
+    fn from_sources(
+        server: Option<String>,
+        env: impl Fn(&str) -> Option<String>,
+        file: &FileConfig,
     ) -> Self {
         let server_url = server
-            .or_else(|| env::var("CUTL_SERVER").ok())
-            .unwrap_or_else(|| "https://cutl.my.id".to_string());
+            .or_else(|| env("CUTL_SERVER"))
+            .or_else(|| file.server.clone())
+            .unwrap_or_else(|| DEFAULT_SERVER_URL.to_string());
 
-        let auth_token = env::var("CUTL_TOKEN").ok();
+        let auth_token = env("CUTL_TOKEN").or_else(|| file.token.clone());
 
         Self {
-            url,
-            code,
-            ttl,
             server_url,
             auth_token,
         }
     }
 }
 
+/// Defaults read from the config file; every key is optional
+///
+/// ```toml
+/// server = "https://cutl.example.com"
+/// token = "secret"
+/// default_ttl = "3d"
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileConfig {
+    /// Server API URL
+    pub server: Option<String>,
+
+    /// Auth token
+    pub token: Option<String>,
+
+    /// TTL used when `--ttl` isn't given
+    pub default_ttl: Option<String>,
+}
+
+impl FileConfig {
+    /// Location of the config file: `$CUTL_CONFIG` if set, otherwise
+    /// `config.toml` in the platform config directory (`~/.config/cutl` on Linux)
+    pub fn path() -> Option<PathBuf> {
+        if let Some(path) = env::var_os("CUTL_CONFIG") {
+            return Some(PathBuf::from(path));
+        }
+
+        directories::ProjectDirs::from("", "", "cutl")
+            .map(|dirs| dirs.config_dir().join(CONFIG_FILE_NAME))
+    }
+
+    /// Loads the config file, or empty defaults when there is none
+    ///
+    /// # Errors
+    /// Returns an error if the file exists but can't be read or parsed.
+    pub fn load() -> Result<Self> {
+        match Self::path() {
+            Some(path) => Self::load_from(&path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Loads a config file from `path`, or empty defaults when it doesn't exist
+    ///
+    /// # Errors
+    /// Returns an error if the file exists but can't be read or parsed.
+    pub fn load_from(path: &Path) -> Result<Self> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+
+        toml::from_str(&contents).with_context(|| format!("Invalid config file {}", path.display()))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Builds a `Config` from arguments alone, ignoring the environment and any
+    /// config file on the machine running the tests
+    fn config_from_args(
+        url: &str,
+        code: Option<&str>,
+        ttl: Option<&str>,
+        server: Option<&str>,
+    ) -> Config {
+        Config::from_sources(
+            url.to_string(),
+            code.map(str::to_string),
+            ttl.map(str::to_string),
+            server.map(str::to_string),
+            |_: &str| None,
+            &FileConfig::default(),
+        )
+        .unwrap()
+    }
+
     #[test]
     fn test_config_new_basic() {
-        let config = Config::new("https://example.com".to_string(), None, None, None);
+        let config = config_from_args("https://example.com", None, None, None);
         assert_eq!(config.url, "https://example.com");
         assert!(config.code.is_none());
         assert!(config.ttl.is_none());
@@ -69,55 +237,45 @@ mod tests {
 
     #[test]
     fn test_config_new_with_code() {
-        let config = Config::new(
-            "https://example.com".to_string(),
-            Some("mycode".to_string()),
-            None,
-            None,
-        );
+        let config = config_from_args("https://example.com", Some("mycode"), None, None);
         assert_eq!(config.code, Some("mycode".to_string()));
     }
 
     #[test]
     fn test_config_new_with_ttl() {
-        let config = Config::new(
-            "https://example.com".to_string(),
-            None,
-            Some("1h".to_string()),
-            None,
-        );
+        let config = config_from_args("https://example.com", None, Some("1h"), None);
         assert_eq!(config.ttl, Some("1h".to_string()));
     }
 
     #[test]
     fn test_config_new_with_server_override() {
-        let config = Config::new(
-            "https://example.com".to_string(),
+        let config = config_from_args(
+            "https://example.com",
             None,
             None,
-            Some("http://custom.server:8080".to_string()),
+            Some("http://custom.server:8080"),
         );
         assert_eq!(config.server_url, "http://custom.server:8080");
     }
 
     #[test]
     fn test_config_server_url_trailing_slash() {
-        let config = Config::new(
-            "https://example.com".to_string(),
+        let config = config_from_args(
+            "https://example.com",
             None,
             None,
-            Some("http://localhost:3000/".to_string()),
+            Some("http://localhost:3000/"),
         );
         assert_eq!(config.server_url, "http://localhost:3000/");
     }
 
     #[test]
     fn test_config_all_fields() {
-        let config = Config::new(
-            "https://example.com".to_string(),
-            Some("test".to_string()),
-            Some("7d".to_string()),
-            Some("http://server:3000".to_string()),
+        let config = config_from_args(
+            "https://example.com",
+            Some("test"),
+            Some("7d"),
+            Some("http://server:3000"),
         );
         assert_eq!(config.url, "https://example.com");
         assert_eq!(config.code, Some("test".to_string()));
@@ -127,13 +285,149 @@ mod tests {
 
     #[test]
     fn test_config_empty_code_becomes_none() {
-        let config = Config::new(
-            "https://example.com".to_string(),
-            Some("".to_string()),
-            None,
-            None,
-        );
+        let config = config_from_args("https://example.com", Some(""), None, None);
         // Empty string is still Some(""), not None
         assert_eq!(config.code, Some("".to_string()));
     }
+
+    #[test]
+    fn test_server_config_override() {
+        let config = ServerConfig::from_sources(
+            Some("http://custom.server:8080".to_string()),
+            |_: &str| None,
+            &FileConfig::default(),
+        );
+        assert_eq!(config.server_url, "http://custom.server:8080");
+    }
+
+    /// Writes `contents` to a config file unique to this test
+    fn write_config(name: &str, contents: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("cutl-{}-{}.toml", name, std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_file_config_load_from() {
+        let path = write_config(
+            "load",
+            "server = \"http://file:3000\"\ntoken = \"file-token\"\ndefault_ttl = \"3d\"\n",
+        );
+        let file = FileConfig::load_from(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(file.server, Some("http://file:3000".to_string()));
+        assert_eq!(file.token, Some("file-token".to_string()));
+        assert_eq!(file.default_ttl, Some("3d".to_string()));
+    }
+
+    #[test]
+    fn test_file_config_missing_file_is_empty() {
+        let path = env::temp_dir().join("cutl-does-not-exist.toml");
+        assert_eq!(FileConfig::load_from(&path).unwrap(), FileConfig::default());
+    }
+
+    #[test]
+    fn test_file_config_invalid() {
+        let path = write_config("invalid", "server = [\n");
+        assert!(FileConfig::load_from(&path).is_err());
+
+        std::fs::write(&path, "sever = \"typo\"\n").unwrap();
+        assert!(FileConfig::load_from(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_config_precedence() {
+        let path = write_config(
+            "precedence",
+            "server = \"http://file:3000\"\ntoken = \"file-token\"\ndefault_ttl = \"3d\"\n",
+        );
+        let file = FileConfig::load_from(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let no_env = |_: &str| None;
+        let env = |name: &str| match name {
+            "CUTL_SERVER" => Some("http://env:3000".to_string()),
+            "CUTL_TOKEN" => Some("env-token".to_string()),
+            _ => None,
+        };
+        let url = || "https://example.com".to_string();
+
+        // Config file beats the built-in defaults
+        let config = Config::from_sources(url(), None, None, None, no_env, &file).unwrap();
+        assert_eq!(config.server_url, "http://file:3000");
+        assert_eq!(config.auth_token, Some("file-token".to_string()));
+        assert_eq!(config.ttl, Some("3d".to_string()));
+
+        // Environment beats the config file
+        let config = Config::from_sources(url(), None, None, None, env, &file).unwrap();
+        assert_eq!(config.server_url, "http://env:3000");
+        assert_eq!(config.auth_token, Some("env-token".to_string()));
+
+        // Arguments beat everything
+        let config = Config::from_sources(
+            url(),
+            None,
+            Some("1h".to_string()),
+            Some("http://arg:3000".to_string()),
+            env,
+            &file,
+        )
+        .unwrap();
+        assert_eq!(config.server_url, "http://arg:3000");
+        assert_eq!(config.ttl, Some("1h".to_string()));
+
+        // Nothing configured falls back to the defaults
+        let config =
+            Config::from_sources(url(), None, None, None, no_env, &FileConfig::default()).unwrap();
+        assert_eq!(config.server_url, DEFAULT_SERVER_URL);
+        assert!(config.auth_token.is_none());
+        assert!(config.ttl.is_none());
+    }
+
+    #[test]
+    fn test_default_ttl_from_env() {
+        let file = FileConfig {
+            default_ttl: Some("3d".to_string()),
+            ..Default::default()
+        };
+        let env = |name: &str| (name == "CUTL_DEFAULT_TTL").then(|| "12h".to_string());
+        let url = || "https://example.com".to_string();
+
+        // CUTL_DEFAULT_TTL beats the config file
+        let config = Config::from_sources(url(), None, None, None, env, &file).unwrap();
+        assert_eq!(config.ttl, Some("12h".to_string()));
+
+        // An explicit --ttl beats both
+        let config =
+            Config::from_sources(url(), None, Some("5m".to_string()), None, env, &file).unwrap();
+        assert_eq!(config.ttl, Some("5m".to_string()));
+    }
+
+    #[test]
+    fn test_invalid_default_ttl_rejected() {
+        let url = || "https://example.com".to_string();
+        let bad_env = |name: &str| (name == "CUTL_DEFAULT_TTL").then(|| "soon".to_string());
+        let err = Config::from_sources(url(), None, None, None, bad_env, &FileConfig::default())
+            .unwrap_err();
+        assert!(err.to_string().contains("soon"));
+
+        let bad_file = FileConfig {
+            default_ttl: Some("3x".to_string()),
+            ..Default::default()
+        };
+        assert!(Config::from_sources(url(), None, None, None, |_: &str| None, &bad_file).is_err());
+
+        // Rejected even when --ttl would override it
+        assert!(Config::from_sources(
+            url(),
+            None,
+            Some("1h".to_string()),
+            None,
+            |_: &str| None,
+            &bad_file
+        )
+        .is_err());
+    }
 }
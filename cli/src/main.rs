@@ -1,10 +1,18 @@
 //! cutl CLI - URL Shortener Client
 //!
-//! Command-line tool for creating shortened URLs via the cutl API.
+//! Command-line tool for creating and managing shortened URLs via the cutl API.
 //!
 //! # Usage
 //! ```bash
-//! cutl <URL> [--ttl TTL] [--code CODE]
+//! cutl <URL> [--ttl TTL] [--code CODE] [--qr] [--json]
+//! cutl --from-file <PATH> [--ttl TTL] [--json]
+//! cutl --stdin [--ttl TTL] [--json]
+//! cutl shorten <URL> [--ttl TTL] [--code CODE] [--qr]
+//! cutl delete <CODE>
+//! cutl stats <CODE>
+//! cutl expand <SHORT_URL|CODE>
+//! cutl health
+//! cutl completions <bash|zsh|fish|powershell|elvish>
 //! ```
 //!
 //! # Examples
@@ -12,15 +20,22 @@
 //! cutl https://example.com
 //! cutl https://example.com --ttl 3d
 //! cutl https://example.com --code docs --ttl 7d
+//! cutl delete docs
+//! cutl stats docs
+//! cutl expand https://cutl.my.id/docs
+//! cutl health && ./shorten-all.sh
 //! ```
 
+mod batch;
 mod client;
 mod config;
 mod output;
 mod validation;
 
-use anyhow::Result;
-use clap::Parser;
+use anyhow::{Context, Result};
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use std::io::Write;
 
 /// cutl - CLI URL Shortener
 #[derive(Parser, Debug)]
@@ -28,34 +43,221 @@ use clap::Parser;
 #[command(author = "cutl")]
 #[command(version = env!("CARGO_PKG_VERSION"))]
 #[command(about = "Shorten URLs using the cutl API", long_about = None)]
-struct Args {
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Commands>,
+
+    /// Shorthand for `cutl shorten <URL>`
+    #[command(flatten)]
+    shorten: ShortenArgs,
+
+    /// Override the default server URL
+    #[arg(short, long, env = "CUTL_SERVER", global = true)]
+    server: Option<String>,
+
+    /// Print results (and errors) as a single line of JSON
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Print plain text without colors (also set by NO_COLOR)
+    #[arg(long, global = true)]
+    no_color: bool,
+
+    /// Seconds to wait for each request before giving up
+    #[arg(long, value_name = "SECS", global = true, default_value_t = client::DEFAULT_TIMEOUT_SECS)]
+    timeout: u64,
+
+    /// Times to retry after a connection error, 5xx, or 429 response
+    #[arg(long, value_name = "N", global = true, default_value_t = client::DEFAULT_RETRIES)]
+    retries: u32,
+}
+
+impl Cli {
+    /// Timeout and retry settings for the API client
+    fn client_options(&self) -> client::ClientOptions {
+        client::ClientOptions {
+            timeout: std::time::Duration::from_secs(self.timeout),
+            retries: self.retries,
+        }
+    }
+}
+
+/// Available subcommands
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Shorten a URL (default when no subcommand is given)
+    Shorten(ShortenArgs),
+
+    /// Delete a short link
+    Delete {
+        /// The short code to delete
+        #[arg(value_name = "CODE")]
+        code: String,
+    },
+
+    /// Show visit analytics for a short link
+    Stats {
+        /// The short code to inspect
+        #[arg(value_name = "CODE")]
+        code: String,
+    },
+
+    /// Show where a short link leads without visiting it
+    #[command(visible_alias = "resolve")]
+    Expand {
+        /// A short URL (https://cutl.my.id/docs) or just its code
+        #[arg(value_name = "SHORT_URL")]
+        target: String,
+    },
+
+    /// Check that the server is reachable and healthy
+    #[command(visible_alias = "ping")]
+    Health,
+
+    /// Print a shell completion script to stdout
+    #[command(hide = true)]
+    Completions {
+        /// Shell to generate completions for
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+}
+
+/// Arguments for shortening a URL
+#[derive(clap::Args, Debug)]
+struct ShortenArgs {
     /// The URL to shorten
-    #[arg(value_name = "URL")]
-    url: String,
+    #[arg(value_name = "URL", required_unless_present_any = ["from_file", "stdin"])]
+    url: Option<String>,
+
+    /// Shorten every URL in a file, one per line
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["url", "stdin", "code", "qr"])]
+    from_file: Option<std::path::PathBuf>,
+
+    /// Shorten every URL read from stdin, one per line
+    #[arg(long, conflicts_with_all = ["url", "code", "qr"])]
+    stdin: bool,
 
     /// Optional: Custom short code (1-32 chars, alphanumeric + - and _)
     #[arg(short, long)]
     code: Option<String>,
 
-    /// Optional: Time-to-live (e.g., 5m, 1h, 3d, 30d)
+    /// Optional: Time-to-live (e.g., 5m, 1h, 3d, 30d); defaults to CUTL_DEFAULT_TTL
     #[arg(short, long)]
     ttl: Option<String>,
 
-    /// Override the default server URL
-    #[arg(short, long, env = "CUTL_SERVER")]
-    server: Option<String>,
+    /// Also display the short URL as a QR code in the terminal
+    #[arg(long)]
+    qr: bool,
+
+    /// Copy the short URL to the clipboard
+    #[arg(long, conflicts_with_all = ["from_file", "stdin"])]
+    copy: bool,
+
+    /// Print only the short URL, without the spinner or decorations
+    #[arg(short, long, conflicts_with_all = ["qr", "from_file", "stdin"])]
+    quiet: bool,
+}
+
+/// How a created link is printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputMode {
+    /// Decorated summary with colors and the expiry
+    Pretty,
+    /// The short URL alone, for piping into other commands
+    Quiet,
+    /// The full response as JSON
+    Json,
+}
+
+impl OutputMode {
+    /// Picks the mode from the flags; `--json` wins over `--quiet`
+    fn from_flags(json: bool, quiet: bool) -> Self {
+        if json {
+            Self::Json
+        } else if quiet {
+            Self::Quiet
+        } else {
+            Self::Pretty
+        }
+    }
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     // Parse command-line arguments
-    let args = Args::parse();
+    let cli = Cli::parse();
+    output::init_color(cli.no_color);
+    let json = cli.json;
+    let options = cli.client_options();
+
+    let result = match cli.command.unwrap_or(Commands::Shorten(cli.shorten)) {
+        Commands::Shorten(args) => shorten(args, cli.server, options, json).await,
+        Commands::Delete { code } => delete(code, cli.server, options, json).await,
+        Commands::Stats { code } => stats(code, cli.server, options, json).await,
+        Commands::Expand { target } => expand(target, cli.server, options, json).await,
+        Commands::Health => health(cli.server, options, json).await,
+        Commands::Completions { shell } => {
+            write_completions(shell, &mut std::io::stdout());
+            Ok(())
+        }
+    };
+
+    // In JSON mode every failure is reported as a JSON object on stdout,
+    // unless the command's own JSON output already describes it
+    if let Err(ref e) = result {
+        if json && !reported_in_output(e) {
+            output::print_json_error(&e.to_string());
+        }
+    }
+
+    result
+}
+
+/// Lines of a `--from-file` or `--stdin` run failed; each is already in the output
+#[derive(Debug)]
+struct PartialFailure {
+    failed: usize,
+    total: usize,
+}
+
+impl std::fmt::Display for PartialFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} of {} URLs could not be shortened",
+            self.failed, self.total
+        )
+    }
+}
+
+impl std::error::Error for PartialFailure {}
+
+/// Whether `e` is already described by the command's output, so only the
+/// exit code and the message on stderr should report it
+fn reported_in_output(e: &anyhow::Error) -> bool {
+    e.downcast_ref::<PartialFailure>().is_some()
+}
+
+/// Creates a short link and prints the result
+async fn shorten(
+    args: ShortenArgs,
+    server: Option<String>,
+    options: client::ClientOptions,
+    json: bool,
+) -> Result<()> {
+    if args.stdin || args.from_file.is_some() {
+        return shorten_lines(args, server, options, json).await;
+    }
+
+    let url = args.url.context("A URL to shorten is required")?;
 
     // Validate the input URL
-    validation::validate_url(&args.url)?;
+    validation::validate_url(&url)?;
 
-    // Get server URL from args or environment variable
-    let config = config::Config::new(args.url, args.code, args.ttl, args.server);
+    // Resolve server, token, and TTL from args, environment, and the config file
+    let config = config::Config::new(url, args.code, args.ttl, server)?;
 
     // Validate custom code format if provided
     if let Some(ref code) = config.code {
@@ -68,10 +270,12 @@ async fn main() -> Result<()> {
     }
 
     // Create API client
-    let client = client::ApiClient::new(config.server_url, config.auth_token)?;
+    let client = client::ApiClient::new(config.server_url, config.auth_token, options)?;
+
+    let mode = OutputMode::from_flags(json, args.quiet);
 
     // Create a spinner for the request
-    let spinner = output::create_spinner("Shortening URL...");
+    let spinner = start_spinner("Shortening URL...", mode != OutputMode::Pretty);
 
     // Send the request
     let result = match client
@@ -84,10 +288,7 @@ async fn main() -> Result<()> {
     {
         Ok(response) => response,
         Err(e) => {
-            spinner.finish_and_clear();
-            // Try to extract HTTP status from error message
-            let status_code = extract_status_code(&e.to_string());
-            output::print_error(&e.to_string(), status_code);
+            report_error(&spinner, &e, json);
             return Err(e);
         }
     };
@@ -95,27 +296,246 @@ async fn main() -> Result<()> {
     spinner.finish_and_clear();
 
     // Format and display the result
-    output::print_success(&result);
+    match mode {
+        OutputMode::Json => output::print_json(&result)?,
+        OutputMode::Quiet => println!("{}", result.short_url),
+        OutputMode::Pretty => {
+            output::print_success(&result);
+
+            if args.qr {
+                output::print_qr(&result.short_url)?;
+            }
+        }
+    }
+
+    // A missing clipboard only warns; the link was still created
+    if args.copy {
+        match output::copy_to_clipboard(&result.short_url) {
+            Ok(()) if mode == OutputMode::Pretty => output::print_copied(),
+            Ok(()) => {}
+            Err(e) => output::print_warning(&format!("Couldn't copy to clipboard: {}", e)),
+        }
+    }
+
+    Ok(())
+}
+
+/// Shortens one URL per line from `--from-file` or `--stdin`
+///
+/// Prints `original<TAB>short_url` for each created link (or a JSON array with
+/// `--json`). Lines that fail are reported on stderr, or as `error` entries in
+/// the JSON array, and the run continues; the exit code is non-zero if any
+/// line failed.
+async fn shorten_lines(
+    args: ShortenArgs,
+    server: Option<String>,
+    options: client::ClientOptions,
+    json: bool,
+) -> Result<()> {
+    let input = match args.from_file {
+        Some(ref path) => std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?,
+        None => std::io::read_to_string(std::io::stdin()).context("Failed to read stdin")?,
+    };
+
+    let config = config::Config::new(String::new(), None, args.ttl, server)?;
+    if let Some(ref ttl) = config.ttl {
+        validation::validate_ttl_format(ttl)?;
+    }
+
+    let client = client::ApiClient::new(config.server_url, config.auth_token, options)?;
+    let lines = batch::parse_lines(&input);
+
+    let spinner = start_spinner(&format!("Shortening {} URLs...", lines.len()), json);
+
+    let outcomes = match batch::shorten_all(&client, lines, config.ttl).await {
+        Ok(outcomes) => outcomes,
+        Err(e) => {
+            report_error(&spinner, &e, json);
+            return Err(e);
+        }
+    };
+
+    spinner.finish_and_clear();
+
+    if json {
+        output::print_json(&outcomes)?;
+    } else {
+        output::print_line_outcomes(&outcomes);
+    }
+
+    let failed = batch::failures(&outcomes).len();
+    if failed > 0 {
+        return Err(PartialFailure {
+            failed,
+            total: outcomes.len(),
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Deletes a short link and prints a confirmation
+async fn delete(
+    code: String,
+    server: Option<String>,
+    options: client::ClientOptions,
+    json: bool,
+) -> Result<()> {
+    validation::validate_code(&code)?;
+
+    let config = config::ServerConfig::new(server)?;
+    let client = client::ApiClient::new(config.server_url, config.auth_token, options)?;
+
+    let spinner = start_spinner("Deleting short link...", json);
+
+    if let Err(e) = client.delete(&code).await {
+        report_error(&spinner, &e, json);
+        return Err(e);
+    }
+
+    spinner.finish_and_clear();
+
+    if json {
+        output::print_json(&serde_json::json!({ "code": code, "deleted": true }))?;
+    } else {
+        output::print_deleted(&code);
+    }
+
+    Ok(())
+}
+
+/// Fetches analytics for a short link and prints them
+async fn stats(
+    code: String,
+    server: Option<String>,
+    options: client::ClientOptions,
+    json: bool,
+) -> Result<()> {
+    validation::validate_code(&code)?;
+
+    let config = config::ServerConfig::new(server)?;
+    let client = client::ApiClient::new(config.server_url, config.auth_token, options)?;
+
+    let spinner = start_spinner("Fetching analytics...", json);
+
+    let stats = match client.analytics(&code).await {
+        Ok(stats) => stats,
+        Err(e) => {
+            report_error(&spinner, &e, json);
+            return Err(e);
+        }
+    };
+
+    spinner.finish_and_clear();
+
+    if json {
+        output::print_json(&stats)?;
+    } else {
+        output::print_stats(&stats);
+    }
 
     Ok(())
 }
 
-/// Extract HTTP status code from error message if available
-fn extract_status_code(error_msg: &str) -> u16 {
-    // Look for common status code patterns in error messages
-    if error_msg.contains("400") || error_msg.contains("Invalid") {
-        400
-    } else if error_msg.contains("401") || error_msg.contains("Unauthorized") {
-        401
-    } else if error_msg.contains("409") || error_msg.contains("exists") {
-        409
-    } else if error_msg.contains("404") {
-        404
-    } else if error_msg.contains("500") || error_msg.contains("Server error") {
-        500
+/// Looks up a short link's destination and prints it
+async fn expand(
+    target: String,
+    server: Option<String>,
+    options: client::ClientOptions,
+    json: bool,
+) -> Result<()> {
+    let code = validation::code_from_input(&target)?;
+
+    let config = config::ServerConfig::new(server)?;
+    let client = client::ApiClient::new(config.server_url, config.auth_token, options)?;
+
+    let spinner = start_spinner("Looking up short link...", json);
+
+    let info = match client.info(&code).await {
+        Ok(info) => info,
+        Err(e) => {
+            report_error(&spinner, &e, json);
+            return Err(e);
+        }
+    };
+
+    spinner.finish_and_clear();
+
+    if json {
+        output::print_json(&info)?;
     } else {
-        0
+        output::print_info(&info);
     }
+
+    Ok(())
+}
+
+/// Checks the server's health endpoint and prints the latency
+///
+/// Fails, and so exits non-zero, when the server can't be reached or reports a problem.
+async fn health(server: Option<String>, options: client::ClientOptions, json: bool) -> Result<()> {
+    let config = config::ServerConfig::new(server)?;
+    let client = client::ApiClient::new(config.server_url.clone(), config.auth_token, options)?;
+
+    let spinner = start_spinner("Checking server...", json);
+
+    let latency = match client.health().await {
+        Ok(latency) => latency,
+        Err(e) => {
+            report_error(&spinner, &e, json);
+            return Err(e);
+        }
+    };
+
+    spinner.finish_and_clear();
+
+    if json {
+        output::print_json(&serde_json::json!({
+            "status": "ok",
+            "server": config.server_url,
+            "latency_ms": latency.as_millis() as u64,
+        }))?;
+    } else {
+        output::print_health(&config.server_url, latency);
+    }
+
+    Ok(())
+}
+
+/// Writes the completion script for `shell` to `out`
+///
+/// Install with e.g. `cutl completions bash > /etc/bash_completion.d/cutl`.
+fn write_completions(shell: Shell, out: &mut dyn Write) {
+    let mut command = Cli::command();
+    let name = command.get_name().to_string();
+    clap_complete::generate(shell, &mut command, name, out);
+}
+
+/// Starts a progress spinner, or a hidden one for JSON or `--quiet` output
+fn start_spinner(message: &str, hidden: bool) -> indicatif::ProgressBar {
+    if hidden {
+        indicatif::ProgressBar::hidden()
+    } else {
+        output::create_spinner(message)
+    }
+}
+
+/// Clears the spinner and prints a failed request in human-readable form
+///
+/// In JSON mode nothing is printed here; `main` reports the error instead.
+fn report_error(spinner: &indicatif::ProgressBar, e: &anyhow::Error, json: bool) {
+    spinner.finish_and_clear();
+
+    if !json {
+        output::print_error(&e.to_string(), status_of(e));
+    }
+}
+
+/// The HTTP status the server answered with, if the error came from a response
+fn status_of(e: &anyhow::Error) -> Option<reqwest::StatusCode> {
+    e.downcast_ref::<client::ApiError>().map(|err| err.status)
 }
 
 #[cfg(test)]
@@ -123,63 +543,218 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_extract_status_code_400() {
-        assert_eq!(extract_status_code("Invalid request"), 400);
-        assert_eq!(extract_status_code("400 Bad Request"), 400);
-        assert_eq!(extract_status_code("Error 400"), 400);
+    fn test_status_of_api_error() {
+        let e = anyhow::Error::from(client::ApiError {
+            status: reqwest::StatusCode::CONFLICT,
+            message: "Code already exists".to_string(),
+        });
+        assert_eq!(status_of(&e), Some(reqwest::StatusCode::CONFLICT));
+
+        // Context added on top doesn't hide the status
+        let e = e.context("Failed to shorten");
+        assert_eq!(status_of(&e), Some(reqwest::StatusCode::CONFLICT));
     }
 
     #[test]
-    fn test_extract_status_code_401() {
-        assert_eq!(extract_status_code("Unauthorized"), 401);
-        assert_eq!(extract_status_code("401 Unauthorized"), 401);
-        assert_eq!(extract_status_code("401"), 401);
-        assert_eq!(extract_status_code("Authentication failed"), 0); // No pattern match
+    fn test_partial_failure_reported_in_output() {
+        let e = anyhow::Error::from(PartialFailure {
+            failed: 2,
+            total: 5,
+        });
+        assert!(reported_in_output(&e));
+        assert_eq!(e.to_string(), "2 of 5 URLs could not be shortened");
+
+        // Any other failure still gets its own JSON error object
+        assert!(!reported_in_output(&anyhow::anyhow!(
+            "Failed to connect to server"
+        )));
+    }
+
+    #[test]
+    fn test_status_of_ignores_message_text() {
+        // Errors that never reached the server carry no status, even if they mention one
+        let e = anyhow::anyhow!("Invalid URL: https://example.com/404");
+        assert_eq!(status_of(&e), None);
+
+        let e = anyhow::anyhow!("Failed to connect to server");
+        assert_eq!(status_of(&e), None);
+    }
+
+    #[test]
+    fn test_cli_url_shorthand() {
+        let cli = Cli::try_parse_from(["cutl", "https://example.com", "--ttl", "1h"]).unwrap();
+        assert!(cli.command.is_none());
+        assert_eq!(cli.shorten.url, Some("https://example.com".to_string()));
+        assert_eq!(cli.shorten.ttl, Some("1h".to_string()));
+    }
+
+    #[test]
+    fn test_cli_shorten_subcommand() {
+        let cli =
+            Cli::try_parse_from(["cutl", "shorten", "https://example.com", "-c", "docs"]).unwrap();
+        match cli.command {
+            Some(Commands::Shorten(args)) => {
+                assert_eq!(args.url, Some("https://example.com".to_string()));
+                assert_eq!(args.code, Some("docs".to_string()));
+            }
+            other => panic!("unexpected command: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_cli_delete_subcommand() {
+        let cli =
+            Cli::try_parse_from(["cutl", "delete", "docs", "--server", "http://host"]).unwrap();
+        assert!(matches!(cli.command, Some(Commands::Delete { ref code }) if code == "docs"));
+        assert_eq!(cli.server, Some("http://host".to_string()));
+    }
+
+    #[test]
+    fn test_cli_stats_subcommand() {
+        let cli = Cli::try_parse_from(["cutl", "stats", "docs", "--json"]).unwrap();
+        assert!(matches!(cli.command, Some(Commands::Stats { ref code }) if code == "docs"));
+        assert!(cli.json);
+    }
+
+    #[test]
+    fn test_cli_expand_subcommand() {
+        let cli = Cli::try_parse_from(["cutl", "expand", "https://cutl.my.id/docs"]).unwrap();
+        assert!(
+            matches!(cli.command, Some(Commands::Expand { ref target }) if target == "https://cutl.my.id/docs")
+        );
+
+        let cli = Cli::try_parse_from(["cutl", "resolve", "docs"]).unwrap();
+        assert!(matches!(cli.command, Some(Commands::Expand { ref target }) if target == "docs"));
+    }
+
+    #[test]
+    fn test_cli_health_subcommand() {
+        let cli = Cli::try_parse_from(["cutl", "health", "--server", "http://host"]).unwrap();
+        assert!(matches!(cli.command, Some(Commands::Health)));
+        assert_eq!(cli.server, Some("http://host".to_string()));
+
+        let cli = Cli::try_parse_from(["cutl", "ping", "--json"]).unwrap();
+        assert!(matches!(cli.command, Some(Commands::Health)));
+        assert!(cli.json);
     }
 
     #[test]
-    fn test_extract_status_code_409() {
-        assert_eq!(extract_status_code("Code already exists"), 409);
-        assert_eq!(extract_status_code("409 Conflict"), 409);
-        assert_eq!(extract_status_code("Resource exists"), 409);
+    fn test_cli_completions_subcommand() {
+        let cli = Cli::try_parse_from(["cutl", "completions", "zsh"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Completions { shell: Shell::Zsh })
+        ));
+        assert!(Cli::try_parse_from(["cutl", "completions", "tcsh"]).is_err());
     }
 
     #[test]
-    fn test_extract_status_code_404() {
-        assert_eq!(extract_status_code("404 Not Found"), 404);
-        assert_eq!(extract_status_code("Resource not found"), 0); // No 404 in message
+    fn test_write_bash_completions() {
+        let mut out = Vec::new();
+        write_completions(Shell::Bash, &mut out);
+
+        let script = String::from_utf8(out).unwrap();
+        assert!(!script.is_empty());
+        assert!(script.contains("cutl"));
+        assert!(script.contains("expand"));
+    }
+
+    #[test]
+    fn test_cli_from_file_and_stdin() {
+        let cli = Cli::try_parse_from(["cutl", "--from-file", "urls.txt", "--ttl", "3d"]).unwrap();
+        assert_eq!(
+            cli.shorten.from_file,
+            Some(std::path::PathBuf::from("urls.txt"))
+        );
+        assert!(cli.shorten.url.is_none());
+
+        let cli = Cli::try_parse_from(["cutl", "shorten", "--stdin"]).unwrap();
+        assert!(matches!(cli.command, Some(Commands::Shorten(ref args)) if args.stdin));
+
+        // A single URL, a custom code, or a QR code don't make sense for many URLs
+        assert!(Cli::try_parse_from(["cutl", "https://example.com", "--stdin"]).is_err());
+        assert!(Cli::try_parse_from(["cutl", "--stdin", "--code", "docs"]).is_err());
+        assert!(Cli::try_parse_from(["cutl", "--from-file", "urls.txt", "--stdin"]).is_err());
+    }
+
+    #[test]
+    fn test_cli_qr_flag() {
+        let cli = Cli::try_parse_from(["cutl", "https://example.com", "--qr"]).unwrap();
+        assert!(cli.shorten.qr);
+    }
+
+    #[test]
+    fn test_cli_quiet_flag() {
+        let cli = Cli::try_parse_from(["cutl", "https://example.com", "-q"]).unwrap();
+        assert!(cli.shorten.quiet);
+
+        let cli =
+            Cli::try_parse_from(["cutl", "shorten", "https://example.com", "--quiet"]).unwrap();
+        match cli.command {
+            Some(Commands::Shorten(args)) => assert!(args.quiet),
+            _ => panic!("expected shorten subcommand"),
+        }
+
+        assert!(Cli::try_parse_from(["cutl", "https://example.com", "-q", "--qr"]).is_err());
+        assert!(Cli::try_parse_from(["cutl", "--stdin", "-q"]).is_err());
     }
 
     #[test]
-    fn test_extract_status_code_500() {
-        assert_eq!(extract_status_code("Server error"), 500);
-        assert_eq!(extract_status_code("500 Internal Server Error"), 500);
-        assert_eq!(extract_status_code("500"), 500);
-        assert_eq!(extract_status_code("Internal error"), 0); // No pattern match
+    fn test_cli_copy_flag() {
+        let cli = Cli::try_parse_from(["cutl", "https://example.com", "--copy"]).unwrap();
+        assert!(cli.shorten.copy);
+
+        let cli = Cli::try_parse_from(["cutl", "https://example.com", "-q", "--copy"]).unwrap();
+        assert!(cli.shorten.copy && cli.shorten.quiet);
+
+        assert!(Cli::try_parse_from(["cutl", "--stdin", "--copy"]).is_err());
     }
 
     #[test]
-    fn test_extract_status_code_unknown() {
-        assert_eq!(extract_status_code("Some random error"), 0);
-        assert_eq!(extract_status_code("Unknown failure"), 0);
-        assert_eq!(extract_status_code("Network timeout"), 0);
+    fn test_output_mode_from_flags() {
+        assert_eq!(OutputMode::from_flags(false, false), OutputMode::Pretty);
+        assert_eq!(OutputMode::from_flags(false, true), OutputMode::Quiet);
+        assert_eq!(OutputMode::from_flags(true, false), OutputMode::Json);
+        assert_eq!(OutputMode::from_flags(true, true), OutputMode::Json);
     }
 
     #[test]
-    fn test_extract_status_code_empty() {
-        assert_eq!(extract_status_code(""), 0);
+    fn test_cli_json_flag() {
+        let cli = Cli::try_parse_from(["cutl", "https://example.com", "--json"]).unwrap();
+        assert!(cli.json);
+
+        let cli = Cli::try_parse_from(["cutl", "https://example.com"]).unwrap();
+        assert!(!cli.json);
     }
 
     #[test]
-    fn test_extract_status_code_case_insensitive() {
-        // The function is case-sensitive, so uppercase won't match
-        assert_eq!(extract_status_code("INVALID REQUEST"), 0);
-        assert_eq!(extract_status_code("UNAUTHORIZED"), 0);
-        assert_eq!(extract_status_code("SERVER ERROR"), 0); // Case-sensitive, "Server" != "SERVER"
+    fn test_cli_no_color_flag() {
+        let cli = Cli::try_parse_from(["cutl", "stats", "docs", "--no-color"]).unwrap();
+        assert!(cli.no_color);
+
+        let cli = Cli::try_parse_from(["cutl", "https://example.com"]).unwrap();
+        assert!(!cli.no_color);
+    }
 
-        // Correct case works
-        assert_eq!(extract_status_code("Invalid request"), 400);
-        assert_eq!(extract_status_code("Unauthorized"), 401);
-        assert_eq!(extract_status_code("Server error"), 500);
+    #[test]
+    fn test_cli_timeout_and_retries() {
+        let cli = Cli::try_parse_from(["cutl", "https://example.com"]).unwrap();
+        assert_eq!(cli.client_options(), client::ClientOptions::default());
+
+        let cli =
+            Cli::try_parse_from(["cutl", "stats", "docs", "--timeout", "5", "--retries", "0"])
+                .unwrap();
+        let options = cli.client_options();
+        assert_eq!(options.timeout, std::time::Duration::from_secs(5));
+        assert_eq!(options.retries, 0);
+
+        assert!(Cli::try_parse_from(["cutl", "stats", "docs", "--retries", "-1"]).is_err());
+    }
+
+    #[test]
+    fn test_cli_requires_url() {
+        assert!(Cli::try_parse_from(["cutl"]).is_err());
+        assert!(Cli::try_parse_from(["cutl", "shorten"]).is_err());
+        assert!(Cli::try_parse_from(["cutl", "delete"]).is_err());
     }
 }
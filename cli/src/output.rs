@@ -3,7 +3,28 @@
 //! Handles styled terminal output for success and error messages.
 
 use chrono::{DateTime, Local, SecondsFormat};
-use console::Style;
+use console::{Style, Term};
+use qrcode::{render::unicode::Dense1x2, QrCode};
+use serde::Serialize;
+
+/// Decides whether output should be styled with ANSI colors
+///
+/// Color is off when `--no-color` is given, when `NO_COLOR` is set to a
+/// non-empty value (see <https://no-color.org>), or when stdout isn't a terminal.
+pub fn color_enabled(no_color_flag: bool, no_color_env: Option<&str>, stdout_is_tty: bool) -> bool {
+    !no_color_flag && no_color_env.is_none_or(str::is_empty) && stdout_is_tty
+}
+
+/// Turns styling on or off for everything printed afterwards, on stdout and stderr
+pub fn init_color(no_color_flag: bool) {
+    let enabled = color_enabled(
+        no_color_flag,
+        std::env::var("NO_COLOR").ok().as_deref(),
+        Term::stdout().is_term(),
+    );
+    console::set_colors_enabled(enabled);
+    console::set_colors_enabled_stderr(enabled);
+}
 
 /// Creates a styled progress spinner
 pub fn create_spinner(message: &str) -> indicatif::ProgressBar {
@@ -43,20 +64,282 @@ pub fn print_success(result: &crate::client::ShortenResponse) {
         bold.apply_to(&result.code)
     );
 
-    // Format expiration timestamp
-    let expires_dt: DateTime<Local> = DateTime::from_timestamp(result.expires_at, 0)
-        .unwrap()
-        .into();
     println!(
         "  {} {}",
         dim.apply_to("Expires:"),
-        bold.apply_to(expires_dt.to_rfc3339_opts(SecondsFormat::Secs, false))
+        bold.apply_to(format_timestamp(result.expires_at))
     );
     println!();
 }
 
+/// Prints where a short link leads and when it expires
+pub fn print_info(info: &crate::client::LinkInfoResponse) {
+    let bold = Style::new().bold();
+    let dim = Style::new().dim();
+    let green = Style::new().green();
+    let yellow = Style::new().yellow();
+
+    println!();
+    println!(
+        "{} {} {}",
+        green.apply_to("✓"),
+        bold.apply_to("Short link"),
+        bold.apply_to(&info.code)
+    );
+    println!();
+    println!(
+        "  {} {}",
+        dim.apply_to("Destination:"),
+        bold.apply_to(&info.original_url)
+    );
+    println!(
+        "  {} {}",
+        dim.apply_to("Created:"),
+        bold.apply_to(format_timestamp(info.created_at))
+    );
+
+    let expires = format_timestamp(info.expires_at);
+    if info.expired {
+        println!(
+            "  {} {}",
+            dim.apply_to("Expired:"),
+            yellow.apply_to(expires)
+        );
+    } else {
+        println!("  {} {}", dim.apply_to("Expires:"), bold.apply_to(expires));
+    }
+    println!();
+}
+
+/// Formats a UNIX timestamp in the local timezone
+fn format_timestamp(timestamp: i64) -> String {
+    match DateTime::from_timestamp(timestamp, 0) {
+        Some(dt) => DateTime::<Local>::from(dt).to_rfc3339_opts(SecondsFormat::Secs, false),
+        None => timestamp.to_string(),
+    }
+}
+
+/// Prints batch results: `original<TAB>short_url` on stdout, failures on stderr
+pub fn print_line_outcomes(outcomes: &[crate::batch::LineOutcome]) {
+    let red = Style::new().red();
+
+    for outcome in outcomes {
+        match outcome.result {
+            crate::batch::LineResult::Created(ref response) => {
+                println!("{}\t{}", outcome.url, response.short_url);
+            }
+            crate::batch::LineResult::Failed { ref error } => {
+                eprintln!(
+                    "{} line {}: {}: {}",
+                    red.apply_to("✗"),
+                    outcome.line,
+                    outcome.url,
+                    error
+                );
+            }
+        }
+    }
+}
+
+/// Renders `data` as a QR code made of unicode half-block characters
+pub fn render_qr(data: &str) -> anyhow::Result<String> {
+    let code = QrCode::new(data.as_bytes())?;
+
+    // Colors are inverted so the code scans on dark terminal backgrounds
+    Ok(code
+        .render::<Dense1x2>()
+        .dark_color(Dense1x2::Light)
+        .light_color(Dense1x2::Dark)
+        .build())
+}
+
+/// Prints a scannable QR code for `data` to stdout
+///
+/// Does nothing but print a warning when stdout is not a terminal.
+pub fn print_qr(data: &str) -> anyhow::Result<()> {
+    if !Term::stdout().is_term() {
+        print_warning("Skipping QR code: stdout is not a terminal");
+        return Ok(());
+    }
+
+    println!("{}", render_qr(data)?);
+    println!();
+    Ok(())
+}
+
+/// Copies `text` to the system clipboard
+///
+/// On Linux, X11 and Wayland only keep the text while its owner runs; when
+/// the clipboard is dropped, `arboard` hands it to the desktop's clipboard
+/// manager so it survives `cutl` exiting.
+///
+/// # Errors
+/// Returns an error when no clipboard can be opened, e.g. on a headless
+/// machine without a display, or when it refuses the text.
+pub fn copy_to_clipboard(text: &str) -> anyhow::Result<()> {
+    let mut clipboard =
+        arboard::Clipboard::new().map_err(|e| anyhow::anyhow!("No clipboard available: {}", e))?;
+    clipboard.set_text(text)?;
+    Ok(())
+}
+
+/// Prints a confirmation that the short URL is on the clipboard
+pub fn print_copied() {
+    let green = Style::new().green();
+
+    println!("{} Copied to clipboard", green.apply_to("✓"));
+    println!();
+}
+
+/// Prints a non-fatal problem to stderr
+pub fn print_warning(message: &str) {
+    let yellow = Style::new().yellow();
+
+    eprintln!("{} {}", yellow.apply_to("!"), message);
+}
+
+/// Prints a confirmation that a short link was deleted
+pub fn print_deleted(code: &str) {
+    let bold = Style::new().bold();
+    let green = Style::new().green();
+
+    println!();
+    println!(
+        "{} {} {}",
+        green.apply_to("✓"),
+        bold.apply_to("Deleted short link"),
+        bold.apply_to(code)
+    );
+    println!();
+}
+
+/// Prints that the server answered its health check, and how quickly
+pub fn print_health(server_url: &str, latency: std::time::Duration) {
+    let bold = Style::new().bold();
+    let dim = Style::new().dim();
+    let green = Style::new().green();
+
+    println!();
+    println!(
+        "{} {} {}",
+        green.apply_to("✓"),
+        bold.apply_to("Server is healthy"),
+        dim.apply_to(format!("({} ms)", latency.as_millis()))
+    );
+    println!(
+        "  {} {}",
+        dim.apply_to("Server:"),
+        bold.apply_to(server_url)
+    );
+    println!();
+}
+
+/// Number of entries shown for country and referer breakdowns
+const TOP_N: usize = 5;
+
+/// Bar characters used to draw sparklines, from lowest to highest
+const SPARK_CHARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Prints visit analytics for a short link
+pub fn print_stats(stats: &crate::client::AnalyticsResponse) {
+    let bold = Style::new().bold();
+    let dim = Style::new().dim();
+    let green = Style::new().green();
+
+    println!();
+    println!(
+        "{} {} {}",
+        green.apply_to("✓"),
+        bold.apply_to("Analytics for"),
+        bold.apply_to(&stats.code)
+    );
+    println!();
+    println!(
+        "  {} {}",
+        dim.apply_to("Destination:"),
+        bold.apply_to(&stats.original_url)
+    );
+    println!(
+        "  {} {}",
+        dim.apply_to("Total visits:"),
+        bold.apply_to(stats.total_visits)
+    );
+    println!(
+        "  {} {}",
+        dim.apply_to("Unique visitors:"),
+        bold.apply_to(stats.unique_visitors)
+    );
+
+    // Daily counts arrive newest first; draw them oldest to newest
+    let daily: Vec<i64> = stats.daily.iter().rev().map(|d| d.count).collect();
+    if !daily.is_empty() {
+        println!(
+            "  {} {}",
+            dim.apply_to("Last 30 days:"),
+            green.apply_to(sparkline(&daily))
+        );
+    }
+
+    print_breakdown("Top countries:", &stats.countries, "(unknown)");
+    print_breakdown("Top referers:", &stats.referers, "(direct)");
+    println!();
+}
+
+/// Prints the top entries of a country/referer breakdown
+fn print_breakdown(title: &str, stats: &[crate::client::CountStat], empty_label: &str) {
+    if stats.is_empty() {
+        return;
+    }
+
+    let dim = Style::new().dim();
+
+    println!();
+    println!("  {}", dim.apply_to(title));
+    for stat in stats.iter().take(TOP_N) {
+        println!(
+            "    {:>6}  {}",
+            stat.count,
+            stat.value.as_deref().unwrap_or(empty_label)
+        );
+    }
+}
+
+/// Renders counts as a unicode sparkline, scaled to the largest value
+pub fn sparkline(values: &[i64]) -> String {
+    let max = values.iter().copied().max().unwrap_or(0);
+    if max <= 0 {
+        return SPARK_CHARS[0].to_string().repeat(values.len());
+    }
+
+    values
+        .iter()
+        .map(|&v| {
+            let idx = (v.max(0) * (SPARK_CHARS.len() as i64 - 1) / max) as usize;
+            SPARK_CHARS[idx]
+        })
+        .collect()
+}
+
+/// Serializes a value as a single line of JSON
+pub fn to_json_line<T: Serialize>(value: &T) -> serde_json::Result<String> {
+    serde_json::to_string(value)
+}
+
+/// Prints a value as a single line of JSON to stdout
+pub fn print_json<T: Serialize>(value: &T) -> serde_json::Result<()> {
+    println!("{}", to_json_line(value)?);
+    Ok(())
+}
+
+/// Prints an error as a single `{"error": ...}` JSON line to stdout
+pub fn print_json_error(message: &str) {
+    println!("{}", serde_json::json!({ "error": message }));
+}
+
 /// Prints an error message with appropriate styling
-pub fn print_error(message: &str, status_code: u16) {
+///
+/// `status` is the HTTP status the server answered with, or `None` for local errors.
+pub fn print_error(message: &str, status: Option<reqwest::StatusCode>) {
     let red = Style::new().red();
     let bold = Style::new().bold();
 
@@ -64,11 +347,13 @@ pub fn print_error(message: &str, status_code: u16) {
     eprintln!("{} {}", red.apply_to("✗"), bold.apply_to("Error"));
 
     // Add context based on status code
-    let context = match status_code {
-        400 => "Invalid request",
-        401 => "Unauthorized - check your CUTL_TOKEN",
-        409 => "Code already exists",
-        500 => "Server error - try again later",
+    let context = match status.map(|s| s.as_u16()) {
+        Some(400) => "Invalid request",
+        Some(401) => "Unauthorized - check your CUTL_TOKEN",
+        Some(404) => "Not found",
+        Some(409) => "Code already exists",
+        Some(429) => "Too many requests",
+        Some(500..=599) => "Server error - try again later",
         _ => "Request failed",
     };
 
@@ -80,6 +365,31 @@ pub fn print_error(message: &str, status_code: u16) {
 mod tests {
     use super::*;
 
+    #[cfg(all(unix, not(target_os = "macos")))]
+    #[test]
+    fn test_copy_without_display_is_an_error() {
+        // Only headless machines, like CI, are sure to have no clipboard
+        if std::env::var_os("DISPLAY").is_some() || std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            return;
+        }
+
+        let err = copy_to_clipboard("https://cutl.my.id/abc").unwrap_err();
+        assert!(err.to_string().starts_with("No clipboard available"));
+    }
+
+    #[test]
+    fn test_color_enabled() {
+        assert!(color_enabled(false, None, true));
+
+        // Any one reason is enough to turn color off
+        assert!(!color_enabled(true, None, true));
+        assert!(!color_enabled(false, Some("1"), true));
+        assert!(!color_enabled(false, None, false));
+
+        // An empty NO_COLOR doesn't count as set
+        assert!(color_enabled(false, Some(""), true));
+    }
+
     #[test]
     fn test_create_spinner() {
         let spinner = create_spinner("Test message");
@@ -105,14 +415,96 @@ mod tests {
         print_success(&response);
     }
 
+    #[test]
+    fn test_print_info() {
+        let mut info = crate::client::LinkInfoResponse {
+            code: "abc123".to_string(),
+            original_url: "https://example.com".to_string(),
+            created_at: 1739000000,
+            expires_at: 1760000000,
+            expired: false,
+        };
+        print_info(&info);
+
+        info.expired = true;
+        print_info(&info);
+    }
+
+    #[test]
+    fn test_print_deleted() {
+        print_deleted("abc123");
+    }
+
+    #[test]
+    fn test_sparkline() {
+        assert_eq!(sparkline(&[]), "");
+        assert_eq!(sparkline(&[0, 0]), "▁▁");
+        assert_eq!(sparkline(&[0, 7, 14]), "▁▄█");
+        assert_eq!(sparkline(&[5]), "█");
+    }
+
+    #[test]
+    fn test_print_stats() {
+        let stats = crate::client::AnalyticsResponse {
+            code: "abc123".to_string(),
+            original_url: "https://example.com".to_string(),
+            created_at: 1739000000,
+            expires_at: 1760000000,
+            total_visits: 3,
+            unique_visitors: 2,
+            countries: vec![crate::client::CountStat {
+                value: None,
+                count: 3,
+            }],
+            referers: vec![],
+            daily: vec![crate::client::DailyStat {
+                date: "2026-02-18".to_string(),
+                count: 3,
+            }],
+            recent_visits: vec![],
+        };
+        print_stats(&stats);
+    }
+
+    #[test]
+    fn test_render_qr() {
+        let qr = render_qr("https://cutl.my.id/abc123").unwrap();
+        assert!(!qr.is_empty());
+        assert!(qr.lines().count() > 1);
+    }
+
+    #[test]
+    fn test_to_json_line_shorten_response() {
+        let response = crate::client::ShortenResponse {
+            code: "abc123".to_string(),
+            short_url: "http://localhost:3000/abc123".to_string(),
+            expires_at: 1735689600,
+        };
+
+        let line = to_json_line(&response).unwrap();
+        assert!(!line.contains('\n'));
+
+        let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(json["code"], "abc123");
+        assert_eq!(json["short_url"], "http://localhost:3000/abc123");
+        assert_eq!(json["expires_at"], 1735689600);
+    }
+
+    #[test]
+    fn test_print_json_error() {
+        print_json_error("Something failed");
+    }
+
     #[test]
     fn test_print_error_various_codes() {
-        print_error("Test error message", 400);
-        print_error("Unauthorized", 401);
-        print_error("Conflict", 409);
-        print_error("Not found", 404);
-        print_error("Server error", 500);
-        print_error("Unknown error", 0);
-        print_error("No code provided", 999);
+        use reqwest::StatusCode;
+
+        print_error("Test error message", Some(StatusCode::BAD_REQUEST));
+        print_error("Unauthorized", Some(StatusCode::UNAUTHORIZED));
+        print_error("Conflict", Some(StatusCode::CONFLICT));
+        print_error("Not found", Some(StatusCode::NOT_FOUND));
+        print_error("Server error", Some(StatusCode::BAD_GATEWAY));
+        print_error("Unknown error", None);
+        print_error("No code provided", Some(StatusCode::IM_A_TEAPOT));
     }
 }
@@ -4,12 +4,20 @@
 
 use anyhow::{bail, Context};
 
+/// Longest URL accepted, matching the server's default `MAX_URL_LENGTH`
+pub const MAX_URL_LENGTH: usize = 2048;
+
 /// Validates that a URL is well-formed and safe
 ///
 /// # Rules
+/// - At most `MAX_URL_LENGTH` bytes long
 /// - Must start with `http://` or `https://`
 /// - Cannot point to `localhost` or `127.0.0.1`
 pub fn validate_url(url: &str) -> anyhow::Result<()> {
+    if url.len() > MAX_URL_LENGTH {
+        bail!("URL too long (max {} characters)", MAX_URL_LENGTH);
+    }
+
     // Check that URL starts with http:// or https://
     if !url.starts_with("http://") && !url.starts_with("https://") {
         bail!("URL must start with http:// or https://");
@@ -52,6 +60,24 @@ pub fn validate_code(code: &str) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Extracts the short code from a short URL or a bare code
+///
+/// `https://cutl.my.id/docs`, `https://cutl.my.id/docs?x=1`, and `docs` all
+/// yield `docs`. The result is checked with `validate_code`.
+pub fn code_from_input(input: &str) -> anyhow::Result<String> {
+    let input = input.trim();
+
+    let code = if input.starts_with("http://") || input.starts_with("https://") {
+        let parsed = url::Url::parse(input).context("Invalid short URL")?;
+        parsed.path().trim_matches('/').to_string()
+    } else {
+        input.to_string()
+    };
+
+    validate_code(&code)?;
+    Ok(code)
+}
+
 /// Validates the format of a TTL string
 ///
 /// # Supported formats
@@ -59,23 +85,49 @@ pub fn validate_code(code: &str) -> anyhow::Result<()> {
 /// - `5m` - 5 minutes
 /// - `1h` - 1 hour
 /// - `1d` - 1 day
+/// - `2w` - 2 weeks
+/// - `1mo` - 1 month (30 days)
+/// - `1h30m`, `2d12h` - compound values
+/// - `max`, `longest`, `default` - resolved by the server from its limits
 pub fn validate_ttl_format(ttl: &str) -> anyhow::Result<()> {
     let ttl = ttl.trim().to_lowercase();
 
+    match ttl.as_str() {
+        "max" | "longest" | "default" => return Ok(()),
+        "never" | "forever" => bail!("Links can't be permanent; use --ttl max for the longest TTL"),
+        _ => {}
+    }
+
     if ttl.len() < 2 {
         bail!("Invalid TTL format. Use format like 5m, 1h, 3d");
     }
 
-    let (num_str, unit) = ttl.split_at(ttl.len() - 1);
-
-    // Check that the number part is valid
-    num_str.parse::<u64>().context("Invalid TTL number")?;
-
-    // Check that the unit is valid
-    match unit {
-        "s" | "m" | "h" | "d" => Ok(()),
-        _ => bail!("Invalid TTL unit: {}. Use s, m, h, or d", unit),
+    let mut rest = ttl.as_str();
+
+    while !rest.is_empty() {
+        let num_len = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let (num_str, after_num) = rest.split_at(num_len);
+        let unit_len = after_num
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(after_num.len());
+        let (unit, after_unit) = after_num.split_at(unit_len);
+
+        // Check that the number part is valid
+        num_str.parse::<u64>().context("Invalid TTL number")?;
+
+        // Check that the unit is valid
+        match unit {
+            "s" | "m" | "h" | "d" | "w" | "mo" => {}
+            "" => bail!("Missing TTL unit after {}", num_str),
+            _ => bail!("Invalid TTL unit: {}. Use s, m, h, d, w, or mo", unit),
+        }
+
+        rest = after_unit;
     }
+
+    Ok(())
 }
 
 #[cfg(test)]
@@ -96,6 +148,16 @@ mod tests {
         assert!(validate_url("https://127.0.0.1").is_err());
     }
 
+    #[test]
+    fn test_validate_url_length_boundary() {
+        let prefix = "https://example.com/";
+        let at_limit = format!("{}{}", prefix, "a".repeat(MAX_URL_LENGTH - prefix.len()));
+        assert!(validate_url(&at_limit).is_ok());
+
+        let err = validate_url(&format!("{}a", at_limit)).unwrap_err();
+        assert!(err.to_string().starts_with("URL too long"));
+    }
+
     #[test]
     fn test_validate_code_valid() {
         assert!(validate_code("abc").is_ok());
@@ -112,18 +174,51 @@ mod tests {
         assert!(validate_code("abc def").is_err());
     }
 
+    #[test]
+    fn test_code_from_input() {
+        assert_eq!(code_from_input("docs").unwrap(), "docs");
+        assert_eq!(code_from_input("https://cutl.my.id/docs").unwrap(), "docs");
+        assert_eq!(
+            code_from_input("http://localhost:3000/abc123/?go=1").unwrap(),
+            "abc123"
+        );
+        assert!(code_from_input("https://cutl.my.id/").is_err());
+        assert!(code_from_input("https://cutl.my.id/a/b").is_err());
+        assert!(code_from_input("not a code").is_err());
+    }
+
     #[test]
     fn test_validate_ttl_format_valid() {
         assert!(validate_ttl_format("5s").is_ok());
         assert!(validate_ttl_format("5m").is_ok());
         assert!(validate_ttl_format("1h").is_ok());
         assert!(validate_ttl_format("3d").is_ok());
+        assert!(validate_ttl_format("2w").is_ok());
+        assert!(validate_ttl_format("1mo").is_ok());
     }
 
     #[test]
     fn test_validate_ttl_format_invalid() {
         assert!(validate_ttl_format("5").is_err());
-        assert!(validate_ttl_format("1w").is_err());
+        assert!(validate_ttl_format("1wk").is_err());
         assert!(validate_ttl_format("abc").is_err());
     }
+
+    #[test]
+    fn test_validate_ttl_format_aliases() {
+        assert!(validate_ttl_format("max").is_ok());
+        assert!(validate_ttl_format("Longest").is_ok());
+        assert!(validate_ttl_format("default").is_ok());
+        assert!(validate_ttl_format("never").is_err());
+        assert!(validate_ttl_format("forever").is_err());
+    }
+
+    #[test]
+    fn test_validate_ttl_format_compound() {
+        assert!(validate_ttl_format("1h30m").is_ok());
+        assert!(validate_ttl_format("2d12h").is_ok());
+        assert!(validate_ttl_format("1h2x").is_err());
+        assert!(validate_ttl_format("h30m").is_err());
+        assert!(validate_ttl_format("1h30").is_err());
+    }
 }
@@ -13,7 +13,41 @@ CREATE TABLE IF NOT EXISTS links (
     expires_at INTEGER NOT NULL,
 
     -- Creation timestamp (UNIX timestamp in seconds)
-    created_at INTEGER NOT NULL
+    created_at INTEGER NOT NULL,
+
+    -- Argon2 PHC hash of the link password; NULL for public links
+    password_hash TEXT,
+
+    -- Visit limit the link was created with; NULL for unlimited links
+    max_visits INTEGER,
+
+    -- Visits left before the link stops working; decremented atomically on redirect
+    remaining_visits INTEGER,
+
+    -- Label of the API token that created the link ("admin" for AUTH_TOKEN); NULL without auth
+    owner TEXT,
+
+    -- Query string merged into original_url on redirect (e.g. "utm_source=flyer"); NULL for none
+    append_query TEXT,
+
+    -- 0 while the link is disabled
+    enabled INTEGER NOT NULL DEFAULT 1,
+
+    -- JSON destination overrides, e.g. {"countries":{"DE":"..."},"languages":{"fr":"..."}}; NULL for none
+    rules TEXT,
+
+    -- Sliding links: seconds allowed without a visit; each visit moves expires_at this far ahead
+    idle_ttl INTEGER,
+
+    -- Sliding links: hard limit that visits can't push expires_at past
+    max_expires_at INTEGER,
+
+    -- 301, 302, 307, or 308 to override REDIRECT_STATUS for this link; NULL for the default
+    redirect_status INTEGER,
+
+    -- Human-readable label and notes shown in listings and the preview page; NULL for none
+    title TEXT,
+    description TEXT
 );
 
 -- Index for faster expiration-based cleanup
@@ -22,6 +56,12 @@ CREATE INDEX IF NOT EXISTS idx_links_expires_at ON links(expires_at);
 -- Index for faster lookups (though primary key is already indexed)
 CREATE INDEX IF NOT EXISTS idx_links_code ON links(code);
 
+-- Index for case-insensitive code lookups (CASE_INSENSITIVE_CODES)
+CREATE INDEX IF NOT EXISTS idx_links_code_nocase ON links(code COLLATE NOCASE);
+
+-- Created at startup only while CASE_INSENSITIVE_CODES is on, and dropped otherwise:
+-- CREATE UNIQUE INDEX IF NOT EXISTS idx_links_code_nocase_unique ON links(code COLLATE NOCASE);
+
 -- Visit tracking for analytics
 CREATE TABLE IF NOT EXISTS visits (
     id         INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -45,6 +85,9 @@ CREATE TABLE IF NOT EXISTS visits (
     -- Full User-Agent header value; NULL if not sent
     user_agent TEXT,
 
+    -- "desktop", "mobile", or "bot", guessed from the User-Agent; NULL if none was sent
+    device     TEXT,
+
     -- Referer header value; NULL if not sent
     referer    TEXT
 );
@@ -54,3 +97,18 @@ CREATE INDEX IF NOT EXISTS idx_visits_code ON visits(code);
 
 -- Index for time-range queries (e.g. daily aggregations)
 CREATE INDEX IF NOT EXISTS idx_visits_visited_at ON visits(visited_at);
+
+-- Read-only tokens that grant access to one link's analytics
+CREATE TABLE IF NOT EXISTS share_tokens (
+    -- Hex-encoded SHA-256 of the token; the token itself is never stored
+    token_hash TEXT    PRIMARY KEY,
+
+    -- The only code this token may read; cascades on delete
+    code       TEXT    NOT NULL REFERENCES links(code) ON DELETE CASCADE,
+
+    -- UNIX timestamp (seconds) when the token was minted
+    created_at INTEGER NOT NULL,
+
+    -- UNIX timestamp (seconds) after which the token is rejected
+    expires_at INTEGER NOT NULL
+);
@@ -0,0 +1,258 @@
+//! Token checks shared by the protected handlers
+//!
+//! With `AUTH_TOKEN` or `API_TOKENS` set, every API route other than redirects
+//! and public stats needs one of those tokens, sent as described in
+//! `utils::extract_token`. Tokens are compared in constant time so response
+//! timing gives away nothing about how close a guess was.
+
+use crate::{
+    models::{ApiError, AppState, Link},
+    utils::extract_token,
+};
+use axum::http::HeaderMap;
+use subtle::{Choice, ConstantTimeEq};
+
+/// Owner recorded for links created with the master `AUTH_TOKEN`
+pub const ADMIN_OWNER: &str = "admin";
+
+/// Checks the request's token against `AUTH_TOKEN` and `API_TOKENS`
+///
+/// Returns the owner label of the matched token, `ADMIN_OWNER` for the master
+/// `AUTH_TOKEN`, or `None` when no tokens are configured and auth is disabled.
+///
+/// # Errors
+/// Returns 401 if tokens are configured and the header is missing or doesn't match.
+pub fn authenticate(state: &AppState, headers: &HeaderMap) -> Result<Option<String>, ApiError> {
+    if state.auth_token.is_none() && state.api_tokens.is_empty() {
+        return Ok(None);
+    }
+
+    if let Some(supplied) = extract_token(headers, &state.auth_header) {
+        if state
+            .auth_token
+            .as_deref()
+            .is_some_and(|token| tokens_match(supplied, token))
+        {
+            return Ok(Some(ADMIN_OWNER.to_string()));
+        }
+        if let Some(api_token) = state
+            .api_tokens
+            .iter()
+            .find(|t| tokens_match(supplied, &t.token))
+        {
+            return Ok(Some(api_token.owner.clone()));
+        }
+    }
+
+    Err(ApiError::unauthorized(
+        "unauthorized",
+        "Invalid or missing authorization token",
+    ))
+}
+
+/// Rejects requests without a valid token, for handlers that don't need its owner
+///
+/// # Errors
+/// Returns 401 if tokens are configured and the header is missing or doesn't match.
+pub fn require_auth(state: &AppState, headers: &HeaderMap) -> Result<(), ApiError> {
+    authenticate(state, headers).map(|_| ())
+}
+
+/// Rejects requests not made with the master `AUTH_TOKEN`
+///
+/// Admin routes stay closed while auth is disabled, since anyone could call
+/// them. `action` names what was attempted in the 403 message.
+pub fn require_admin(state: &AppState, headers: &HeaderMap, action: &str) -> Result<(), ApiError> {
+    match authenticate(state, headers)? {
+        Some(owner) if owner == ADMIN_OWNER => Ok(()),
+        Some(_) => Err(ApiError::forbidden(
+            "admin_required",
+            format!("{} requires the admin token", action),
+        )),
+        None => Err(ApiError::forbidden(
+            "admin_required",
+            format!("{} requires AUTH_TOKEN to be set", action),
+        )),
+    }
+}
+
+/// Rejects callers who may not manage `link`
+///
+/// `owner` is what `authenticate` returned. The master token, and anyone while
+/// auth is disabled, may manage every link; an `API_TOKENS` token only the
+/// links it created. Other owners' links answer 404, as if they didn't exist.
+pub fn require_owner(owner: Option<&str>, link: &Link) -> Result<(), ApiError> {
+    match owner {
+        None | Some(ADMIN_OWNER) => Ok(()),
+        Some(owner) if link.owner.as_deref() == Some(owner) => Ok(()),
+        Some(_) => Err(ApiError::not_found("not_found", "Short link not found")),
+    }
+}
+
+/// Compares a supplied token with a configured one in constant time
+///
+/// The lengths are compared first without branching on the result, then the
+/// bytes over the whole configured token, so the timing shows neither how long
+/// the token is nor where a guess first goes wrong.
+fn tokens_match(supplied: &str, expected: &str) -> bool {
+    let supplied = supplied.as_bytes();
+    let expected = expected.as_bytes();
+
+    let same_len = (supplied.len() as u64).ct_eq(&(expected.len() as u64));
+    let same_bytes = expected
+        .iter()
+        .enumerate()
+        .fold(Choice::from(1), |same, (i, byte)| {
+            // Past the end of a short guess, compare against 0; same_len fails it anyway
+            same & supplied.get(i).copied().unwrap_or(0).ct_eq(byte)
+        });
+
+    (same_len & same_bytes).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{handlers::tests::test_state, models::ApiToken};
+    use axum::http::{header, HeaderValue, StatusCode};
+    use sqlx::SqlitePool;
+
+    async fn state() -> AppState {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let mut state = test_state(pool);
+        state.auth_token = Some("secret".to_string());
+        state.api_tokens = vec![ApiToken {
+            owner: "alice".to_string(),
+            token: "alice-token".to_string(),
+        }];
+        state
+    }
+
+    fn bearer(value: &[u8]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_bytes(value).unwrap(),
+        );
+        headers
+    }
+
+    #[tokio::test]
+    async fn test_require_auth_accepts_configured_tokens() {
+        let state = state().await;
+
+        assert!(require_auth(&state, &bearer(b"Bearer secret")).is_ok());
+        assert!(require_auth(&state, &bearer(b"Bearer alice-token")).is_ok());
+        assert_eq!(
+            authenticate(&state, &bearer(b"Bearer secret")).unwrap(),
+            Some(ADMIN_OWNER.to_string())
+        );
+        assert_eq!(
+            authenticate(&state, &bearer(b"Bearer alice-token")).unwrap(),
+            Some("alice".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_require_auth_rejects_almost_right_tokens() {
+        let state = state().await;
+
+        for value in [
+            &b"Bearer secreT"[..],
+            b"Bearer secre",
+            b"Bearer secret ",
+            b"Bearer secrets",
+            b"Bearer alice-toke",
+            b"bearer secret",
+            b"Bearer ",
+            b"secret",
+        ] {
+            let err = require_auth(&state, &bearer(value)).unwrap_err();
+            assert_eq!(err.status, StatusCode::UNAUTHORIZED);
+        }
+        assert!(require_auth(&state, &HeaderMap::new()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_require_auth_rejects_non_ascii_header() {
+        let state = state().await;
+
+        // Not valid header text, so it is rejected rather than sliced
+        let err = require_auth(&state, &bearer("Bearer sécret".as_bytes())).unwrap_err();
+        assert_eq!(err.status, StatusCode::UNAUTHORIZED);
+        let err = require_auth(&state, &bearer("Bé".as_bytes())).unwrap_err();
+        assert_eq!(err.status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_require_auth_disabled_without_tokens() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let state = test_state(pool);
+
+        assert!(require_auth(&state, &HeaderMap::new()).is_ok());
+        assert!(require_auth(&state, &bearer(b"Bearer anything")).is_ok());
+    }
+
+    #[test]
+    fn test_tokens_match_various_lengths() {
+        let long = "k".repeat(256);
+        for expected in ["a", "secret", "0123456789abcdef0123456789abcdef", &long] {
+            assert!(tokens_match(expected, expected));
+
+            // Shorter, longer, and same-length-but-different guesses
+            assert!(!tokens_match("", expected));
+            assert!(!tokens_match(&expected[..expected.len() - 1], expected));
+            assert!(!tokens_match(&format!("{}x", expected), expected));
+            assert!(!tokens_match(&format!("{}\0", expected), expected));
+            assert!(!tokens_match(&"z".repeat(expected.len()), expected));
+            assert!(!tokens_match(&expected.repeat(2), expected));
+        }
+
+        assert!(tokens_match("", ""));
+        assert!(!tokens_match("a", ""));
+        assert!(!tokens_match("sécret", "secret"));
+    }
+
+    #[tokio::test]
+    async fn test_require_auth_tokens_of_various_lengths() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let mut state = test_state(pool);
+        let tokens = ["x", "short", &"long-token-".repeat(20)].map(str::to_string);
+        state.api_tokens = tokens
+            .iter()
+            .map(|token| ApiToken {
+                owner: format!("owner-{}", token.len()),
+                token: token.clone(),
+            })
+            .collect();
+
+        for token in &tokens {
+            let headers = bearer(format!("Bearer {}", token).as_bytes());
+            assert_eq!(
+                authenticate(&state, &headers).unwrap(),
+                Some(format!("owner-{}", token.len()))
+            );
+
+            for guess in [&token[1..], &format!("{}x", token)] {
+                let headers = bearer(format!("Bearer {}", guess).as_bytes());
+                assert!(require_auth(&state, &headers).is_err());
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_require_admin() {
+        let state = state().await;
+
+        assert!(require_admin(&state, &bearer(b"Bearer secret"), "Cleanup").is_ok());
+        let err = require_admin(&state, &bearer(b"Bearer alice-token"), "Cleanup").unwrap_err();
+        assert_eq!(err.status, StatusCode::FORBIDDEN);
+        assert_eq!(err.message, "Cleanup requires the admin token");
+
+        // Closed to everyone while auth is disabled
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let open = test_state(pool);
+        let err = require_admin(&open, &HeaderMap::new(), "Cleanup").unwrap_err();
+        assert_eq!(err.status, StatusCode::FORBIDDEN);
+    }
+}
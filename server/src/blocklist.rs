@@ -0,0 +1,129 @@
+//! Destination domains that short links may not point to
+//!
+//! Domains come from `BLOCKLIST_DOMAINS` and, optionally, a `BLOCKLIST_FILE`
+//! with one domain per line. The file is re-read periodically, so a feed of
+//! known phishing domains can be updated without restarting the server.
+
+use crate::{shutdown, utils::DomainList};
+use anyhow::{Context, Result};
+use std::{
+    path::PathBuf,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+/// How often `BLOCKLIST_FILE` is re-read
+pub const BLOCKLIST_RELOAD_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Blocked destination domains, shared by every handler
+#[derive(Debug, Clone, Default)]
+pub struct Blocklist {
+    /// Domains from `BLOCKLIST_DOMAINS`, fixed for the life of the process
+    domains: DomainList,
+    /// File re-read by `reload`, if any
+    file: Option<PathBuf>,
+    /// Domains from the last successful read of `file`
+    from_file: Arc<RwLock<DomainList>>,
+}
+
+impl Blocklist {
+    /// Creates a blocklist; call `reload` to read `file` before using it
+    pub fn new(domains: DomainList, file: Option<PathBuf>) -> Self {
+        Self {
+            domains,
+            file,
+            from_file: Arc::default(),
+        }
+    }
+
+    /// Re-reads the blocklist file, returning the number of domains it lists
+    ///
+    /// # Errors
+    /// Returns an error if the file can't be read; the previous list stays in use.
+    pub async fn reload(&self) -> Result<usize> {
+        let Some(ref path) = self.file else {
+            return Ok(0);
+        };
+
+        let contents = tokio::fs::read_to_string(path)
+            .await
+            .with_context(|| format!("Failed to read blocklist file {}", path.display()))?;
+        let domains = DomainList::parse(&contents);
+        let count = domains.len();
+
+        *self.from_file.write().unwrap_or_else(|e| e.into_inner()) = domains;
+        Ok(count)
+    }
+
+    /// Checks whether the host of `url` is blocked
+    pub fn blocks_url(&self, url: &str) -> bool {
+        self.domains.contains_url(url)
+            || self
+                .from_file
+                .read()
+                .unwrap_or_else(|e| e.into_inner())
+                .contains_url(url)
+    }
+}
+
+/// Checks a destination against the blocklist and, when it isn't empty, the allowlist
+///
+/// Shared by every path that stores a destination, so links created, updated
+/// and imported are held to the same lists.
+pub fn destination_allowed(blocklist: &Blocklist, allowed_domains: &DomainList, url: &str) -> bool {
+    (allowed_domains.is_empty() || allowed_domains.contains_url(url)) && !blocklist.blocks_url(url)
+}
+
+/// Background task that re-reads the blocklist file every `BLOCKLIST_RELOAD_INTERVAL`
+///
+/// Returns immediately when no file is configured. A failed read is logged and
+/// the previous list kept.
+pub async fn reload_task(blocklist: Blocklist, mut shutdown_rx: watch::Receiver<bool>) {
+    if blocklist.file.is_none() {
+        return;
+    }
+
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(BLOCKLIST_RELOAD_INTERVAL) => {}
+            _ = shutdown::requested(&mut shutdown_rx) => break,
+        }
+
+        match blocklist.reload().await {
+            Ok(count) => info!("Reloaded blocklist file: {} domains", count),
+            Err(e) => warn!("{:#}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_reload_reads_file_and_keeps_list_on_error() {
+        let path = std::env::temp_dir().join(format!("cutl-blocklist-{}", std::process::id()));
+        std::fs::write(&path, "# feed\nphish.example\n").unwrap();
+
+        let blocklist = Blocklist::new(DomainList::parse("evil.com"), Some(path.clone()));
+        assert!(!blocklist.blocks_url("https://phish.example"));
+
+        assert_eq!(blocklist.reload().await.unwrap(), 1);
+        assert!(blocklist.blocks_url("https://login.phish.example/"));
+        assert!(blocklist.blocks_url("https://evil.com/"));
+        assert!(!blocklist.blocks_url("https://example.com/"));
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(blocklist.reload().await.is_err());
+        assert!(blocklist.blocks_url("https://phish.example"));
+    }
+
+    #[tokio::test]
+    async fn test_reload_without_file() {
+        let blocklist = Blocklist::default();
+        assert_eq!(blocklist.reload().await.unwrap(), 0);
+        assert!(!blocklist.blocks_url("https://example.com"));
+    }
+}
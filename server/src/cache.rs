@@ -0,0 +1,236 @@
+//! In-memory cache of links for the redirect path
+//!
+//! With `LINK_CACHE_SIZE` set, redirects look links up here before asking
+//! SQLite. Entries are dropped when the link is changed or deleted through the
+//! API, once they are older than `LINK_CACHE_TTL`, or when the least recently
+//! used entry has to make room. Links whose state changes on every visit
+//! (visit limits and sliding expiry) are never cached.
+
+use crate::models::Link;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+};
+
+/// Least-recently-used cache of links keyed by code, shared by every handler
+#[derive(Debug, Clone)]
+pub struct LinkCache {
+    inner: Arc<Mutex<CacheInner>>,
+    capacity: usize,
+    ttl_seconds: i64,
+}
+
+#[derive(Debug, Default)]
+struct CacheInner {
+    entries: HashMap<String, CacheEntry>,
+    /// Codes by the tick they were last used, oldest first
+    recency: BTreeMap<u64, String>,
+    /// Counter ordering uses; bumped on every insert and hit
+    tick: u64,
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    link: Link,
+    /// When the link was read from the database (UNIX seconds)
+    cached_at: i64,
+    /// Key of this entry in `recency`
+    last_used: u64,
+}
+
+impl CacheInner {
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    fn remove(&mut self, code: &str) {
+        if let Some(entry) = self.entries.remove(code) {
+            self.recency.remove(&entry.last_used);
+        }
+    }
+}
+
+impl LinkCache {
+    /// Creates a cache holding at most `capacity` links for `ttl_seconds` each
+    pub fn new(capacity: usize, ttl_seconds: i64) -> Self {
+        Self {
+            inner: Arc::default(),
+            capacity,
+            ttl_seconds,
+        }
+    }
+
+    /// Whether a link may be cached
+    ///
+    /// Visit-limited and sliding links are updated by every redirect, so a
+    /// cached copy would go stale straight away.
+    pub fn is_cacheable(link: &Link) -> bool {
+        link.remaining_visits.is_none() && link.idle_ttl.is_none()
+    }
+
+    /// Returns the link cached under `code`, unless it is missing, stale, or expired
+    pub fn get(&self, code: &str, now: i64) -> Option<Link> {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+
+        let fresh = inner.entries.get(code).map(|entry| {
+            now - entry.cached_at < self.ttl_seconds && now <= entry.link.expires_at
+        })?;
+        if !fresh {
+            inner.remove(code);
+            return None;
+        }
+
+        let tick = inner.next_tick();
+        let entry = inner.entries.get_mut(code)?;
+        let previous = std::mem::replace(&mut entry.last_used, tick);
+        let link = entry.link.clone();
+        inner.recency.remove(&previous);
+        inner.recency.insert(tick, code.to_string());
+
+        Some(link)
+    }
+
+    /// Caches `link` under `key` as read at `now`, evicting the least recently used link if full
+    pub fn insert(&self, key: &str, link: Link, now: i64) {
+        if self.capacity == 0 || !Self::is_cacheable(&link) {
+            return;
+        }
+
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.remove(key);
+
+        while inner.entries.len() >= self.capacity {
+            match inner.recency.pop_first() {
+                Some((_, oldest)) => {
+                    inner.entries.remove(&oldest);
+                }
+                None => break,
+            }
+        }
+
+        let tick = inner.next_tick();
+        inner.recency.insert(tick, key.to_string());
+        inner.entries.insert(
+            key.to_string(),
+            CacheEntry {
+                link,
+                cached_at: now,
+                last_used: tick,
+            },
+        );
+    }
+
+    /// Drops `code` after its link was changed or deleted
+    pub fn remove(&self, code: &str) {
+        self.inner
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(code);
+    }
+
+    /// Drops every cached link, for changes that touch many links at once
+    pub fn clear(&self) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.entries.clear();
+        inner.recency.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn link(code: &str, expires_at: i64) -> Link {
+        Link {
+            code: code.to_string(),
+            original_url: "https://example.com".to_string(),
+            expires_at,
+            created_at: 1000,
+            password_hash: None,
+            max_visits: None,
+            remaining_visits: None,
+            owner: None,
+            append_query: None,
+            enabled: true,
+            rules: None,
+            idle_ttl: None,
+            max_expires_at: None,
+            redirect_status: None,
+            title: None,
+            description: None,
+        }
+    }
+
+    #[test]
+    fn test_get_returns_inserted_link() {
+        let cache = LinkCache::new(10, 60);
+        assert!(cache.get("abc", 1000).is_none());
+
+        cache.insert("abc", link("abc", 5000), 1000);
+        assert_eq!(cache.get("abc", 1010).unwrap().code, "abc");
+
+        cache.remove("abc");
+        assert!(cache.get("abc", 1010).is_none());
+    }
+
+    #[test]
+    fn test_get_skips_stale_and_expired_links() {
+        let cache = LinkCache::new(10, 60);
+
+        cache.insert("old", link("old", 5000), 1000);
+        assert!(cache.get("old", 1059).is_some());
+        assert!(cache.get("old", 1060).is_none());
+
+        // Never served past the link's own expiry, however fresh the entry
+        cache.insert("short", link("short", 1030), 1000);
+        assert!(cache.get("short", 1030).is_some());
+        assert!(cache.get("short", 1031).is_none());
+    }
+
+    #[test]
+    fn test_insert_evicts_least_recently_used() {
+        let cache = LinkCache::new(2, 60);
+
+        cache.insert("a", link("a", 5000), 1000);
+        cache.insert("b", link("b", 5000), 1000);
+        // Using `a` leaves `b` as the oldest
+        assert!(cache.get("a", 1000).is_some());
+        cache.insert("c", link("c", 5000), 1000);
+
+        assert!(cache.get("a", 1000).is_some());
+        assert!(cache.get("b", 1000).is_none());
+        assert!(cache.get("c", 1000).is_some());
+    }
+
+    #[test]
+    fn test_insert_skips_uncacheable_links() {
+        let cache = LinkCache::new(10, 60);
+
+        let mut limited = link("limited", 5000);
+        limited.remaining_visits = Some(3);
+        cache.insert("limited", limited, 1000);
+        assert!(cache.get("limited", 1000).is_none());
+
+        let mut sliding = link("sliding", 5000);
+        sliding.idle_ttl = Some(3600);
+        cache.insert("sliding", sliding, 1000);
+        assert!(cache.get("sliding", 1000).is_none());
+
+        // A zero-size cache holds nothing
+        let cache = LinkCache::new(0, 60);
+        cache.insert("abc", link("abc", 5000), 1000);
+        assert!(cache.get("abc", 1000).is_none());
+    }
+
+    #[test]
+    fn test_clear_drops_everything() {
+        let cache = LinkCache::new(10, 60);
+        cache.insert("a", link("a", 5000), 1000);
+        cache.insert("b", link("b", 5000), 1000);
+
+        cache.clear();
+        assert!(cache.get("a", 1000).is_none());
+        assert!(cache.get("b", 1000).is_none());
+    }
+}
@@ -0,0 +1,466 @@
+//! Background sweep of expired links and old visits
+//!
+//! The sweep runs on a configurable interval with a little random jitter,
+//! so several replicas sharing a database don't all write at the same moment.
+//! A second task scrubs IPs and user agents from visits past
+//! `PII_RETENTION_DAYS` on its own schedule.
+
+use crate::{
+    database::{
+        anonymize_old_visits, delete_expired_links, delete_expired_share_tokens,
+        delete_visits_older_than,
+    },
+    shutdown,
+    utils::now_unix,
+    webhook::{Webhook, WebhookPayload},
+};
+use rand::{Rng, RngExt};
+use sqlx::{Pool, Sqlite};
+use std::time::Duration;
+use tokio::sync::watch;
+use tracing::info;
+
+/// Jitter is at most `1 / CLEANUP_JITTER_DIVISOR` of the interval (10%)
+const CLEANUP_JITTER_DIVISOR: u64 = 10;
+
+/// Seconds in a day, for converting `VISIT_RETENTION_DAYS` and `PII_RETENTION_DAYS`
+pub const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// Returns the delay before the next sweep: `interval_seconds` plus up to 10% jitter
+pub fn next_delay<R: Rng + ?Sized>(rng: &mut R, interval_seconds: u64) -> Duration {
+    let jitter = rng.random_range(0..=interval_seconds / CLEANUP_JITTER_DIVISOR);
+    Duration::from_secs(interval_seconds + jitter)
+}
+
+/// Background task that periodically deletes expired links and old visits
+///
+/// Sweeps once at startup, then every `interval_seconds` (plus jitter) until a
+/// shutdown is requested.
+/// Visits are kept forever when `visit_retention_days` is `None`.
+/// Each deleted link is announced to `webhook` as an `expired` event.
+/// An interval of 0 disables the task, and it returns immediately.
+pub async fn cleanup_task(
+    db: Pool<Sqlite>,
+    interval_seconds: u64,
+    visit_retention_days: Option<u64>,
+    webhook: Option<Webhook>,
+    mut shutdown_rx: watch::Receiver<bool>,
+) {
+    if interval_seconds == 0 {
+        info!("Expired link cleanup is disabled");
+        return;
+    }
+
+    loop {
+        sweep(&db, now_unix(), visit_retention_days, webhook.as_ref()).await;
+
+        let delay = next_delay(&mut rand::rng(), interval_seconds);
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = shutdown::requested(&mut shutdown_rx) => break,
+        }
+    }
+}
+
+/// Runs one cleanup pass, logging what was deleted
+async fn sweep(
+    db: &Pool<Sqlite>,
+    now: i64,
+    visit_retention_days: Option<u64>,
+    webhook: Option<&Webhook>,
+) {
+    match delete_expired_links(db, now).await {
+        Ok(links) => {
+            if !links.is_empty() {
+                info!("Cleaned up {} expired links", links.len());
+            }
+            if let Some(webhook) = webhook {
+                for link in &links {
+                    webhook.send(WebhookPayload::expired(link, now));
+                }
+            }
+        }
+        Err(e) => {
+            tracing::error!("Failed to cleanup expired links: {}", e);
+        }
+    }
+
+    if let Err(e) = delete_expired_share_tokens(db, now).await {
+        tracing::error!("Failed to delete expired share tokens: {}", e);
+    }
+
+    if let Some(days) = visit_retention_days {
+        let cutoff = days_before(now, days);
+
+        match delete_visits_older_than(db, cutoff).await {
+            Ok(count) => {
+                if count > 0 {
+                    info!("Deleted {} visits older than {} days", count, days);
+                }
+            }
+            Err(e) => {
+                tracing::error!("Failed to delete old visits: {}", e);
+            }
+        }
+    }
+}
+
+/// Background task that periodically anonymizes visits older than `retention_days`
+///
+/// Runs once at startup, then every `interval_seconds` (plus jitter) until a
+/// shutdown is requested.
+/// IPs are hashed with `ip_hash_salt`, the salt `IP_STORAGE=hashed` uses.
+/// Returns immediately when `retention_days` is `None` or the interval is 0.
+pub async fn anonymize_task(
+    db: Pool<Sqlite>,
+    interval_seconds: u64,
+    retention_days: Option<u64>,
+    ip_hash_salt: String,
+    mut shutdown_rx: watch::Receiver<bool>,
+) {
+    let Some(days) = retention_days.filter(|_| interval_seconds > 0) else {
+        info!("Visit anonymization is disabled");
+        return;
+    };
+
+    loop {
+        scrub(&db, now_unix(), days, &ip_hash_salt).await;
+
+        let delay = next_delay(&mut rand::rng(), interval_seconds);
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = shutdown::requested(&mut shutdown_rx) => break,
+        }
+    }
+}
+
+/// Returns the time `days` days before `now`
+///
+/// Saturates instead of overflowing, so a huge retention window just keeps
+/// everything.
+pub fn days_before(now: i64, days: u64) -> i64 {
+    i64::try_from(days)
+        .ok()
+        .and_then(|days| days.checked_mul(SECONDS_PER_DAY))
+        .map_or(i64::MIN, |seconds| now.saturating_sub(seconds))
+}
+
+/// Runs one anonymization pass, logging how many visits were scrubbed
+async fn scrub(db: &Pool<Sqlite>, now: i64, retention_days: u64, ip_hash_salt: &str) {
+    let cutoff = days_before(now, retention_days);
+
+    match anonymize_old_visits(db, cutoff, ip_hash_salt).await {
+        Ok(count) => {
+            if count > 0 {
+                info!(
+                    "Anonymized {} visits older than {} days",
+                    count, retention_days
+                );
+            }
+        }
+        Err(e) => {
+            tracing::error!("Failed to anonymize old visits: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{
+        count_unique_visitors, count_visits, get_link, insert_link, insert_visit, run_migrations,
+    };
+    use crate::models::{NewLink, NewVisit};
+    use rand::{rngs::StdRng, SeedableRng};
+    use sqlx::SqlitePool;
+
+    #[test]
+    fn test_next_delay_stays_within_jitter() {
+        let mut rng = StdRng::seed_from_u64(7);
+
+        for _ in 0..100 {
+            let delay = next_delay(&mut rng, 300);
+            assert!(delay >= Duration::from_secs(300));
+            assert!(delay <= Duration::from_secs(330));
+        }
+
+        // Intervals under ten seconds get no jitter
+        assert_eq!(next_delay(&mut rng, 5), Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_task_disabled_returns_immediately() {
+        let pool = SqlitePool::connect_lazy("sqlite::memory:").unwrap();
+        // Keep the sender alive so only the zero interval can end the task
+        let (_shutdown_tx, shutdown_rx) = watch::channel(false);
+
+        tokio::time::timeout(
+            Duration::from_secs(1),
+            cleanup_task(pool, 0, None, None, shutdown_rx),
+        )
+        .await
+        .expect("a zero interval should disable the task");
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_task_stops_on_shutdown() {
+        let pool = SqlitePool::connect_lazy("sqlite::memory:").unwrap();
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+
+        let task = tokio::spawn(cleanup_task(pool, 3600, None, None, shutdown_rx));
+        shutdown_tx.send_replace(true);
+
+        tokio::time::timeout(Duration::from_secs(1), task)
+            .await
+            .expect("the task should stop on shutdown")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_task_sweeps_at_startup() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        run_migrations(&pool).await.unwrap();
+        insert_link(
+            &pool,
+            &NewLink {
+                code: "gone",
+                original_url: "https://example.com",
+                expires_at: 1500000000,
+                created_at: 1000000000,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+
+        // The first sweep comes long before the hour-long interval is up
+        let task = tokio::spawn(cleanup_task(pool.clone(), 3600, None, None, shutdown_rx));
+        for _ in 0..100 {
+            if get_link(&pool, "gone").await.unwrap().is_none() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(get_link(&pool, "gone").await.unwrap().is_none());
+
+        shutdown_tx.send_replace(true);
+        task.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_sweep_applies_visit_retention() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        run_migrations(&pool).await.unwrap();
+        insert_link(
+            &pool,
+            &NewLink {
+                code: "kept",
+                original_url: "https://example.com",
+                expires_at: 9999999999,
+                created_at: 1000000000,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let now = 2000000000;
+        for visited_at in [now - 40 * SECONDS_PER_DAY, now - SECONDS_PER_DAY] {
+            insert_visit(
+                &pool,
+                &NewVisit {
+                    code: "kept".to_string(),
+                    visited_at,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        }
+
+        // Without a retention window nothing is pruned
+        sweep(&pool, now, None, None).await;
+        assert_eq!(count_visits(&pool, "kept").await.unwrap(), 2);
+
+        // A window too long to express in seconds keeps everything
+        sweep(&pool, now, Some(u64::MAX), None).await;
+        assert_eq!(count_visits(&pool, "kept").await.unwrap(), 2);
+
+        sweep(&pool, now, Some(30), None).await;
+        assert_eq!(count_visits(&pool, "kept").await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_anonymize_task_disabled_returns_immediately() {
+        let pool = SqlitePool::connect_lazy("sqlite::memory:").unwrap();
+        let (_shutdown_tx, shutdown_rx) = watch::channel(false);
+
+        tokio::time::timeout(
+            Duration::from_secs(1),
+            anonymize_task(pool.clone(), 3600, None, String::new(), shutdown_rx.clone()),
+        )
+        .await
+        .expect("no retention window should disable the task");
+
+        tokio::time::timeout(
+            Duration::from_secs(1),
+            anonymize_task(pool, 0, Some(30), String::new(), shutdown_rx),
+        )
+        .await
+        .expect("a zero interval should disable the task");
+    }
+
+    #[tokio::test]
+    async fn test_scrub_keeps_recent_visits() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        run_migrations(&pool).await.unwrap();
+        insert_link(
+            &pool,
+            &NewLink {
+                code: "kept",
+                original_url: "https://example.com",
+                expires_at: 9999999999,
+                created_at: 1000000000,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let now = 2000000000;
+        for visited_at in [now - 40 * SECONDS_PER_DAY, now - SECONDS_PER_DAY] {
+            insert_visit(
+                &pool,
+                &NewVisit {
+                    code: "kept".to_string(),
+                    visited_at,
+                    ip: Some("203.0.113.7".to_string()),
+                    user_agent: Some("Mozilla/5.0".to_string()),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        }
+
+        scrub(&pool, now, 30, "salt").await;
+
+        let visits = crate::database::recent_visits(&pool, "kept", 10, 0)
+            .await
+            .unwrap();
+        assert_eq!(visits.len(), 2);
+        assert_eq!(visits[0].ip.as_deref(), Some("203.0.113.7"));
+        assert_eq!(
+            visits[1].ip,
+            Some(crate::utils::hash_ip("203.0.113.7", "salt"))
+        );
+        assert!(visits[1].user_agent.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_scrub_keeps_unique_visitors() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        run_migrations(&pool).await.unwrap();
+        insert_link(
+            &pool,
+            &NewLink {
+                code: "kept",
+                original_url: "https://example.com",
+                expires_at: 9999999999,
+                created_at: 1000000000,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let now = 2000000000;
+        let old = now - 40 * SECONDS_PER_DAY;
+        for (visited_at, ip) in [
+            (old, "203.0.113.7"),
+            (old + 1, "203.0.113.7"),
+            (old + 2, "203.0.113.8"),
+            (old + 3, "2001:db8::1"),
+            (now - SECONDS_PER_DAY, "198.51.100.1"),
+        ] {
+            insert_visit(
+                &pool,
+                &NewVisit {
+                    code: "kept".to_string(),
+                    visited_at,
+                    ip: Some(ip.to_string()),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        }
+        assert_eq!(count_unique_visitors(&pool, "kept").await.unwrap(), 4);
+
+        scrub(&pool, now, 30, "salt").await;
+        assert_eq!(count_unique_visitors(&pool, "kept").await.unwrap(), 4);
+
+        // A second pass doesn't hash the hashes
+        scrub(&pool, now, 30, "salt").await;
+        assert_eq!(count_unique_visitors(&pool, "kept").await.unwrap(), 4);
+    }
+
+    #[test]
+    fn test_days_before_saturates() {
+        assert_eq!(days_before(2000000000, 1), 2000000000 - SECONDS_PER_DAY);
+        assert_eq!(days_before(2000000000, u64::MAX), i64::MIN);
+        assert_eq!(days_before(2000000000, i64::MAX as u64), i64::MIN);
+        assert_eq!(
+            days_before(0, (i64::MAX / SECONDS_PER_DAY) as u64),
+            -(i64::MAX / SECONDS_PER_DAY) * SECONDS_PER_DAY
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sweep_announces_expired_links() {
+        use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        run_migrations(&pool).await.unwrap();
+        for (code, expires_at) in [("gone", 1500000000), ("live", 9999999999)] {
+            insert_link(
+                &pool,
+                &NewLink {
+                    code,
+                    original_url: "https://example.com",
+                    expires_at,
+                    created_at: 1000000000,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        }
+
+        let webhook = Webhook::new(server.uri()).unwrap();
+        sweep(&pool, 2000000000, None, Some(&webhook)).await;
+
+        let mut requests = Vec::new();
+        for _ in 0..100 {
+            requests = server.received_requests().await.unwrap();
+            if !requests.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        let payload: serde_json::Value = requests[0].body_json().unwrap();
+        assert_eq!(payload["event"], "expired");
+        assert_eq!(payload["code"], "gone");
+        assert_eq!(payload["expires_at"], 1500000000);
+        assert_eq!(payload["timestamp"], 2000000000);
+    }
+}
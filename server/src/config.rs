@@ -2,8 +2,48 @@
 //!
 //! Loads configuration from environment variables with sensible defaults.
 
-use anyhow::Result;
-use std::env;
+use crate::{
+    auth::ADMIN_OWNER,
+    database::{
+        PoolSettings, DEFAULT_BUSY_TIMEOUT_SECONDS, DEFAULT_MAX_CONNECTIONS,
+        DEFAULT_MIN_CONNECTIONS,
+    },
+    middleware::RateLimitKey,
+    models::{ApiToken, RedirectStatus},
+    utils::{
+        parse_duration, CodeAlphabet, DomainList, IpAllowlist, IpStorage, UrlNormalization,
+        BOT_USER_AGENT_PATTERNS, DEFAULT_CODE_MAX_ATTEMPTS, DEFAULT_CODE_MAX_LENGTH,
+        DEFAULT_CODE_MIN_LENGTH, DEFAULT_MAX_URL_LENGTH, DEFAULT_RESERVED_CODES,
+        DEFAULT_TTL_SECONDS, MAX_CODE_LENGTH, MAX_TTL_SECONDS, MIN_TTL_SECONDS,
+    },
+};
+use anyhow::{bail, Context, Result};
+use sqlx::sqlite::SqliteJournalMode;
+use std::{env, str::FromStr, time::Duration};
+
+/// Default burst size for the redirect rate limit, when one is set
+pub const DEFAULT_REDIRECT_RATE_LIMIT_BURST: u32 = 10;
+
+/// Default password attempts per minute per client on `POST /{code}`
+pub const DEFAULT_PASSWORD_RATE_LIMIT: u32 = 5;
+
+/// Default burst size for password attempts
+pub const DEFAULT_PASSWORD_RATE_LIMIT_BURST: u32 = 3;
+
+/// Default time between sweeps of expired links (60 seconds)
+pub const DEFAULT_CLEANUP_INTERVAL_SECONDS: u64 = 60;
+
+/// Default time between anonymization passes over old visits (1 hour)
+pub const DEFAULT_PII_SCRUB_INTERVAL_SECONDS: u64 = 60 * 60;
+
+/// Default time a cached link is trusted before it's read again (60 seconds)
+pub const DEFAULT_LINK_CACHE_TTL_SECONDS: i64 = 60;
+
+/// Default time allowed for in-flight requests to finish on shutdown (30 seconds)
+pub const DEFAULT_SHUTDOWN_TIMEOUT_SECONDS: i64 = 30;
+
+/// Default limit on `POST /shorten` and `/api/shorten` request bodies (16 KiB)
+pub const DEFAULT_MAX_BODY_BYTES: usize = 16 * 1024;
 
 /// Server configuration loaded from environment variables
 #[derive(Debug, Clone)]
@@ -11,24 +51,199 @@ pub struct Config {
     /// SQLite database file path (e.g., "sqlite:cutl.db")
     pub database_url: String,
 
+    /// SQLite journal mode (default: WAL)
+    pub sqlite_journal_mode: SqliteJournalMode,
+
+    /// Seconds a connection waits on a locked database before failing (default: 5)
+    pub sqlite_busy_timeout_seconds: i64,
+
+    /// Maximum open database connections (default: 5)
+    pub db_max_connections: u32,
+
+    /// Database connections kept open while idle (default: 1)
+    pub db_min_connections: u32,
+
     /// Base URL for generating short links (e.g., "http://localhost:3000")
     pub base_url: String,
 
+    /// Extra hosts, such as vanity domains, whose requests get short links on that host
+    pub allowed_hosts: Vec<String>,
+
     /// Address to bind the server to (e.g., "0.0.0.0:3000")
     pub bind_address: String,
 
     /// Optional bearer token for API authentication
     pub auth_token: Option<String>,
 
+    /// Named bearer tokens; links record the owner of the token that created them
+    pub api_tokens: Vec<ApiToken>,
+
+    /// Further header that may carry the API token, beside `Authorization: Bearer` and `X-API-Key` (default: Authorization)
+    pub auth_header: axum::http::HeaderName,
+
+    /// Take client IPs from `X-Forwarded-For`/`X-Real-IP`/`Forwarded` (default: false)
+    pub trust_proxy: bool,
+
     /// Rate limit: maximum requests per minute (default: 10)
     pub rate_limit: u32,
 
     /// Rate limit burst size (default: 2)
     pub rate_limit_burst: u32,
 
+    /// Redirect rate limit: maximum `GET /{code}` requests per minute per client (0 = unlimited, default)
+    pub redirect_rate_limit: u32,
+
+    /// Redirect rate limit burst size (default: 10)
+    pub redirect_rate_limit_burst: u32,
+
+    /// Password attempts (`POST /{code}`) per minute per client (default: 5)
+    pub password_rate_limit: u32,
+
+    /// Password attempt burst size (default: 3)
+    pub password_rate_limit_burst: u32,
+
+    /// Client IPs and networks exempt from both rate limits (default: none)
+    pub rate_limit_allowlist: IpAllowlist,
+
+    /// Where rate limits get the client IP (default: forwarded with `TRUST_PROXY`, socket without)
+    pub rate_limit_key: RateLimitKey,
+
     /// Optional path to a GeoLite2 .mmdb file for IP geolocation.
     /// If None, country/city columns are stored as NULL.
     pub geoip_db_path: Option<String>,
+
+    /// What visits store as the visitor's IP: raw, hashed, or nothing (default: raw)
+    pub ip_storage: IpStorage,
+
+    /// Salt for hashed IPs; a random one is used when unset (default: None)
+    pub ip_hash_salt: Option<String>,
+
+    /// Return the existing code when a URL is shortened again (default: false)
+    pub dedup_urls: bool,
+
+    /// How submitted URLs are normalized before storing (default: off)
+    pub url_normalization: UrlNormalization,
+
+    /// Destination domains, with their subdomains, that links may not point to (default: none)
+    pub blocklist_domains: DomainList,
+
+    /// File of further blocked domains, one per line, re-read periodically (default: None)
+    pub blocklist_file: Option<String>,
+
+    /// When set, the only destination domains (with subdomains) links may point to (default: any)
+    pub allowed_domains: DomainList,
+
+    /// Reject destinations whose hostname resolves to a private address (default: true)
+    pub resolve_destinations: bool,
+
+    /// Redirect status for short links (default: temporary)
+    pub redirect_status: RedirectStatus,
+
+    /// Minimum TTL in seconds (default: 5 minutes)
+    pub min_ttl_seconds: i64,
+
+    /// Maximum TTL in seconds (default: 30 days)
+    pub max_ttl_seconds: i64,
+
+    /// TTL in seconds used when a request doesn't specify one (default: 7 days)
+    pub default_ttl_seconds: i64,
+
+    /// Maximum number of links in one batch shorten request (default: 500)
+    pub max_batch_size: usize,
+
+    /// Largest accepted body for single-link shorten requests, in bytes (default: 16 KiB)
+    pub max_body_bytes: usize,
+
+    /// Longest destination URL accepted, in bytes (default: 2048)
+    pub max_url_length: usize,
+
+    /// Shortest auto-generated code (default: 6)
+    pub code_min_length: usize,
+
+    /// Longest auto-generated code (default: 8)
+    pub code_max_length: usize,
+
+    /// Character set for auto-generated codes (default: base62)
+    pub code_alphabet: CodeAlphabet,
+
+    /// Random codes tried before falling back to a longer code (default: 10)
+    pub code_max_attempts: usize,
+
+    /// Match short codes ignoring case (default: false)
+    pub case_insensitive_codes: bool,
+
+    /// Regenerate auto-generated codes that spell a blocklisted word (default: true)
+    pub filter_bad_codes: bool,
+
+    /// Codes that can't be registered: the built-in route names plus `RESERVED_CODES`
+    pub reserved_codes: Vec<String>,
+
+    /// Show an interstitial page with the destination before redirecting (default: false)
+    pub preview_mode: bool,
+
+    /// Record visits from crawlers and link-preview bots (default: false)
+    pub count_bots: bool,
+
+    /// Lowercase User-Agent substrings of bots: the built-ins plus `BOT_USER_AGENTS`
+    pub bot_user_agents: Vec<String>,
+
+    /// Serve the unauthenticated `POST /api/shorten` route (default: true)
+    pub public_shorten: bool,
+
+    /// Cache lifetime in seconds for redirect responses (default: None, sends `no-store`)
+    pub redirect_cache_max_age: Option<i64>,
+
+    /// Seconds between sweeps of expired links; 0 disables the sweep (default: 60)
+    pub cleanup_interval_seconds: u64,
+
+    /// Days of visit history kept by the cleanup sweep (default: None, kept forever)
+    pub visit_retention_days: Option<u64>,
+
+    /// Days before a visit's IP is hashed and its user agent cleared (default: None, kept)
+    pub pii_retention_days: Option<u64>,
+
+    /// Seconds between anonymization passes; 0 disables them (default: 3600)
+    pub pii_scrub_interval_seconds: u64,
+
+    /// Seconds visits are buffered before being written; 0 writes each immediately (default: 0)
+    pub visit_flush_interval_seconds: i64,
+
+    /// Links kept in the in-memory redirect cache; 0 disables it (default: 0)
+    pub link_cache_size: usize,
+
+    /// Seconds a cached link is served before it's read again (default: 60)
+    pub link_cache_ttl_seconds: i64,
+
+    /// URL that receives a POST for every link created or expired (default: None)
+    pub webhook_url: Option<String>,
+
+    /// Seconds in-flight requests get to finish after a shutdown signal (default: 30)
+    pub shutdown_timeout_seconds: i64,
+
+    /// Log output format (default: pretty)
+    pub log_format: LogFormat,
+}
+
+/// Output format for server logs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human-readable lines
+    #[default]
+    Pretty,
+    /// One JSON object per line, for log shippers
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "pretty" => Ok(Self::Pretty),
+            "json" => Ok(Self::Json),
+            other => bail!("Invalid log format: {}. Use pretty or json", other),
+        }
+    }
 }
 
 impl Config {
@@ -36,17 +251,174 @@ impl Config {
     ///
     /// Environment variables:
     /// - `DATABASE_URL`: SQLite database path (default: "sqlite:cutl.db")
+    /// - `SQLITE_JOURNAL_MODE`: `wal`, `delete`, `truncate`, `persist`, `memory`, or `off` (default: wal)
+    /// - `SQLITE_BUSY_TIMEOUT`: How long to wait on a locked database (default: "5s")
+    /// - `DB_MAX_CONNECTIONS`: Maximum open database connections (default: 5)
+    /// - `DB_MIN_CONNECTIONS`: Database connections kept open while idle (default: 1)
     /// - `BASE_URL`: Base URL for short links (default: "http://localhost:3000")
+    /// - `ALLOWED_HOSTS`: Comma-separated hosts that short links may use instead of `BASE_URL`'s
     /// - `BIND_ADDRESS`: Server bind address (default: "0.0.0.0:3000")
     /// - `AUTH_TOKEN`: Optional bearer token for API auth
+    /// - `API_TOKENS`: Comma-separated `owner:token` pairs (default: none)
+    /// - `AUTH_HEADER`: Another header carrying the bare token, beside `X-API-Key` (default: Authorization)
+    /// - `TRUST_PROXY`: Honor client IP headers set by a reverse proxy (default: false)
     /// - `RATE_LIMIT`: Rate limit requests per minute (default: 10)
     /// - `RATE_LIMIT_BURST`: Rate limit burst size (default: 2)
+    /// - `REDIRECT_RATE_LIMIT`: Redirects per minute per client, 0 for unlimited (default: 0)
+    /// - `REDIRECT_RATE_LIMIT_BURST`: Redirect rate limit burst size (default: 10)
+    /// - `PASSWORD_RATE_LIMIT`: Password attempts per minute per client (default: 5)
+    /// - `PASSWORD_RATE_LIMIT_BURST`: Password attempt burst size (default: 3)
+    /// - `RATE_LIMIT_KEY`: `socket` or `forwarded` (default: forwarded with `TRUST_PROXY`, else socket)
+    /// - `RATE_LIMIT_ALLOWLIST`: Comma-separated IPs/CIDRs never rate limited, `private` for private ranges (default: none)
+    /// - `GEOIP_DB_PATH`: Optional path to a GeoLite2 .mmdb file
+    /// - `IP_STORAGE`: `raw`, `hashed`, or `none` (default: raw)
+    /// - `IP_HASH_SALT`: Salt for `IP_STORAGE=hashed` (default: random per process)
+    /// - `DEDUP_URLS`: Reuse existing codes for identical URLs (default: false)
+    /// - `NORMALIZE_URLS`: `off`, `basic`, or `full` (default: off)
+    /// - `BLOCKLIST_DOMAINS`: Comma-separated destination domains to reject, subdomains included (default: none)
+    /// - `BLOCKLIST_FILE`: Path to a file of domains to reject, one per line (default: unset)
+    /// - `ALLOWED_DOMAINS`: Comma-separated destination domains to accept, rejecting all others (default: any)
+    /// - `RESOLVE_DESTINATIONS`: Look up destination hosts and reject private addresses (default: true)
+    /// - `REDIRECT_STATUS`: `permanent`, `temporary`, or `see_other` (default: temporary)
+    /// - `MIN_TTL`: Minimum TTL as a duration string (default: "5m")
+    /// - `MAX_TTL`: Maximum TTL as a duration string (default: "30d")
+    /// - `DEFAULT_TTL`: Default TTL as a duration string (default: "7d")
+    /// - `MAX_BATCH_SIZE`: Maximum links per batch shorten request (default: 500)
+    /// - `MAX_BODY_BYTES`: Largest `/shorten` and `/api/shorten` body in bytes (default: 16384)
+    /// - `MAX_URL_LENGTH`: Longest destination URL in bytes (default: 2048)
+    /// - `CODE_LENGTH`: Generated code length, fixed (`10`) or a range (`6-8`) (default: 6-8)
+    /// - `CODE_ALPHABET`: `base62`, `base58`, `lowercase`, or `no-ambiguous` (default: base62)
+    /// - `CODE_MAX_ATTEMPTS`: Random codes tried before using a longer one (default: 10)
+    /// - `CASE_INSENSITIVE_CODES`: Match short codes ignoring case (default: false)
+    /// - `FILTER_BAD_CODES`: Never generate codes containing offensive words (default: true)
+    /// - `RESERVED_CODES`: Comma-separated codes to reserve on top of the route names
+    /// - `PREVIEW_MODE`: Show the destination before redirecting (default: false)
+    /// - `COUNT_BOTS`: Record visits from crawlers in analytics (default: false)
+    /// - `BOT_USER_AGENTS`: Comma-separated User-Agent substrings to treat as bots, on top of the built-ins
+    /// - `PUBLIC_SHORTEN`: Serve the unauthenticated `POST /api/shorten` route (default: true)
+    /// - `REDIRECT_CACHE_MAX_AGE`: Duration browsers may cache redirects (default: unset, no-store)
+    /// - `CLEANUP_INTERVAL_SECONDS`: Seconds between expired-link sweeps, 0 disables (default: 60)
+    /// - `VISIT_RETENTION_DAYS`: Delete visits older than this many days (default: unset, keep forever)
+    /// - `PII_RETENTION_DAYS`: Hash IPs and clear user agents of visits older than this (default: unset)
+    /// - `PII_SCRUB_INTERVAL_SECONDS`: Seconds between anonymization passes, 0 disables (default: 3600)
+    /// - `VISIT_FLUSH_INTERVAL`: Buffer visits and write them this often (default: unset, write each visit)
+    /// - `LINK_CACHE_SIZE`: Links cached in memory for redirects, 0 disables (default: 0)
+    /// - `LINK_CACHE_TTL`: How long a cached link is served before re-reading it (default: "60s")
+    /// - `WEBHOOK_URL`: http(s) URL notified when links are created or expire (default: unset)
+    /// - `SHUTDOWN_TIMEOUT`: How long to drain connections on shutdown (default: "30s")
+    /// - `LOG_FORMAT`: `pretty` or `json` (default: pretty)
+    ///
+    /// # Errors
+    /// Returns an error if a value is malformed or the TTL limits are inconsistent.
     pub fn from_env() -> Result<Self> {
+        let min_ttl_seconds = env_duration("MIN_TTL", MIN_TTL_SECONDS)?;
+        let max_ttl_seconds = env_duration("MAX_TTL", MAX_TTL_SECONDS)?;
+        let default_ttl_seconds = env_duration("DEFAULT_TTL", DEFAULT_TTL_SECONDS)?;
+
+        if min_ttl_seconds <= 0 {
+            bail!("MIN_TTL must be greater than zero");
+        }
+        if min_ttl_seconds > max_ttl_seconds {
+            bail!("MIN_TTL cannot be greater than MAX_TTL");
+        }
+        if default_ttl_seconds < min_ttl_seconds || default_ttl_seconds > max_ttl_seconds {
+            bail!("DEFAULT_TTL must be between MIN_TTL and MAX_TTL");
+        }
+
+        let (code_min_length, code_max_length) = match env::var("CODE_LENGTH") {
+            Ok(value) => parse_code_length(&value)?,
+            Err(_) => (DEFAULT_CODE_MIN_LENGTH, DEFAULT_CODE_MAX_LENGTH),
+        };
+
+        let code_max_attempts = match env::var("CODE_MAX_ATTEMPTS") {
+            Ok(value) => value.trim().parse().context("Invalid CODE_MAX_ATTEMPTS")?,
+            Err(_) => DEFAULT_CODE_MAX_ATTEMPTS,
+        };
+        if code_max_attempts == 0 {
+            bail!("CODE_MAX_ATTEMPTS must be greater than zero");
+        }
+
+        // Also decides the default RATE_LIMIT_KEY
+        let trust_proxy = env_flag("TRUST_PROXY");
+
+        let visit_retention_days: Option<u64> = match env::var("VISIT_RETENTION_DAYS") {
+            Ok(value) => Some(
+                value
+                    .trim()
+                    .parse()
+                    .context("Invalid VISIT_RETENTION_DAYS")?,
+            ),
+            Err(_) => None,
+        };
+        if visit_retention_days == Some(0) {
+            bail!("VISIT_RETENTION_DAYS must be greater than zero");
+        }
+
+        let pii_retention_days: Option<u64> = match env::var("PII_RETENTION_DAYS") {
+            Ok(value) => Some(value.trim().parse().context("Invalid PII_RETENTION_DAYS")?),
+            Err(_) => None,
+        };
+        if pii_retention_days == Some(0) {
+            bail!("PII_RETENTION_DAYS must be greater than zero");
+        }
+
+        let webhook_url = match env::var("WEBHOOK_URL") {
+            Ok(value) => {
+                let url = url::Url::parse(value.trim()).context("Invalid WEBHOOK_URL")?;
+                if !matches!(url.scheme(), "http" | "https") {
+                    bail!("WEBHOOK_URL must be an http or https URL");
+                }
+                Some(url.to_string())
+            }
+            Err(_) => None,
+        };
+
+        let db_max_connections: u32 = match env::var("DB_MAX_CONNECTIONS") {
+            Ok(value) => value.trim().parse().context("Invalid DB_MAX_CONNECTIONS")?,
+            Err(_) => DEFAULT_MAX_CONNECTIONS,
+        };
+        let db_min_connections: u32 = match env::var("DB_MIN_CONNECTIONS") {
+            Ok(value) => value.trim().parse().context("Invalid DB_MIN_CONNECTIONS")?,
+            Err(_) => DEFAULT_MIN_CONNECTIONS.min(db_max_connections),
+        };
+        if db_max_connections == 0 {
+            bail!("DB_MAX_CONNECTIONS must be greater than zero");
+        }
+        if db_min_connections > db_max_connections {
+            bail!("DB_MIN_CONNECTIONS cannot be greater than DB_MAX_CONNECTIONS");
+        }
+
         Ok(Self {
             database_url: env::var("DATABASE_URL").unwrap_or_else(|_| "sqlite:cutl.db".to_string()),
+            sqlite_journal_mode: match env::var("SQLITE_JOURNAL_MODE") {
+                Ok(value) => value
+                    .trim()
+                    .parse()
+                    .context("Invalid SQLITE_JOURNAL_MODE")?,
+                Err(_) => SqliteJournalMode::Wal,
+            },
+            sqlite_busy_timeout_seconds: env_duration(
+                "SQLITE_BUSY_TIMEOUT",
+                DEFAULT_BUSY_TIMEOUT_SECONDS,
+            )?,
+            db_max_connections,
+            db_min_connections,
             base_url: env::var("BASE_URL").unwrap_or_else(|_| "http://localhost:3000".to_string()),
+            allowed_hosts: allowed_hosts(env::var("ALLOWED_HOSTS").ok().as_deref()),
             bind_address: env::var("BIND_ADDRESS").unwrap_or_else(|_| "0.0.0.0:3000".to_string()),
             auth_token: env::var("AUTH_TOKEN").ok(),
+            api_tokens: match env::var("API_TOKENS") {
+                Ok(value) => parse_api_tokens(&value)?,
+                Err(_) => Vec::new(),
+            },
+            auth_header: match env::var("AUTH_HEADER") {
+                Ok(value) => value
+                    .trim()
+                    .parse()
+                    .with_context(|| format!("Invalid AUTH_HEADER: {}", value))?,
+                Err(_) => axum::http::header::AUTHORIZATION,
+            },
+            trust_proxy,
             rate_limit: env::var("RATE_LIMIT")
                 .ok()
                 .and_then(|s| s.parse().ok())
@@ -55,9 +427,266 @@ impl Config {
                 .ok()
                 .and_then(|s| s.parse().ok())
                 .unwrap_or(2),
+            redirect_rate_limit: env::var("REDIRECT_RATE_LIMIT")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(0),
+            redirect_rate_limit_burst: env::var("REDIRECT_RATE_LIMIT_BURST")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(DEFAULT_REDIRECT_RATE_LIMIT_BURST),
+            password_rate_limit: env::var("PASSWORD_RATE_LIMIT")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(DEFAULT_PASSWORD_RATE_LIMIT),
+            password_rate_limit_burst: env::var("PASSWORD_RATE_LIMIT_BURST")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(DEFAULT_PASSWORD_RATE_LIMIT_BURST),
+            rate_limit_allowlist: match env::var("RATE_LIMIT_ALLOWLIST") {
+                Ok(value) => IpAllowlist::parse(&value).context("Invalid RATE_LIMIT_ALLOWLIST")?,
+                Err(_) => IpAllowlist::default(),
+            },
+            rate_limit_key: match env::var("RATE_LIMIT_KEY") {
+                Ok(value) => value.parse()?,
+                Err(_) if trust_proxy => RateLimitKey::Forwarded,
+                Err(_) => RateLimitKey::Socket,
+            },
             geoip_db_path: env::var("GEOIP_DB_PATH").ok(),
+            ip_storage: match env::var("IP_STORAGE") {
+                Ok(value) => value.parse()?,
+                Err(_) => IpStorage::default(),
+            },
+            ip_hash_salt: env::var("IP_HASH_SALT").ok().filter(|s| !s.is_empty()),
+            dedup_urls: env_flag("DEDUP_URLS"),
+            url_normalization: match env::var("NORMALIZE_URLS") {
+                Ok(value) => value.parse()?,
+                Err(_) => UrlNormalization::default(),
+            },
+            blocklist_domains: DomainList::parse(
+                &env::var("BLOCKLIST_DOMAINS").unwrap_or_default(),
+            ),
+            blocklist_file: env::var("BLOCKLIST_FILE").ok(),
+            allowed_domains: DomainList::parse(&env::var("ALLOWED_DOMAINS").unwrap_or_default()),
+            resolve_destinations: env_flag_or("RESOLVE_DESTINATIONS", true),
+            redirect_status: match env::var("REDIRECT_STATUS") {
+                Ok(value) => value.parse()?,
+                Err(_) => RedirectStatus::default(),
+            },
+            min_ttl_seconds,
+            max_ttl_seconds,
+            default_ttl_seconds,
+            max_batch_size: match env::var("MAX_BATCH_SIZE") {
+                Ok(value) => value.trim().parse().context("Invalid MAX_BATCH_SIZE")?,
+                Err(_) => 500,
+            },
+            max_body_bytes: match env::var("MAX_BODY_BYTES") {
+                Ok(value) => value.trim().parse().context("Invalid MAX_BODY_BYTES")?,
+                Err(_) => DEFAULT_MAX_BODY_BYTES,
+            },
+            max_url_length: match env::var("MAX_URL_LENGTH") {
+                Ok(value) => value.trim().parse().context("Invalid MAX_URL_LENGTH")?,
+                Err(_) => DEFAULT_MAX_URL_LENGTH,
+            },
+            code_min_length,
+            code_max_length,
+            code_alphabet: match env::var("CODE_ALPHABET") {
+                Ok(value) => value.parse()?,
+                Err(_) => CodeAlphabet::default(),
+            },
+            code_max_attempts,
+            case_insensitive_codes: env_flag("CASE_INSENSITIVE_CODES"),
+            filter_bad_codes: env_flag_or("FILTER_BAD_CODES", true),
+            reserved_codes: reserved_codes(env::var("RESERVED_CODES").ok().as_deref()),
+            preview_mode: env_flag("PREVIEW_MODE"),
+            count_bots: env_flag("COUNT_BOTS"),
+            bot_user_agents: bot_user_agents(env::var("BOT_USER_AGENTS").ok().as_deref()),
+            public_shorten: env_flag_or("PUBLIC_SHORTEN", true),
+            redirect_cache_max_age: match env::var("REDIRECT_CACHE_MAX_AGE") {
+                Ok(value) => {
+                    Some(parse_duration(&value).context("Invalid REDIRECT_CACHE_MAX_AGE")?)
+                }
+                Err(_) => None,
+            },
+            cleanup_interval_seconds: match env::var("CLEANUP_INTERVAL_SECONDS") {
+                Ok(value) => value
+                    .trim()
+                    .parse()
+                    .context("Invalid CLEANUP_INTERVAL_SECONDS")?,
+                Err(_) => DEFAULT_CLEANUP_INTERVAL_SECONDS,
+            },
+            visit_retention_days,
+            pii_retention_days,
+            pii_scrub_interval_seconds: match env::var("PII_SCRUB_INTERVAL_SECONDS") {
+                Ok(value) => value
+                    .trim()
+                    .parse()
+                    .context("Invalid PII_SCRUB_INTERVAL_SECONDS")?,
+                Err(_) => DEFAULT_PII_SCRUB_INTERVAL_SECONDS,
+            },
+            visit_flush_interval_seconds: env_duration("VISIT_FLUSH_INTERVAL", 0)?,
+            link_cache_size: match env::var("LINK_CACHE_SIZE") {
+                Ok(value) => value.trim().parse().context("Invalid LINK_CACHE_SIZE")?,
+                Err(_) => 0,
+            },
+            link_cache_ttl_seconds: env_duration("LINK_CACHE_TTL", DEFAULT_LINK_CACHE_TTL_SECONDS)?,
+            webhook_url,
+            shutdown_timeout_seconds: env_duration(
+                "SHUTDOWN_TIMEOUT",
+                DEFAULT_SHUTDOWN_TIMEOUT_SECONDS,
+            )?,
+            log_format: match env::var("LOG_FORMAT") {
+                Ok(value) => value.parse()?,
+                Err(_) => LogFormat::default(),
+            },
         })
     }
+
+    /// Connection settings for `database::create_pool`
+    pub fn pool_settings(&self) -> PoolSettings {
+        PoolSettings {
+            journal_mode: self.sqlite_journal_mode,
+            busy_timeout: Duration::from_secs(self.sqlite_busy_timeout_seconds as u64),
+            max_connections: self.db_max_connections,
+            min_connections: self.db_min_connections,
+        }
+    }
+}
+
+/// Parses `CODE_LENGTH` as a fixed length (`10`) or an inclusive range (`6-8`)
+///
+/// # Errors
+/// Returns an error if a length isn't a number or the range is outside `1..=MAX_CODE_LENGTH`.
+fn parse_code_length(value: &str) -> Result<(usize, usize)> {
+    let (min, max) = value.split_once('-').unwrap_or((value, value));
+    let min: usize = min.trim().parse().context("Invalid CODE_LENGTH")?;
+    let max: usize = max.trim().parse().context("Invalid CODE_LENGTH")?;
+
+    if min == 0 || min > max || max > MAX_CODE_LENGTH {
+        bail!(
+            "CODE_LENGTH must be between 1 and {}, with the minimum first",
+            MAX_CODE_LENGTH
+        );
+    }
+
+    Ok((min, max))
+}
+
+/// Combines `DEFAULT_RESERVED_CODES` with a comma-separated list of extra codes
+fn reserved_codes(extra: Option<&str>) -> Vec<String> {
+    let extra = extra
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|c| !c.is_empty());
+
+    let mut codes: Vec<String> = Vec::new();
+    for code in DEFAULT_RESERVED_CODES.iter().copied().chain(extra) {
+        if !codes.iter().any(|c| c.eq_ignore_ascii_case(code)) {
+            codes.push(code.to_lowercase());
+        }
+    }
+
+    codes
+}
+
+/// Combines `BOT_USER_AGENT_PATTERNS` with a comma-separated list of extra
+/// substrings, lowercased so `is_bot` can match them
+fn bot_user_agents(extra: Option<&str>) -> Vec<String> {
+    let extra = extra
+        .unwrap_or_default()
+        .split(',')
+        .map(|p| p.trim().to_lowercase())
+        .filter(|p| !p.is_empty());
+
+    let mut patterns: Vec<String> = Vec::new();
+    for pattern in BOT_USER_AGENT_PATTERNS
+        .iter()
+        .map(|p| p.to_string())
+        .chain(extra)
+    {
+        if !patterns.contains(&pattern) {
+            patterns.push(pattern);
+        }
+    }
+
+    patterns
+}
+
+/// Parses `ALLOWED_HOSTS` as a comma-separated list of `host` or `host:port` entries, lowercased
+fn allowed_hosts(value: Option<&str>) -> Vec<String> {
+    let mut hosts: Vec<String> = Vec::new();
+
+    for host in value.unwrap_or_default().split(',').map(str::trim) {
+        let host = host.to_lowercase();
+        if !host.is_empty() && !hosts.contains(&host) {
+            hosts.push(host);
+        }
+    }
+
+    hosts
+}
+
+/// Parses a comma-separated list of `owner:token` pairs
+///
+/// # Errors
+/// Returns an error if an entry is missing its owner or token, or an owner is repeated.
+fn parse_api_tokens(value: &str) -> Result<Vec<ApiToken>> {
+    let mut tokens: Vec<ApiToken> = Vec::new();
+
+    for entry in value.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let Some((owner, token)) = entry.split_once(':') else {
+            bail!("Invalid API_TOKENS entry: expected owner:token");
+        };
+        let (owner, token) = (owner.trim(), token.trim());
+
+        if owner.is_empty() || token.is_empty() {
+            bail!("Invalid API_TOKENS entry: owner and token cannot be empty");
+        }
+        if owner == ADMIN_OWNER {
+            bail!(
+                "Invalid API_TOKENS owner: {} is reserved for AUTH_TOKEN",
+                ADMIN_OWNER
+            );
+        }
+        if tokens.iter().any(|t| t.owner == owner) {
+            bail!("Duplicate API_TOKENS owner: {}", owner);
+        }
+
+        tokens.push(ApiToken {
+            owner: owner.to_string(),
+            token: token.to_string(),
+        });
+    }
+
+    Ok(tokens)
+}
+
+/// Reads a duration string (e.g. "1h", "30d") from the environment as seconds
+fn env_duration(name: &str, default: i64) -> Result<i64> {
+    match env::var(name) {
+        Ok(value) => parse_duration(&value).with_context(|| format!("Invalid {}", name)),
+        Err(_) => Ok(default),
+    }
+}
+
+/// Reads a boolean flag from the environment
+///
+/// Accepts "1", "true", "yes", or "on" (case-insensitive); anything else is false.
+fn env_flag(name: &str) -> bool {
+    env_flag_or(name, false)
+}
+
+/// Reads a boolean flag from the environment, returning `default` when it is unset
+fn env_flag_or(name: &str, default: bool) -> bool {
+    env::var(name)
+        .map(|v| {
+            matches!(
+                v.trim().to_lowercase().as_str(),
+                "1" | "true" | "yes" | "on"
+            )
+        })
+        .unwrap_or(default)
 }
 
 #[cfg(test)]
@@ -71,23 +700,124 @@ mod tests {
     /// Helper to clear all config-related environment variables
     fn clear_env_vars() {
         std::env::remove_var("DATABASE_URL");
+        std::env::remove_var("SQLITE_JOURNAL_MODE");
+        std::env::remove_var("SQLITE_BUSY_TIMEOUT");
+        std::env::remove_var("DB_MAX_CONNECTIONS");
+        std::env::remove_var("DB_MIN_CONNECTIONS");
         std::env::remove_var("BASE_URL");
+        std::env::remove_var("IP_STORAGE");
+        std::env::remove_var("IP_HASH_SALT");
+        std::env::remove_var("ALLOWED_HOSTS");
         std::env::remove_var("BIND_ADDRESS");
         std::env::remove_var("AUTH_TOKEN");
+        std::env::remove_var("API_TOKENS");
+        std::env::remove_var("AUTH_HEADER");
+        std::env::remove_var("TRUST_PROXY");
         std::env::remove_var("RATE_LIMIT");
         std::env::remove_var("RATE_LIMIT_BURST");
+        std::env::remove_var("REDIRECT_RATE_LIMIT");
+        std::env::remove_var("REDIRECT_RATE_LIMIT_BURST");
+        std::env::remove_var("RATE_LIMIT_ALLOWLIST");
+        std::env::remove_var("PASSWORD_RATE_LIMIT");
+        std::env::remove_var("PASSWORD_RATE_LIMIT_BURST");
+        std::env::remove_var("RATE_LIMIT_KEY");
+        std::env::remove_var("DEDUP_URLS");
+        std::env::remove_var("NORMALIZE_URLS");
+        std::env::remove_var("BLOCKLIST_DOMAINS");
+        std::env::remove_var("BLOCKLIST_FILE");
+        std::env::remove_var("ALLOWED_DOMAINS");
+        std::env::remove_var("RESOLVE_DESTINATIONS");
+        std::env::remove_var("REDIRECT_STATUS");
+        std::env::remove_var("MIN_TTL");
+        std::env::remove_var("MAX_TTL");
+        std::env::remove_var("DEFAULT_TTL");
+        std::env::remove_var("MAX_BATCH_SIZE");
+        std::env::remove_var("MAX_BODY_BYTES");
+        std::env::remove_var("MAX_URL_LENGTH");
+        std::env::remove_var("CODE_LENGTH");
+        std::env::remove_var("CODE_ALPHABET");
+        std::env::remove_var("CODE_MAX_ATTEMPTS");
+        std::env::remove_var("CASE_INSENSITIVE_CODES");
+        std::env::remove_var("FILTER_BAD_CODES");
+        std::env::remove_var("RESERVED_CODES");
+        std::env::remove_var("PREVIEW_MODE");
+        std::env::remove_var("COUNT_BOTS");
+        std::env::remove_var("BOT_USER_AGENTS");
+        std::env::remove_var("PUBLIC_SHORTEN");
+        std::env::remove_var("REDIRECT_CACHE_MAX_AGE");
+        std::env::remove_var("CLEANUP_INTERVAL_SECONDS");
+        std::env::remove_var("VISIT_RETENTION_DAYS");
+        std::env::remove_var("PII_RETENTION_DAYS");
+        std::env::remove_var("PII_SCRUB_INTERVAL_SECONDS");
+        std::env::remove_var("VISIT_FLUSH_INTERVAL");
+        std::env::remove_var("LINK_CACHE_SIZE");
+        std::env::remove_var("LINK_CACHE_TTL");
+        std::env::remove_var("WEBHOOK_URL");
+        std::env::remove_var("SHUTDOWN_TIMEOUT");
+        std::env::remove_var("LOG_FORMAT");
     }
 
     #[test]
     fn test_config_new() {
         let config = Config {
             database_url: "sqlite:test.db".to_string(),
+            sqlite_journal_mode: SqliteJournalMode::Wal,
+            sqlite_busy_timeout_seconds: DEFAULT_BUSY_TIMEOUT_SECONDS,
+            db_max_connections: DEFAULT_MAX_CONNECTIONS,
+            db_min_connections: DEFAULT_MIN_CONNECTIONS,
             base_url: "http://localhost:3000".to_string(),
+            allowed_hosts: Vec::new(),
             bind_address: "0.0.0.0:3000".to_string(),
             auth_token: Some("token".to_string()),
+            api_tokens: Vec::new(),
+            auth_header: axum::http::header::AUTHORIZATION,
+            trust_proxy: false,
             rate_limit: 10,
             rate_limit_burst: 2,
+            redirect_rate_limit: 0,
+            redirect_rate_limit_burst: DEFAULT_REDIRECT_RATE_LIMIT_BURST,
+            password_rate_limit: DEFAULT_PASSWORD_RATE_LIMIT,
+            password_rate_limit_burst: DEFAULT_PASSWORD_RATE_LIMIT_BURST,
+            rate_limit_allowlist: IpAllowlist::default(),
+            rate_limit_key: RateLimitKey::Socket,
             geoip_db_path: None,
+            ip_storage: IpStorage::Raw,
+            ip_hash_salt: None,
+            dedup_urls: false,
+            url_normalization: UrlNormalization::Off,
+            blocklist_domains: DomainList::default(),
+            blocklist_file: None,
+            allowed_domains: DomainList::default(),
+            resolve_destinations: true,
+            redirect_status: RedirectStatus::Temporary,
+            min_ttl_seconds: MIN_TTL_SECONDS,
+            max_ttl_seconds: MAX_TTL_SECONDS,
+            default_ttl_seconds: DEFAULT_TTL_SECONDS,
+            max_batch_size: 500,
+            max_body_bytes: DEFAULT_MAX_BODY_BYTES,
+            max_url_length: DEFAULT_MAX_URL_LENGTH,
+            code_min_length: DEFAULT_CODE_MIN_LENGTH,
+            code_max_length: DEFAULT_CODE_MAX_LENGTH,
+            code_alphabet: CodeAlphabet::Base62,
+            code_max_attempts: DEFAULT_CODE_MAX_ATTEMPTS,
+            case_insensitive_codes: false,
+            filter_bad_codes: true,
+            reserved_codes: Vec::new(),
+            preview_mode: false,
+            count_bots: false,
+            bot_user_agents: Vec::new(),
+            public_shorten: true,
+            redirect_cache_max_age: None,
+            cleanup_interval_seconds: DEFAULT_CLEANUP_INTERVAL_SECONDS,
+            visit_retention_days: None,
+            pii_retention_days: None,
+            pii_scrub_interval_seconds: DEFAULT_PII_SCRUB_INTERVAL_SECONDS,
+            visit_flush_interval_seconds: 0,
+            link_cache_size: 0,
+            link_cache_ttl_seconds: DEFAULT_LINK_CACHE_TTL_SECONDS,
+            webhook_url: None,
+            shutdown_timeout_seconds: DEFAULT_SHUTDOWN_TIMEOUT_SECONDS,
+            log_format: LogFormat::Pretty,
         };
 
         assert_eq!(config.database_url, "sqlite:test.db");
@@ -102,12 +832,63 @@ mod tests {
     fn test_config_debug_clone() {
         let config = Config {
             database_url: "sqlite:test.db".to_string(),
+            sqlite_journal_mode: SqliteJournalMode::Wal,
+            sqlite_busy_timeout_seconds: DEFAULT_BUSY_TIMEOUT_SECONDS,
+            db_max_connections: DEFAULT_MAX_CONNECTIONS,
+            db_min_connections: DEFAULT_MIN_CONNECTIONS,
             base_url: "http://localhost:3000".to_string(),
+            allowed_hosts: Vec::new(),
             bind_address: "0.0.0.0:3000".to_string(),
             auth_token: Some("token".to_string()),
+            api_tokens: Vec::new(),
+            auth_header: axum::http::header::AUTHORIZATION,
+            trust_proxy: false,
             rate_limit: 10,
             rate_limit_burst: 2,
+            redirect_rate_limit: 0,
+            redirect_rate_limit_burst: DEFAULT_REDIRECT_RATE_LIMIT_BURST,
+            password_rate_limit: DEFAULT_PASSWORD_RATE_LIMIT,
+            password_rate_limit_burst: DEFAULT_PASSWORD_RATE_LIMIT_BURST,
+            rate_limit_allowlist: IpAllowlist::default(),
+            rate_limit_key: RateLimitKey::Socket,
             geoip_db_path: None,
+            ip_storage: IpStorage::Raw,
+            ip_hash_salt: None,
+            dedup_urls: false,
+            url_normalization: UrlNormalization::Off,
+            blocklist_domains: DomainList::default(),
+            blocklist_file: None,
+            allowed_domains: DomainList::default(),
+            resolve_destinations: true,
+            redirect_status: RedirectStatus::Temporary,
+            min_ttl_seconds: MIN_TTL_SECONDS,
+            max_ttl_seconds: MAX_TTL_SECONDS,
+            default_ttl_seconds: DEFAULT_TTL_SECONDS,
+            max_batch_size: 500,
+            max_body_bytes: DEFAULT_MAX_BODY_BYTES,
+            max_url_length: DEFAULT_MAX_URL_LENGTH,
+            code_min_length: DEFAULT_CODE_MIN_LENGTH,
+            code_max_length: DEFAULT_CODE_MAX_LENGTH,
+            code_alphabet: CodeAlphabet::Base62,
+            code_max_attempts: DEFAULT_CODE_MAX_ATTEMPTS,
+            case_insensitive_codes: false,
+            filter_bad_codes: true,
+            reserved_codes: Vec::new(),
+            preview_mode: false,
+            count_bots: false,
+            bot_user_agents: Vec::new(),
+            public_shorten: true,
+            redirect_cache_max_age: None,
+            cleanup_interval_seconds: DEFAULT_CLEANUP_INTERVAL_SECONDS,
+            visit_retention_days: None,
+            pii_retention_days: None,
+            pii_scrub_interval_seconds: DEFAULT_PII_SCRUB_INTERVAL_SECONDS,
+            visit_flush_interval_seconds: 0,
+            link_cache_size: 0,
+            link_cache_ttl_seconds: DEFAULT_LINK_CACHE_TTL_SECONDS,
+            webhook_url: None,
+            shutdown_timeout_seconds: DEFAULT_SHUTDOWN_TIMEOUT_SECONDS,
+            log_format: LogFormat::Pretty,
         };
 
         // Test Clone trait
@@ -125,9 +906,422 @@ mod tests {
         clear_env_vars();
         let config = Config::from_env().unwrap();
         assert_eq!(config.database_url, "sqlite:cutl.db");
+        assert_eq!(config.sqlite_journal_mode, SqliteJournalMode::Wal);
+        assert_eq!(
+            config.sqlite_busy_timeout_seconds,
+            DEFAULT_BUSY_TIMEOUT_SECONDS
+        );
+        assert_eq!(config.db_max_connections, DEFAULT_MAX_CONNECTIONS);
+        assert_eq!(config.db_min_connections, DEFAULT_MIN_CONNECTIONS);
         assert_eq!(config.base_url, "http://localhost:3000");
+        assert!(config.allowed_hosts.is_empty());
         assert_eq!(config.bind_address, "0.0.0.0:3000");
         assert!(config.auth_token.is_none());
+        assert!(config.api_tokens.is_empty());
+        assert_eq!(config.auth_header, axum::http::header::AUTHORIZATION);
+        assert!(!config.trust_proxy);
+        assert_eq!(config.redirect_rate_limit, 0);
+        assert_eq!(
+            config.redirect_rate_limit_burst,
+            DEFAULT_REDIRECT_RATE_LIMIT_BURST
+        );
+        assert_eq!(config.password_rate_limit, DEFAULT_PASSWORD_RATE_LIMIT);
+        assert_eq!(
+            config.password_rate_limit_burst,
+            DEFAULT_PASSWORD_RATE_LIMIT_BURST
+        );
+        assert!(config.rate_limit_allowlist.is_empty());
+        assert_eq!(config.rate_limit_key, RateLimitKey::Socket);
+        assert_eq!(config.ip_storage, IpStorage::Raw);
+        assert!(config.ip_hash_salt.is_none());
+        assert!(!config.dedup_urls);
+        assert_eq!(config.url_normalization, UrlNormalization::Off);
+        assert!(config.blocklist_domains.is_empty());
+        assert!(config.blocklist_file.is_none());
+        assert!(config.allowed_domains.is_empty());
+        assert!(config.resolve_destinations);
+        assert_eq!(config.redirect_status, RedirectStatus::Temporary);
+        assert_eq!(config.min_ttl_seconds, MIN_TTL_SECONDS);
+        assert_eq!(config.max_ttl_seconds, MAX_TTL_SECONDS);
+        assert_eq!(config.default_ttl_seconds, DEFAULT_TTL_SECONDS);
+        assert_eq!(config.max_batch_size, 500);
+        assert_eq!(config.max_body_bytes, DEFAULT_MAX_BODY_BYTES);
+        assert_eq!(config.max_url_length, DEFAULT_MAX_URL_LENGTH);
+        assert_eq!(config.code_min_length, DEFAULT_CODE_MIN_LENGTH);
+        assert_eq!(config.code_max_length, DEFAULT_CODE_MAX_LENGTH);
+        assert_eq!(config.code_alphabet, CodeAlphabet::Base62);
+        assert_eq!(config.code_max_attempts, DEFAULT_CODE_MAX_ATTEMPTS);
+        assert!(!config.case_insensitive_codes);
+        assert!(config.filter_bad_codes);
+        assert_eq!(config.reserved_codes.len(), DEFAULT_RESERVED_CODES.len());
+        assert!(!config.preview_mode);
+        assert!(!config.count_bots);
+        assert_eq!(config.bot_user_agents.len(), BOT_USER_AGENT_PATTERNS.len());
+        assert!(config.public_shorten);
+        assert!(config.redirect_cache_max_age.is_none());
+        assert_eq!(
+            config.cleanup_interval_seconds,
+            DEFAULT_CLEANUP_INTERVAL_SECONDS
+        );
+        assert!(config.visit_retention_days.is_none());
+        assert!(config.pii_retention_days.is_none());
+        assert_eq!(
+            config.pii_scrub_interval_seconds,
+            DEFAULT_PII_SCRUB_INTERVAL_SECONDS
+        );
+        assert_eq!(config.visit_flush_interval_seconds, 0);
+        assert_eq!(config.link_cache_size, 0);
+        assert_eq!(
+            config.link_cache_ttl_seconds,
+            DEFAULT_LINK_CACHE_TTL_SECONDS
+        );
+        assert!(config.webhook_url.is_none());
+        assert_eq!(
+            config.shutdown_timeout_seconds,
+            DEFAULT_SHUTDOWN_TIMEOUT_SECONDS
+        );
+        assert_eq!(config.log_format, LogFormat::Pretty);
+    }
+
+    #[test]
+    fn test_config_from_env_custom_ttl_limits() {
+        let _lock = ENV_MUTEX.lock().unwrap();
+        clear_env_vars();
+        std::env::set_var("MIN_TTL", "1m");
+        std::env::set_var("MAX_TTL", "52w");
+        std::env::set_var("DEFAULT_TTL", "24h");
+        let config = Config::from_env().unwrap();
+        assert_eq!(config.min_ttl_seconds, 60);
+        assert_eq!(config.max_ttl_seconds, 52 * 7 * 24 * 60 * 60);
+        assert_eq!(config.default_ttl_seconds, 24 * 60 * 60);
+        clear_env_vars();
+    }
+
+    #[test]
+    fn test_config_from_env_invalid_ttl_limits() {
+        let _lock = ENV_MUTEX.lock().unwrap();
+        clear_env_vars();
+
+        std::env::set_var("MAX_TTL", "forever");
+        assert!(Config::from_env().is_err());
+        clear_env_vars();
+
+        std::env::set_var("MIN_TTL", "2d");
+        std::env::set_var("MAX_TTL", "1d");
+        assert!(Config::from_env().is_err());
+        clear_env_vars();
+
+        std::env::set_var("DEFAULT_TTL", "60d");
+        assert!(Config::from_env().is_err());
+        clear_env_vars();
+    }
+
+    #[test]
+    fn test_config_from_env_redirect_status() {
+        let _lock = ENV_MUTEX.lock().unwrap();
+        clear_env_vars();
+        std::env::set_var("REDIRECT_STATUS", "permanent");
+        let config = Config::from_env().unwrap();
+        assert_eq!(config.redirect_status, RedirectStatus::Permanent);
+
+        std::env::set_var("REDIRECT_STATUS", "bogus");
+        assert!(Config::from_env().is_err());
+        std::env::remove_var("REDIRECT_STATUS");
+    }
+
+    #[test]
+    fn test_config_from_env_ip_storage() {
+        let _lock = ENV_MUTEX.lock().unwrap();
+        clear_env_vars();
+        std::env::set_var("IP_STORAGE", "hashed");
+        std::env::set_var("IP_HASH_SALT", "s3cret");
+        let config = Config::from_env().unwrap();
+        assert_eq!(config.ip_storage, IpStorage::Hashed);
+        assert_eq!(config.ip_hash_salt.as_deref(), Some("s3cret"));
+
+        std::env::set_var("IP_STORAGE", "none");
+        assert_eq!(Config::from_env().unwrap().ip_storage, IpStorage::Discard);
+
+        std::env::set_var("IP_STORAGE", "masked");
+        assert!(Config::from_env().is_err());
+        std::env::remove_var("IP_STORAGE");
+        std::env::remove_var("IP_HASH_SALT");
+    }
+
+    #[test]
+    fn test_config_from_env_dedup_urls() {
+        let _lock = ENV_MUTEX.lock().unwrap();
+        clear_env_vars();
+        std::env::set_var("DEDUP_URLS", "true");
+        let config = Config::from_env().unwrap();
+        assert!(config.dedup_urls);
+
+        std::env::set_var("DEDUP_URLS", "0");
+        let config = Config::from_env().unwrap();
+        assert!(!config.dedup_urls);
+        std::env::remove_var("DEDUP_URLS");
+    }
+
+    #[test]
+    fn test_config_from_env_preview_mode() {
+        let _lock = ENV_MUTEX.lock().unwrap();
+        clear_env_vars();
+        std::env::set_var("PREVIEW_MODE", "on");
+        let config = Config::from_env().unwrap();
+        assert!(config.preview_mode);
+        std::env::remove_var("PREVIEW_MODE");
+    }
+
+    #[test]
+    fn test_config_from_env_public_shorten_off() {
+        let _lock = ENV_MUTEX.lock().unwrap();
+        clear_env_vars();
+        std::env::set_var("PUBLIC_SHORTEN", "false");
+        let config = Config::from_env().unwrap();
+        assert!(!config.public_shorten);
+        std::env::remove_var("PUBLIC_SHORTEN");
+    }
+
+    #[test]
+    fn test_config_from_env_count_bots() {
+        let _lock = ENV_MUTEX.lock().unwrap();
+        clear_env_vars();
+        std::env::set_var("COUNT_BOTS", "true");
+        let config = Config::from_env().unwrap();
+        assert!(config.count_bots);
+        std::env::remove_var("COUNT_BOTS");
+    }
+
+    #[test]
+    fn test_config_from_env_bot_user_agents() {
+        let _lock = ENV_MUTEX.lock().unwrap();
+        clear_env_vars();
+        std::env::set_var("BOT_USER_AGENTS", "UptimeRobot, ,curl/");
+        let config = Config::from_env().unwrap();
+
+        // Extra patterns are added to the built-ins, lowercased and without duplicates
+        assert!(config.bot_user_agents.contains(&"uptimerobot".to_string()));
+        assert!(config.bot_user_agents.contains(&"slurp".to_string()));
+        assert_eq!(
+            config.bot_user_agents.len(),
+            BOT_USER_AGENT_PATTERNS.len() + 1
+        );
+        std::env::remove_var("BOT_USER_AGENTS");
+    }
+
+    #[test]
+    fn test_config_from_env_redirect_cache_max_age() {
+        let _lock = ENV_MUTEX.lock().unwrap();
+        clear_env_vars();
+        std::env::set_var("REDIRECT_CACHE_MAX_AGE", "5m");
+        let config = Config::from_env().unwrap();
+        assert_eq!(config.redirect_cache_max_age, Some(300));
+
+        std::env::set_var("REDIRECT_CACHE_MAX_AGE", "soon");
+        assert!(Config::from_env().is_err());
+        std::env::remove_var("REDIRECT_CACHE_MAX_AGE");
+    }
+
+    #[test]
+    fn test_config_from_env_cleanup_interval() {
+        let _lock = ENV_MUTEX.lock().unwrap();
+        clear_env_vars();
+        std::env::set_var("CLEANUP_INTERVAL_SECONDS", "3600");
+        assert_eq!(Config::from_env().unwrap().cleanup_interval_seconds, 3600);
+
+        std::env::set_var("CLEANUP_INTERVAL_SECONDS", "0");
+        assert_eq!(Config::from_env().unwrap().cleanup_interval_seconds, 0);
+
+        std::env::set_var("CLEANUP_INTERVAL_SECONDS", "-1");
+        assert!(Config::from_env().is_err());
+
+        std::env::set_var("CLEANUP_INTERVAL_SECONDS", "hourly");
+        assert!(Config::from_env().is_err());
+        clear_env_vars();
+    }
+
+    #[test]
+    fn test_config_from_env_visit_retention_days() {
+        let _lock = ENV_MUTEX.lock().unwrap();
+        clear_env_vars();
+        std::env::set_var("VISIT_RETENTION_DAYS", "90");
+        assert_eq!(Config::from_env().unwrap().visit_retention_days, Some(90));
+
+        std::env::set_var("VISIT_RETENTION_DAYS", "0");
+        assert!(Config::from_env().is_err());
+
+        std::env::set_var("VISIT_RETENTION_DAYS", "forever");
+        assert!(Config::from_env().is_err());
+        clear_env_vars();
+    }
+
+    #[test]
+    fn test_config_from_env_pii_retention() {
+        let _lock = ENV_MUTEX.lock().unwrap();
+        clear_env_vars();
+        std::env::set_var("PII_RETENTION_DAYS", "30");
+        std::env::set_var("PII_SCRUB_INTERVAL_SECONDS", "600");
+        let config = Config::from_env().unwrap();
+        assert_eq!(config.pii_retention_days, Some(30));
+        assert_eq!(config.pii_scrub_interval_seconds, 600);
+
+        std::env::set_var("PII_RETENTION_DAYS", "0");
+        assert!(Config::from_env().is_err());
+
+        std::env::set_var("PII_RETENTION_DAYS", "30");
+        std::env::set_var("PII_SCRUB_INTERVAL_SECONDS", "hourly");
+        assert!(Config::from_env().is_err());
+        clear_env_vars();
+    }
+
+    #[test]
+    fn test_config_from_env_link_cache() {
+        let _lock = ENV_MUTEX.lock().unwrap();
+        clear_env_vars();
+        std::env::set_var("LINK_CACHE_SIZE", "10000");
+        std::env::set_var("LINK_CACHE_TTL", "5m");
+        let config = Config::from_env().unwrap();
+        assert_eq!(config.link_cache_size, 10000);
+        assert_eq!(config.link_cache_ttl_seconds, 300);
+
+        std::env::set_var("LINK_CACHE_SIZE", "lots");
+        assert!(Config::from_env().is_err());
+
+        std::env::set_var("LINK_CACHE_SIZE", "10000");
+        std::env::set_var("LINK_CACHE_TTL", "briefly");
+        assert!(Config::from_env().is_err());
+        clear_env_vars();
+    }
+
+    #[test]
+    fn test_config_from_env_visit_flush_interval() {
+        let _lock = ENV_MUTEX.lock().unwrap();
+        clear_env_vars();
+        std::env::set_var("VISIT_FLUSH_INTERVAL", "5s");
+        assert_eq!(Config::from_env().unwrap().visit_flush_interval_seconds, 5);
+
+        std::env::set_var("VISIT_FLUSH_INTERVAL", "1m30s");
+        assert_eq!(Config::from_env().unwrap().visit_flush_interval_seconds, 90);
+
+        std::env::set_var("VISIT_FLUSH_INTERVAL", "often");
+        assert!(Config::from_env().is_err());
+        clear_env_vars();
+    }
+
+    #[test]
+    fn test_config_from_env_blocklist() {
+        let _lock = ENV_MUTEX.lock().unwrap();
+        clear_env_vars();
+        std::env::set_var("BLOCKLIST_DOMAINS", "evil.com, phish.example");
+        std::env::set_var("BLOCKLIST_FILE", "/etc/cutl/blocklist.txt");
+        let config = Config::from_env().unwrap();
+        assert_eq!(config.blocklist_domains.len(), 2);
+        assert!(config.blocklist_domains.contains_host("www.evil.com"));
+        assert_eq!(
+            config.blocklist_file.as_deref(),
+            Some("/etc/cutl/blocklist.txt")
+        );
+        clear_env_vars();
+    }
+
+    #[test]
+    fn test_config_from_env_allowed_domains() {
+        let _lock = ENV_MUTEX.lock().unwrap();
+        clear_env_vars();
+        std::env::set_var("ALLOWED_DOMAINS", "corp.example, corp.example.net");
+        let config = Config::from_env().unwrap();
+        assert_eq!(config.allowed_domains.len(), 2);
+        assert!(config.allowed_domains.contains_host("wiki.corp.example"));
+        std::env::remove_var("ALLOWED_DOMAINS");
+    }
+
+    #[test]
+    fn test_config_from_env_resolve_destinations() {
+        let _lock = ENV_MUTEX.lock().unwrap();
+        clear_env_vars();
+        std::env::set_var("RESOLVE_DESTINATIONS", "false");
+        assert!(!Config::from_env().unwrap().resolve_destinations);
+        std::env::remove_var("RESOLVE_DESTINATIONS");
+    }
+
+    #[test]
+    fn test_config_from_env_max_batch_size() {
+        let _lock = ENV_MUTEX.lock().unwrap();
+        clear_env_vars();
+        std::env::set_var("MAX_BATCH_SIZE", "50");
+        assert_eq!(Config::from_env().unwrap().max_batch_size, 50);
+
+        std::env::set_var("MAX_BATCH_SIZE", "lots");
+        let err = Config::from_env().unwrap_err();
+        assert!(err.to_string().contains("MAX_BATCH_SIZE"));
+        std::env::remove_var("MAX_BATCH_SIZE");
+    }
+
+    #[test]
+    fn test_config_from_env_max_body_bytes() {
+        let _lock = ENV_MUTEX.lock().unwrap();
+        clear_env_vars();
+        std::env::set_var("MAX_BODY_BYTES", "4096");
+        assert_eq!(Config::from_env().unwrap().max_body_bytes, 4096);
+
+        std::env::set_var("MAX_BODY_BYTES", "16KB");
+        assert!(Config::from_env().is_err());
+        std::env::remove_var("MAX_BODY_BYTES");
+    }
+
+    #[test]
+    fn test_config_from_env_max_url_length() {
+        let _lock = ENV_MUTEX.lock().unwrap();
+        clear_env_vars();
+        std::env::set_var("MAX_URL_LENGTH", "8192");
+        assert_eq!(Config::from_env().unwrap().max_url_length, 8192);
+
+        std::env::set_var("MAX_URL_LENGTH", "-1");
+        assert!(Config::from_env().is_err());
+        std::env::remove_var("MAX_URL_LENGTH");
+    }
+
+    #[test]
+    fn test_config_from_env_webhook_url() {
+        let _lock = ENV_MUTEX.lock().unwrap();
+        clear_env_vars();
+        std::env::set_var("WEBHOOK_URL", "https://events.example.com/cutl");
+        assert_eq!(
+            Config::from_env().unwrap().webhook_url.as_deref(),
+            Some("https://events.example.com/cutl")
+        );
+
+        std::env::set_var("WEBHOOK_URL", "ftp://events.example.com");
+        assert!(Config::from_env().is_err());
+
+        std::env::set_var("WEBHOOK_URL", "not a url");
+        assert!(Config::from_env().is_err());
+        clear_env_vars();
+    }
+
+    #[test]
+    fn test_config_from_env_shutdown_timeout() {
+        let _lock = ENV_MUTEX.lock().unwrap();
+        clear_env_vars();
+        std::env::set_var("SHUTDOWN_TIMEOUT", "2m");
+        assert_eq!(Config::from_env().unwrap().shutdown_timeout_seconds, 120);
+
+        std::env::set_var("SHUTDOWN_TIMEOUT", "later");
+        assert!(Config::from_env().is_err());
+        clear_env_vars();
+    }
+
+    #[test]
+    fn test_config_from_env_log_format() {
+        let _lock = ENV_MUTEX.lock().unwrap();
+        clear_env_vars();
+        std::env::set_var("LOG_FORMAT", "JSON");
+        let config = Config::from_env().unwrap();
+        assert_eq!(config.log_format, LogFormat::Json);
+
+        std::env::set_var("LOG_FORMAT", "xml");
+        assert!(Config::from_env().is_err());
+        std::env::remove_var("LOG_FORMAT");
     }
 
     #[test]
@@ -140,6 +1334,40 @@ mod tests {
         std::env::remove_var("DATABASE_URL");
     }
 
+    #[test]
+    fn test_config_from_env_sqlite_settings() {
+        let _lock = ENV_MUTEX.lock().unwrap();
+        clear_env_vars();
+        std::env::set_var("SQLITE_JOURNAL_MODE", "DELETE");
+        std::env::set_var("SQLITE_BUSY_TIMEOUT", "30s");
+        let settings = Config::from_env().unwrap().pool_settings();
+        assert_eq!(settings.journal_mode, SqliteJournalMode::Delete);
+        assert_eq!(settings.busy_timeout, Duration::from_secs(30));
+
+        std::env::set_var("SQLITE_JOURNAL_MODE", "fast");
+        assert!(Config::from_env().is_err());
+        clear_env_vars();
+    }
+
+    #[test]
+    fn test_config_from_env_db_connections() {
+        let _lock = ENV_MUTEX.lock().unwrap();
+        clear_env_vars();
+        std::env::set_var("DB_MAX_CONNECTIONS", "8");
+        std::env::set_var("DB_MIN_CONNECTIONS", "2");
+        let settings = Config::from_env().unwrap().pool_settings();
+        assert_eq!(settings.max_connections, 8);
+        assert_eq!(settings.min_connections, 2);
+
+        std::env::set_var("DB_MIN_CONNECTIONS", "9");
+        assert!(Config::from_env().is_err());
+
+        std::env::set_var("DB_MAX_CONNECTIONS", "0");
+        std::env::remove_var("DB_MIN_CONNECTIONS");
+        assert!(Config::from_env().is_err());
+        clear_env_vars();
+    }
+
     #[test]
     fn test_config_from_env_custom_base_url() {
         let _lock = ENV_MUTEX.lock().unwrap();
@@ -170,6 +1398,247 @@ mod tests {
         std::env::remove_var("AUTH_TOKEN");
     }
 
+    #[test]
+    fn test_config_from_env_auth_header() {
+        let _lock = ENV_MUTEX.lock().unwrap();
+        clear_env_vars();
+
+        std::env::set_var("AUTH_HEADER", " X-API-Key ");
+        let config = Config::from_env().unwrap();
+        assert_eq!(config.auth_header, "x-api-key");
+
+        std::env::set_var("AUTH_HEADER", "not a header");
+        assert!(Config::from_env().is_err());
+
+        std::env::remove_var("AUTH_HEADER");
+    }
+
+    #[test]
+    fn test_config_from_env_normalize_urls() {
+        let _lock = ENV_MUTEX.lock().unwrap();
+        clear_env_vars();
+        std::env::set_var("NORMALIZE_URLS", "full");
+        let config = Config::from_env().unwrap();
+        assert_eq!(config.url_normalization, UrlNormalization::Full);
+
+        std::env::set_var("NORMALIZE_URLS", "sometimes");
+        assert!(Config::from_env().is_err());
+        clear_env_vars();
+    }
+
+    #[test]
+    fn test_config_from_env_code_settings() {
+        let _lock = ENV_MUTEX.lock().unwrap();
+        clear_env_vars();
+        std::env::set_var("CODE_LENGTH", "10");
+        std::env::set_var("CODE_ALPHABET", "no-ambiguous");
+        let config = Config::from_env().unwrap();
+        assert_eq!(config.code_min_length, 10);
+        assert_eq!(config.code_max_length, 10);
+        assert_eq!(config.code_alphabet, CodeAlphabet::NoAmbiguous);
+
+        std::env::set_var("CODE_LENGTH", "4-12");
+        let config = Config::from_env().unwrap();
+        assert_eq!((config.code_min_length, config.code_max_length), (4, 12));
+
+        std::env::set_var("CODE_ALPHABET", "emoji");
+        assert!(Config::from_env().is_err());
+        clear_env_vars();
+    }
+
+    #[test]
+    fn test_config_from_env_case_insensitive_codes() {
+        let _lock = ENV_MUTEX.lock().unwrap();
+        clear_env_vars();
+        std::env::set_var("CASE_INSENSITIVE_CODES", "true");
+        assert!(Config::from_env().unwrap().case_insensitive_codes);
+        clear_env_vars();
+    }
+
+    #[test]
+    fn test_config_from_env_redirect_rate_limit() {
+        let _lock = ENV_MUTEX.lock().unwrap();
+        clear_env_vars();
+        std::env::set_var("REDIRECT_RATE_LIMIT", "120");
+        std::env::set_var("REDIRECT_RATE_LIMIT_BURST", "30");
+
+        let config = Config::from_env().unwrap();
+        assert_eq!(config.redirect_rate_limit, 120);
+        assert_eq!(config.redirect_rate_limit_burst, 30);
+        // The shorten limit is configured separately
+        assert_eq!(config.rate_limit, 10);
+        clear_env_vars();
+    }
+
+    #[test]
+    fn test_config_from_env_password_rate_limit() {
+        let _lock = ENV_MUTEX.lock().unwrap();
+        clear_env_vars();
+        std::env::set_var("PASSWORD_RATE_LIMIT", "20");
+        std::env::set_var("PASSWORD_RATE_LIMIT_BURST", "1");
+
+        let config = Config::from_env().unwrap();
+        assert_eq!(config.password_rate_limit, 20);
+        assert_eq!(config.password_rate_limit_burst, 1);
+        clear_env_vars();
+    }
+
+    #[test]
+    fn test_config_from_env_rate_limit_allowlist() {
+        let _lock = ENV_MUTEX.lock().unwrap();
+        clear_env_vars();
+        std::env::set_var("RATE_LIMIT_ALLOWLIST", "10.0.0.0/8, 2001:db8::1");
+
+        let config = Config::from_env().unwrap();
+        assert_eq!(config.rate_limit_allowlist.len(), 2);
+        assert!(config
+            .rate_limit_allowlist
+            .contains("10.20.30.40".parse().unwrap()));
+
+        std::env::set_var("RATE_LIMIT_ALLOWLIST", "10.0.0.0/33");
+        assert!(Config::from_env().is_err());
+        clear_env_vars();
+    }
+
+    #[test]
+    fn test_config_from_env_trust_proxy() {
+        let _lock = ENV_MUTEX.lock().unwrap();
+        clear_env_vars();
+        std::env::set_var("TRUST_PROXY", "true");
+        assert!(Config::from_env().unwrap().trust_proxy);
+        clear_env_vars();
+    }
+
+    #[test]
+    fn test_config_from_env_rate_limit_key() {
+        let _lock = ENV_MUTEX.lock().unwrap();
+        clear_env_vars();
+
+        // Follows TRUST_PROXY unless set explicitly
+        std::env::set_var("TRUST_PROXY", "true");
+        assert_eq!(
+            Config::from_env().unwrap().rate_limit_key,
+            RateLimitKey::Forwarded
+        );
+
+        std::env::set_var("RATE_LIMIT_KEY", "socket");
+        assert_eq!(
+            Config::from_env().unwrap().rate_limit_key,
+            RateLimitKey::Socket
+        );
+
+        std::env::remove_var("TRUST_PROXY");
+        std::env::set_var("RATE_LIMIT_KEY", "forwarded");
+        assert_eq!(
+            Config::from_env().unwrap().rate_limit_key,
+            RateLimitKey::Forwarded
+        );
+
+        std::env::set_var("RATE_LIMIT_KEY", "header");
+        assert!(Config::from_env().is_err());
+        clear_env_vars();
+    }
+
+    #[test]
+    fn test_config_from_env_allowed_hosts() {
+        let _lock = ENV_MUTEX.lock().unwrap();
+        clear_env_vars();
+        std::env::set_var(
+            "ALLOWED_HOSTS",
+            "go.example.com, Links.Example.ORG:8080,,go.example.com",
+        );
+        let config = Config::from_env().unwrap();
+
+        assert_eq!(
+            config.allowed_hosts,
+            vec!["go.example.com", "links.example.org:8080"]
+        );
+        clear_env_vars();
+    }
+
+    #[test]
+    fn test_config_from_env_reserved_codes() {
+        let _lock = ENV_MUTEX.lock().unwrap();
+        clear_env_vars();
+        std::env::set_var("RESERVED_CODES", "Login, docs,,api");
+        let config = Config::from_env().unwrap();
+
+        // Extra codes are added to the built-ins, lowercased and without duplicates
+        assert!(config.reserved_codes.contains(&"shorten".to_string()));
+        assert!(config.reserved_codes.contains(&"login".to_string()));
+        assert!(config.reserved_codes.contains(&"docs".to_string()));
+        assert_eq!(
+            config.reserved_codes.len(),
+            DEFAULT_RESERVED_CODES.len() + 2
+        );
+        clear_env_vars();
+    }
+
+    #[test]
+    fn test_config_from_env_filter_bad_codes() {
+        let _lock = ENV_MUTEX.lock().unwrap();
+        clear_env_vars();
+        std::env::set_var("FILTER_BAD_CODES", "false");
+        assert!(!Config::from_env().unwrap().filter_bad_codes);
+        clear_env_vars();
+    }
+
+    #[test]
+    fn test_config_from_env_code_max_attempts() {
+        let _lock = ENV_MUTEX.lock().unwrap();
+        clear_env_vars();
+        std::env::set_var("CODE_MAX_ATTEMPTS", "50");
+        assert_eq!(Config::from_env().unwrap().code_max_attempts, 50);
+
+        std::env::set_var("CODE_MAX_ATTEMPTS", "0");
+        assert!(Config::from_env().is_err());
+
+        std::env::set_var("CODE_MAX_ATTEMPTS", "many");
+        assert!(Config::from_env().is_err());
+        clear_env_vars();
+    }
+
+    #[test]
+    fn test_parse_code_length_invalid() {
+        assert!(parse_code_length("0").is_err());
+        assert!(parse_code_length("8-6").is_err());
+        assert!(parse_code_length("33").is_err());
+        assert!(parse_code_length("ten").is_err());
+        assert!(parse_code_length("6-").is_err());
+    }
+
+    #[test]
+    fn test_config_from_env_with_api_tokens() {
+        let _lock = ENV_MUTEX.lock().unwrap();
+        clear_env_vars();
+        std::env::set_var("API_TOKENS", "alice:token-a, bob:token-b");
+        let config = Config::from_env().unwrap();
+        assert_eq!(
+            config.api_tokens,
+            vec![
+                ApiToken {
+                    owner: "alice".to_string(),
+                    token: "token-a".to_string(),
+                },
+                ApiToken {
+                    owner: "bob".to_string(),
+                    token: "token-b".to_string(),
+                },
+            ]
+        );
+        clear_env_vars();
+    }
+
+    #[test]
+    fn test_parse_api_tokens_invalid() {
+        assert!(parse_api_tokens("alice").is_err());
+        assert!(parse_api_tokens(":token").is_err());
+        assert!(parse_api_tokens("alice:").is_err());
+        assert!(parse_api_tokens("alice:a,alice:b").is_err());
+        assert!(parse_api_tokens("admin:token").is_err());
+        assert!(parse_api_tokens("").unwrap().is_empty());
+    }
+
     #[test]
     fn test_config_from_env_all_custom() {
         let _lock = ENV_MUTEX.lock().unwrap();
@@ -194,7 +1663,10 @@ mod tests {
         std::env::remove_var("BASE_URL");
         std::env::remove_var("BIND_ADDRESS");
         std::env::remove_var("AUTH_TOKEN");
+        std::env::remove_var("API_TOKENS");
         std::env::remove_var("RATE_LIMIT");
         std::env::remove_var("RATE_LIMIT_BURST");
+        std::env::remove_var("REDIRECT_RATE_LIMIT");
+        std::env::remove_var("REDIRECT_RATE_LIMIT_BURST");
     }
 }
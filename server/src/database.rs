@@ -3,17 +3,106 @@
 //! Handles all SQLite database operations including migrations, CRUD operations,
 //! and cleanup of expired links.
 
-use crate::models::{Link, VisitRow};
-use anyhow::Result;
-use sqlx::{sqlite::SqlitePool, Pool, Sqlite};
+use crate::{
+    models::{Link, LinkRecord, NewLink, NewVisit, VisitRow},
+    utils::{hash_ip, referer_host, IpStorage},
+};
+use anyhow::{Context, Result};
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteSynchronous},
+    Executor, Pool, Sqlite, SqliteConnection,
+};
+use std::{net::IpAddr, str::FromStr, time::Duration};
 use tracing::info;
 
+/// Number of recent visits returned when no limit is requested
+pub const DEFAULT_RECENT_VISITS: u32 = 20;
+
+/// Upper bound on the number of recent visits returned in one page
+pub const MAX_RECENT_VISITS: u32 = 500;
+
+/// Number of countries and referers listed in analytics when no `top` is requested
+pub const DEFAULT_TOP_VALUES: u32 = 10;
+
+/// Value of the entry that folds together every group past the top N
+pub const OTHERS_BUCKET: &str = "others";
+
+/// Number of links returned by `list_links` when no limit is requested
+pub const DEFAULT_LINKS_PAGE_SIZE: u32 = 20;
+
+/// Upper bound on the number of links returned in one page
+pub const MAX_LINKS_PAGE_SIZE: u32 = 500;
+
+/// Columns selected when loading a `Link`
+const LINK_COLUMNS: &str =
+    "code, original_url, expires_at, created_at, password_hash, max_visits, remaining_visits, owner, append_query, enabled, rules, idle_ttl, max_expires_at, redirect_status, title, description";
+
+/// `WHERE` clause shared by `list_links` and `count_links`
+const LINK_FILTER: &str =
+    "owner IS ? AND (? IS NULL OR original_url LIKE ? ESCAPE '\\' OR code LIKE ? ESCAPE '\\')";
+
+/// Default time a connection waits on a locked database before failing
+pub const DEFAULT_BUSY_TIMEOUT_SECONDS: i64 = 5;
+
+/// Default upper bound on open connections
+///
+/// SQLite allows a single writer at a time, so a handful of connections is
+/// enough to let redirects read concurrently without piling up on the lock.
+pub const DEFAULT_MAX_CONNECTIONS: u32 = 5;
+
+/// Default number of connections kept open while idle
+pub const DEFAULT_MIN_CONNECTIONS: u32 = 1;
+
+/// Visits rewritten per transaction when scrubbing or re-storing IPs, so a
+/// large `visits` table never holds the write lock for the whole pass
+const VISIT_REWRITE_BATCH_SIZE: i64 = 1000;
+
+/// Connection settings applied by `create_pool`
+#[derive(Debug, Clone, Copy)]
+pub struct PoolSettings {
+    /// SQLite journal mode; WAL lets readers run alongside a writer
+    pub journal_mode: SqliteJournalMode,
+    /// How long a connection retries while another holds the write lock
+    pub busy_timeout: Duration,
+    /// Maximum number of open connections
+    pub max_connections: u32,
+    /// Connections kept open even when idle
+    pub min_connections: u32,
+}
+
+impl Default for PoolSettings {
+    fn default() -> Self {
+        Self {
+            journal_mode: SqliteJournalMode::Wal,
+            busy_timeout: Duration::from_secs(DEFAULT_BUSY_TIMEOUT_SECONDS as u64),
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+            min_connections: DEFAULT_MIN_CONNECTIONS,
+        }
+    }
+}
+
 /// Creates a new database connection pool
 ///
+/// In WAL mode connections also use `synchronous = NORMAL`, which is safe
+/// with WAL and avoids an fsync on every commit.
+///
 /// # Arguments
 /// * `database_url` - SQLite connection string (e.g., "sqlite:cutl.db")
-pub async fn create_pool(database_url: &str) -> Result<Pool<Sqlite>> {
-    let pool = SqlitePool::connect(database_url).await?;
+/// * `settings` - Pool size, plus journal mode and busy timeout for each connection
+pub async fn create_pool(database_url: &str, settings: &PoolSettings) -> Result<Pool<Sqlite>> {
+    let mut options = SqliteConnectOptions::from_str(database_url)?
+        .journal_mode(settings.journal_mode)
+        .busy_timeout(settings.busy_timeout);
+
+    if settings.journal_mode == SqliteJournalMode::Wal {
+        options = options.synchronous(SqliteSynchronous::Normal);
+    }
+
+    let pool = SqlitePoolOptions::new()
+        .max_connections(settings.max_connections)
+        .min_connections(settings.min_connections)
+        .connect_with(options)
+        .await?;
     Ok(pool)
 }
 
@@ -53,6 +142,20 @@ pub async fn run_migrations(pool: &Pool<Sqlite>) -> Result<()> {
     .execute(pool)
     .await?;
 
+    // Create index on original_url for duplicate URL lookups
+    sqlx::query(
+        r#"
+        CREATE INDEX IF NOT EXISTS idx_links_original_url ON links(original_url)
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Case-insensitive code lookups (CASE_INSENSITIVE_CODES) can't use the primary key
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_links_code_nocase ON links(code COLLATE NOCASE)")
+        .execute(pool)
+        .await?;
+
     // Create the visits table
     sqlx::query(
         r#"
@@ -79,265 +182,2693 @@ pub async fn run_migrations(pool: &Pool<Sqlite>) -> Result<()> {
         .execute(pool)
         .await?;
 
+    // Read-only analytics tokens, stored as SHA-256 hashes
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS share_tokens (
+            token_hash TEXT    PRIMARY KEY,
+            code       TEXT    NOT NULL REFERENCES links(code) ON DELETE CASCADE,
+            created_at INTEGER NOT NULL,
+            expires_at INTEGER NOT NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Columns added after the initial schema
+    add_column_if_missing(pool, "links", "password_hash", "TEXT").await?;
+    add_column_if_missing(pool, "links", "max_visits", "INTEGER").await?;
+    add_column_if_missing(pool, "links", "remaining_visits", "INTEGER").await?;
+    add_column_if_missing(pool, "links", "owner", "TEXT").await?;
+    add_column_if_missing(pool, "links", "append_query", "TEXT").await?;
+    add_column_if_missing(pool, "links", "enabled", "INTEGER NOT NULL DEFAULT 1").await?;
+    add_column_if_missing(pool, "links", "rules", "TEXT").await?;
+    add_column_if_missing(pool, "links", "idle_ttl", "INTEGER").await?;
+    add_column_if_missing(pool, "links", "max_expires_at", "INTEGER").await?;
+    add_column_if_missing(pool, "links", "redirect_status", "INTEGER").await?;
+    add_column_if_missing(pool, "links", "title", "TEXT").await?;
+    add_column_if_missing(pool, "links", "description", "TEXT").await?;
+    add_column_if_missing(pool, "visits", "device", "TEXT").await?;
+
     info!("Database migrations completed");
     Ok(())
 }
 
+/// Adds a column to an existing table unless it is already present
+///
+/// `CREATE TABLE IF NOT EXISTS` leaves older databases untouched, so columns
+/// introduced later are added here instead.
+async fn add_column_if_missing(
+    pool: &Pool<Sqlite>,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<()> {
+    let exists = sqlx::query_scalar::<_, i64>(&format!(
+        "SELECT COUNT(*) FROM pragma_table_info('{}') WHERE name = ?",
+        table
+    ))
+    .bind(column)
+    .fetch_one(pool)
+    .await?;
+
+    if exists == 0 {
+        sqlx::query(&format!(
+            "ALTER TABLE {} ADD COLUMN {} {}",
+            table, column, definition
+        ))
+        .execute(pool)
+        .await?;
+    }
+
+    Ok(())
+}
+
 /// Checks if a short code already exists in the database
-pub async fn code_exists(pool: &Pool<Sqlite>, code: &str) -> Result<bool> {
-    let count = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM links WHERE code = ?")
+///
+/// With `case_insensitive`, codes differing only in ASCII case count as the same.
+/// Accepts a pool, a connection, or a transaction.
+pub async fn code_exists<'e, E>(executor: E, code: &str, case_insensitive: bool) -> Result<bool>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    let sql = if case_insensitive {
+        "SELECT COUNT(*) FROM links WHERE code = ? COLLATE NOCASE"
+    } else {
+        "SELECT COUNT(*) FROM links WHERE code = ?"
+    };
+
+    let count = sqlx::query_scalar::<_, i64>(sql)
         .bind(code)
-        .fetch_one(pool)
+        .fetch_one(executor)
         .await?;
 
     Ok(count > 0)
 }
 
+/// Makes the database enforce how `CASE_INSENSITIVE_CODES` compares codes
+///
+/// When on, a unique `NOCASE` index stops two codes that differ only in case
+/// from both being stored, even when they are created at the same moment. It
+/// can't be built while such codes exist, so startup fails until one of each
+/// pair is removed. When off, the index is dropped so they may coexist again.
+pub async fn enforce_code_case(pool: &Pool<Sqlite>, case_insensitive: bool) -> Result<()> {
+    if case_insensitive {
+        sqlx::query(
+            "CREATE UNIQUE INDEX IF NOT EXISTS idx_links_code_nocase_unique \
+             ON links(code COLLATE NOCASE)",
+        )
+        .execute(pool)
+        .await
+        .context(
+            "Codes differing only in case exist; remove one of each pair \
+             before enabling CASE_INSENSITIVE_CODES",
+        )?;
+    } else {
+        sqlx::query("DROP INDEX IF EXISTS idx_links_code_nocase_unique")
+            .execute(pool)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Returns the stored spelling of a code that matches `code` ignoring ASCII case
+///
+/// An exact match wins if several stored codes differ only in case.
+/// Returns `None` if no code matches.
+pub async fn find_code_ignore_case(pool: &Pool<Sqlite>, code: &str) -> Result<Option<String>> {
+    let stored = sqlx::query_scalar::<_, String>(
+        "SELECT code FROM links WHERE code = ? COLLATE NOCASE ORDER BY code = ? DESC LIMIT 1",
+    )
+    .bind(code)
+    .bind(code)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(stored)
+}
+
 /// Inserts a new link into the database
-pub async fn insert_link(
-    pool: &Pool<Sqlite>,
-    code: &str,
-    original_url: &str,
-    expires_at: i64,
-    created_at: i64,
-) -> Result<()> {
+///
+/// When `max_visits` is set the link starts with that many remaining visits.
+/// Accepts a pool, a connection, or a transaction.
+pub async fn insert_link<'e, E>(executor: E, link: &NewLink<'_>) -> Result<()>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
     sqlx::query(
-        "INSERT INTO links (code, original_url, expires_at, created_at) VALUES (?, ?, ?, ?)",
+        "INSERT INTO links (code, original_url, expires_at, created_at, password_hash, max_visits, remaining_visits, owner, append_query, rules, idle_ttl, max_expires_at, redirect_status, title, description) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(link.code)
+    .bind(link.original_url)
+    .bind(link.expires_at)
+    .bind(link.created_at)
+    .bind(link.password_hash)
+    .bind(link.max_visits)
+    .bind(link.max_visits)
+    .bind(link.owner)
+    .bind(link.append_query)
+    .bind(link.rules)
+    .bind(link.idle_ttl)
+    .bind(link.max_expires_at)
+    .bind(link.redirect_status)
+    .bind(link.title)
+    .bind(link.description)
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+/// Inserts an imported link, or with `overwrite` replaces the link with its code
+///
+/// An overwritten link keeps nothing of the old one: every setting the record
+/// doesn't carry goes back to its default, the same as a freshly imported link.
+/// Password-protected and disabled links are never overwritten, since an export
+/// carries neither setting and replacing them would quietly open them up.
+/// Returns false if the code already exists and wasn't overwritten.
+pub async fn upsert_link<'e, E>(executor: E, link: &LinkRecord, overwrite: bool) -> Result<bool>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    let conflict = if overwrite {
+        "DO UPDATE SET original_url = excluded.original_url, \
+         expires_at = excluded.expires_at, created_at = excluded.created_at, \
+         password_hash = NULL, max_visits = NULL, remaining_visits = NULL, owner = NULL, \
+         append_query = NULL, rules = NULL, enabled = 1, idle_ttl = NULL, \
+         max_expires_at = NULL, redirect_status = NULL, title = NULL, description = NULL \
+         WHERE links.password_hash IS NULL AND links.enabled = 1"
+    } else {
+        "DO NOTHING"
+    };
+
+    let result = sqlx::query(&format!(
+        "INSERT INTO links (code, original_url, expires_at, created_at) VALUES (?, ?, ?, ?) \
+         ON CONFLICT(code) {}",
+        conflict
+    ))
+    .bind(&link.code)
+    .bind(&link.original_url)
+    .bind(link.expires_at)
+    .bind(link.created_at)
+    .execute(executor)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Restarts a sliding link's idle window from `now`
+///
+/// Raises `expires_at` to `now + idle_ttl`, capped at `max_expires_at`. The
+/// expiry only ever moves later, so a touch can't undo a longer expiry already
+/// set. Links without an `idle_ttl` are left alone. Returns false if nothing was
+/// updated.
+pub async fn touch_link(pool: &Pool<Sqlite>, code: &str, now: i64) -> Result<bool> {
+    let result = sqlx::query(
+        "UPDATE links SET expires_at = \
+         MAX(expires_at, MIN(? + idle_ttl, COALESCE(max_expires_at, ? + idle_ttl))) \
+         WHERE code = ? AND idle_ttl IS NOT NULL",
     )
+    .bind(now)
+    .bind(now)
     .bind(code)
-    .bind(original_url)
-    .bind(expires_at)
-    .bind(created_at)
     .execute(pool)
     .await?;
 
-    Ok(())
+    Ok(result.rows_affected() > 0)
 }
 
 /// Retrieves a link by its short code
 ///
 /// Returns `None` if the code doesn't exist.
 pub async fn get_link(pool: &Pool<Sqlite>, code: &str) -> Result<Option<Link>> {
-    let result = sqlx::query_as::<_, (String, String, i64, i64)>(
-        "SELECT code, original_url, expires_at, created_at FROM links WHERE code = ?",
-    )
+    let link = sqlx::query_as::<_, Link>(&format!(
+        "SELECT {} FROM links WHERE code = ?",
+        LINK_COLUMNS
+    ))
     .bind(code)
     .fetch_optional(pool)
     .await?;
 
-    Ok(
-        result.map(|(code, original_url, expires_at, created_at)| Link {
-            code,
-            original_url,
-            expires_at,
-            created_at,
-        }),
-    )
+    Ok(link)
 }
 
-/// Deletes a link by its short code
-pub async fn delete_link(pool: &Pool<Sqlite>, code: &str) -> Result<bool> {
-    let result = sqlx::query("DELETE FROM links WHERE code = ?")
-        .bind(code)
-        .execute(pool)
-        .await?;
+/// Finds the most recent non-expired, plain link pointing at `original_url`
+///
+/// Only links created with a lifetime of `ttl_seconds` match, so a request for
+/// a different TTL never gets back a link expiring sooner or later than asked.
+/// Plain links have no password, visit limit, appended query, rules, sliding
+/// expiry, redirect status, title, or description, and aren't disabled, so
+/// reusing one behaves exactly like a freshly created link.
+///
+/// Only links created by `owner` are considered, so tokens never share links.
+/// Returns `None` if no such link exists. Accepts a pool, a connection, or a transaction.
+pub async fn find_link_by_url<'e, E>(
+    executor: E,
+    original_url: &str,
+    owner: Option<&str>,
+    ttl_seconds: i64,
+    now: i64,
+) -> Result<Option<Link>>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    let link = sqlx::query_as::<_, Link>(&format!(
+        "SELECT {} FROM links WHERE original_url = ? AND owner IS ? AND expires_at > ? \
+         AND expires_at - created_at = ? \
+         AND password_hash IS NULL AND max_visits IS NULL AND append_query IS NULL \
+         AND rules IS NULL AND idle_ttl IS NULL AND redirect_status IS NULL \
+         AND title IS NULL AND description IS NULL AND enabled = 1 \
+         ORDER BY created_at DESC LIMIT 1",
+        LINK_COLUMNS
+    ))
+    .bind(original_url)
+    .bind(owner)
+    .bind(now)
+    .bind(ttl_seconds)
+    .fetch_optional(executor)
+    .await?;
 
-    Ok(result.rows_affected() > 0)
+    Ok(link)
 }
 
-/// Deletes all expired links from the database
-///
-/// Returns the number of links deleted.
-pub async fn delete_expired_links(pool: &Pool<Sqlite>, now: i64) -> Result<u64> {
-    let result = sqlx::query("DELETE FROM links WHERE expires_at < ?")
-        .bind(now)
-        .execute(pool)
-        .await?;
+/// Escapes `%`, `_`, and `\` so `text` matches literally inside a `LIKE ... ESCAPE '\'` pattern
+fn escape_like(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
 
-    Ok(result.rows_affected())
+    for c in text.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
 }
 
-/// Records a single visit for a short code.
-#[allow(clippy::too_many_arguments)]
-pub async fn insert_visit(
+/// Returns up to `limit` links created by `owner`, newest first, skipping `offset`
+///
+/// `None` matches links created without a token. When `q` is set, only links whose
+/// URL or code contains it (case-insensitively) are returned.
+/// `limit` is capped at `MAX_LINKS_PAGE_SIZE`.
+pub async fn list_links(
     pool: &Pool<Sqlite>,
-    code: &str,
-    visited_at: i64,
-    ip: Option<&str>,
-    country: Option<&str>,
-    city: Option<&str>,
-    user_agent: Option<&str>,
-    referer: Option<&str>,
-) -> Result<()> {
-    sqlx::query(
-        "INSERT INTO visits (code, visited_at, ip, country, city, user_agent, referer) VALUES (?, ?, ?, ?, ?, ?, ?)",
-    )
-    .bind(code)
-    .bind(visited_at)
-    .bind(ip)
-    .bind(country)
-    .bind(city)
-    .bind(user_agent)
-    .bind(referer)
-    .execute(pool)
+    owner: Option<&str>,
+    q: Option<&str>,
+    limit: u32,
+    offset: u32,
+) -> Result<Vec<Link>> {
+    let pattern = q.map(|q| format!("%{}%", escape_like(q)));
+
+    let links = sqlx::query_as::<_, Link>(&format!(
+        "SELECT {} FROM links WHERE {} ORDER BY created_at DESC, code LIMIT ? OFFSET ?",
+        LINK_COLUMNS, LINK_FILTER
+    ))
+    .bind(owner)
+    .bind(&pattern)
+    .bind(&pattern)
+    .bind(&pattern)
+    .bind(limit.min(MAX_LINKS_PAGE_SIZE))
+    .bind(offset)
+    .fetch_all(pool)
     .await?;
 
+    Ok(links)
+}
+
+/// Counts the links `list_links` would return across all pages
+pub async fn count_links(pool: &Pool<Sqlite>, owner: Option<&str>, q: Option<&str>) -> Result<i64> {
+    let pattern = q.map(|q| format!("%{}%", escape_like(q)));
+
+    let (count,): (i64,) =
+        sqlx::query_as(&format!("SELECT COUNT(*) FROM links WHERE {}", LINK_FILTER))
+            .bind(owner)
+            .bind(&pattern)
+            .bind(&pattern)
+            .bind(&pattern)
+            .fetch_one(pool)
+            .await?;
+
+    Ok(count)
+}
+
+/// Counts every link, returning `(total, active)`
+///
+/// Active links are those with `expires_at` after `now`.
+pub async fn count_links_by_state(pool: &Pool<Sqlite>, now: i64) -> Result<(i64, i64)> {
+    let (total, active): (i64, i64) =
+        sqlx::query_as("SELECT COUNT(*), COUNT(CASE WHEN expires_at > ? THEN 1 END) FROM links")
+            .bind(now)
+            .fetch_one(pool)
+            .await?;
+
+    Ok((total, active))
+}
+
+/// Runs a trivial query to confirm the database answers
+pub async fn ping(pool: &Pool<Sqlite>) -> Result<()> {
+    sqlx::query("SELECT 1").execute(pool).await?;
     Ok(())
 }
 
-/// Returns total visit count for `code`.
-pub async fn count_visits(pool: &Pool<Sqlite>, code: &str) -> Result<i64> {
-    let count = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM visits WHERE code = ?")
-        .bind(code)
+/// Returns the number of visits recorded across all links
+pub async fn count_all_visits(pool: &Pool<Sqlite>) -> Result<i64> {
+    let count = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM visits")
         .fetch_one(pool)
         .await?;
 
     Ok(count)
 }
 
-/// Returns visit counts grouped by country, ordered by count DESC.
-pub async fn visits_by_country(
+/// Updates the target URL and/or expiration of an existing link
+///
+/// Fields passed as `None` are left unchanged. A new expiry turns a sliding link
+/// into a fixed one, like `set_link_expiry`. Returns `false` if the code doesn't exist.
+pub async fn update_link(
     pool: &Pool<Sqlite>,
     code: &str,
-) -> Result<Vec<(Option<String>, i64)>> {
-    let rows = sqlx::query_as::<_, (Option<String>, i64)>(
-        "SELECT country, COUNT(*) as count FROM visits WHERE code = ? GROUP BY country ORDER BY count DESC",
+    original_url: Option<&str>,
+    expires_at: Option<i64>,
+) -> Result<bool> {
+    let result = sqlx::query(
+        "UPDATE links SET original_url = COALESCE(?, original_url), expires_at = COALESCE(?, expires_at), \
+         idle_ttl = CASE WHEN ? IS NULL THEN idle_ttl END, \
+         max_expires_at = CASE WHEN ? IS NULL THEN max_expires_at END \
+         WHERE code = ?",
     )
+    .bind(original_url)
+    .bind(expires_at)
+    .bind(expires_at)
+    .bind(expires_at)
     .bind(code)
-    .fetch_all(pool)
+    .execute(pool)
     .await?;
 
-    Ok(rows)
+    Ok(result.rows_affected() > 0)
 }
 
-/// Returns visit counts grouped by referer, ordered by count DESC.
-pub async fn visits_by_referer(
-    pool: &Pool<Sqlite>,
-    code: &str,
-) -> Result<Vec<(Option<String>, i64)>> {
-    let rows = sqlx::query_as::<_, (Option<String>, i64)>(
-        "SELECT referer, COUNT(*) as count FROM visits WHERE code = ? GROUP BY referer ORDER BY count DESC",
+/// Sets a link's expiration time without touching its target
+///
+/// A sliding link becomes a fixed one: its `idle_ttl` and `max_expires_at` are
+/// cleared, so later visits neither shorten nor cap the expiry just set.
+/// Returns `false` if no link has that code.
+pub async fn set_link_expiry(pool: &Pool<Sqlite>, code: &str, expires_at: i64) -> Result<bool> {
+    let result = sqlx::query(
+        "UPDATE links SET expires_at = ?, idle_ttl = NULL, max_expires_at = NULL WHERE code = ?",
     )
+    .bind(expires_at)
     .bind(code)
-    .fetch_all(pool)
+    .execute(pool)
     .await?;
 
-    Ok(rows)
+    Ok(result.rows_affected() > 0)
 }
 
-/// Returns daily visit counts for the last 30 days, newest first.
-pub async fn visits_daily(pool: &Pool<Sqlite>, code: &str) -> Result<Vec<(String, i64)>> {
-    let rows = sqlx::query_as::<_, (String, i64)>(
-        r#"SELECT strftime('%Y-%m-%d', datetime(visited_at, 'unixepoch')) as date,
-                  COUNT(*) as count
-           FROM visits
-           WHERE code = ?
-             AND visited_at >= strftime('%s', 'now', '-30 days')
-           GROUP BY date
-           ORDER BY date DESC"#,
+/// Turns a link's redirect on or off, keeping the link and its visits
+///
+/// Returns `false` if no link has that code.
+pub async fn set_link_enabled(pool: &Pool<Sqlite>, code: &str, enabled: bool) -> Result<bool> {
+    let result = sqlx::query("UPDATE links SET enabled = ? WHERE code = ?")
+        .bind(enabled)
+        .bind(code)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Moves a link to `new_code`, keeping everything else about it
+///
+/// With `keep_visits`, its visits move to the new code too; otherwise they are
+/// deleted. `visits.code` references `links(code)` with `ON DELETE CASCADE`,
+/// so visits can't be left behind detached from any link. Run this in a
+/// transaction. Returns `false` if no link has `old_code`, and a UNIQUE
+/// violation (see `is_unique_violation`) if `new_code` is taken.
+pub async fn rotate_link_code(
+    conn: &mut SqliteConnection,
+    old_code: &str,
+    new_code: &str,
+    keep_visits: bool,
+) -> Result<bool> {
+    // Every column but the code is copied as it is
+    let columns = LINK_COLUMNS.trim_start_matches("code, ");
+
+    // Visits reference links(code), so the new row has to exist before they move
+    let copied = sqlx::query(&format!(
+        "INSERT INTO links (code, {columns}) SELECT ?, {columns} FROM links WHERE code = ?"
+    ))
+    .bind(new_code)
+    .bind(old_code)
+    .execute(&mut *conn)
+    .await?;
+
+    if copied.rows_affected() == 0 {
+        return Ok(false);
+    }
+
+    if keep_visits {
+        sqlx::query("UPDATE visits SET code = ? WHERE code = ?")
+            .bind(new_code)
+            .bind(old_code)
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    // Visits still on the old code go with it via ON DELETE CASCADE
+    sqlx::query("DELETE FROM links WHERE code = ?")
+        .bind(old_code)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(true)
+}
+
+/// Returns true if `err` is SQLite rejecting a duplicate key
+pub fn is_unique_violation(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<sqlx::Error>()
+        .and_then(sqlx::Error::as_database_error)
+        .is_some_and(|e| e.is_unique_violation())
+}
+
+/// Atomically consumes one of a visit-limited link's remaining visits
+///
+/// Returns `false` once the limit is exhausted. The decrement and check happen
+/// in a single statement, so concurrent visits can't overshoot the limit.
+pub async fn claim_visit(pool: &Pool<Sqlite>, code: &str) -> Result<bool> {
+    let result = sqlx::query(
+        "UPDATE links SET remaining_visits = remaining_visits - 1 WHERE code = ? AND remaining_visits > 0",
     )
     .bind(code)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Deletes a link by its short code
+pub async fn delete_link(pool: &Pool<Sqlite>, code: &str) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM links WHERE code = ?")
+        .bind(code)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Deletes all expired links from the database
+///
+/// Returns the links that were deleted.
+pub async fn delete_expired_links(pool: &Pool<Sqlite>, now: i64) -> Result<Vec<Link>> {
+    let links = sqlx::query_as::<_, Link>(&format!(
+        "DELETE FROM links WHERE expires_at < ? RETURNING {}",
+        LINK_COLUMNS
+    ))
+    .bind(now)
     .fetch_all(pool)
     .await?;
 
-    Ok(rows)
+    Ok(links)
 }
 
-/// Returns the last 20 individual visit rows for `code`, newest first.
-pub async fn recent_visits(pool: &Pool<Sqlite>, code: &str) -> Result<Vec<VisitRow>> {
-    let rows = sqlx::query_as::<_, (i64, Option<String>, Option<String>, Option<String>, Option<String>, Option<String>)>(
-        "SELECT visited_at, ip, country, city, user_agent, referer FROM visits WHERE code = ? ORDER BY visited_at DESC LIMIT 20",
+/// Deletes links created before `created_before` that have never been visited
+///
+/// Returns the number of links deleted.
+pub async fn delete_unused_links(pool: &Pool<Sqlite>, created_before: i64) -> Result<u64> {
+    let result = sqlx::query(
+        "DELETE FROM links WHERE created_at < ? \
+         AND NOT EXISTS (SELECT 1 FROM visits WHERE visits.code = links.code)",
     )
-    .bind(code)
-    .fetch_all(pool)
+    .bind(created_before)
+    .execute(pool)
     .await?;
 
-    Ok(rows
-        .into_iter()
-        .map(
-            |(visited_at, ip, country, city, user_agent, referer)| VisitRow {
-                visited_at,
-                ip,
-                country,
-                city,
-                user_agent,
-                referer,
-            },
-        )
-        .collect())
+    Ok(result.rows_affected())
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Deletes visits recorded before `cutoff`, regardless of whether their link still exists
+///
+/// Returns the number of visits deleted.
+pub async fn delete_visits_older_than(pool: &Pool<Sqlite>, cutoff: i64) -> Result<u64> {
+    let result = sqlx::query("DELETE FROM visits WHERE visited_at < ?")
+        .bind(cutoff)
+        .execute(pool)
+        .await?;
 
-    async fn setup_db() -> Pool<Sqlite> {
-        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
-        run_migrations(&pool).await.unwrap();
-        pool
-    }
+    Ok(result.rows_affected())
+}
+
+/// Stores a share token for `code`, valid until `expires_at`
+///
+/// Only the hash of the token is kept; see `utils::hash_share_token`.
+pub async fn insert_share_token(
+    pool: &Pool<Sqlite>,
+    token_hash: &str,
+    code: &str,
+    created_at: i64,
+    expires_at: i64,
+) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO share_tokens (token_hash, code, created_at, expires_at) VALUES (?, ?, ?, ?)",
+    )
+    .bind(token_hash)
+    .bind(code)
+    .bind(created_at)
+    .bind(expires_at)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Returns the code a share token was minted for, unless it has expired by `now`
+pub async fn share_token_code(
+    pool: &Pool<Sqlite>,
+    token_hash: &str,
+    now: i64,
+) -> Result<Option<String>> {
+    let code = sqlx::query_scalar(
+        "SELECT code FROM share_tokens WHERE token_hash = ? AND expires_at >= ?",
+    )
+    .bind(token_hash)
+    .bind(now)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(code)
+}
+
+/// Deletes share tokens that expired before `now`
+///
+/// Returns the number of tokens deleted.
+pub async fn delete_expired_share_tokens(pool: &Pool<Sqlite>, now: i64) -> Result<u64> {
+    let result = sqlx::query("DELETE FROM share_tokens WHERE expires_at < ?")
+        .bind(now)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Hashes the IP and clears the user agent of visits recorded before `cutoff`
+///
+/// Raw IPs are replaced by `hash_ip(ip, salt)`, the value `IP_STORAGE=hashed`
+/// stores, so unique visitor counts among the scrubbed visits don't change.
+/// IPs that are already hashed are left as they are. The rows themselves stay,
+/// along with their country, city, device, and referrer, so visit counts and
+/// breakdowns don't change either. Returns the number of visits scrubbed.
+///
+/// Visits are scrubbed `VISIT_REWRITE_BATCH_SIZE` at a time in id order, each
+/// batch in its own transaction.
+pub async fn anonymize_old_visits(pool: &Pool<Sqlite>, cutoff: i64, salt: &str) -> Result<u64> {
+    let mut scrubbed = 0;
+    let mut after_id = 0;
+
+    loop {
+        let mut tx = pool.begin().await?;
+
+        // Hashes are plain hex, so only raw addresses contain '.' or ':'
+        let rows = sqlx::query_as::<_, (i64, Option<String>)>(
+            "SELECT id, ip FROM visits WHERE id > ? AND visited_at < ? \
+             AND (ip LIKE '%.%' OR ip LIKE '%:%' OR user_agent IS NOT NULL) \
+             ORDER BY id LIMIT ?",
+        )
+        .bind(after_id)
+        .bind(cutoff)
+        .bind(VISIT_REWRITE_BATCH_SIZE)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let Some(last_id) = rows.last().map(|(id, _)| *id) else {
+            break;
+        };
+
+        for (id, ip) in &rows {
+            let ip = ip.as_deref().map(|ip| match ip.parse::<IpAddr>() {
+                Ok(_) => hash_ip(ip, salt),
+                Err(_) => ip.to_string(),
+            });
+
+            sqlx::query("UPDATE visits SET ip = ?, user_agent = NULL WHERE id = ?")
+                .bind(ip)
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+        scrubbed += rows.len() as u64;
+        after_id = last_id;
+    }
+
+    Ok(scrubbed)
+}
+
+/// Rewrites visitor IPs already in `visits` the way `storage` stores new ones
+///
+/// Switching `IP_STORAGE` away from `raw` would otherwise leave earlier visits
+/// with their raw addresses. Hashed mode replaces each raw IP with its salted
+/// hash, leaving already-hashed values alone; discard mode clears every IP.
+/// Raw mode changes nothing. Returns the number of visits rewritten.
+///
+/// Visits are rewritten `VISIT_REWRITE_BATCH_SIZE` at a time, each batch in
+/// its own transaction, so startup doesn't hold the write lock for the whole table.
+pub async fn apply_ip_storage(pool: &Pool<Sqlite>, storage: IpStorage, salt: &str) -> Result<u64> {
+    let mut rewritten = 0;
+
+    match storage {
+        IpStorage::Raw => {}
+        IpStorage::Discard => loop {
+            let result = sqlx::query(
+                "UPDATE visits SET ip = NULL WHERE id IN \
+                 (SELECT id FROM visits WHERE ip IS NOT NULL LIMIT ?)",
+            )
+            .bind(VISIT_REWRITE_BATCH_SIZE)
+            .execute(pool)
+            .await?;
+
+            if result.rows_affected() == 0 {
+                break;
+            }
+            rewritten += result.rows_affected();
+        },
+        IpStorage::Hashed => {
+            let mut after_id = 0;
+            loop {
+                let mut tx = pool.begin().await?;
+
+                // Hashes are plain hex, so only raw addresses contain '.' or ':'
+                let rows = sqlx::query_as::<_, (i64, String)>(
+                    "SELECT id, ip FROM visits WHERE id > ? AND (ip LIKE '%.%' OR ip LIKE '%:%') \
+                     ORDER BY id LIMIT ?",
+                )
+                .bind(after_id)
+                .bind(VISIT_REWRITE_BATCH_SIZE)
+                .fetch_all(&mut *tx)
+                .await?;
+
+                let Some(last_id) = rows.last().map(|(id, _)| *id) else {
+                    break;
+                };
+
+                for (id, ip) in &rows {
+                    if ip.parse::<IpAddr>().is_err() {
+                        continue;
+                    }
+                    sqlx::query("UPDATE visits SET ip = ? WHERE id = ?")
+                        .bind(storage.store(Some(ip), salt))
+                        .bind(id)
+                        .execute(&mut *tx)
+                        .await?;
+                    rewritten += 1;
+                }
+
+                tx.commit().await?;
+                after_id = last_id;
+            }
+        }
+    }
+
+    Ok(rewritten)
+}
+
+/// Inserts one row into `visits`, unless its link no longer exists
+const INSERT_VISIT_SQL: &str =
+    "INSERT INTO visits (code, visited_at, ip, country, city, user_agent, device, referer) \
+     SELECT ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8 WHERE EXISTS (SELECT 1 FROM links WHERE code = ?1)";
+
+/// Records a single visit for a short code.
+///
+/// A visit to a link deleted in the meantime is skipped.
+pub async fn insert_visit(pool: &Pool<Sqlite>, visit: &NewVisit) -> Result<()> {
+    sqlx::query(INSERT_VISIT_SQL)
+        .bind(&visit.code)
+        .bind(visit.visited_at)
+        .bind(&visit.ip)
+        .bind(&visit.country)
+        .bind(&visit.city)
+        .bind(&visit.user_agent)
+        .bind(&visit.device)
+        .bind(&visit.referer)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Records a batch of visits in one transaction.
+///
+/// Visits to links deleted since they were queued are skipped, so one missing
+/// link doesn't fail the whole batch.
+pub async fn insert_visits(pool: &Pool<Sqlite>, visits: &[NewVisit]) -> Result<()> {
+    let mut tx = pool.begin().await?;
+
+    for visit in visits {
+        sqlx::query(INSERT_VISIT_SQL)
+            .bind(&visit.code)
+            .bind(visit.visited_at)
+            .bind(&visit.ip)
+            .bind(&visit.country)
+            .bind(&visit.city)
+            .bind(&visit.user_agent)
+            .bind(&visit.device)
+            .bind(&visit.referer)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Returns total visit count for `code`.
+pub async fn count_visits(pool: &Pool<Sqlite>, code: &str) -> Result<i64> {
+    count_visits_between(pool, code, i64::MIN, i64::MAX).await
+}
+
+/// Returns the visit count for `code` and the time of its latest visit, if any.
+pub async fn visit_summary(pool: &Pool<Sqlite>, code: &str) -> Result<(i64, Option<i64>)> {
+    let summary = sqlx::query_as::<_, (i64, Option<i64>)>(
+        "SELECT COUNT(*), MAX(visited_at) FROM visits WHERE code = ?",
+    )
+    .bind(code)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(summary)
+}
+
+/// Returns the visit count for `code` with `visited_at` in `[from, to]`.
+pub async fn count_visits_between(
+    pool: &Pool<Sqlite>,
+    code: &str,
+    from: i64,
+    to: i64,
+) -> Result<i64> {
+    let count = sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM visits WHERE code = ? AND visited_at BETWEEN ? AND ?",
+    )
+    .bind(code)
+    .bind(from)
+    .bind(to)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(count)
+}
+
+/// Returns the number of distinct IPs that visited `code`.
+///
+/// Visits without a recorded IP are excluded.
+pub async fn count_unique_visitors(pool: &Pool<Sqlite>, code: &str) -> Result<i64> {
+    count_unique_visitors_between(pool, code, i64::MIN, i64::MAX).await
+}
+
+/// Returns the number of distinct IPs that visited `code` in `[from, to]`.
+///
+/// Visits without a recorded IP are excluded.
+pub async fn count_unique_visitors_between(
+    pool: &Pool<Sqlite>,
+    code: &str,
+    from: i64,
+    to: i64,
+) -> Result<i64> {
+    let count = sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(DISTINCT ip) FROM visits WHERE code = ? AND visited_at BETWEEN ? AND ?",
+    )
+    .bind(code)
+    .bind(from)
+    .bind(to)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(count)
+}
+
+/// Returns visit counts grouped by country, ordered by count DESC.
+///
+/// With a `limit`, only the top countries are returned; see `visits_grouped_by`.
+pub async fn visits_by_country(
+    pool: &Pool<Sqlite>,
+    code: &str,
+    limit: Option<u32>,
+) -> Result<Vec<(Option<String>, i64)>> {
+    visits_by_country_between(pool, code, i64::MIN, i64::MAX, limit).await
+}
+
+/// Returns visit counts in `[from, to]` grouped by country, ordered by count DESC.
+pub async fn visits_by_country_between(
+    pool: &Pool<Sqlite>,
+    code: &str,
+    from: i64,
+    to: i64,
+    limit: Option<u32>,
+) -> Result<Vec<(Option<String>, i64)>> {
+    visits_grouped_by(pool, code, "country", from, to, limit).await
+}
+
+/// Returns visit counts in `[from, to]` grouped by referer, ordered by count DESC.
+pub async fn visits_by_referer_between(
+    pool: &Pool<Sqlite>,
+    code: &str,
+    from: i64,
+    to: i64,
+    limit: Option<u32>,
+) -> Result<Vec<(Option<String>, i64)>> {
+    visits_grouped_by(pool, code, "referer", from, to, limit).await
+}
+
+/// Returns visit counts in `[from, to]` grouped by the referer's host, ordered by count DESC
+///
+/// `https://twitter.com/a` and `https://twitter.com/b` both count toward `twitter.com`.
+/// Missing and malformed referers are grouped as `None`. With a `limit`, the
+/// rest are folded into an `OTHERS_BUCKET` entry as in `visits_grouped_by`.
+pub async fn visits_by_referer_host_between(
+    pool: &Pool<Sqlite>,
+    code: &str,
+    from: i64,
+    to: i64,
+    limit: Option<u32>,
+) -> Result<Vec<(Option<String>, i64)>> {
+    // Hosts can't be parsed in SQL, so group the full referers and merge them here
+    let referers = visits_grouped_by(pool, code, "referer", from, to, None).await?;
+
+    let mut hosts: Vec<(Option<String>, i64)> = Vec::new();
+    for (referer, count) in referers {
+        let host = referer.as_deref().and_then(referer_host);
+        match hosts.iter_mut().find(|(h, _)| *h == host) {
+            Some((_, total)) => *total += count,
+            None => hosts.push((host, count)),
+        }
+    }
+    // Ties are broken by host so the order is stable across requests
+    hosts.sort_by(|(a_host, a_count), (b_host, b_count)| {
+        b_count.cmp(a_count).then_with(|| a_host.cmp(b_host))
+    });
+
+    if let Some(limit) = limit {
+        let limit = limit as usize;
+        if hosts.len() > limit {
+            let others = hosts.split_off(limit).iter().map(|(_, count)| count).sum();
+            hosts.push((Some(OTHERS_BUCKET.to_string()), others));
+        }
+    }
+
+    Ok(hosts)
+}
+
+/// Returns visit counts grouped by device type, ordered by count DESC.
+pub async fn visits_by_device(
+    pool: &Pool<Sqlite>,
+    code: &str,
+) -> Result<Vec<(Option<String>, i64)>> {
+    visits_by_device_between(pool, code, i64::MIN, i64::MAX).await
+}
+
+/// Returns visit counts in `[from, to]` grouped by device type, ordered by count DESC.
+pub async fn visits_by_device_between(
+    pool: &Pool<Sqlite>,
+    code: &str,
+    from: i64,
+    to: i64,
+) -> Result<Vec<(Option<String>, i64)>> {
+    visits_grouped_by(pool, code, "device", from, to, None).await
+}
+
+/// Counts visits in `[from, to]` grouped by `column`, largest groups first
+///
+/// With a `limit`, only that many groups are returned, followed by an
+/// `OTHERS_BUCKET` entry counting every remaining visit, so the counts still
+/// add up to the total. `column` must be a trusted column name.
+async fn visits_grouped_by(
+    pool: &Pool<Sqlite>,
+    code: &str,
+    column: &str,
+    from: i64,
+    to: i64,
+    limit: Option<u32>,
+) -> Result<Vec<(Option<String>, i64)>> {
+    let mut rows = sqlx::query_as::<_, (Option<String>, i64)>(&format!(
+        "SELECT {column}, COUNT(*) as count FROM visits WHERE code = ? AND visited_at BETWEEN ? AND ? GROUP BY {column} ORDER BY count DESC LIMIT ?",
+    ))
+    .bind(code)
+    .bind(from)
+    .bind(to)
+    // SQLite treats a negative LIMIT as no limit
+    .bind(limit.map_or(-1, i64::from))
+    .fetch_all(pool)
+    .await?;
+
+    if limit.is_some_and(|limit| rows.len() == limit as usize) {
+        let total = count_visits_between(pool, code, from, to).await?;
+        let others = total - rows.iter().map(|(_, count)| count).sum::<i64>();
+
+        if others > 0 {
+            rows.push((Some(OTHERS_BUCKET.to_string()), others));
+        }
+    }
+
+    Ok(rows)
+}
+
+/// Returns daily visit counts for the last 30 days, newest first.
+pub async fn visits_daily(pool: &Pool<Sqlite>, code: &str) -> Result<Vec<(String, i64)>> {
+    let rows = sqlx::query_as::<_, (String, i64)>(
+        r#"SELECT strftime('%Y-%m-%d', datetime(visited_at, 'unixepoch')) as date,
+                  COUNT(*) as count
+           FROM visits
+           WHERE code = ?
+             AND visited_at >= strftime('%s', 'now', '-30 days')
+           GROUP BY date
+           ORDER BY date DESC"#,
+    )
+    .bind(code)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows)
+}
+
+/// Returns daily visit counts for visits in `[from, to]`, newest first.
+pub async fn visits_daily_between(
+    pool: &Pool<Sqlite>,
+    code: &str,
+    from: i64,
+    to: i64,
+) -> Result<Vec<(String, i64)>> {
+    let rows = sqlx::query_as::<_, (String, i64)>(
+        r#"SELECT strftime('%Y-%m-%d', datetime(visited_at, 'unixepoch')) as date,
+                  COUNT(*) as count
+           FROM visits
+           WHERE code = ?
+             AND visited_at BETWEEN ? AND ?
+           GROUP BY date
+           ORDER BY date DESC"#,
+    )
+    .bind(code)
+    .bind(from)
+    .bind(to)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows)
+}
+
+/// Returns up to `limit` individual visit rows for `code`, newest first, skipping `offset`.
+///
+/// `limit` is capped at `MAX_RECENT_VISITS`.
+pub async fn recent_visits(
+    pool: &Pool<Sqlite>,
+    code: &str,
+    limit: u32,
+    offset: u32,
+) -> Result<Vec<VisitRow>> {
+    let rows = sqlx::query_as::<_, (i64, Option<String>, Option<String>, Option<String>, Option<String>, Option<String>)>(
+        "SELECT visited_at, ip, country, city, user_agent, referer FROM visits WHERE code = ? ORDER BY visited_at DESC, id DESC LIMIT ? OFFSET ?",
+    )
+    .bind(code)
+    .bind(limit.min(MAX_RECENT_VISITS))
+    .bind(offset)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(
+            |(visited_at, ip, country, city, user_agent, referer)| VisitRow {
+                visited_at,
+                ip,
+                country,
+                city,
+                user_agent,
+                referer,
+            },
+        )
+        .collect())
+}
+
+/// Returns up to `limit` links with a code sorting after `after_code`, in code order
+///
+/// Pass the last code back as `after_code` to fetch the next page. Start with
+/// an empty string.
+pub async fn links_after(
+    pool: &Pool<Sqlite>,
+    after_code: &str,
+    limit: u32,
+) -> Result<Vec<LinkRecord>> {
+    let links = sqlx::query_as::<_, LinkRecord>(
+        "SELECT code, original_url, expires_at, created_at FROM links WHERE code > ? ORDER BY code LIMIT ?",
+    )
+    .bind(after_code)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(links)
+}
+
+/// Returns up to `limit` visit rows for `code` with an id greater than `after_id`, oldest first
+///
+/// Each row is paired with its id so callers can pass the last one back as
+/// `after_id` to fetch the next page. Start with `after_id = 0`.
+pub async fn visits_after(
+    pool: &Pool<Sqlite>,
+    code: &str,
+    after_id: i64,
+    limit: u32,
+) -> Result<Vec<(i64, VisitRow)>> {
+    let rows = sqlx::query_as::<_, (i64, i64, Option<String>, Option<String>, Option<String>, Option<String>, Option<String>)>(
+        "SELECT id, visited_at, ip, country, city, user_agent, referer FROM visits WHERE code = ? AND id > ? ORDER BY id LIMIT ?",
+    )
+    .bind(code)
+    .bind(after_id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(id, visited_at, ip, country, city, user_agent, referer)| {
+            (
+                id,
+                VisitRow {
+                    visited_at,
+                    ip,
+                    country,
+                    city,
+                    user_agent,
+                    referer,
+                },
+            )
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::SqlitePool;
+
+    async fn setup_db() -> Pool<Sqlite> {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        run_migrations(&pool).await.unwrap();
+        pool
+    }
+
+    #[tokio::test]
+    async fn test_create_pool_uses_wal() {
+        let path = std::env::temp_dir().join(format!("cutl-wal-{}.db", std::process::id()));
+        let url = format!("sqlite:{}?mode=rwc", path.display());
+
+        let pool = create_pool(&url, &PoolSettings::default()).await.unwrap();
+        let (journal_mode,): (String,) = sqlx::query_as("PRAGMA journal_mode")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        let (busy_timeout,): (i64,) = sqlx::query_as("PRAGMA busy_timeout")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        pool.close().await;
+
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+
+        assert_eq!(journal_mode, "wal");
+        assert_eq!(busy_timeout, DEFAULT_BUSY_TIMEOUT_SECONDS * 1000);
+    }
+
+    #[tokio::test]
+    async fn test_create_pool_applies_connection_limits() {
+        let settings = PoolSettings {
+            journal_mode: SqliteJournalMode::Memory,
+            max_connections: 3,
+            min_connections: 0,
+            ..Default::default()
+        };
+
+        let pool = create_pool("sqlite::memory:", &settings).await.unwrap();
+        assert_eq!(pool.options().get_max_connections(), 3);
+        assert_eq!(pool.options().get_min_connections(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_insert_and_count_visits() {
+        let pool = setup_db().await;
+        insert_link(
+            &pool,
+            &NewLink {
+                code: "abc",
+                original_url: "https://example.com",
+                expires_at: 9999999999,
+                created_at: 1000000000,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        insert_visit(
+            &pool,
+            &NewVisit {
+                code: "abc".to_string(),
+                visited_at: 1000000001,
+                ip: Some("1.2.3.4".to_string()),
+                country: Some("US".to_string()),
+                city: Some("New York".to_string()),
+                user_agent: Some("Mozilla/5.0".to_string()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        insert_visit(
+            &pool,
+            &NewVisit {
+                code: "abc".to_string(),
+                visited_at: 1000000002,
+                ip: Some("5.6.7.8".to_string()),
+                country: Some("ID".to_string()),
+                city: Some("Jakarta".to_string()),
+                referer: Some("https://twitter.com/".to_string()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        insert_visit(
+            &pool,
+            &NewVisit {
+                code: "abc".to_string(),
+                visited_at: 1000000003,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let count = count_visits(&pool, "abc").await.unwrap();
+        assert_eq!(count, 3);
+    }
+
+    #[tokio::test]
+    async fn test_visits_by_device() {
+        let pool = setup_db().await;
+        insert_link(
+            &pool,
+            &NewLink {
+                code: "dev",
+                original_url: "https://example.com",
+                expires_at: 9999999999,
+                created_at: 1000000000,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        for (visited_at, device) in [
+            (1000000001, Some("mobile")),
+            (1000000002, Some("desktop")),
+            (1000000003, Some("mobile")),
+            (1000000004, None),
+        ] {
+            insert_visit(
+                &pool,
+                &NewVisit {
+                    code: "dev".to_string(),
+                    visited_at,
+                    device: device.map(str::to_string),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        }
+
+        let devices = visits_by_device(&pool, "dev").await.unwrap();
+        assert_eq!(devices[0], (Some("mobile".to_string()), 2));
+        assert_eq!(devices.len(), 3);
+        assert!(devices.contains(&(Some("desktop".to_string()), 1)));
+        assert!(devices.contains(&(None, 1)));
+
+        let devices = visits_by_device_between(&pool, "dev", 1000000002, 1000000003)
+            .await
+            .unwrap();
+        assert_eq!(devices.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_delete_visits_older_than() {
+        let pool = setup_db().await;
+        insert_link(
+            &pool,
+            &NewLink {
+                code: "old",
+                original_url: "https://example.com",
+                expires_at: 9999999999,
+                created_at: 1000000000,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        for visited_at in [1000000001, 1000000002, 2000000000] {
+            insert_visit(
+                &pool,
+                &NewVisit {
+                    code: "old".to_string(),
+                    visited_at,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        }
+
+        let deleted = delete_visits_older_than(&pool, 1500000000).await.unwrap();
+        assert_eq!(deleted, 2);
+
+        // Only the recent visit is left, and the link itself is untouched
+        let remaining = recent_visits(&pool, "old", 10, 0).await.unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].visited_at, 2000000000);
+        assert!(get_link(&pool, "old").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_anonymize_old_visits() {
+        let pool = setup_db().await;
+        insert_link(
+            &pool,
+            &NewLink {
+                code: "pii",
+                original_url: "https://example.com",
+                expires_at: 9999999999,
+                created_at: 1000000000,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        for visited_at in [1000000001, 2000000000] {
+            insert_visit(
+                &pool,
+                &NewVisit {
+                    code: "pii".to_string(),
+                    visited_at,
+                    ip: Some("203.0.113.7".to_string()),
+                    country: Some("DE".to_string()),
+                    city: Some("Berlin".to_string()),
+                    user_agent: Some("Mozilla/5.0".to_string()),
+                    device: Some("desktop".to_string()),
+                    referer: Some("https://news.example".to_string()),
+                },
+            )
+            .await
+            .unwrap();
+        }
+
+        assert_eq!(
+            anonymize_old_visits(&pool, 1500000000, "salt")
+                .await
+                .unwrap(),
+            1
+        );
+        // Already-scrubbed rows aren't counted again
+        assert_eq!(
+            anonymize_old_visits(&pool, 1500000000, "salt")
+                .await
+                .unwrap(),
+            0
+        );
+
+        let visits = recent_visits(&pool, "pii", 10, 0).await.unwrap();
+        assert_eq!(visits.len(), 2);
+
+        let recent = &visits[0];
+        assert_eq!(recent.visited_at, 2000000000);
+        assert_eq!(recent.ip.as_deref(), Some("203.0.113.7"));
+        assert_eq!(recent.user_agent.as_deref(), Some("Mozilla/5.0"));
+
+        let old = &visits[1];
+        assert_eq!(old.visited_at, 1000000001);
+        assert_eq!(old.ip, Some(hash_ip("203.0.113.7", "salt")));
+        assert!(old.user_agent.is_none());
+        assert_eq!(old.country.as_deref(), Some("DE"));
+        assert_eq!(old.city.as_deref(), Some("Berlin"));
+        assert_eq!(old.referer.as_deref(), Some("https://news.example"));
+        assert_eq!(count_visits(&pool, "pii").await.unwrap(), 2);
+    }
+
+    /// Inserts `count` visits to `code` from raw IPs, all at `visited_at`
+    async fn insert_raw_visits(pool: &Pool<Sqlite>, code: &str, count: i64, visited_at: i64) {
+        sqlx::query(
+            "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < ?) \
+             INSERT INTO visits (code, visited_at, ip, user_agent) \
+             SELECT ?, ?, '203.0.113.' || (i % 250), 'Mozilla/5.0' FROM n",
+        )
+        .bind(count)
+        .bind(code)
+        .bind(visited_at)
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_apply_ip_storage_in_batches() {
+        let pool = setup_db().await;
+        insert_link(
+            &pool,
+            &NewLink {
+                code: "many",
+                original_url: "https://example.com",
+                expires_at: 9999999999,
+                created_at: 1000000000,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        let count = VISIT_REWRITE_BATCH_SIZE * 2 + 1;
+        insert_raw_visits(&pool, "many", count, 1000000001).await;
+
+        for storage in [IpStorage::Hashed, IpStorage::Discard] {
+            assert_eq!(
+                apply_ip_storage(&pool, storage, "salt").await.unwrap(),
+                count as u64
+            );
+        }
+        let kept: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM visits WHERE ip IS NOT NULL")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(kept, 0);
+    }
+
+    #[tokio::test]
+    async fn test_anonymize_old_visits_in_batches() {
+        let pool = setup_db().await;
+        insert_link(
+            &pool,
+            &NewLink {
+                code: "many",
+                original_url: "https://example.com",
+                expires_at: 9999999999,
+                created_at: 1000000000,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        let count = VISIT_REWRITE_BATCH_SIZE * 2 + 1;
+        insert_raw_visits(&pool, "many", count, 1000000001).await;
+
+        assert_eq!(
+            anonymize_old_visits(&pool, 1500000000, "salt")
+                .await
+                .unwrap(),
+            count as u64
+        );
+        let raw: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM visits WHERE ip LIKE '%.%' OR user_agent IS NOT NULL",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(raw, 0);
+    }
+
+    #[tokio::test]
+    async fn test_apply_ip_storage() {
+        let pool = setup_db().await;
+        insert_link(
+            &pool,
+            &NewLink {
+                code: "ips",
+                original_url: "https://example.com",
+                expires_at: 9999999999,
+                created_at: 1000000000,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let hashed = hash_ip("198.51.100.1", "salt");
+        for (visited_at, ip) in [
+            (1000000001, Some("203.0.113.7")),
+            (1000000002, Some("2001:db8::1")),
+            (1000000003, Some(hashed.as_str())),
+            (1000000004, None),
+        ] {
+            insert_visit(
+                &pool,
+                &NewVisit {
+                    code: "ips".to_string(),
+                    visited_at,
+                    ip: ip.map(str::to_string),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        }
+        let ips = |visits: Vec<VisitRow>| -> Vec<Option<String>> {
+            visits.into_iter().rev().map(|visit| visit.ip).collect()
+        };
+
+        assert_eq!(
+            apply_ip_storage(&pool, IpStorage::Raw, "salt")
+                .await
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            apply_ip_storage(&pool, IpStorage::Hashed, "salt")
+                .await
+                .unwrap(),
+            2
+        );
+        assert_eq!(
+            ips(recent_visits(&pool, "ips", 10, 0).await.unwrap()),
+            vec![
+                Some(hash_ip("203.0.113.7", "salt")),
+                Some(hash_ip("2001:db8::1", "salt")),
+                Some(hashed),
+                None,
+            ]
+        );
+        // Running it again finds no raw IPs left
+        assert_eq!(
+            apply_ip_storage(&pool, IpStorage::Hashed, "salt")
+                .await
+                .unwrap(),
+            0
+        );
+
+        assert_eq!(
+            apply_ip_storage(&pool, IpStorage::Discard, "salt")
+                .await
+                .unwrap(),
+            3
+        );
+        assert!(ips(recent_visits(&pool, "ips", 10, 0).await.unwrap())
+            .iter()
+            .all(Option::is_none));
+    }
+
+    #[tokio::test]
+    async fn test_count_unique_visitors() {
+        let pool = setup_db().await;
+        insert_link(
+            &pool,
+            &NewLink {
+                code: "uniq",
+                original_url: "https://example.com",
+                expires_at: 9999999999,
+                created_at: 1000000000,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        for (visited_at, ip) in [
+            (1000000001, Some("1.2.3.4")),
+            (1000000002, Some("1.2.3.4")),
+            (1000000003, Some("5.6.7.8")),
+            (1000000004, Some("1.2.3.4")),
+            (1000000005, None),
+            (1000000006, None),
+        ] {
+            insert_visit(
+                &pool,
+                &NewVisit {
+                    code: "uniq".to_string(),
+                    visited_at,
+                    ip: ip.map(str::to_string),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        }
+
+        assert_eq!(count_visits(&pool, "uniq").await.unwrap(), 6);
+        // Visits without an IP are not counted
+        assert_eq!(count_unique_visitors(&pool, "uniq").await.unwrap(), 2);
+        assert_eq!(
+            count_unique_visitors_between(&pool, "uniq", 1000000001, 1000000002)
+                .await
+                .unwrap(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_visits_by_country() {
+        let pool = setup_db().await;
+        insert_link(
+            &pool,
+            &NewLink {
+                code: "xyz",
+                original_url: "https://example.com",
+                expires_at: 9999999999,
+                created_at: 1000000000,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        insert_visit(
+            &pool,
+            &NewVisit {
+                code: "xyz".to_string(),
+                visited_at: 1000000001,
+                country: Some("ID".to_string()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        insert_visit(
+            &pool,
+            &NewVisit {
+                code: "xyz".to_string(),
+                visited_at: 1000000002,
+                country: Some("ID".to_string()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        insert_visit(
+            &pool,
+            &NewVisit {
+                code: "xyz".to_string(),
+                visited_at: 1000000003,
+                country: Some("US".to_string()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let rows = visits_by_country(&pool, "xyz", None).await.unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].0, Some("ID".to_string()));
+        assert_eq!(rows[0].1, 2);
+        assert_eq!(rows[1].0, Some("US".to_string()));
+        assert_eq!(rows[1].1, 1);
+    }
+
+    #[tokio::test]
+    async fn test_visits_by_country_top_n_folds_others() {
+        let pool = setup_db().await;
+        insert_link(
+            &pool,
+            &NewLink {
+                code: "top",
+                original_url: "https://example.com",
+                expires_at: 9999999999,
+                created_at: 1000000000,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        // ID x4, US x3, DE x2, FR x1, unknown x1
+        let countries = [
+            Some("ID"),
+            Some("ID"),
+            Some("ID"),
+            Some("ID"),
+            Some("US"),
+            Some("US"),
+            Some("US"),
+            Some("DE"),
+            Some("DE"),
+            Some("FR"),
+            None,
+        ];
+        for (i, country) in countries.into_iter().enumerate() {
+            insert_visit(
+                &pool,
+                &NewVisit {
+                    code: "top".to_string(),
+                    visited_at: 1000000000 + i as i64,
+                    country: country.map(str::to_string),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        }
+
+        let total = count_visits(&pool, "top").await.unwrap();
+        let all = visits_by_country(&pool, "top", None).await.unwrap();
+        assert_eq!(all.len(), 5);
+        assert_eq!(all.iter().map(|(_, c)| c).sum::<i64>(), total);
+
+        let top = visits_by_country(&pool, "top", Some(2)).await.unwrap();
+        assert_eq!(
+            top,
+            vec![
+                (Some("ID".to_string()), 4),
+                (Some("US".to_string()), 3),
+                (Some(OTHERS_BUCKET.to_string()), 4),
+            ]
+        );
+        assert_eq!(top.iter().map(|(_, c)| c).sum::<i64>(), total);
+
+        // No "others" entry when nothing is left over
+        let top = visits_by_country(&pool, "top", Some(5)).await.unwrap();
+        assert_eq!(top, all);
+
+        let referers = visits_by_referer_between(&pool, "top", i64::MIN, i64::MAX, Some(1))
+            .await
+            .unwrap();
+        assert_eq!(referers, vec![(None, total)]);
+    }
+
+    #[tokio::test]
+    async fn test_visits_by_referer_host() {
+        let pool = setup_db().await;
+        insert_link(
+            &pool,
+            &NewLink {
+                code: "ref",
+                original_url: "https://example.com",
+                expires_at: 9999999999,
+                created_at: 1000000000,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let referers = [
+            Some("https://twitter.com/a"),
+            Some("https://twitter.com/b"),
+            Some("https://Twitter.com/c?x=1"),
+            Some("https://news.ycombinator.com/item?id=1"),
+            Some("https://news.ycombinator.com/"),
+            Some("https://example.org/"),
+            Some("garbage"),
+            None,
+        ];
+        for (i, referer) in referers.into_iter().enumerate() {
+            insert_visit(
+                &pool,
+                &NewVisit {
+                    code: "ref".to_string(),
+                    visited_at: 1000000000 + i as i64,
+                    referer: referer.map(str::to_string),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        }
+
+        let hosts = visits_by_referer_host_between(&pool, "ref", i64::MIN, i64::MAX, None)
+            .await
+            .unwrap();
+        assert_eq!(
+            hosts,
+            vec![
+                (Some("twitter.com".to_string()), 3),
+                (None, 2),
+                (Some("news.ycombinator.com".to_string()), 2),
+                (Some("example.org".to_string()), 1),
+            ]
+        );
+
+        let top = visits_by_referer_host_between(&pool, "ref", i64::MIN, i64::MAX, Some(1))
+            .await
+            .unwrap();
+        assert_eq!(
+            top,
+            vec![
+                (Some("twitter.com".to_string()), 3),
+                (Some(OTHERS_BUCKET.to_string()), 5),
+            ]
+        );
+    }
+
+    async fn setup_link_with_visits(code: &str, visits: i64) -> Pool<Sqlite> {
+        let pool = setup_db().await;
+        insert_link(
+            &pool,
+            &NewLink {
+                code,
+                original_url: "https://example.com",
+                expires_at: 9999999999,
+                created_at: 1000000000,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        for i in 1..=visits {
+            insert_visit(
+                &pool,
+                &NewVisit {
+                    code: code.to_string(),
+                    visited_at: 1000000000 + i,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        }
+
+        pool
+    }
+
+    #[tokio::test]
+    async fn test_recent_visits_limit() {
+        let pool = setup_link_with_visits("page", 30).await;
+
+        let rows = recent_visits(&pool, "page", DEFAULT_RECENT_VISITS, 0)
+            .await
+            .unwrap();
+        assert_eq!(rows.len(), 20);
+        assert_eq!(rows[0].visited_at, 1000000030);
+
+        let rows = recent_visits(&pool, "page", 5, 0).await.unwrap();
+        assert_eq!(rows.len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_recent_visits_offset() {
+        let pool = setup_link_with_visits("page", 30).await;
+
+        let rows = recent_visits(&pool, "page", 5, 10).await.unwrap();
+        assert_eq!(rows.len(), 5);
+        assert_eq!(rows[0].visited_at, 1000000020);
+        assert_eq!(rows[4].visited_at, 1000000016);
+
+        let rows = recent_visits(&pool, "page", 20, 25).await.unwrap();
+        assert_eq!(rows.len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_recent_visits_max_cap() {
+        let pool = setup_link_with_visits("page", MAX_RECENT_VISITS as i64 + 10).await;
+
+        let rows = recent_visits(&pool, "page", u32::MAX, 0).await.unwrap();
+        assert_eq!(rows.len(), MAX_RECENT_VISITS as usize);
+    }
+
+    #[tokio::test]
+    async fn test_visits_between_excludes_out_of_range() {
+        let pool = setup_db().await;
+        insert_link(
+            &pool,
+            &NewLink {
+                code: "win",
+                original_url: "https://example.com",
+                expires_at: 9999999999,
+                created_at: 1000000000,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        // One visit before, two inside, and one after the window
+        for (visited_at, country) in [
+            (1000000000, "US"),
+            (1000086400, "ID"),
+            (1000090000, "ID"),
+            (1000200000, "US"),
+        ] {
+            insert_visit(
+                &pool,
+                &NewVisit {
+                    code: "win".to_string(),
+                    visited_at,
+                    country: Some(country.to_string()),
+                    referer: Some("https://news.ycombinator.com/".to_string()),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        }
+
+        let (from, to) = (1000086400, 1000090000);
+
+        assert_eq!(count_visits(&pool, "win").await.unwrap(), 4);
+        assert_eq!(
+            count_visits_between(&pool, "win", from, to).await.unwrap(),
+            2
+        );
+
+        let countries = visits_by_country_between(&pool, "win", from, to, None)
+            .await
+            .unwrap();
+        assert_eq!(countries, vec![(Some("ID".to_string()), 2)]);
+
+        let referers = visits_by_referer_between(&pool, "win", from, to, None)
+            .await
+            .unwrap();
+        assert_eq!(
+            referers,
+            vec![(Some("https://news.ycombinator.com/".to_string()), 2)]
+        );
+
+        let daily = visits_daily_between(&pool, "win", from, to).await.unwrap();
+        assert_eq!(daily, vec![("2001-09-10".to_string(), 2)]);
+    }
+
+    #[tokio::test]
+    async fn test_find_link_by_url_skips_expired() {
+        let pool = setup_db().await;
+        insert_link(
+            &pool,
+            &NewLink {
+                code: "old",
+                original_url: "https://example.com",
+                expires_at: 1000000100,
+                created_at: 1000000000,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        assert!(
+            find_link_by_url(&pool, "https://example.com", None, 100, 1000000200)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        insert_link(
+            &pool,
+            &NewLink {
+                code: "new",
+                original_url: "https://example.com",
+                expires_at: 1000000250,
+                created_at: 1000000150,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let link = find_link_by_url(&pool, "https://example.com", None, 100, 1000000200)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(link.code, "new");
+    }
 
     #[tokio::test]
-    async fn test_insert_and_count_visits() {
+    async fn test_find_link_by_url_skips_links_with_settings() {
         let pool = setup_db().await;
-        insert_link(&pool, "abc", "https://example.com", 9999999999, 1000000000)
+        let base = NewLink {
+            original_url: "https://example.com",
+            expires_at: 9999999999,
+            created_at: 1000000000,
+            ..Default::default()
+        };
+        for link in [
+            NewLink {
+                code: "rules",
+                rules: Some(r#"{"geo":{"DE":"https://example.de"}}"#),
+                ..base
+            },
+            NewLink {
+                code: "sliding",
+                idle_ttl: Some(3600),
+                max_expires_at: Some(9999999999),
+                ..base
+            },
+            NewLink {
+                code: "status",
+                redirect_status: Some(301),
+                ..base
+            },
+            NewLink {
+                code: "titled",
+                title: Some("Docs"),
+                ..base
+            },
+            NewLink {
+                code: "described",
+                description: Some("Notes"),
+                ..base
+            },
+            NewLink {
+                code: "disabled",
+                ..base
+            },
+        ] {
+            insert_link(&pool, &link).await.unwrap();
+        }
+        set_link_enabled(&pool, "disabled", false).await.unwrap();
+        let ttl = base.expires_at - base.created_at;
+
+        assert!(
+            find_link_by_url(&pool, "https://example.com", None, ttl, 1000000200)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        insert_link(
+            &pool,
+            &NewLink {
+                code: "plain",
+                expires_at: 9999999998,
+                created_at: 999999999,
+                ..base
+            },
+        )
+        .await
+        .unwrap();
+        let link = find_link_by_url(&pool, "https://example.com", None, ttl, 1000000200)
             .await
+            .unwrap()
             .unwrap();
+        assert_eq!(link.code, "plain");
+    }
 
-        insert_visit(
+    #[tokio::test]
+    async fn test_find_link_by_url_matches_ttl() {
+        let pool = setup_db().await;
+        insert_link(
             &pool,
-            "abc",
-            1000000001,
-            Some("1.2.3.4"),
-            Some("US"),
-            Some("New York"),
-            Some("Mozilla/5.0"),
-            None,
+            &NewLink {
+                code: "day",
+                original_url: "https://example.com",
+                expires_at: 1000086400,
+                created_at: 1000000000,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        for ttl in [3600, 7 * 86400] {
+            assert!(
+                find_link_by_url(&pool, "https://example.com", None, ttl, 1000000100)
+                    .await
+                    .unwrap()
+                    .is_none()
+            );
+        }
+        let link = find_link_by_url(&pool, "https://example.com", None, 86400, 1000000100)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(link.code, "day");
+    }
+
+    #[tokio::test]
+    async fn test_claim_visit() {
+        let pool = setup_db().await;
+
+        insert_link(
+            &pool,
+            &NewLink {
+                code: "twice",
+                original_url: "https://example.com",
+                expires_at: 9999999999,
+                created_at: 1000000000,
+                max_visits: Some(2),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        insert_link(
+            &pool,
+            &NewLink {
+                code: "open",
+                original_url: "https://example.com",
+                expires_at: 9999999999,
+                created_at: 1000000000,
+                ..Default::default()
+            },
         )
         .await
         .unwrap();
+
+        assert!(claim_visit(&pool, "twice").await.unwrap());
+        assert!(claim_visit(&pool, "twice").await.unwrap());
+        assert!(!claim_visit(&pool, "twice").await.unwrap());
+
+        let link = get_link(&pool, "twice").await.unwrap().unwrap();
+        assert_eq!(link.max_visits, Some(2));
+        assert_eq!(link.remaining_visits, Some(0));
+
+        // Unlimited links have no counter to claim from
+        assert!(!claim_visit(&pool, "open").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_delete_unused_links() {
+        let pool = setup_db().await;
+
+        for (code, created_at) in [("old", 1000), ("visited", 1000), ("fresh", 5000)] {
+            insert_link(
+                &pool,
+                &NewLink {
+                    code,
+                    original_url: "https://example.com",
+                    expires_at: 9999999999,
+                    created_at,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        }
         insert_visit(
             &pool,
-            "abc",
-            1000000002,
-            Some("5.6.7.8"),
-            Some("ID"),
-            Some("Jakarta"),
-            None,
-            Some("https://twitter.com/"),
+            &NewVisit {
+                code: "visited".to_string(),
+                visited_at: 2000,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        // Only links both older than the cutoff and never visited go
+        assert_eq!(delete_unused_links(&pool, 3000).await.unwrap(), 1);
+        assert!(get_link(&pool, "old").await.unwrap().is_none());
+        assert!(get_link(&pool, "visited").await.unwrap().is_some());
+        assert!(get_link(&pool, "fresh").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_set_link_expiry() {
+        let pool = setup_db().await;
+
+        insert_link(
+            &pool,
+            &NewLink {
+                code: "later",
+                original_url: "https://example.com",
+                expires_at: 2000000000,
+                created_at: 1000000000,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        assert!(set_link_expiry(&pool, "later", 2100000000).await.unwrap());
+        let link = get_link(&pool, "later").await.unwrap().unwrap();
+        assert_eq!(link.expires_at, 2100000000);
+        assert_eq!(link.original_url, "https://example.com");
+
+        assert!(!set_link_expiry(&pool, "missing", 2100000000).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_touch_link_slides_expiry_up_to_max() {
+        let pool = setup_db().await;
+
+        insert_link(
+            &pool,
+            &NewLink {
+                code: "sliding",
+                original_url: "https://example.com",
+                expires_at: 1000000100,
+                created_at: 1000000000,
+                idle_ttl: Some(100),
+                max_expires_at: Some(1000000250),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        insert_link(
+            &pool,
+            &NewLink {
+                code: "fixed",
+                original_url: "https://example.com",
+                expires_at: 1000000100,
+                created_at: 1000000000,
+                ..Default::default()
+            },
         )
         .await
         .unwrap();
-        insert_visit(&pool, "abc", 1000000003, None, None, None, None, None)
+
+        assert!(touch_link(&pool, "sliding", 1000000090).await.unwrap());
+        let link = get_link(&pool, "sliding").await.unwrap().unwrap();
+        assert_eq!(link.expires_at, 1000000190);
+        assert_eq!(link.idle_ttl, Some(100));
+
+        // The hard limit wins over the idle window
+        touch_link(&pool, "sliding", 1000000180).await.unwrap();
+        let link = get_link(&pool, "sliding").await.unwrap().unwrap();
+        assert_eq!(link.expires_at, 1000000250);
+
+        // A touch never moves the expiry earlier
+        touch_link(&pool, "sliding", 1000000100).await.unwrap();
+        let link = get_link(&pool, "sliding").await.unwrap().unwrap();
+        assert_eq!(link.expires_at, 1000000250);
+
+        // Fixed-expiry links aren't touched
+        assert!(!touch_link(&pool, "fixed", 1000000090).await.unwrap());
+        let link = get_link(&pool, "fixed").await.unwrap().unwrap();
+        assert_eq!(link.expires_at, 1000000100);
+    }
+
+    #[tokio::test]
+    async fn test_new_expiry_makes_sliding_link_fixed() {
+        let pool = setup_db().await;
+        for code in ["extended", "updated", "renamed"] {
+            insert_link(
+                &pool,
+                &NewLink {
+                    code,
+                    original_url: "https://example.com",
+                    expires_at: 1000000100,
+                    created_at: 1000000000,
+                    idle_ttl: Some(100),
+                    max_expires_at: Some(1000000250),
+                    ..Default::default()
+                },
+            )
             .await
             .unwrap();
+        }
 
-        let count = count_visits(&pool, "abc").await.unwrap();
-        assert_eq!(count, 3);
+        set_link_expiry(&pool, "extended", 1000005000)
+            .await
+            .unwrap();
+        update_link(&pool, "updated", None, Some(1000005000))
+            .await
+            .unwrap();
+        for code in ["extended", "updated"] {
+            let link = get_link(&pool, code).await.unwrap().unwrap();
+            assert_eq!(link.expires_at, 1000005000);
+            assert_eq!(link.idle_ttl, None);
+            assert_eq!(link.max_expires_at, None);
+
+            // Later visits leave the new expiry alone
+            assert!(!touch_link(&pool, code, 1000000200).await.unwrap());
+        }
+
+        // Changing only the URL keeps the link sliding
+        update_link(&pool, "renamed", Some("https://example.com/new"), None)
+            .await
+            .unwrap();
+        let link = get_link(&pool, "renamed").await.unwrap().unwrap();
+        assert_eq!(link.idle_ttl, Some(100));
+        assert_eq!(link.max_expires_at, Some(1000000250));
     }
 
     #[tokio::test]
-    async fn test_visits_by_country() {
+    async fn test_set_link_enabled() {
+        let pool = setup_db().await;
+
+        insert_link(
+            &pool,
+            &NewLink {
+                code: "toggle",
+                original_url: "https://example.com",
+                expires_at: 9999999999,
+                created_at: 1000000000,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        // New links start enabled
+        assert!(get_link(&pool, "toggle").await.unwrap().unwrap().enabled);
+
+        assert!(set_link_enabled(&pool, "toggle", false).await.unwrap());
+        assert!(!get_link(&pool, "toggle").await.unwrap().unwrap().enabled);
+
+        assert!(set_link_enabled(&pool, "toggle", true).await.unwrap());
+        assert!(get_link(&pool, "toggle").await.unwrap().unwrap().enabled);
+
+        assert!(!set_link_enabled(&pool, "missing", false).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_find_code_ignore_case_uses_index() {
+        let pool = setup_db().await;
+
+        let plan: Vec<(i64, i64, i64, String)> = sqlx::query_as(
+            "EXPLAIN QUERY PLAN SELECT code FROM links WHERE code = ? COLLATE NOCASE ORDER BY code = ? DESC LIMIT 1",
+        )
+        .bind("AbC")
+        .bind("AbC")
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert!(plan
+            .iter()
+            .any(|(_, _, _, detail)| detail.contains("idx_links_code_nocase")));
+    }
+
+    #[tokio::test]
+    async fn test_upsert_link_overwrite_resets_settings() {
+        let pool = setup_db().await;
+        insert_link(
+            &pool,
+            &NewLink {
+                code: "dup",
+                original_url: "https://old.example.com",
+                expires_at: 9999999999,
+                created_at: 1000000000,
+                max_visits: Some(3),
+                owner: Some("alice"),
+                append_query: Some("utm_source=x"),
+                rules: Some("{}"),
+                idle_ttl: Some(60),
+                max_expires_at: Some(9999999999),
+                redirect_status: Some(301),
+                title: Some("Old"),
+                description: Some("Old notes"),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let record = LinkRecord {
+            code: "dup".to_string(),
+            original_url: "https://new.example.com".to_string(),
+            expires_at: 8888888888,
+            created_at: 1000000500,
+        };
+        assert!(!upsert_link(&pool, &record, false).await.unwrap());
+        assert_eq!(
+            get_link(&pool, "dup").await.unwrap().unwrap().original_url,
+            "https://old.example.com"
+        );
+
+        assert!(upsert_link(&pool, &record, true).await.unwrap());
+        let link = get_link(&pool, "dup").await.unwrap().unwrap();
+        assert_eq!(link.original_url, "https://new.example.com");
+        assert_eq!(link.expires_at, 8888888888);
+        assert_eq!(link.created_at, 1000000500);
+        assert_eq!(link.password_hash, None);
+        assert_eq!(link.max_visits, None);
+        assert_eq!(link.remaining_visits, None);
+        assert_eq!(link.owner, None);
+        assert_eq!(link.append_query, None);
+        assert_eq!(link.rules, None);
+        assert!(link.enabled);
+        assert_eq!(link.idle_ttl, None);
+        assert_eq!(link.max_expires_at, None);
+        assert_eq!(link.redirect_status, None);
+        assert_eq!(link.title, None);
+        assert_eq!(link.description, None);
+    }
+
+    #[tokio::test]
+    async fn test_enforce_code_case() {
+        let pool = setup_db().await;
+        let link = |code| NewLink {
+            code,
+            original_url: "https://example.com",
+            expires_at: 9999999999,
+            created_at: 1000000000,
+            ..Default::default()
+        };
+
+        enforce_code_case(&pool, true).await.unwrap();
+        insert_link(&pool, &link("Docs")).await.unwrap();
+        let err = insert_link(&pool, &link("docs")).await.unwrap_err();
+        assert!(is_unique_violation(&err));
+
+        // Turning the option off lets both spellings exist, and then it can't come back on
+        enforce_code_case(&pool, false).await.unwrap();
+        insert_link(&pool, &link("docs")).await.unwrap();
+        assert!(enforce_code_case(&pool, true).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_upsert_link_keeps_protected_and_disabled_links() {
         let pool = setup_db().await;
-        insert_link(&pool, "xyz", "https://example.com", 9999999999, 1000000000)
+        for (code, password_hash) in [("locked", Some("hash")), ("off", None)] {
+            insert_link(
+                &pool,
+                &NewLink {
+                    code,
+                    original_url: "https://old.example.com",
+                    expires_at: 9999999999,
+                    created_at: 1000000000,
+                    password_hash,
+                    ..Default::default()
+                },
+            )
             .await
             .unwrap();
+        }
+        set_link_enabled(&pool, "off", false).await.unwrap();
+
+        for code in ["locked", "off"] {
+            let record = LinkRecord {
+                code: code.to_string(),
+                original_url: "https://new.example.com".to_string(),
+                expires_at: 8888888888,
+                created_at: 1000000500,
+            };
+            assert!(!upsert_link(&pool, &record, true).await.unwrap());
+
+            let link = get_link(&pool, code).await.unwrap().unwrap();
+            assert_eq!(link.original_url, "https://old.example.com");
+        }
+        let locked = get_link(&pool, "locked").await.unwrap().unwrap();
+        assert_eq!(locked.password_hash.as_deref(), Some("hash"));
+        assert!(!get_link(&pool, "off").await.unwrap().unwrap().enabled);
+    }
+
+    #[tokio::test]
+    async fn test_rotate_link_code() {
+        let pool = setup_db().await;
 
-        insert_visit(&pool, "xyz", 1000000001, None, Some("ID"), None, None, None)
+        for code in ["leaked", "dropped"] {
+            insert_link(
+                &pool,
+                &NewLink {
+                    code,
+                    original_url: "https://example.com",
+                    expires_at: 9999999999,
+                    created_at: 1000000000,
+                    password_hash: Some("hash"),
+                    title: Some("Launch"),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+            insert_visit(
+                &pool,
+                &NewVisit {
+                    code: code.to_string(),
+                    visited_at: 1000000050,
+                    ..Default::default()
+                },
+            )
             .await
             .unwrap();
-        insert_visit(&pool, "xyz", 1000000002, None, Some("ID"), None, None, None)
+        }
+
+        let mut conn = pool.acquire().await.unwrap();
+        assert!(rotate_link_code(&mut conn, "leaked", "fresh", true)
+            .await
+            .unwrap());
+        assert!(get_link(&pool, "leaked").await.unwrap().is_none());
+        let link = get_link(&pool, "fresh").await.unwrap().unwrap();
+        assert_eq!(link.original_url, "https://example.com");
+        assert_eq!(link.expires_at, 9999999999);
+        assert_eq!(link.password_hash.as_deref(), Some("hash"));
+        assert_eq!(link.title.as_deref(), Some("Launch"));
+        assert_eq!(count_visits(&pool, "fresh").await.unwrap(), 1);
+        assert_eq!(count_visits(&pool, "leaked").await.unwrap(), 0);
+
+        // Without keep_visits the history goes away with the old code
+        assert!(rotate_link_code(&mut conn, "dropped", "clean", false)
+            .await
+            .unwrap());
+        assert_eq!(count_visits(&pool, "clean").await.unwrap(), 0);
+        assert_eq!(count_visits(&pool, "dropped").await.unwrap(), 0);
+
+        assert!(!rotate_link_code(&mut conn, "missing", "other", true)
+            .await
+            .unwrap());
+        assert!(get_link(&pool, "other").await.unwrap().is_none());
+
+        // A taken code is reported as a UNIQUE violation, for callers to retry
+        let err = rotate_link_code(&mut conn, "fresh", "clean", true)
+            .await
+            .unwrap_err();
+        assert!(is_unique_violation(&err));
+        assert!(get_link(&pool, "fresh").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_share_tokens() {
+        let pool = setup_db().await;
+        insert_link(
+            &pool,
+            &NewLink {
+                code: "shared",
+                original_url: "https://example.com",
+                expires_at: 9999999999,
+                created_at: 1000000000,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        insert_share_token(&pool, "live", "shared", 1000000000, 1000003600)
             .await
             .unwrap();
-        insert_visit(&pool, "xyz", 1000000003, None, Some("US"), None, None, None)
+        insert_share_token(&pool, "stale", "shared", 1000000000, 1000000060)
             .await
             .unwrap();
 
-        let rows = visits_by_country(&pool, "xyz").await.unwrap();
-        assert_eq!(rows.len(), 2);
-        assert_eq!(rows[0].0, Some("ID".to_string()));
-        assert_eq!(rows[0].1, 2);
-        assert_eq!(rows[1].0, Some("US".to_string()));
-        assert_eq!(rows[1].1, 1);
+        assert_eq!(
+            share_token_code(&pool, "live", 1000000100).await.unwrap(),
+            Some("shared".to_string())
+        );
+        assert_eq!(
+            share_token_code(&pool, "stale", 1000000100).await.unwrap(),
+            None
+        );
+        assert_eq!(
+            share_token_code(&pool, "unknown", 1000000100)
+                .await
+                .unwrap(),
+            None
+        );
+
+        assert_eq!(
+            delete_expired_share_tokens(&pool, 1000000100)
+                .await
+                .unwrap(),
+            1
+        );
+
+        // Tokens go with their link
+        delete_link(&pool, "shared").await.unwrap();
+        assert_eq!(
+            share_token_code(&pool, "live", 1000000100).await.unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_list_links_by_owner() {
+        let pool = setup_db().await;
+        for (code, owner, created_at) in [
+            ("a1", Some("alice"), 1000000001),
+            ("a2", Some("alice"), 1000000002),
+            ("b1", Some("bob"), 1000000003),
+            ("anon", None, 1000000004),
+        ] {
+            insert_link(
+                &pool,
+                &NewLink {
+                    code,
+                    original_url: "https://example.com",
+                    expires_at: 9999999999,
+                    created_at,
+                    owner,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        }
+
+        let alice = list_links(&pool, Some("alice"), None, DEFAULT_LINKS_PAGE_SIZE, 0)
+            .await
+            .unwrap();
+        let codes: Vec<_> = alice.iter().map(|l| l.code.as_str()).collect();
+        assert_eq!(codes, ["a2", "a1"]);
+        assert_eq!(alice[0].owner.as_deref(), Some("alice"));
+
+        let page = list_links(&pool, Some("alice"), None, 1, 1).await.unwrap();
+        assert_eq!(page[0].code, "a1");
+
+        let anon = list_links(&pool, None, None, DEFAULT_LINKS_PAGE_SIZE, 0)
+            .await
+            .unwrap();
+        assert_eq!(anon.len(), 1);
+        assert_eq!(anon[0].code, "anon");
+    }
+
+    #[tokio::test]
+    async fn test_find_link_by_url_scoped_to_owner() {
+        let pool = setup_db().await;
+        insert_link(
+            &pool,
+            &NewLink {
+                code: "mine",
+                original_url: "https://example.com",
+                expires_at: 9999999999,
+                created_at: 1000000000,
+                owner: Some("alice"),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        assert!(find_link_by_url(
+            &pool,
+            "https://example.com",
+            Some("bob"),
+            8999999999,
+            1000000100
+        )
+        .await
+        .unwrap()
+        .is_none());
+        assert!(find_link_by_url(
+            &pool,
+            "https://example.com",
+            Some("alice"),
+            8999999999,
+            1000000100
+        )
+        .await
+        .unwrap()
+        .is_some());
+    }
+
+    async fn setup_searchable_links() -> Pool<Sqlite> {
+        let pool = setup_db().await;
+        for (i, (code, url)) in [
+            ("docs", "https://docs.rs/axum"),
+            ("blog", "https://example.com/blog/100%_rust"),
+            ("shop", "https://shop.example.com"),
+            ("news", "https://news.ycombinator.com"),
+            ("rust", "https://www.rust-lang.org"),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            insert_link(
+                &pool,
+                &NewLink {
+                    code,
+                    original_url: url,
+                    expires_at: 9999999999,
+                    created_at: 1000000000 + i as i64,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        }
+        pool
+    }
+
+    #[tokio::test]
+    async fn test_list_links_pagination_boundaries() {
+        let pool = setup_searchable_links().await;
+
+        let first = list_links(&pool, None, None, 2, 0).await.unwrap();
+        let codes: Vec<_> = first.iter().map(|l| l.code.as_str()).collect();
+        assert_eq!(codes, ["rust", "news"]);
+
+        // Last page is partial
+        let last = list_links(&pool, None, None, 2, 4).await.unwrap();
+        assert_eq!(last.len(), 1);
+        assert_eq!(last[0].code, "docs");
+
+        // Offset at or past the end is empty, not an error
+        assert!(list_links(&pool, None, None, 2, 5)
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(list_links(&pool, None, None, 0, 0)
+            .await
+            .unwrap()
+            .is_empty());
+
+        assert_eq!(count_links(&pool, None, None).await.unwrap(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_list_links_search() {
+        let pool = setup_searchable_links().await;
+
+        // Matches URL substrings case-insensitively
+        let links = list_links(&pool, None, Some("EXAMPLE.COM"), 20, 0)
+            .await
+            .unwrap();
+        let codes: Vec<_> = links.iter().map(|l| l.code.as_str()).collect();
+        assert_eq!(codes, ["shop", "blog"]);
+        assert_eq!(
+            count_links(&pool, None, Some("example.com")).await.unwrap(),
+            2
+        );
+
+        // Matches codes
+        let links = list_links(&pool, None, Some("ne"), 20, 0).await.unwrap();
+        assert_eq!(links[0].code, "news");
+
+        // LIKE wildcards are matched literally
+        let links = list_links(&pool, None, Some("100%_"), 20, 0).await.unwrap();
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].code, "blog");
+        assert_eq!(count_links(&pool, None, Some("%")).await.unwrap(), 1);
+
+        assert_eq!(count_links(&pool, None, Some("missing")).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_visits_after_pages_by_id() {
+        let pool = setup_link_with_visits("page", 5).await;
+
+        let first = visits_after(&pool, "page", 0, 3).await.unwrap();
+        assert_eq!(first.len(), 3);
+        assert_eq!(first[0].1.visited_at, 1000000001);
+
+        let last_id = first.last().unwrap().0;
+        let rest = visits_after(&pool, "page", last_id, 3).await.unwrap();
+        assert_eq!(rest.len(), 2);
+        assert_eq!(rest[1].1.visited_at, 1000000005);
+
+        let done = visits_after(&pool, "page", rest.last().unwrap().0, 3)
+            .await
+            .unwrap();
+        assert!(done.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_links_after_pages_by_code() {
+        let pool = setup_searchable_links().await;
+
+        let first = links_after(&pool, "", 3).await.unwrap();
+        let codes: Vec<&str> = first.iter().map(|l| l.code.as_str()).collect();
+        assert_eq!(codes, ["blog", "docs", "news"]);
+        assert_eq!(first[0].original_url, "https://example.com/blog/100%_rust");
+
+        let rest = links_after(&pool, "news", 3).await.unwrap();
+        let codes: Vec<&str> = rest.iter().map(|l| l.code.as_str()).collect();
+        assert_eq!(codes, ["rust", "shop"]);
+
+        assert!(links_after(&pool, "shop", 3).await.unwrap().is_empty());
     }
 }
@@ -0,0 +1,153 @@
+//! Raw visit and link exports for offline analysis and backups
+//!
+//! Rows are read from the database in pages and streamed to the client,
+//! so exports of any size use a bounded amount of memory.
+
+use crate::{
+    database::{links_after, visits_after},
+    models::{LinkRecord, VisitRow},
+};
+use axum::body::Body;
+use futures_util::stream;
+use sqlx::{Pool, Sqlite};
+
+/// First line of every CSV export
+pub const CSV_HEADER: &str = "visited_at,ip,country,city,user_agent,referer\n";
+
+/// Number of visits or links fetched from the database per page
+pub const EXPORT_PAGE_SIZE: u32 = 1000;
+
+/// Quotes a CSV field when it contains a delimiter, quote, or line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Formats one visit as a CSV line; missing values become empty fields
+pub fn csv_row(visit: &VisitRow) -> String {
+    let optional = |value: &Option<String>| value.as_deref().map(csv_field).unwrap_or_default();
+
+    format!(
+        "{},{},{},{},{},{}\n",
+        visit.visited_at,
+        optional(&visit.ip),
+        optional(&visit.country),
+        optional(&visit.city),
+        optional(&visit.user_agent),
+        optional(&visit.referer),
+    )
+}
+
+/// Streams every visit for `code` as CSV, oldest first
+///
+/// A database error mid-stream aborts the response body.
+pub fn visits_csv(pool: Pool<Sqlite>, code: String) -> Body {
+    let header = stream::once(async { Ok::<_, anyhow::Error>(CSV_HEADER.to_string()) });
+
+    // State is the id of the last exported visit, or None once the export is done
+    let rows = stream::try_unfold(Some(0), move |after_id| {
+        let pool = pool.clone();
+        let code = code.clone();
+
+        async move {
+            let Some(after_id) = after_id else {
+                return Ok(None);
+            };
+
+            let page = visits_after(&pool, &code, after_id, EXPORT_PAGE_SIZE).await?;
+            let Some(&(last_id, _)) = page.last() else {
+                return Ok(None);
+            };
+
+            let chunk: String = page.iter().map(|(_, visit)| csv_row(visit)).collect();
+            let next = (page.len() == EXPORT_PAGE_SIZE as usize).then_some(last_id);
+
+            Ok(Some((chunk, next)))
+        }
+    });
+
+    Body::from_stream(futures_util::StreamExt::chain(header, rows))
+}
+
+/// Formats one link as a line of NDJSON
+pub fn ndjson_line(link: &LinkRecord) -> String {
+    let mut line = serde_json::to_string(link).expect("link records always serialize");
+    line.push('\n');
+    line
+}
+
+/// Streams every link as NDJSON, one object per line in code order
+///
+/// A database error mid-stream aborts the response body.
+pub fn links_ndjson(pool: Pool<Sqlite>) -> Body {
+    // State is the last exported code, or None once the export is done
+    let lines = stream::try_unfold(Some(String::new()), move |after_code| {
+        let pool = pool.clone();
+
+        async move {
+            let Some(after_code) = after_code else {
+                return Ok::<_, anyhow::Error>(None);
+            };
+
+            let page = links_after(&pool, &after_code, EXPORT_PAGE_SIZE).await?;
+            let Some(last) = page.last() else {
+                return Ok(None);
+            };
+
+            let chunk: String = page.iter().map(ndjson_line).collect();
+            let next = (page.len() == EXPORT_PAGE_SIZE as usize).then(|| last.code.clone());
+
+            Ok(Some((chunk, next)))
+        }
+    });
+
+    Body::from_stream(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_field_quotes_special_characters() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn test_ndjson_line() {
+        let link = LinkRecord {
+            code: "abc".to_string(),
+            original_url: "https://example.com/\"quoted\"\n".to_string(),
+            expires_at: 1760000000,
+            created_at: 1739000000,
+        };
+
+        let line = ndjson_line(&link);
+        assert!(line.ends_with('\n'));
+        assert_eq!(line.matches('\n').count(), 1);
+        assert_eq!(serde_json::from_str::<LinkRecord>(&line).unwrap(), link);
+    }
+
+    #[test]
+    fn test_csv_row() {
+        let visit = VisitRow {
+            visited_at: 1739000000,
+            ip: Some("1.2.3.4".to_string()),
+            country: Some("ID".to_string()),
+            city: None,
+            user_agent: Some("Mozilla/5.0 (X11, Linux)".to_string()),
+            referer: None,
+        };
+
+        assert_eq!(
+            csv_row(&visit),
+            "1739000000,1.2.3.4,ID,,\"Mozilla/5.0 (X11, Linux)\",\n"
+        );
+    }
+}
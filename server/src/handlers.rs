@@ -3,25 +3,68 @@
 //! Handles all incoming HTTP requests for creating and redirecting short links.
 
 use crate::{
+    auth::{authenticate, require_admin, require_auth, require_owner},
+    blocklist::destination_allowed,
+    cleanup::SECONDS_PER_DAY,
     database::{
-        code_exists, count_visits, delete_link, get_link, insert_link, insert_visit, recent_visits,
-        visits_by_country, visits_by_referer, visits_daily,
+        claim_visit, code_exists, count_all_visits, count_links, count_links_by_state,
+        count_unique_visitors, count_unique_visitors_between, count_visits, count_visits_between,
+        delete_expired_links, delete_link, delete_unused_links, find_code_ignore_case,
+        find_link_by_url, get_link, insert_link, insert_share_token, insert_visit,
+        is_unique_violation, ping, recent_visits, rotate_link_code, set_link_enabled,
+        set_link_expiry, share_token_code, touch_link, update_link, visit_summary,
+        visits_by_country, visits_by_country_between, visits_by_device, visits_by_device_between,
+        visits_by_referer_between, visits_by_referer_host_between, visits_daily,
+        visits_daily_between, DEFAULT_LINKS_PAGE_SIZE, DEFAULT_RECENT_VISITS, DEFAULT_TOP_VALUES,
     },
+    export,
+    import::Importer,
+    metrics,
     models::{
-        AnalyticsResponse, ApiError, AppState, CountStat, DailyStat, ShortenRequest,
-        ShortenResponse,
+        AnalyticsQuery, AnalyticsResponse, ApiError, AppState, BatchItemError, BatchItemResult,
+        BatchShortenRequest, BatchShortenResponse, CleanupQuery, CleanupResponse, ConflictMode,
+        CountStat, DailyStat, DeleteResponse, ExtendMode, ExtendRequest, HealthResponse,
+        ImportQuery, ImportResponse, Link, LinkInfoResponse, LinkRules, LinksQuery, LinksResponse,
+        NewLink, NewVisit, PasswordForm, PublicStatsResponse, QrFormat, QrQuery, RedirectQuery,
+        RefererGrouping, RotateQuery, ShareQuery, ShareResponse, ShortenRequest, ShortenResponse,
+        StatsResponse, UpdateRequest, LINK_REDIRECT_STATUSES, MAX_DESCRIPTION_LENGTH,
+        MAX_TITLE_LENGTH,
     },
+    pages, qr,
     utils::{
-        extract_client_ip, generate_code, now_unix, parse_ttl, resolve_geo, validate_code,
-        validate_url,
+        append_query_params, classify_device, client_ip, etag_matches, extract_token,
+        format_rfc3339, generate_code, generate_share_token, hash_password, hash_share_token,
+        is_bot, is_reserved_code, is_valid_code, normalize_url, now_unix, parse_ttl, prefers_json,
+        primary_language, request_base_url, resolves_to_blocked_ip, validate_code, validate_url,
+        verify_password, CodeSettings, MAX_CODE_LENGTH,
     },
+    webhook::WebhookPayload,
 };
 use axum::{
-    extract::{Path, State},
-    response::{Json, Redirect},
+    body::Body,
+    extract::{ConnectInfo, Form, Path, Query, RawQuery, State},
+    http::{header, StatusCode},
+    response::{Html, IntoResponse, Json, Response},
+    Extension,
 };
+use futures_util::StreamExt;
+use sqlx::SqliteConnection;
+use std::collections::BTreeMap;
+use std::net::{IpAddr, SocketAddr};
 use tracing::info;
 
+/// Served at `/robots.txt`: short links, analytics, and the API aren't for crawling
+const ROBOTS_TXT: &str = "User-agent: *\nDisallow: /\nDisallow: /analytics/\nDisallow: /api/\n";
+
+/// Lets browsers and crawlers cache `/robots.txt` and `/favicon.ico` for a day
+const STATIC_CACHE_CONTROL: &str = "public, max-age=86400";
+
+/// Lifetime of a share token minted without a `ttl`: 7 days
+const DEFAULT_SHARE_TTL_SECONDS: i64 = 7 * SECONDS_PER_DAY;
+
+/// Fresh codes `rotate` tries when another link takes its new code first
+const ROTATE_ATTEMPTS: usize = 3;
+
 /// POST /shorten - Creates a new short link
 ///
 /// # Request Body
@@ -47,453 +90,6403 @@ use tracing::info;
 /// - 401: Invalid or missing auth token
 /// - 409: Code already exists
 /// - 500: Internal server error
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/shorten",
+    request_body = ShortenRequest,
+    responses(
+        (status = 200, description = "Short link created", body = ShortenResponse),
+        (status = 400, description = "Invalid URL, code, or TTL", body = ApiError),
+        (status = 401, description = "Invalid or missing auth token", body = ApiError),
+        (status = 409, description = "Code already exists", body = ApiError),
+    ),
+    security((), ("bearer_auth" = []))
+))]
 pub async fn shorten(
     State(state): State<AppState>,
     headers: axum::http::HeaderMap,
     Json(req): Json<ShortenRequest>,
 ) -> Result<Json<ShortenResponse>, ApiError> {
-    // Validate auth token if configured
-    if let Some(ref token) = state.auth_token {
-        let auth_header = headers
-            .get("authorization")
-            .and_then(|h| h.to_str().ok())
-            .unwrap_or("");
-
-        if !auth_header.starts_with("Bearer ") || auth_header[7..] != *token {
-            return Err(ApiError::unauthorized(
-                "Invalid or missing authorization token",
-            ));
-        }
-    }
+    metrics::record_shorten("/shorten");
 
-    // Validate URL
-    validate_url(&req.url).map_err(|e| ApiError::bad_request(format!("Invalid URL: {}", e)))?;
+    // Validate auth token if configured
+    let owner =
+        authenticate(&state, &headers).inspect_err(|e| metrics::record_error("shorten", e))?;
 
-    // Parse TTL or use default (7 days)
-    let ttl_seconds = if let Some(ref ttl_str) = req.ttl {
-        parse_ttl(ttl_str).map_err(|e| ApiError::bad_request(format!("Invalid TTL: {}", e)))?
-    } else {
-        // Default TTL: 7 days
-        7 * 24 * 60 * 60
-    };
+    let base_url = request_base_url(&headers, &state.base_url, &state.allowed_hosts);
 
-    // Get or generate short code
-    let code = if let Some(custom_code) = req.code {
-        // Validate custom code format
-        validate_code(&custom_code)
-            .map_err(|e| ApiError::bad_request(format!("Invalid code: {}", e)))?;
+    create_link(&state, &base_url, req, owner.as_deref())
+        .await
+        .map(Json)
+        .inspect_err(|e| metrics::record_error("shorten", e))
+}
 
-        // Check if code already exists
-        let exists = code_exists(&state.db, &custom_code)
-            .await
-            .map_err(|e| ApiError::internal(format!("Database error: {}", e)))?;
+/// GET /{code} - Redirects to the original URL
+///
+/// # Behavior
+/// - Redirects to the original URL with the configured status
+///   (302 by default, see `REDIRECT_STATUS`)
+/// - For password-protected links, responds 401 with an HTML password form,
+///   which submits to `POST /{code}`
+/// - With `PREVIEW_MODE` on, unprotected links render an interstitial page showing
+///   the destination; `?go=1` performs the redirect. Only redirects record visits
+/// - Requests with `Accept: application/json` get the link's metadata (as from
+///   `GET /api/info/{code}`) with 200 instead of a redirect, and record no visit
+/// - Returns 404 if the link doesn't exist, has expired, or has used up its
+///   `max_visits`
+///
+/// # Errors
+/// - 404: Link not found, expired, or out of visits
+/// - 500: Internal server error
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/{code}",
+    params(("code" = String, Path, description = "Short code")),
+    responses(
+        (status = 302, description = "Redirect to the original URL (status set by REDIRECT_STATUS)"),
+        (status = 200, description = "Link metadata for `Accept: application/json`, or a preview page when PREVIEW_MODE is on", body = LinkInfoResponse),
+        (status = 401, description = "Password form for a protected link", content_type = "text/html"),
+        (status = 404, description = "Link not found, expired, disabled, or out of visits", body = ApiError),
+    )
+))]
+pub async fn redirect(
+    State(state): State<AppState>,
+    Path(code): Path<String>,
+    Query(query): Query<RedirectQuery>,
+    headers: axum::http::HeaderMap,
+    connect_info: Option<Extension<ConnectInfo<SocketAddr>>>,
+) -> Result<Response, ApiError> {
+    visit(state, code, query, None, headers, connect_info).await
+}
 
-        if exists {
-            return Err(ApiError::conflict(format!(
-                "Code '{}' already exists",
-                custom_code
-            )));
-        }
+/// POST /{code} - Submits the password for a protected link
+///
+/// The password form shown by `GET /{code}` posts here, so the password travels
+/// in a form-encoded body and stays out of URLs, browser history, and access
+/// logs. A correct password redirects (and records the visit) like
+/// `GET /{code}`; a wrong one shows the form again. Attempts are limited per
+/// client by `PASSWORD_RATE_LIMIT`.
+///
+/// # Errors
+/// - 404: Link not found, expired, or out of visits
+/// - 429: Too many password attempts
+/// - 500: Internal server error
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/{code}",
+    params(("code" = String, Path, description = "Short code")),
+    request_body(content = PasswordForm, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 302, description = "Correct password; redirect to the original URL (status set by REDIRECT_STATUS)"),
+        (status = 401, description = "Wrong password; the form again", content_type = "text/html"),
+        (status = 404, description = "Link not found, expired, disabled, or out of visits", body = ApiError),
+        (status = 429, description = "Too many password attempts", body = ApiError),
+    )
+))]
+pub async fn unlock(
+    State(state): State<AppState>,
+    Path(code): Path<String>,
+    Query(query): Query<RedirectQuery>,
+    headers: axum::http::HeaderMap,
+    connect_info: Option<Extension<ConnectInfo<SocketAddr>>>,
+    Form(form): Form<PasswordForm>,
+) -> Result<Response, ApiError> {
+    visit(
+        state,
+        code,
+        query,
+        Some(form.password),
+        headers,
+        connect_info,
+    )
+    .await
+}
 
-        custom_code
+/// Answers `redirect` and `unlock`, recording redirect metrics
+async fn visit(
+    state: AppState,
+    code: String,
+    query: RedirectQuery,
+    password: Option<String>,
+    headers: axum::http::HeaderMap,
+    connect_info: Option<Extension<ConnectInfo<SocketAddr>>>,
+) -> Result<Response, ApiError> {
+    let peer = connect_info.map(|Extension(ConnectInfo(addr))| addr.ip());
+    let mode = if prefers_json(&headers) {
+        RedirectMode::Metadata
     } else {
-        // Generate unique random code
-        generate_unique_code(&state.db).await?
+        RedirectMode::Visit
     };
-
-    // Calculate expiration timestamp
-    let expires_at = now_unix() + ttl_seconds;
-
-    // Insert into database
-    insert_link(&state.db, &code, &req.url, expires_at, now_unix())
+    // Lookup outcomes are counted by `resolve_redirect` itself
+    resolve_redirect(state, code, query, password, headers, peer, mode)
         .await
-        .map_err(|e| ApiError::internal(format!("Failed to save link: {}", e)))?;
-
-    // Build response
-    let short_url = format!("{}/{}", state.base_url.trim_end_matches('/'), code);
-    info!("Created short link: {} -> {}", short_url, req.url);
-
-    Ok(Json(ShortenResponse {
-        code,
-        short_url,
-        expires_at,
-    }))
+        .inspect_err(|e| metrics::record_error("redirect", e))
 }
 
-/// GET /{code} - Redirects to the original URL
+/// HEAD /{code} - Returns the redirect headers without recording a visit
 ///
-/// # Behavior
-/// - Returns HTTP 302 redirect to the original URL
-/// - Returns 404 if the link doesn't exist or has expired
+/// Responds with the same status and `Location` as `GET /{code}` and an empty
+/// body, for link checkers and HTTP clients that probe before fetching.
+/// Visit-limited links don't use up a visit.
 ///
 /// # Errors
-/// - 404: Link not found or expired
+/// - 404: Link not found, expired, or out of visits
 /// - 500: Internal server error
-pub async fn redirect(
+#[cfg_attr(feature = "openapi", utoipa::path(
+    head,
+    path = "/{code}",
+    params(("code" = String, Path, description = "Short code")),
+    responses(
+        (status = 302, description = "Redirect headers, without recording a visit"),
+        (status = 404, description = "Link not found, expired, disabled, or out of visits"),
+    )
+))]
+pub async fn redirect_head(
     State(state): State<AppState>,
     Path(code): Path<String>,
+    Query(query): Query<RedirectQuery>,
+    headers: axum::http::HeaderMap,
+) -> Result<Response, ApiError> {
+    resolve_redirect(state, code, query, None, headers, None, RedirectMode::Probe).await
+}
+
+/// How `resolve_redirect` answers for a live link
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RedirectMode {
+    /// Redirect and record the visit (`GET /{code}`)
+    Visit,
+    /// Redirect headers only, without recording a visit (`HEAD /{code}`)
+    Probe,
+    /// The link's metadata as JSON, without recording a visit
+    Metadata,
+}
+
+/// Looks up a short link and builds the response for `redirect` and `redirect_head`
+///
+/// Visits are only consumed and recorded in `RedirectMode::Visit`. `password`
+/// is the one submitted to `unlock`, if any.
+async fn resolve_redirect(
+    state: AppState,
+    code: String,
+    query: RedirectQuery,
+    password: Option<String>,
     headers: axum::http::HeaderMap,
-) -> Result<Redirect, ApiError> {
-    // Validate code format (basic check)
-    if code.is_empty() || code.len() > 32 {
-        return Err(ApiError::not_found("Short link not found"));
+    peer: Option<IpAddr>,
+    mode: RedirectMode,
+) -> Result<Response, ApiError> {
+    // Paths that can't be a short code (e.g. `/wp-login.php`) never reach the database
+    if !is_valid_code(&code) {
+        return Err(ApiError::not_found("not_found", "Short link not found"));
     }
 
-    // Look up the link
-    let link = get_link(&state.db, &code)
-        .await
-        .map_err(|e| ApiError::internal(format!("Database error: {}", e)))?;
+    let (code, link) = lookup_link(&state, code).await?;
 
     match link {
         Some(link) => {
             // Check if expired
             let now = now_unix();
             if now > link.expires_at {
+                state.redirect_counters.record_expired();
+
                 // Delete expired link
                 delete_link(&state.db, &code).await.ok();
+                forget_link(&state, &code);
 
-                return Err(ApiError::not_found("Short link has expired"));
+                return Err(ApiError::not_found(
+                    "link_expired",
+                    "Short link has expired",
+                ));
             }
 
-            // Record visit (best-effort, don't fail redirect on analytics error)
-            let ip = extract_client_ip(&headers);
-            let (country, city) = if let (Some(ref r), Some(ref ip_str)) = (&state.geoip, &ip) {
-                resolve_geo(r, ip_str)
+            // Disabled links look missing to visitors, and their visits aren't recorded
+            if !link.enabled {
+                state.redirect_counters.record_miss();
+                return Err(ApiError::not_found(
+                    "link_disabled",
+                    "Short link is disabled",
+                ));
+            }
+            state.redirect_counters.record_hit();
+
+            let ip = client_ip(&headers, peer, state.trust_proxy);
+            let (country, city) = if let (Some(ref geoip), Some(ref ip_str)) = (&state.geoip, &ip) {
+                geoip.resolve(ip_str)
             } else {
                 (None, None)
             };
+
+            // A matching country or language rule replaces the link's own destination
+            let rules = link.rules();
+            let by_language = rules
+                .as_ref()
+                .is_some_and(|rules| !rules.languages.is_empty());
+            let target = rules
+                .as_ref()
+                .and_then(|rules| {
+                    let language = primary_language(&headers);
+                    rules
+                        .destination_for(country.as_deref(), language.as_deref())
+                        .map(str::to_owned)
+                })
+                .unwrap_or_else(|| link.original_url.clone());
+
+            let destination = match link.append_query {
+                Some(ref extra) => append_query_params(&target, extra).map_err(|e| {
+                    ApiError::internal("internal_error", format!("Invalid destination: {}", e))
+                })?,
+                None => target,
+            };
+
+            // Ask for the password before redirecting to a protected link
+            if let Some(ref hash) = link.password_hash {
+                match password.as_deref() {
+                    Some(password) if verify_password(password, hash) => {}
+                    _ if mode == RedirectMode::Metadata => {
+                        return Err(ApiError::unauthorized(
+                            "password_required",
+                            "Password required",
+                        ));
+                    }
+                    supplied => {
+                        let form = pages::password_form(&code, supplied.is_some());
+                        return Ok((StatusCode::UNAUTHORIZED, Html(form)).into_response());
+                    }
+                }
+            } else if state.preview_mode && query.go != Some(1) && mode != RedirectMode::Metadata {
+                let page = pages::preview_page(
+                    &code,
+                    &destination,
+                    link.title.as_deref(),
+                    link.description.as_deref(),
+                );
+                return Ok(Html(page).into_response());
+            }
+
+            if mode != RedirectMode::Visit {
+                if link.remaining_visits == Some(0) {
+                    return Err(ApiError::not_found(
+                        "visit_limit_reached",
+                        "Short link has reached its visit limit",
+                    ));
+                }
+
+                return match mode {
+                    RedirectMode::Metadata => {
+                        Ok(([(header::VARY, "accept")], Json(link_info(link))).into_response())
+                    }
+                    _ => redirect_response(&state, link.redirect_status, &destination, by_language),
+                };
+            }
+
+            // Consume a visit from visit-limited links; exhausted links are removed
+            if link.remaining_visits.is_some() {
+                let claimed = claim_visit(&state.db, &code).await.map_err(|e| {
+                    ApiError::internal("database_error", format!("Database error: {}", e))
+                })?;
+
+                if !claimed {
+                    delete_link(&state.db, &code).await.ok();
+
+                    return Err(ApiError::not_found(
+                        "visit_limit_reached",
+                        "Short link has reached its visit limit",
+                    ));
+                }
+            }
+
+            // Record visit (best-effort, don't fail redirect on analytics error)
             let ua = headers
                 .get("user-agent")
                 .and_then(|v| v.to_str().ok())
                 .map(str::to_owned);
-            let ref_ = headers
-                .get("referer")
-                .and_then(|v| v.to_str().ok())
-                .map(str::to_owned);
 
-            insert_visit(
-                &state.db,
-                &code,
-                now_unix(),
-                ip.as_deref(),
-                country.as_deref(),
-                city.as_deref(),
-                ua.as_deref(),
-                ref_.as_deref(),
-            )
-            .await
-            .ok(); // swallow errors — redirect still completes
+            // Crawlers and link-preview bots are left out of analytics unless COUNT_BOTS is on
+            let bot = ua
+                .as_deref()
+                .is_some_and(|ua| is_bot(ua, &state.bot_user_agents));
+            if state.count_bots || !bot {
+                let device = ua
+                    .as_deref()
+                    .map(|ua| classify_device(ua, &state.bot_user_agents));
+                let ref_ = headers
+                    .get("referer")
+                    .and_then(|v| v.to_str().ok())
+                    .map(str::to_owned);
+
+                // Geo lookup above used the real address; only this is persisted
+                let stored_ip = state.ip_storage.store(ip.as_deref(), &state.ip_hash_salt);
 
-            info!("Redirecting {} to {}", code, link.original_url);
-            Ok(Redirect::permanent(&link.original_url))
+                let visit = NewVisit {
+                    code: code.clone(),
+                    visited_at: now_unix(),
+                    ip: stored_ip,
+                    country,
+                    city,
+                    user_agent: ua,
+                    device: device.map(str::to_owned),
+                    referer: ref_,
+                };
+
+                match state.visit_buffer {
+                    Some(ref buffer) => buffer.push(visit).await,
+                    // Swallow errors; the redirect still completes
+                    None => {
+                        insert_visit(&state.db, &visit).await.ok();
+                    }
+                }
+            }
+
+            // Each visit restarts a sliding link's idle clock
+            if link.idle_ttl.is_some() {
+                touch_link(&state.db, &code, now_unix()).await.ok();
+            }
+
+            info!("Redirecting {} to {}", code, destination);
+            redirect_response(&state, link.redirect_status, &destination, by_language)
+        }
+        None => {
+            state.redirect_counters.record_miss();
+            Err(ApiError::not_found("not_found", "Short link not found"))
         }
-        None => Err(ApiError::not_found("Short link not found")),
     }
 }
 
-/// Generates a unique code that doesn't exist in the database
+/// Builds the redirect response for a short link
+///
+/// Sets `Location` plus a `Cache-Control` header so browsers come back to the
+/// server on each visit (`no-store`) unless `REDIRECT_CACHE_MAX_AGE` is set.
+/// `Vary: Accept` keeps caches from serving it to JSON clients. With
+/// `by_language`, set for links with language rules, it also names
+/// `Accept-Language`, since visitors with another language may be sent elsewhere.
+fn redirect_response(
+    state: &AppState,
+    link_status: Option<u16>,
+    location: &str,
+    by_language: bool,
+) -> Result<Response, ApiError> {
+    // A link's own status wins over the server-wide REDIRECT_STATUS
+    let status = link_status
+        .and_then(|code| StatusCode::from_u16(code).ok())
+        .unwrap_or_else(|| state.redirect_status.status_code());
+
+    let cache_control = match state.redirect_cache_max_age {
+        Some(max_age) => format!("private, max-age={}", max_age),
+        None => "no-store".to_string(),
+    };
+
+    Response::builder()
+        .status(status)
+        .header(header::LOCATION, location)
+        .header(header::CACHE_CONTROL, cache_control)
+        .header(
+            header::VARY,
+            if by_language {
+                "accept, accept-language"
+            } else {
+                "accept"
+            },
+        )
+        .body(axum::body::Body::empty())
+        .map_err(|e| {
+            ApiError::internal("internal_error", format!("Failed to build redirect: {}", e))
+        })
+}
+
+/// Looks up `code` for a caller authenticated as `owner`
 ///
-/// Will attempt up to 10 times to generate a unique random code.
-async fn generate_unique_code(db: &sqlx::Pool<sqlx::Sqlite>) -> Result<String, ApiError> {
-    const MAX_ATTEMPTS: usize = 10;
+/// # Errors
+/// Returns 404 if the link doesn't exist or belongs to another owner; see `require_owner`.
+async fn owned_link(state: &AppState, code: &str, owner: Option<&str>) -> Result<Link, ApiError> {
+    let link = get_link(&state.db, code)
+        .await
+        .map_err(|e| ApiError::internal("database_error", format!("Database error: {}", e)))?
+        .ok_or_else(|| ApiError::not_found("not_found", "Short link not found"))?;
 
-    for _ in 0..MAX_ATTEMPTS {
-        let code = generate_code();
+    require_owner(owner, &link)?;
+    Ok(link)
+}
 
-        // Check if code already exists
-        let exists = code_exists(db, &code)
-            .await
-            .map_err(|e| ApiError::internal(format!("Database error: {}", e)))?;
+/// Maps a requested code to its stored spelling when `CASE_INSENSITIVE_CODES` is on
+///
+/// Returns `code` unchanged when the option is off or nothing matches, so callers
+/// can keep using exact-match queries.
+async fn resolve_code(state: &AppState, code: String) -> Result<String, ApiError> {
+    if !state.case_insensitive_codes {
+        return Ok(code);
+    }
+
+    let stored = find_code_ignore_case(&state.db, &code)
+        .await
+        .map_err(|e| ApiError::internal("database_error", format!("Database error: {}", e)))?;
 
-        if !exists {
-            return Ok(code);
+    Ok(stored.unwrap_or(code))
+}
+
+/// Looks up a link for the redirect path, trying the link cache first
+///
+/// Returns the code as stored alongside the link, if there is one.
+async fn lookup_link(state: &AppState, code: String) -> Result<(String, Option<Link>), ApiError> {
+    let key = cache_key(state, &code);
+    if let Some(ref cache) = state.link_cache {
+        let cached = cache.get(&key, now_unix());
+        metrics::record_link_cache(cached.is_some());
+        if let Some(link) = cached {
+            return Ok((link.code.clone(), Some(link)));
         }
     }
 
-    Err(ApiError::internal(
-        "Failed to generate unique code after multiple attempts",
-    ))
+    let code = resolve_code(state, code).await?;
+    let link = get_link(&state.db, &code)
+        .await
+        .map_err(|e| ApiError::internal("database_error", format!("Database error: {}", e)))?;
+
+    if let (Some(cache), Some(link)) = (&state.link_cache, &link) {
+        cache.insert(&key, link.clone(), now_unix());
+    }
+
+    Ok((code, link))
 }
 
-/// POST /api/shorten - Creates short link without auth (for web UI)
-///
-/// Same logic as shorten() but without authentication check.
-/// Rate limiting is applied via middleware.
+/// Key a code is cached under; every spelling of a code shares one key when
+/// codes are case-insensitive
+fn cache_key(state: &AppState, code: &str) -> String {
+    if state.case_insensitive_codes {
+        code.to_ascii_lowercase()
+    } else {
+        code.to_string()
+    }
+}
+
+/// Drops a link from the link cache after it was changed or deleted
+fn forget_link(state: &AppState, code: &str) {
+    if let Some(ref cache) = state.link_cache {
+        cache.remove(&cache_key(state, code));
+    }
+}
+
+/// Empties the link cache after a change to many links at once
+fn forget_all_links(state: &AppState) {
+    if let Some(ref cache) = state.link_cache {
+        cache.clear();
+    }
+}
+
+/// Builds the metadata response for a link
+fn link_info(link: Link) -> LinkInfoResponse {
+    LinkInfoResponse {
+        expired: now_unix() > link.expires_at,
+        enabled: link.enabled,
+        rules: link.rules(),
+        idle_ttl: link.idle_ttl,
+        max_expires_at: link.max_expires_at,
+        redirect_status: link.redirect_status,
+        title: link.title,
+        description: link.description,
+        code: link.code,
+        original_url: link.original_url,
+        created_at: link.created_at,
+        expires_at: link.expires_at,
+    }
+}
+
+/// Shared creation logic for `shorten` and `shorten_noauth`
 ///
-/// # Request Body
-/// ```json
-/// {
-///   "url": "https://example.com",
-///   "code": "optional_custom_code",
-///   "ttl": "3d"
-/// }
-/// ```
+/// Checks the request, then acquires a pooled connection, delegates to
+/// `insert_prepared_link`, and announces the new link to the webhook.
+async fn create_link(
+    state: &AppState,
+    base_url: &str,
+    req: ShortenRequest,
+    owner: Option<&str>,
+) -> Result<ShortenResponse, ApiError> {
+    let link = prepare_link(state, req).await?;
+    let mut conn = state
+        .db
+        .acquire()
+        .await
+        .map_err(|e| ApiError::internal("database_error", format!("Database error: {}", e)))?;
+
+    let mut created = Vec::new();
+    let response =
+        insert_prepared_link(state, &mut conn, base_url, link, owner, &mut created).await?;
+    announce(state, created);
+
+    Ok(response)
+}
+
+/// Sends webhook events, if a webhook is configured
 ///
-/// # Response (200 OK)
-/// ```json
-/// {
-///   "code": "abc123",
-///   "short_url": "https://cutl.my.id/abc123",
-///   "expires_at": 1760000000
-/// }
-/// ```
+/// Only call this once the links involved are committed.
+fn announce(state: &AppState, events: Vec<WebhookPayload>) {
+    if let Some(ref webhook) = state.webhook {
+        for event in events {
+            webhook.send(event);
+        }
+    }
+}
+
+/// A shorten request that passed every check not needing the database
 ///
-/// # Errors
-/// - 400: Invalid URL, code, or TTL
-/// - 409: Code already exists
-/// - 429: Rate limit exceeded
-/// - 500: Internal server error
-pub async fn shorten_noauth(
-    State(state): State<AppState>,
-    Json(req): Json<ShortenRequest>,
-) -> Result<Json<ShortenResponse>, ApiError> {
-    // NO auth check - this endpoint is for public web UI use
-    // Rate limiting still applies via middleware
+/// `prepare_link` builds one, doing the slow work (DNS lookups, password
+/// hashing) up front, so `insert_prepared_link` can run inside a transaction
+/// without holding the write lock for long.
+struct PreparedLink {
+    /// URL as submitted, for logging
+    requested_url: String,
+    /// Normalized URL to store
+    url: String,
+    /// Custom code, already checked for format and reserved names
+    code: Option<String>,
+    ttl_seconds: i64,
+    /// Idle window and total lifetime in seconds, for sliding links
+    sliding: Option<(i64, i64)>,
+    redirect_status: Option<u16>,
+    title: Option<String>,
+    description: Option<String>,
+    password_hash: Option<String>,
+    max_visits: Option<i64>,
+    append_query: Option<String>,
+    rules_json: Option<String>,
+    dry_run: bool,
+}
 
-    // Validate URL
-    validate_url(&req.url).map_err(|e| ApiError::bad_request(format!("Invalid URL: {}", e)))?;
+/// Validates a shorten request, normalizing its URL and hashing its password
+///
+/// Nothing here touches the database, so batch requests can prepare every
+/// link before opening their transaction.
+async fn prepare_link(state: &AppState, req: ShortenRequest) -> Result<PreparedLink, ApiError> {
+    // Validate URL, then store it in its normalized form
+    validate_url(&req.url, state.max_url_length)
+        .map_err(|e| ApiError::bad_request("invalid_url", format!("Invalid URL: {}", e)))?;
+    check_destination(state, &req.url)?;
+    check_resolved_destination(state, &req.url).await?;
+    let url = normalize_url(&req.url, state.url_normalization)
+        .map_err(|e| ApiError::bad_request("invalid_url", format!("Invalid URL: {}", e)))?;
 
-    // Parse TTL or use default (7 days)
+    // Parse TTL or use the configured default
     let ttl_seconds = if let Some(ref ttl_str) = req.ttl {
-        parse_ttl(ttl_str).map_err(|e| ApiError::bad_request(format!("Invalid TTL: {}", e)))?
+        parse_ttl(ttl_str, &state.ttl_limits)
+            .map_err(|e| ApiError::bad_request("invalid_ttl", format!("Invalid TTL: {}", e)))?
     } else {
-        // Default TTL: 7 days
-        7 * 24 * 60 * 60
+        state.ttl_limits.default_seconds
     };
 
-    // Get or generate short code
-    let code = if let Some(custom_code) = req.code {
-        // Validate custom code format
-        validate_code(&custom_code)
-            .map_err(|e| ApiError::bad_request(format!("Invalid code: {}", e)))?;
+    // Sliding links expire after `idle_ttl` without a visit, but never outlive
+    // their `ttl` (or `MAX_TTL` when no `ttl` is given)
+    let sliding = if req.sliding {
+        let idle_seconds = match req.idle_ttl {
+            Some(ref idle_ttl) => parse_ttl(idle_ttl, &state.ttl_limits).map_err(|e| {
+                ApiError::bad_request("invalid_ttl", format!("Invalid idle_ttl: {}", e))
+            })?,
+            None => state.ttl_limits.default_seconds,
+        };
+        let lifetime_seconds = match req.ttl {
+            Some(_) => ttl_seconds,
+            None => state.ttl_limits.max_seconds,
+        };
+        Some((idle_seconds.min(lifetime_seconds), lifetime_seconds))
+    } else if req.idle_ttl.is_some() {
+        return Err(ApiError::bad_request(
+            "invalid_ttl",
+            "idle_ttl requires sliding",
+        ));
+    } else {
+        None
+    };
+
+    if let Some(status) = req.redirect_status {
+        if !LINK_REDIRECT_STATUSES.contains(&status) {
+            return Err(ApiError::bad_request(
+                "invalid_redirect_status",
+                "redirect_status must be 301, 302, 307, or 308",
+            ));
+        }
+    }
+
+    let title = link_text(req.title.as_deref(), MAX_TITLE_LENGTH)
+        .map_err(|e| ApiError::bad_request("invalid_title", format!("Invalid title: {}", e)))?;
+    let description =
+        link_text(req.description.as_deref(), MAX_DESCRIPTION_LENGTH).map_err(|e| {
+            ApiError::bad_request("invalid_description", format!("Invalid description: {}", e))
+        })?;
+
+    // Hash the password, if any; plaintext is never stored
+    let password_hash = match req.password {
+        Some(ref password) if password.is_empty() => {
+            return Err(ApiError::bad_request(
+                "invalid_password",
+                "Password cannot be empty",
+            ));
+        }
+        Some(ref password) => Some(hash_password(password).map_err(|e| {
+            ApiError::internal("internal_error", format!("Failed to hash password: {}", e))
+        })?),
+        None => None,
+    };
+
+    if matches!(req.max_visits, Some(max_visits) if max_visits < 1) {
+        return Err(ApiError::bad_request(
+            "invalid_max_visits",
+            "max_visits must be at least 1",
+        ));
+    }
+
+    // Normalize the appended query and make sure it merges into a valid URL
+    let append_query = req
+        .append_query
+        .as_deref()
+        .map(|q| q.trim().trim_start_matches('?'))
+        .filter(|q| !q.is_empty());
+    if let Some(extra) = append_query {
+        append_query_params(&url, extra).map_err(|e| {
+            ApiError::bad_request(
+                "invalid_append_query",
+                format!("Invalid append_query: {}", e),
+            )
+        })?;
+    }
+
+    let rules = match req.rules {
+        Some(rules) => validate_rules(rules, state).await?,
+        None => None,
+    };
+    let rules_json = rules
+        .as_ref()
+        .map(serde_json::to_string)
+        .transpose()
+        .map_err(|e| {
+            ApiError::internal("internal_error", format!("Failed to encode rules: {}", e))
+        })?;
+
+    // A custom code must be well-formed and not reserved; whether it's taken is
+    // checked when the link is inserted
+    if let Some(ref custom_code) = req.code {
+        validate_code(custom_code)
+            .map_err(|e| ApiError::bad_request("invalid_code", format!("Invalid code: {}", e)))?;
+
+        if is_reserved_code(custom_code, &state.reserved_codes) {
+            return Err(ApiError::bad_request(
+                "code_reserved",
+                format!("Code '{}' is reserved", custom_code),
+            ));
+        }
+    }
+
+    Ok(PreparedLink {
+        requested_url: req.url,
+        url,
+        code: req.code,
+        ttl_seconds,
+        sliding,
+        redirect_status: req.redirect_status,
+        title,
+        description,
+        password_hash,
+        max_visits: req.max_visits,
+        append_query: append_query.map(str::to_owned),
+        rules_json,
+        dry_run: req.dry_run,
+    })
+}
 
+/// Resolves the short code for a prepared link and persists it on `conn`
+///
+/// `conn` may be a transaction, which lets batch requests create links atomically.
+/// When `dedup_urls` is enabled and no custom code is requested, an existing
+/// non-expired link for the same URL and TTL is returned instead of creating a new one.
+/// Short URLs in the response start with `base_url`.
+/// With `dry_run`, everything is checked but nothing is written.
+/// A newly inserted link adds its `created` event to `created`, for the caller
+/// to `announce` after committing.
+async fn insert_prepared_link(
+    state: &AppState,
+    conn: &mut SqliteConnection,
+    base_url: &str,
+    link: PreparedLink,
+    owner: Option<&str>,
+    created: &mut Vec<WebhookPayload>,
+) -> Result<ShortenResponse, ApiError> {
+    let PreparedLink {
+        requested_url,
+        url,
+        code,
+        ttl_seconds,
+        sliding,
+        redirect_status,
+        title,
+        description,
+        password_hash,
+        max_visits,
+        append_query,
+        rules_json,
+        dry_run,
+    } = link;
+
+    // Reuse an existing link for the same URL if dedup is enabled
+    // (protected, visit-limited, and query-appending links are always created fresh)
+    if state.dedup_urls
+        && code.is_none()
+        && password_hash.is_none()
+        && max_visits.is_none()
+        && append_query.is_none()
+        && rules_json.is_none()
+        && sliding.is_none()
+        && redirect_status.is_none()
+        && title.is_none()
+        && description.is_none()
+    {
+        let existing = find_link_by_url(&mut *conn, &url, owner, ttl_seconds, now_unix())
+            .await
+            .map_err(|e| ApiError::internal("database_error", format!("Database error: {}", e)))?;
+
+        if let Some(link) = existing {
+            let short_url = format!("{}/{}", base_url, link.code);
+            info!("Reusing short link: {} -> {}", short_url, link.original_url);
+
+            return Ok(ShortenResponse::new(link.code, short_url, link.expires_at));
+        }
+    }
+
+    // Get or generate short code
+    let code = if let Some(custom_code) = code {
         // Check if code already exists
-        let exists = code_exists(&state.db, &custom_code)
+        let exists = code_exists(&mut *conn, &custom_code, state.case_insensitive_codes)
             .await
-            .map_err(|e| ApiError::internal(format!("Database error: {}", e)))?;
+            .map_err(|e| ApiError::internal("database_error", format!("Database error: {}", e)))?;
 
         if exists {
-            return Err(ApiError::conflict(format!(
-                "Code '{}' already exists",
-                custom_code
-            )));
+            return Err(ApiError::conflict(
+                "code_exists",
+                format!("Code '{}' already exists", custom_code),
+            ));
         }
 
         custom_code
     } else {
         // Generate unique random code
-        generate_unique_code(&state.db).await?
+        generate_unique_code(
+            conn,
+            &state.code_settings,
+            &state.reserved_codes,
+            state.case_insensitive_codes,
+        )
+        .await?
     };
 
     // Calculate expiration timestamp
-    let expires_at = now_unix() + ttl_seconds;
+    let created_at = now_unix();
+    let (expires_at, idle_ttl, max_expires_at) = match sliding {
+        Some((idle_seconds, lifetime_seconds)) => (
+            created_at + idle_seconds,
+            Some(idle_seconds),
+            Some(created_at + lifetime_seconds),
+        ),
+        None => (created_at + ttl_seconds, None, None),
+    };
+
+    let short_url = format!("{}/{}", base_url, code);
+
+    // Every check has passed; a dry run stops short of saving
+    if dry_run {
+        return Ok(ShortenResponse::new(code, short_url, expires_at));
+    }
 
     // Insert into database
-    insert_link(&state.db, &code, &req.url, expires_at, now_unix())
-        .await
-        .map_err(|e| ApiError::internal(format!("Failed to save link: {}", e)))?;
+    insert_link(
+        &mut *conn,
+        &NewLink {
+            code: &code,
+            original_url: &url,
+            expires_at,
+            created_at,
+            password_hash: password_hash.as_deref(),
+            max_visits,
+            owner,
+            append_query: append_query.as_deref(),
+            rules: rules_json.as_deref(),
+            idle_ttl,
+            max_expires_at,
+            redirect_status,
+            title: title.as_deref(),
+            description: description.as_deref(),
+        },
+    )
+    .await
+    .map_err(|e| {
+        // Another request took the code since it was checked
+        if is_unique_violation(&e) {
+            ApiError::conflict("code_exists", format!("Code '{}' already exists", code))
+        } else {
+            ApiError::internal("internal_error", format!("Failed to save link: {}", e))
+        }
+    })?;
 
-    // Build response
-    let short_url = format!("{}/{}", state.base_url.trim_end_matches('/'), code);
-    info!("Created short link: {} -> {}", short_url, req.url);
+    created.push(WebhookPayload::created(&code, &url, created_at, expires_at));
 
-    Ok(Json(ShortenResponse {
-        code,
-        short_url,
-        expires_at,
-    }))
+    info!("Created short link: {} -> {}", short_url, requested_url);
+
+    Ok(ShortenResponse::new(code, short_url, expires_at))
 }
 
-/// GET /analytics/{code} – Returns visit statistics for a short link
+/// Trims a title or description, treating blank text as none
 ///
-/// # Errors
-/// - 401: Missing/invalid token (when auth is enabled)
-/// - 404: Code not found or expired
-pub async fn analytics(
-    State(state): State<AppState>,
-    Path(code): Path<String>,
-    headers: axum::http::HeaderMap,
-) -> Result<Json<AnalyticsResponse>, ApiError> {
-    // Validate auth token if configured
-    if let Some(ref token) = state.auth_token {
-        let auth_header = headers
-            .get("authorization")
-            .and_then(|h| h.to_str().ok())
-            .unwrap_or("");
-
-        if !auth_header.starts_with("Bearer ") || auth_header[7..] != *token {
-            return Err(ApiError::unauthorized(
-                "Invalid or missing authorization token",
-            ));
-        }
+/// Fails if the text is longer than `max_length` characters.
+fn link_text(text: Option<&str>, max_length: usize) -> Result<Option<String>, String> {
+    let Some(text) = text.map(str::trim).filter(|t| !t.is_empty()) else {
+        return Ok(None);
+    };
+
+    if text.chars().count() > max_length {
+        return Err(format!("must be at most {} characters", max_length));
     }
+    Ok(Some(text.to_string()))
+}
 
-    // Look up the link
-    let link = get_link(&state.db, &code)
-        .await
-        .map_err(|e| ApiError::internal(format!("Database error: {}", e)))?
-        .ok_or_else(|| ApiError::not_found("Short link not found"))?;
+/// Rejects destinations on the blocklist, or off the allowlist when one is set
+fn check_destination(state: &AppState, url: &str) -> Result<(), ApiError> {
+    if !destination_allowed(&state.blocklist, &state.allowed_domains, url) {
+        return Err(ApiError::bad_request(
+            "destination_not_allowed",
+            "Destination not allowed",
+        ));
+    }
 
-    // Check if expired
-    if now_unix() > link.expires_at {
-        return Err(ApiError::not_found("Short link has expired"));
+    Ok(())
+}
+
+/// Rejects destinations whose hostname resolves to a loopback or private
+/// address, when `RESOLVE_DESTINATIONS` is on
+async fn check_resolved_destination(state: &AppState, url: &str) -> Result<(), ApiError> {
+    let Some(ref resolver) = state.resolver else {
+        return Ok(());
+    };
+    if resolves_to_blocked_ip(resolver.as_ref(), url).await {
+        return Err(ApiError::bad_request(
+            "invalid_url",
+            "Invalid URL: URL cannot point to localhost or a private network address",
+        ));
     }
 
-    let total_visits = count_visits(&state.db, &code)
-        .await
-        .map_err(|e| ApiError::internal(format!("Database error: {}", e)))?;
+    Ok(())
+}
 
-    let countries = visits_by_country(&state.db, &code)
+/// Checks a link's rules, normalizing country codes to uppercase, languages to
+/// lowercase, and URLs like `url`
+///
+/// Returns `None` when no rule is set.
+async fn validate_rules(rules: LinkRules, state: &AppState) -> Result<Option<LinkRules>, ApiError> {
+    let rule_url = |key: &str, url: &str| -> Result<String, ApiError> {
+        let url = validate_url(url, state.max_url_length)
+            .and_then(|()| normalize_url(url, state.url_normalization))
+            .map_err(|e| {
+                ApiError::bad_request(
+                    "invalid_rules",
+                    format!("Invalid rules: URL for {}: {}", key, e),
+                )
+            })?;
+        check_destination(state, &url)?;
+        Ok(url)
+    };
+
+    let mut countries = BTreeMap::new();
+    for (country, url) in rules.countries {
+        if country.len() != 2 || !country.chars().all(|c| c.is_ascii_alphabetic()) {
+            return Err(ApiError::bad_request(
+                "invalid_rules",
+                format!(
+                    "Invalid rules: '{}' is not a two-letter country code",
+                    country
+                ),
+            ));
+        }
+        let url = rule_url(&country, &url)?;
+        countries.insert(country.to_ascii_uppercase(), url);
+    }
+
+    let mut languages = BTreeMap::new();
+    for (language, url) in rules.languages {
+        if !(2..=3).contains(&language.len()) || !language.chars().all(|c| c.is_ascii_alphabetic())
+        {
+            return Err(ApiError::bad_request(
+                "invalid_rules",
+                format!(
+                    "Invalid rules: '{}' is not a primary language tag like 'en'",
+                    language
+                ),
+            ));
+        }
+        let url = rule_url(&language, &url)?;
+        languages.insert(language.to_ascii_lowercase(), url);
+    }
+
+    for url in countries.values().chain(languages.values()) {
+        check_resolved_destination(state, url).await?;
+    }
+
+    let rules = LinkRules {
+        countries,
+        languages,
+    };
+    Ok((!rules.is_empty()).then_some(rules))
+}
+
+/// Generates a unique code that doesn't exist in the database
+///
+/// Tries `settings.max_attempts` random codes. If every one collides, the
+/// namespace is getting dense, so one more round is made with codes a character
+/// longer before giving up with a retriable 503.
+/// With `case_insensitive`, a code collides with any stored code differing only in case.
+/// Codes in `reserved` are never generated.
+async fn generate_unique_code(
+    conn: &mut SqliteConnection,
+    settings: &CodeSettings,
+    reserved: &[String],
+    case_insensitive: bool,
+) -> Result<String, ApiError> {
+    let longer = CodeSettings {
+        min_length: settings.max_length + 1,
+        max_length: settings.max_length + 1,
+        ..*settings
+    };
+    let rounds: &[&CodeSettings] = if longer.max_length <= MAX_CODE_LENGTH {
+        &[settings, &longer]
+    } else {
+        &[settings]
+    };
+
+    for round in rounds {
+        for _ in 0..round.max_attempts {
+            let code = generate_code(round, reserved).map_err(|e| {
+                ApiError::service_unavailable(
+                    "code_generation_failed",
+                    format!("Could not generate a code: {}", e),
+                )
+            })?;
+
+            // Check if code already exists
+            let exists = code_exists(&mut *conn, &code, case_insensitive)
+                .await
+                .map_err(|e| {
+                    ApiError::internal("database_error", format!("Database error: {}", e))
+                })?;
+
+            if !exists {
+                return Ok(code);
+            }
+        }
+    }
+
+    Err(ApiError::service_unavailable(
+        "code_generation_failed",
+        "Could not generate a unique code; please retry or choose a custom code",
+    ))
+}
+
+/// POST /api/shorten - Creates short link without auth (for web UI)
+///
+/// Same logic as shorten() but without authentication check.
+/// Rate limiting is applied via middleware.
+///
+/// # Request Body
+/// ```json
+/// {
+///   "url": "https://example.com",
+///   "code": "optional_custom_code",
+///   "ttl": "3d"
+/// }
+/// ```
+///
+/// # Response (200 OK)
+/// ```json
+/// {
+///   "code": "abc123",
+///   "short_url": "https://cutl.my.id/abc123",
+///   "expires_at": 1760000000
+/// }
+/// ```
+///
+/// # Errors
+/// - 400: Invalid URL, code, or TTL
+/// - 409: Code already exists
+/// - 429: Rate limit exceeded
+/// - 500: Internal server error
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/api/shorten",
+    request_body = ShortenRequest,
+    responses(
+        (status = 200, description = "Short link created", body = ShortenResponse),
+        (status = 400, description = "Invalid URL, code, or TTL", body = ApiError),
+        (status = 409, description = "Code already exists", body = ApiError),
+    )
+))]
+pub async fn shorten_noauth(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(req): Json<ShortenRequest>,
+) -> Result<Json<ShortenResponse>, ApiError> {
+    // NO auth check - this endpoint is for public web UI use
+    // Rate limiting still applies via middleware
+    metrics::record_shorten("/api/shorten");
+
+    let base_url = request_base_url(&headers, &state.base_url, &state.allowed_hosts);
+
+    create_link(&state, &base_url, req, None)
+        .await
+        .map(Json)
+        .inspect_err(|e| metrics::record_error("shorten_noauth", e))
+}
+
+/// POST /api/shorten/batch - Creates several short links in one request
+///
+/// # Request Body
+/// ```json
+/// {
+///   "links": [
+///     { "url": "https://example.com", "code": "optional_custom_code", "ttl": "3d" },
+///     { "url": "https://example.org" }
+///   ],
+///   "atomic": false
+/// }
+/// ```
+///
+/// Each item is processed like `POST /shorten`. Without `atomic`, valid items are
+/// created even if others fail. With `atomic`, all links are created in a single
+/// transaction and nothing is created if any item fails; the response is still
+/// 200, with `created: 0` and an error for every item.
+///
+/// # Response (200 OK)
+/// ```json
+/// {
+///   "created": 1,
+///   "results": [
+///     { "code": "abc123", "short_url": "https://cutl.my.id/abc123", "expires_at": 1760000000 },
+///     { "index": 1, "code": "invalid_url", "error": "Invalid URL: URL must start with http:// or https://" }
+///   ]
+/// }
+/// ```
+///
+/// # Errors
+/// - 400: Empty batch or more than `MAX_BATCH_SIZE` links
+/// - 401: Invalid or missing auth token
+/// - 429: Rate limit exceeded
+/// - 500: Internal server error
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/api/shorten/batch",
+    request_body = BatchShortenRequest,
+    responses(
+        (status = 200, description = "Per-link results, in request order; a failed atomic batch reports `created: 0` and an error for every item", body = BatchShortenResponse),
+        (status = 400, description = "Empty or oversized batch", body = ApiError),
+        (status = 401, description = "Invalid or missing auth token", body = ApiError),
+    ),
+    security((), ("bearer_auth" = []))
+))]
+pub async fn shorten_batch(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(req): Json<BatchShortenRequest>,
+) -> Result<Json<BatchShortenResponse>, ApiError> {
+    // Validate auth token if configured
+    let owner = authenticate(&state, &headers)?;
+
+    if req.links.is_empty() {
+        return Err(ApiError::bad_request(
+            "invalid_batch",
+            "Batch cannot be empty",
+        ));
+    }
+    if req.links.len() > state.max_batch_size {
+        return Err(ApiError::bad_request(
+            "invalid_batch",
+            format!("Batch cannot exceed {} links", state.max_batch_size),
+        ));
+    }
+
+    let base_url = request_base_url(&headers, &state.base_url, &state.allowed_hosts);
+    let mut results = Vec::with_capacity(req.links.len());
+
+    if req.atomic {
+        // DNS lookups and password hashing happen before the transaction opens,
+        // so its write lock is held only for the inserts
+        let mut prepared = Vec::with_capacity(req.links.len());
+        for item in req.links {
+            prepared.push(prepare_link(&state, item).await);
+        }
+
+        let mut outcomes = Vec::with_capacity(prepared.len());
+        if prepared.iter().all(Result::is_ok) {
+            let mut tx = state.db.begin().await.map_err(|e| {
+                ApiError::internal("database_error", format!("Database error: {}", e))
+            })?;
+
+            let mut created = Vec::new();
+            for link in prepared.into_iter().flatten() {
+                let outcome = insert_prepared_link(
+                    &state,
+                    &mut tx,
+                    &base_url,
+                    link,
+                    owner.as_deref(),
+                    &mut created,
+                )
+                .await;
+                outcomes.push(outcome.map(Some));
+            }
+
+            // Otherwise dropping the transaction rolls back every insert from this batch
+            if outcomes.iter().all(Result::is_ok) {
+                tx.commit().await.map_err(|e| {
+                    ApiError::internal("internal_error", format!("Failed to save links: {}", e))
+                })?;
+                announce(&state, created);
+            }
+        } else {
+            outcomes.extend(prepared.into_iter().map(|link| link.map(|_| None)));
+        }
+
+        let failed = outcomes.iter().any(Result::is_err);
+        for (index, outcome) in outcomes.into_iter().enumerate() {
+            results.push(match outcome {
+                Ok(Some(response)) if !failed => BatchItemResult::Created(response),
+                Ok(_) => BatchItemResult::Failed(BatchItemError {
+                    index,
+                    code: "batch_aborted",
+                    error: "Not created: another item in this atomic batch failed".to_string(),
+                }),
+                Err(e) => BatchItemResult::Failed(BatchItemError {
+                    index,
+                    code: e.code,
+                    error: e.message,
+                }),
+            });
+        }
+    } else {
+        for (index, item) in req.links.into_iter().enumerate() {
+            match create_link(&state, &base_url, item, owner.as_deref()).await {
+                Ok(response) => results.push(BatchItemResult::Created(response)),
+                Err(e) => results.push(BatchItemResult::Failed(BatchItemError {
+                    index,
+                    code: e.code,
+                    error: e.message,
+                })),
+            }
+        }
+    }
+
+    let created = results
+        .iter()
+        .filter(|result| matches!(result, BatchItemResult::Created(_)))
+        .count();
+    info!("Batch created {} of {} links", created, results.len());
+
+    Ok(Json(BatchShortenResponse { created, results }))
+}
+
+/// PATCH /{code} - Updates the target URL and/or TTL of a short link
+///
+/// # Request Body
+/// ```json
+/// {
+///   "url": "https://example.com/new",
+///   "ttl": "3d"
+/// }
+/// ```
+///
+/// Both fields are optional. A new TTL is counted from the time of the update
+/// and turns a sliding link into one with a fixed expiry.
+///
+/// # Response (200 OK)
+/// ```json
+/// {
+///   "code": "abc123",
+///   "short_url": "https://cutl.my.id/abc123",
+///   "expires_at": 1760000000
+/// }
+/// ```
+///
+/// # Errors
+/// - 400: Invalid URL or TTL
+/// - 401: Invalid or missing auth token
+/// - 404: Code not found
+/// - 500: Internal server error
+#[cfg_attr(feature = "openapi", utoipa::path(
+    patch,
+    path = "/{code}",
+    params(("code" = String, Path, description = "Short code")),
+    request_body = UpdateRequest,
+    responses(
+        (status = 200, description = "Link updated", body = ShortenResponse),
+        (status = 400, description = "Invalid URL or TTL", body = ApiError),
+        (status = 401, description = "Invalid or missing auth token", body = ApiError),
+        (status = 404, description = "Code not found", body = ApiError),
+    ),
+    security((), ("bearer_auth" = []))
+))]
+pub async fn update(
+    State(state): State<AppState>,
+    Path(code): Path<String>,
+    headers: axum::http::HeaderMap,
+    Json(req): Json<UpdateRequest>,
+) -> Result<Json<ShortenResponse>, ApiError> {
+    // Validate auth token if configured
+    let owner = authenticate(&state, &headers)?;
+
+    let code = resolve_code(&state, code).await?;
+    owned_link(&state, &code, owner.as_deref()).await?;
+
+    // Validate and normalize the new URL if provided
+    let url = match req.url {
+        Some(ref url) => {
+            validate_url(url, state.max_url_length)
+                .map_err(|e| ApiError::bad_request("invalid_url", format!("Invalid URL: {}", e)))?;
+            check_destination(&state, url)?;
+            check_resolved_destination(&state, url).await?;
+            Some(
+                normalize_url(url, state.url_normalization).map_err(|e| {
+                    ApiError::bad_request("invalid_url", format!("Invalid URL: {}", e))
+                })?,
+            )
+        }
+        None => None,
+    };
+
+    // Parse new TTL if provided
+    let expires_at = match req.ttl {
+        Some(ref ttl_str) => {
+            let ttl_seconds = parse_ttl(ttl_str, &state.ttl_limits)
+                .map_err(|e| ApiError::bad_request("invalid_ttl", format!("Invalid TTL: {}", e)))?;
+            Some(now_unix() + ttl_seconds)
+        }
+        None => None,
+    };
+
+    let updated = update_link(&state.db, &code, url.as_deref(), expires_at)
+        .await
+        .map_err(|e| ApiError::internal("database_error", format!("Database error: {}", e)))?;
+
+    if !updated {
+        return Err(ApiError::not_found("not_found", "Short link not found"));
+    }
+    forget_link(&state, &code);
+
+    let link = get_link(&state.db, &code)
+        .await
+        .map_err(|e| ApiError::internal("database_error", format!("Database error: {}", e)))?
+        .ok_or_else(|| ApiError::not_found("not_found", "Short link not found"))?;
+
+    let short_url = format!("{}/{}", state.base_url.trim_end_matches('/'), link.code);
+    info!("Updated short link: {} -> {}", short_url, link.original_url);
+
+    Ok(Json(ShortenResponse::new(
+        link.code,
+        short_url,
+        link.expires_at,
+    )))
+}
+
+/// DELETE /{code} - Deletes a short link before it expires
+///
+/// Associated visit rows are removed via `ON DELETE CASCADE`.
+///
+/// # Response (200 OK)
+/// ```json
+/// { "deleted": true }
+/// ```
+///
+/// # Errors
+/// - 401: Invalid or missing auth token
+/// - 404: Code not found
+/// - 500: Internal server error
+#[cfg_attr(feature = "openapi", utoipa::path(
+    delete,
+    path = "/{code}",
+    params(("code" = String, Path, description = "Short code")),
+    responses(
+        (status = 200, description = "Link and its visits deleted", body = DeleteResponse),
+        (status = 401, description = "Invalid or missing auth token", body = ApiError),
+        (status = 404, description = "Code not found", body = ApiError),
+    ),
+    security((), ("bearer_auth" = []))
+))]
+pub async fn delete(
+    State(state): State<AppState>,
+    Path(code): Path<String>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<DeleteResponse>, ApiError> {
+    // Validate auth token if configured
+    let owner = authenticate(&state, &headers)?;
+
+    let code = resolve_code(&state, code).await?;
+    owned_link(&state, &code, owner.as_deref()).await?;
+
+    let deleted = delete_link(&state.db, &code)
+        .await
+        .map_err(|e| ApiError::internal("database_error", format!("Database error: {}", e)))?;
+
+    if !deleted {
+        return Err(ApiError::not_found("not_found", "Short link not found"));
+    }
+    forget_link(&state, &code);
+
+    info!("Deleted short link: {}", code);
+
+    Ok(Json(DeleteResponse { deleted }))
+}
+
+/// POST /api/links/{code}/extend - Pushes back a short link's expiry
+///
+/// The target URL is left alone. In `set` mode (the default) the link expires
+/// `ttl` from now; in `add` mode `ttl` is added to the current expiry, or to now
+/// if the link has already expired. Either way the link may not end up living
+/// longer than the configured maximum TTL from now. A sliding link keeps the
+/// new expiry as a fixed one.
+///
+/// # Request Body
+/// ```json
+/// { "ttl": "7d", "mode": "add" }
+/// ```
+///
+/// # Response (200 OK)
+/// Same shape as `POST /shorten`, with the new `expires_at`.
+///
+/// # Errors
+/// - 400: Invalid TTL, or the new expiry is past the maximum TTL
+/// - 401: Invalid or missing auth token
+/// - 404: Code not found
+/// - 500: Internal server error
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/api/links/{code}/extend",
+    params(("code" = String, Path, description = "Short code")),
+    request_body = ExtendRequest,
+    responses(
+        (status = 200, description = "Link extended", body = ShortenResponse),
+        (status = 400, description = "Invalid TTL, or past the maximum TTL", body = ApiError),
+        (status = 401, description = "Invalid or missing auth token", body = ApiError),
+        (status = 404, description = "Code not found", body = ApiError),
+    ),
+    security((), ("bearer_auth" = []))
+))]
+pub async fn extend(
+    State(state): State<AppState>,
+    Path(code): Path<String>,
+    headers: axum::http::HeaderMap,
+    Json(req): Json<ExtendRequest>,
+) -> Result<Json<ShortenResponse>, ApiError> {
+    // Validate auth token if configured
+    let owner = authenticate(&state, &headers)?;
+
+    let code = resolve_code(&state, code).await?;
+
+    let ttl_seconds = parse_ttl(&req.ttl, &state.ttl_limits)
+        .map_err(|e| ApiError::bad_request("invalid_ttl", format!("Invalid TTL: {}", e)))?;
+
+    let link = owned_link(&state, &code, owner.as_deref()).await?;
+
+    let now = now_unix();
+    let expires_at = match req.mode {
+        ExtendMode::Set => now + ttl_seconds,
+        ExtendMode::Add => link.expires_at.max(now) + ttl_seconds,
+    };
+
+    if expires_at - now > state.ttl_limits.max_seconds {
+        return Err(ApiError::bad_request(
+            "invalid_ttl",
+            format!(
+                "Invalid TTL: the link cannot expire more than {} seconds from now",
+                state.ttl_limits.max_seconds
+            ),
+        ));
+    }
+
+    let updated = set_link_expiry(&state.db, &code, expires_at)
+        .await
+        .map_err(|e| ApiError::internal("database_error", format!("Database error: {}", e)))?;
+
+    if !updated {
+        return Err(ApiError::not_found("not_found", "Short link not found"));
+    }
+    forget_link(&state, &code);
+
+    let short_url = format!("{}/{}", state.base_url.trim_end_matches('/'), link.code);
+    info!("Extended short link {} until {}", link.code, expires_at);
+
+    Ok(Json(ShortenResponse::new(link.code, short_url, expires_at)))
+}
+
+/// POST /api/links/{code}/disable - Stops a short link from redirecting
+///
+/// The link responds 404 until it is enabled again. Unlike `DELETE /{code}`,
+/// the link and its visit history are kept.
+///
+/// # Response (200 OK)
+/// The link's metadata, as from `GET /api/info/{code}`, with `"enabled": false`.
+///
+/// # Errors
+/// - 401: Invalid or missing auth token
+/// - 404: Code not found
+/// - 500: Internal server error
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/api/links/{code}/disable",
+    params(("code" = String, Path, description = "Short code")),
+    responses(
+        (status = 200, description = "Link disabled", body = LinkInfoResponse),
+        (status = 401, description = "Invalid or missing auth token", body = ApiError),
+        (status = 404, description = "Code not found", body = ApiError),
+    ),
+    security((), ("bearer_auth" = []))
+))]
+pub async fn disable(
+    State(state): State<AppState>,
+    Path(code): Path<String>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<LinkInfoResponse>, ApiError> {
+    set_enabled(state, code, headers, false).await
+}
+
+/// POST /api/links/{code}/enable - Resumes redirects for a disabled short link
+///
+/// # Response (200 OK)
+/// The link's metadata, as from `GET /api/info/{code}`, with `"enabled": true`.
+///
+/// # Errors
+/// - 401: Invalid or missing auth token
+/// - 404: Code not found
+/// - 500: Internal server error
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/api/links/{code}/enable",
+    params(("code" = String, Path, description = "Short code")),
+    responses(
+        (status = 200, description = "Link enabled", body = LinkInfoResponse),
+        (status = 401, description = "Invalid or missing auth token", body = ApiError),
+        (status = 404, description = "Code not found", body = ApiError),
+    ),
+    security((), ("bearer_auth" = []))
+))]
+pub async fn enable(
+    State(state): State<AppState>,
+    Path(code): Path<String>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<LinkInfoResponse>, ApiError> {
+    set_enabled(state, code, headers, true).await
+}
+
+/// Shared logic for `disable` and `enable`
+async fn set_enabled(
+    state: AppState,
+    code: String,
+    headers: axum::http::HeaderMap,
+    enabled: bool,
+) -> Result<Json<LinkInfoResponse>, ApiError> {
+    // Validate auth token if configured
+    let owner = authenticate(&state, &headers)?;
+
+    let code = resolve_code(&state, code).await?;
+    owned_link(&state, &code, owner.as_deref()).await?;
+
+    let updated = set_link_enabled(&state.db, &code, enabled)
+        .await
+        .map_err(|e| ApiError::internal("database_error", format!("Database error: {}", e)))?;
+
+    if !updated {
+        return Err(ApiError::not_found("not_found", "Short link not found"));
+    }
+    forget_link(&state, &code);
+
+    let link = get_link(&state.db, &code)
+        .await
+        .map_err(|e| ApiError::internal("database_error", format!("Database error: {}", e)))?
+        .ok_or_else(|| ApiError::not_found("not_found", "Short link not found"))?;
+
+    info!(
+        "{} short link: {}",
+        if enabled { "Enabled" } else { "Disabled" },
+        code
+    );
+
+    Ok(Json(link_info(link)))
+}
+
+/// POST /api/links/{code}/rotate - Moves a short link to a new random code
+///
+/// For a code that was shared by mistake: the destination, expiry, and every
+/// other setting carry over, and the old code stops working at once. Visit
+/// history moves to the new code unless `keep_visits=false`, which deletes it
+/// rather than orphaning it, since visits must belong to a link.
+/// The new code is generated and claimed in one transaction, retried up to
+/// `ROTATE_ATTEMPTS` times if a concurrent insert takes it first.
+///
+/// # Response (200 OK)
+/// Same shape as `POST /shorten`, with the new code.
+///
+/// # Errors
+/// - 401: Invalid or missing auth token
+/// - 404: Code not found
+/// - 503: No unused code could be generated
+/// - 500: Internal server error
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/api/links/{code}/rotate",
+    params(("code" = String, Path, description = "Short code"), RotateQuery),
+    responses(
+        (status = 200, description = "Link moved to a new code", body = ShortenResponse),
+        (status = 401, description = "Invalid or missing auth token", body = ApiError),
+        (status = 404, description = "Code not found", body = ApiError),
+        (status = 503, description = "No unused code could be generated", body = ApiError),
+    ),
+    security((), ("bearer_auth" = []))
+))]
+pub async fn rotate(
+    State(state): State<AppState>,
+    Path(code): Path<String>,
+    Query(query): Query<RotateQuery>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<ShortenResponse>, ApiError> {
+    // Validate auth token if configured
+    let owner = authenticate(&state, &headers)?;
+
+    let code = resolve_code(&state, code).await?;
+    owned_link(&state, &code, owner.as_deref()).await?;
+
+    let keep_visits = query.keep_visits.unwrap_or(true);
+    let mut new_code = None;
+    for _ in 0..ROTATE_ATTEMPTS {
+        let mut tx =
+            state.db.begin().await.map_err(|e| {
+                ApiError::internal("database_error", format!("Database error: {}", e))
+            })?;
+        let candidate = generate_unique_code(
+            &mut tx,
+            &state.code_settings,
+            &state.reserved_codes,
+            state.case_insensitive_codes,
+        )
+        .await?;
+
+        match rotate_link_code(&mut tx, &code, &candidate, keep_visits).await {
+            Ok(true) => {
+                tx.commit().await.map_err(|e| {
+                    ApiError::internal("database_error", format!("Database error: {}", e))
+                })?;
+                new_code = Some(candidate);
+                break;
+            }
+            Ok(false) => return Err(ApiError::not_found("not_found", "Short link not found")),
+            // Taken since it was generated; dropping tx rolls back, so try another
+            Err(e) if is_unique_violation(&e) => continue,
+            Err(e) => {
+                return Err(ApiError::internal(
+                    "database_error",
+                    format!("Database error: {}", e),
+                ))
+            }
+        }
+    }
+    let new_code = new_code.ok_or_else(|| {
+        ApiError::service_unavailable(
+            "code_generation_failed",
+            "Could not generate a unique code; please retry",
+        )
+    })?;
+    forget_link(&state, &code);
+
+    let link = get_link(&state.db, &new_code)
+        .await
+        .map_err(|e| ApiError::internal("database_error", format!("Database error: {}", e)))?
+        .ok_or_else(|| ApiError::not_found("not_found", "Short link not found"))?;
+
+    let short_url = format!("{}/{}", state.base_url.trim_end_matches('/'), link.code);
+    info!("Rotated short link {} to {}", code, link.code);
+
+    Ok(Json(ShortenResponse::new(
+        link.code,
+        short_url,
+        link.expires_at,
+    )))
+}
+
+/// POST /api/links/{code}/share - Mints a read-only token for one link's analytics
+///
+/// The token works as a bearer token on `GET /analytics/{code}` for this code
+/// only, until it expires, and nowhere else. Only its SHA-256 hash is stored,
+/// so it can't be shown again; mint a new one instead.
+///
+/// # Response (200 OK)
+/// ```json
+/// {
+///   "token": "9f86d081884c7d65...",
+///   "code": "abc123",
+///   "expires_at": 1760000000
+/// }
+/// ```
+///
+/// # Errors
+/// - 400: Invalid TTL
+/// - 401: Invalid or missing auth token
+/// - 404: Code not found or expired
+/// - 500: Internal server error
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/api/links/{code}/share",
+    params(("code" = String, Path, description = "Short code"), ShareQuery),
+    responses(
+        (status = 200, description = "Share token minted", body = ShareResponse),
+        (status = 400, description = "Invalid TTL", body = ApiError),
+        (status = 401, description = "Invalid or missing auth token", body = ApiError),
+        (status = 404, description = "Code not found or expired", body = ApiError),
+    ),
+    security((), ("bearer_auth" = []))
+))]
+pub async fn share(
+    State(state): State<AppState>,
+    Path(code): Path<String>,
+    Query(query): Query<ShareQuery>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<ShareResponse>, ApiError> {
+    // Validate auth token if configured
+    let owner = authenticate(&state, &headers)?;
+
+    let ttl_seconds = match query.ttl.as_deref() {
+        Some(ttl) => parse_ttl(ttl, &state.ttl_limits)
+            .map_err(|e| ApiError::bad_request("invalid_ttl", format!("Invalid TTL: {}", e)))?,
+        None => DEFAULT_SHARE_TTL_SECONDS,
+    };
+
+    let code = resolve_code(&state, code).await?;
+    let link = owned_link(&state, &code, owner.as_deref()).await?;
+
+    let now = now_unix();
+    if now > link.expires_at {
+        return Err(ApiError::not_found(
+            "link_expired",
+            "Short link has expired",
+        ));
+    }
+
+    let token = generate_share_token();
+    let expires_at = (now + ttl_seconds).min(link.expires_at);
+    insert_share_token(
+        &state.db,
+        &hash_share_token(&token),
+        &link.code,
+        now,
+        expires_at,
+    )
+    .await
+    .map_err(|e| ApiError::internal("database_error", format!("Database error: {}", e)))?;
+
+    info!("Minted share token for {}", link.code);
+
+    Ok(Json(ShareResponse {
+        token,
+        code: link.code,
+        expires_at,
+    }))
+}
+
+/// POST /api/admin/cleanup - Deletes expired links now, and optionally unused ones
+///
+/// Runs the same expired-link sweep as the background task without waiting for
+/// the next interval. With `unused_days`, links that were created more than that
+/// many days ago and have never been visited are deleted too.
+///
+/// Only the master `AUTH_TOKEN` may call this; without one configured it is closed.
+///
+/// # Response (200 OK)
+/// ```json
+/// { "expired_deleted": 12, "unused_deleted": 3 }
+/// ```
+///
+/// # Errors
+/// - 401: Invalid or missing auth token
+/// - 403: Token from `API_TOKENS` rather than `AUTH_TOKEN`, or `AUTH_TOKEN` is unset
+/// - 500: Internal server error
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/api/admin/cleanup",
+    params(CleanupQuery),
+    responses(
+        (status = 200, description = "Number of links deleted", body = CleanupResponse),
+        (status = 401, description = "Invalid or missing auth token", body = ApiError),
+        (status = 403, description = "Not the master AUTH_TOKEN", body = ApiError),
+    ),
+    security((), ("bearer_auth" = []))
+))]
+pub async fn admin_cleanup(
+    State(state): State<AppState>,
+    Query(query): Query<CleanupQuery>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<CleanupResponse>, ApiError> {
+    // Other users' links are at stake, so per-user API tokens aren't enough
+    require_admin(&state, &headers, "Cleanup")?;
+
+    let now = now_unix();
+
+    let expired = delete_expired_links(&state.db, now)
+        .await
+        .map_err(|e| ApiError::internal("database_error", format!("Database error: {}", e)))?;
+    let expired_deleted = expired.len() as u64;
+    announce(
+        &state,
+        expired
+            .iter()
+            .map(|link| WebhookPayload::expired(link, now))
+            .collect(),
+    );
+
+    let unused_deleted = match query.unused_days {
+        Some(days) => {
+            let created_before = now - i64::from(days) * SECONDS_PER_DAY;
+            delete_unused_links(&state.db, created_before)
+                .await
+                .map_err(|e| {
+                    ApiError::internal("database_error", format!("Database error: {}", e))
+                })?
+        }
+        None => 0,
+    };
+    forget_all_links(&state);
+
+    info!(
+        "Manual cleanup deleted {} expired and {} unused links",
+        expired_deleted, unused_deleted
+    );
+
+    Ok(Json(CleanupResponse {
+        expired_deleted,
+        unused_deleted,
+    }))
+}
+
+/// GET /api/info/{code} - Returns link metadata without redirecting
+///
+/// Does not record a visit.
+///
+/// # Response (200 OK)
+/// ```json
+/// {
+///   "code": "abc123",
+///   "original_url": "https://example.com",
+///   "created_at": 1739000000,
+///   "expires_at": 1760000000,
+///   "expired": false,
+///   "enabled": true
+/// }
+/// ```
+///
+/// # Errors
+/// - 401: Missing/invalid token (when auth is enabled)
+/// - 404: Code not found
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/api/info/{code}",
+    params(("code" = String, Path, description = "Short code")),
+    responses(
+        (status = 200, description = "Link metadata", body = LinkInfoResponse),
+        (status = 401, description = "Invalid or missing auth token", body = ApiError),
+        (status = 404, description = "Code not found", body = ApiError),
+    ),
+    security((), ("bearer_auth" = []))
+))]
+pub async fn info(
+    State(state): State<AppState>,
+    Path(code): Path<String>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<LinkInfoResponse>, ApiError> {
+    // Validate auth token if configured
+    let owner = authenticate(&state, &headers)?;
+
+    let code = resolve_code(&state, code).await?;
+
+    let link = owned_link(&state, &code, owner.as_deref()).await?;
+
+    Ok(Json(link_info(link)))
+}
+
+/// GET /api/links - Lists links created by the requesting token, newest first
+///
+/// Links created with the master `AUTH_TOKEN` are listed under that token;
+/// when auth is disabled, links created without a token are listed.
+///
+/// # Query Parameters
+/// - `limit`: page size (default 20, max 500)
+/// - `offset`: number of links to skip (default 0)
+/// - `q`: only return links whose URL or code contains this text (case-insensitive)
+///
+/// # Response
+/// ```json
+/// {
+///   "items": [
+///     {
+///       "code": "abc123",
+///       "original_url": "https://example.com",
+///       "created_at": 1739000000,
+///       "expires_at": 1760000000,
+///       "expired": false,
+///       "enabled": true
+///     }
+///   ],
+///   "total": 1
+/// }
+/// ```
+///
+/// # Errors
+/// - 401: Missing/invalid token (when auth is enabled)
+/// - 500: Internal server error
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/api/links",
+    params(LinksQuery),
+    responses(
+        (status = 200, description = "A page of the caller's links", body = LinksResponse),
+        (status = 401, description = "Invalid or missing auth token", body = ApiError),
+    ),
+    security((), ("bearer_auth" = []))
+))]
+pub async fn list_links(
+    State(state): State<AppState>,
+    Query(query): Query<LinksQuery>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<LinksResponse>, ApiError> {
+    let owner = authenticate(&state, &headers)?;
+    let owner = owner.as_deref();
+    let q = query.q.as_deref().filter(|q| !q.is_empty());
+
+    let (links, total) = tokio::try_join!(
+        crate::database::list_links(
+            &state.db,
+            owner,
+            q,
+            query.limit.unwrap_or(DEFAULT_LINKS_PAGE_SIZE),
+            query.offset.unwrap_or(0),
+        ),
+        count_links(&state.db, owner, q),
+    )
+    .map_err(|e| ApiError::internal("database_error", format!("Database error: {}", e)))?;
+
+    Ok(Json(LinksResponse {
+        items: links.into_iter().map(link_info).collect(),
+        total,
+    }))
+}
+
+/// GET /api/stats - Returns link and visit counts across the whole server
+///
+/// `redirects` counts redirect lookups (`GET` and `HEAD /{code}`) since this
+/// server process started, for gauging how many requests find a live link.
+///
+/// # Response
+/// ```json
+/// {
+///   "total_links": 12,
+///   "active_links": 10,
+///   "expired_links": 2,
+///   "total_visits": 345,
+///   "redirects": { "hit": 340, "miss": 25, "expired": 3 }
+/// }
+/// ```
+///
+/// # Errors
+/// - 401: Missing/invalid token (when auth is enabled)
+/// - 500: Internal server error
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/api/stats",
+    responses(
+        (status = 200, description = "Server-wide counts", body = StatsResponse),
+        (status = 401, description = "Invalid or missing auth token", body = ApiError),
+    ),
+    security((), ("bearer_auth" = []))
+))]
+pub async fn stats(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<StatsResponse>, ApiError> {
+    require_auth(&state, &headers)?;
+
+    let ((total_links, active_links), total_visits) = tokio::try_join!(
+        count_links_by_state(&state.db, now_unix()),
+        count_all_visits(&state.db),
+    )
+    .map_err(|e| ApiError::internal("database_error", format!("Database error: {}", e)))?;
+
+    Ok(Json(StatsResponse {
+        total_links,
+        active_links,
+        expired_links: total_links - active_links,
+        total_visits,
+        redirects: state.redirect_counters.snapshot(),
+    }))
+}
+
+/// GET /qr/{code} - Returns a QR code encoding the full short URL
+///
+/// # Query Parameters
+/// - `format`: `png` (default) or `svg`
+/// - `size`: image size in pixels (default 256, clamped to 64-1024)
+///
+/// # Errors
+/// - 404: Code not found or expired
+/// - 500: Internal server error
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/qr/{code}",
+    params(("code" = String, Path, description = "Short code"), QrQuery),
+    responses(
+        (status = 200, description = "QR code image (SVG with `format=svg`)", content_type = "image/png"),
+        (status = 404, description = "Code not found or expired", body = ApiError),
+    )
+))]
+pub async fn qr(
+    State(state): State<AppState>,
+    Path(code): Path<String>,
+    Query(query): Query<QrQuery>,
+) -> Result<Response, ApiError> {
+    let code = resolve_code(&state, code).await?;
+
+    let link = get_link(&state.db, &code)
+        .await
+        .map_err(|e| ApiError::internal("database_error", format!("Database error: {}", e)))?
+        .ok_or_else(|| ApiError::not_found("not_found", "Short link not found"))?;
+
+    if now_unix() > link.expires_at {
+        return Err(ApiError::not_found(
+            "link_expired",
+            "Short link has expired",
+        ));
+    }
+
+    let short_url = format!("{}/{}", state.base_url.trim_end_matches('/'), link.code);
+    let size = qr::clamp_size(query.size);
+
+    let response = match query.format {
+        QrFormat::Png => {
+            let png = qr::render_png(&short_url, size).map_err(|e| {
+                ApiError::internal("internal_error", format!("QR code error: {}", e))
+            })?;
+            ([(header::CONTENT_TYPE, "image/png")], png).into_response()
+        }
+        QrFormat::Svg => {
+            let svg = qr::render_svg(&short_url, size).map_err(|e| {
+                ApiError::internal("internal_error", format!("QR code error: {}", e))
+            })?;
+            ([(header::CONTENT_TYPE, "image/svg+xml")], svg).into_response()
+        }
+    };
+
+    Ok(response)
+}
+
+/// GET /healthz - Reports whether the server and its database are up
+///
+/// Meant for load balancers and `cutl health`, so it needs no token and is
+/// never rate limited.
+///
+/// # Errors
+/// - 503: The database didn't answer
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/healthz",
+    responses(
+        (status = 200, description = "Server and database are up", body = HealthResponse),
+        (status = 503, description = "Database unavailable", body = ApiError),
+    )
+))]
+pub async fn healthz(State(state): State<AppState>) -> Result<Json<HealthResponse>, ApiError> {
+    ping(&state.db).await.map_err(|e| {
+        ApiError::service_unavailable(
+            "database_unavailable",
+            format!("Database unavailable: {}", e),
+        )
+    })?;
+
+    Ok(Json(HealthResponse { status: "ok" }))
+}
+
+/// GET /robots.txt - Asks crawlers to stay away from short links and analytics
+///
+/// Every short code lives at the root, so the whole site is disallowed.
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/robots.txt",
+    responses((status = 200, description = "Crawler rules", content_type = "text/plain"))
+))]
+pub async fn robots() -> Response {
+    (
+        [
+            (header::CONTENT_TYPE, "text/plain; charset=utf-8"),
+            (header::CACHE_CONTROL, STATIC_CACHE_CONTROL),
+        ],
+        ROBOTS_TXT,
+    )
+        .into_response()
+}
+
+/// GET /favicon.ico - Answers browser favicon requests with no content
+///
+/// Saves a database lookup and a 404 log line on every page view of a preview
+/// or password page.
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/favicon.ico",
+    responses((status = 204, description = "No favicon"))
+))]
+pub async fn favicon() -> Response {
+    (
+        StatusCode::NO_CONTENT,
+        [(header::CACHE_CONTROL, STATIC_CACHE_CONTROL)],
+    )
+        .into_response()
+}
+
+/// GET /metrics - Returns counters in Prometheus text format
+///
+/// # Errors
+/// - 404: Metrics are not enabled
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/metrics",
+    responses(
+        (status = 200, description = "Counters in Prometheus text format", content_type = "text/plain"),
+        (status = 404, description = "Metrics are not enabled", body = ApiError),
+    )
+))]
+pub async fn metrics(State(state): State<AppState>) -> Result<Response, ApiError> {
+    let handle = state
+        .metrics
+        .as_ref()
+        .ok_or_else(|| ApiError::not_found("not_found", "Metrics are not enabled"))?;
+
+    Ok((
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        handle.render(),
+    )
+        .into_response())
+}
+
+/// GET /analytics/{code} – Returns visit statistics for a short link
+///
+/// # Query Parameters
+/// - `limit`: number of recent visits to return (default 20, max 500)
+/// - `offset`: number of recent visits to skip (default 0)
+/// - `from`, `to`: UNIX timestamps bounding the visits counted in `total_visits`,
+///   `unique_visitors`, `countries`, `referers`, `devices`, and `daily` (inclusive). Without them, totals cover
+///   all visits and `daily` covers the last 30 days
+/// - `top`: number of `countries` and `referers` to list (default 10, 0 for all); the
+///   rest are summed into a final `"others"` entry
+/// - `group_by`: `full` (default) groups `referers` by URL, `host` by hostname
+///
+/// Responses carry a weak `ETag` that changes when a visit is recorded; a
+/// request whose `If-None-Match` matches it gets 304 with no body.
+///
+/// Besides the usual tokens, a share token minted for this code by
+/// `POST /api/links/{code}/share` is accepted as the bearer token.
+///
+/// # Errors
+/// - 400: `from` is after `to`
+/// - 401: Missing/invalid token (when auth is enabled)
+/// - 404: Code not found or expired
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/analytics/{code}",
+    params(("code" = String, Path, description = "Short code"), AnalyticsQuery),
+    responses(
+        (status = 200, description = "Visit statistics", body = AnalyticsResponse),
+        (status = 304, description = "Unchanged since the ETag in `If-None-Match`"),
+        (status = 400, description = "`from` is after `to`", body = ApiError),
+        (status = 401, description = "Invalid or missing auth token", body = ApiError),
+        (status = 404, description = "Code not found or expired", body = ApiError),
+    ),
+    security((), ("bearer_auth" = []))
+))]
+pub async fn analytics(
+    State(state): State<AppState>,
+    Path(code): Path<String>,
+    Query(query): Query<AnalyticsQuery>,
+    RawQuery(raw_query): RawQuery,
+    headers: axum::http::HeaderMap,
+) -> Result<Response, ApiError> {
+    metrics::record_analytics();
+
+    analytics_response(state, code, query, raw_query, headers)
+        .await
+        .inspect_err(|e| metrics::record_error("analytics", e))
+}
+
+/// Answers `analytics`, skipping the statistics when the client's copy is current
+async fn analytics_response(
+    state: AppState,
+    code: String,
+    query: AnalyticsQuery,
+    raw_query: Option<String>,
+    headers: axum::http::HeaderMap,
+) -> Result<Response, ApiError> {
+    let link = analytics_link(&state, code, &query, &headers).await?;
+
+    let (visits, last_visit_at) = visit_summary(&state.db, &link.code)
+        .await
+        .map_err(|e| ApiError::internal("database_error", format!("Database error: {}", e)))?;
+    let etag = analytics_etag(&link, visits, last_visit_at, raw_query.as_deref());
+
+    let if_none_match = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok());
+    if if_none_match.is_some_and(|value| etag_matches(value, &etag)) {
+        return Ok((StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response());
+    }
+
+    let stats = build_analytics(&state, link, query).await?;
+    Ok(([(header::ETAG, etag)], Json(stats)).into_response())
+}
+
+/// Weak ETag for an analytics response
+///
+/// Covers the link, the visit count and latest visit, the query string, and
+/// the current UTC day, since `daily` is a window ending today.
+fn analytics_etag(
+    link: &Link,
+    visits: i64,
+    last_visit_at: Option<i64>,
+    raw_query: Option<&str>,
+) -> String {
+    use std::hash::{DefaultHasher, Hash, Hasher};
+
+    let mut hasher = DefaultHasher::new();
+    (
+        &link.code,
+        &link.original_url,
+        link.expires_at,
+        visits,
+        last_visit_at,
+        raw_query,
+        now_unix().div_euclid(SECONDS_PER_DAY),
+    )
+        .hash(&mut hasher);
+
+    format!("W/\"{:016x}\"", hasher.finish())
+}
+
+/// Validates access to `code` for `analytics`, returning its live link
+async fn analytics_link(
+    state: &AppState,
+    code: String,
+    query: &AnalyticsQuery,
+    headers: &axum::http::HeaderMap,
+) -> Result<Link, ApiError> {
+    let code = resolve_code(state, code).await?;
+
+    // Validate auth token if configured, falling back to a share token for this code
+    let owner = match authenticate(state, headers) {
+        Ok(owner) => Some(owner),
+        Err(e) if !has_share_token(state, headers, &code).await? => return Err(e),
+        // A share token only ever opens the code it was minted for
+        Err(_) => None,
+    };
+
+    // Look up the link
+    let link = get_link(&state.db, &code)
+        .await
+        .map_err(|e| ApiError::internal("database_error", format!("Database error: {}", e)))?
+        .ok_or_else(|| ApiError::not_found("not_found", "Short link not found"))?;
+    if let Some(owner) = owner {
+        require_owner(owner.as_deref(), &link)?;
+    }
+
+    // Check if expired
+    if now_unix() > link.expires_at {
+        return Err(ApiError::not_found(
+            "link_expired",
+            "Short link has expired",
+        ));
+    }
+
+    if let (Some(from), Some(to)) = (query.from, query.to) {
+        if from > to {
+            return Err(ApiError::bad_request(
+                "invalid_range",
+                "'from' must not be after 'to'",
+            ));
+        }
+    }
+
+    Ok(link)
+}
+
+/// Whether the request's bearer token is an unexpired share token for `code`
+async fn has_share_token(
+    state: &AppState,
+    headers: &axum::http::HeaderMap,
+    code: &str,
+) -> Result<bool, ApiError> {
+    let Some(supplied) = extract_token(headers, &state.auth_header) else {
+        return Ok(false);
+    };
+
+    let shared_code = share_token_code(&state.db, &hash_share_token(supplied), now_unix())
+        .await
+        .map_err(|e| ApiError::internal("database_error", format!("Database error: {}", e)))?;
+
+    Ok(shared_code.as_deref() == Some(code))
+}
+
+/// Gathers the statistics for `analytics`
+async fn build_analytics(
+    state: &AppState,
+    link: Link,
+    query: AnalyticsQuery,
+) -> Result<AnalyticsResponse, ApiError> {
+    let code = link.code.clone();
+
+    let top = match query.top {
+        Some(0) => None,
+        Some(top) => Some(top),
+        None => Some(DEFAULT_TOP_VALUES),
+    };
+
+    let (total_visits, unique_visitors, countries, referers, devices, daily) =
+        if query.from.is_none() && query.to.is_none() {
+            tokio::try_join!(
+                count_visits(&state.db, &code),
+                count_unique_visitors(&state.db, &code),
+                visits_by_country(&state.db, &code, top),
+                referers_between(state, &code, i64::MIN, i64::MAX, top, query.group_by),
+                visits_by_device(&state.db, &code),
+                visits_daily(&state.db, &code),
+            )
+        } else {
+            let from = query.from.unwrap_or(i64::MIN);
+            let to = query.to.unwrap_or(i64::MAX);
+
+            tokio::try_join!(
+                count_visits_between(&state.db, &code, from, to),
+                count_unique_visitors_between(&state.db, &code, from, to),
+                visits_by_country_between(&state.db, &code, from, to, top),
+                referers_between(state, &code, from, to, top, query.group_by),
+                visits_by_device_between(&state.db, &code, from, to),
+                visits_daily_between(&state.db, &code, from, to),
+            )
+        }
+        .map_err(|e| ApiError::internal("database_error", format!("Database error: {}", e)))?;
+
+    let countries = countries
+        .into_iter()
+        .map(|(value, count)| CountStat { value, count })
+        .collect();
+
+    let referers = referers
+        .into_iter()
+        .map(|(value, count)| CountStat { value, count })
+        .collect();
+
+    let devices = devices
+        .into_iter()
+        .map(|(value, count)| CountStat { value, count })
+        .collect();
+
+    let daily = daily
+        .into_iter()
+        .map(|(date, count)| DailyStat { date, count })
+        .collect();
+
+    let recent = recent_visits(
+        &state.db,
+        &code,
+        query.limit.unwrap_or(DEFAULT_RECENT_VISITS),
+        query.offset.unwrap_or(0),
+    )
+    .await
+    .map_err(|e| ApiError::internal("database_error", format!("Database error: {}", e)))?;
+
+    Ok(AnalyticsResponse {
+        code: link.code,
+        original_url: link.original_url,
+        created_at: link.created_at,
+        created_at_iso: format_rfc3339(link.created_at),
+        expires_at: link.expires_at,
+        expires_at_iso: format_rfc3339(link.expires_at),
+        total_visits,
+        unique_visitors,
+        countries,
+        referers,
+        devices,
+        daily,
+        recent_visits: recent,
+    })
+}
+
+/// Referer counts in `[from, to]`, grouped by full URL or by host
+async fn referers_between(
+    state: &AppState,
+    code: &str,
+    from: i64,
+    to: i64,
+    top: Option<u32>,
+    group_by: RefererGrouping,
+) -> anyhow::Result<Vec<(Option<String>, i64)>> {
+    match group_by {
+        RefererGrouping::Full => visits_by_referer_between(&state.db, code, from, to, top).await,
+        RefererGrouping::Host => {
+            visits_by_referer_host_between(&state.db, code, from, to, top).await
+        }
+    }
+}
+
+/// GET /{code}/+ - Returns a link's public visit counts, no token needed
+///
+/// Only the total and the daily trend are shared; visitor details stay behind
+/// `GET /analytics/{code}`. Disabled and expired links look missing.
+///
+/// # Response
+/// ```json
+/// {
+///   "code": "abc123",
+///   "total_visits": 42,
+///   "daily": [{ "date": "2025-02-08", "count": 12 }]
+/// }
+/// ```
+///
+/// # Errors
+/// - 404: Code not found, disabled, or expired
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/{code}/+",
+    params(("code" = String, Path, description = "Short code")),
+    responses(
+        (status = 200, description = "Aggregate visit counts", body = PublicStatsResponse),
+        (status = 404, description = "Code not found, disabled, or expired", body = ApiError),
+    )
+))]
+pub async fn public_stats(
+    State(state): State<AppState>,
+    Path(code): Path<String>,
+) -> Result<Json<PublicStatsResponse>, ApiError> {
+    let code = resolve_code(&state, code).await?;
+
+    let link = get_link(&state.db, &code)
+        .await
+        .map_err(|e| ApiError::internal("database_error", format!("Database error: {}", e)))?
+        .ok_or_else(|| ApiError::not_found("not_found", "Short link not found"))?;
+
+    if now_unix() > link.expires_at {
+        return Err(ApiError::not_found(
+            "link_expired",
+            "Short link has expired",
+        ));
+    }
+
+    if !link.enabled {
+        return Err(ApiError::not_found(
+            "link_disabled",
+            "Short link is disabled",
+        ));
+    }
+
+    let (total_visits, daily) = tokio::try_join!(
+        count_visits(&state.db, &link.code),
+        visits_daily(&state.db, &link.code),
+    )
+    .map_err(|e| ApiError::internal("database_error", format!("Database error: {}", e)))?;
+
+    Ok(Json(PublicStatsResponse {
+        code: link.code,
+        total_visits,
+        daily: daily
+            .into_iter()
+            .map(|(date, count)| DailyStat { date, count })
+            .collect(),
+    }))
+}
+
+/// GET /analytics/{code}/export.csv - Streams every recorded visit as CSV
+///
+/// Columns: `visited_at,ip,country,city,user_agent,referer`, oldest visit first.
+/// Rows are fetched in pages while the response is written, so large exports
+/// are never held in memory.
+///
+/// # Errors
+/// - 401: Missing/invalid token (when auth is enabled)
+/// - 404: Code not found or expired
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/analytics/{code}/export.csv",
+    params(("code" = String, Path, description = "Short code")),
+    responses(
+        (status = 200, description = "Every recorded visit, oldest first", content_type = "text/csv"),
+        (status = 401, description = "Invalid or missing auth token", body = ApiError),
+        (status = 404, description = "Code not found or expired", body = ApiError),
+    ),
+    security((), ("bearer_auth" = []))
+))]
+pub async fn export_visits(
+    State(state): State<AppState>,
+    Path(code): Path<String>,
+    headers: axum::http::HeaderMap,
+) -> Result<Response, ApiError> {
+    // Validate auth token if configured
+    let owner = authenticate(&state, &headers)?;
+
+    let code = resolve_code(&state, code).await?;
+
+    let link = owned_link(&state, &code, owner.as_deref()).await?;
+
+    if now_unix() > link.expires_at {
+        return Err(ApiError::not_found(
+            "link_expired",
+            "Short link has expired",
+        ));
+    }
+
+    let disposition = format!("attachment; filename=\"{}-visits.csv\"", link.code);
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        export::visits_csv(state.db, link.code),
+    )
+        .into_response())
+}
+
+/// GET /api/export/links.ndjson - Streams every link as NDJSON for backups
+///
+/// Each line is one link: `{"code","original_url","expires_at","created_at"}`,
+/// in code order. Links are fetched in pages while the response is written,
+/// so large tables are never held in memory. Expired links are included.
+///
+/// # Errors
+/// - 401: Missing/invalid token (when auth is enabled)
+/// - 403: Token is not the master `AUTH_TOKEN`, or `AUTH_TOKEN` is unset
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/api/export/links.ndjson",
+    responses(
+        (status = 200, description = "Every link, one JSON object per line", body = crate::models::LinkRecord, content_type = "application/x-ndjson"),
+        (status = 401, description = "Invalid or missing auth token", body = ApiError),
+        (status = 403, description = "Not the master AUTH_TOKEN", body = ApiError),
+    ),
+    security((), ("bearer_auth" = []))
+))]
+pub async fn export_links(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+) -> Result<Response, ApiError> {
+    // The export covers every owner's links
+    require_admin(&state, &headers, "Export")?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/x-ndjson"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"links.ndjson\"",
+            ),
+        ],
+        export::links_ndjson(state.db),
+    )
+        .into_response())
+}
+
+/// POST /api/import/links - Restores links from an NDJSON export
+///
+/// The body has one `{"code","original_url","expires_at","created_at"}` object
+/// per line, as written by `GET /api/export/links.ndjson`. Codes and URLs are
+/// validated like `POST /shorten`; bad lines are counted as failed without
+/// stopping the import. Links are written in chunks of `IMPORT_CHUNK_SIZE`, one
+/// transaction each.
+///
+/// # Query Parameters
+/// - `on_conflict`: `skip` (default) keeps existing links, `overwrite` replaces them
+///
+/// # Response (200 OK)
+/// ```json
+/// {
+///   "imported": 120,
+///   "skipped": 1,
+///   "failed": 1,
+///   "skipped_codes": ["docs"],
+///   "errors": [{ "line": 7, "error": "Invalid URL: ..." }]
+/// }
+/// ```
+///
+/// # Errors
+/// - 400: The body couldn't be read
+/// - 401: Missing/invalid token (when auth is enabled)
+/// - 403: Token is not the master `AUTH_TOKEN`, or `AUTH_TOKEN` is unset
+/// - 500: Database error; chunks written before it stay imported
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/api/import/links",
+    params(ImportQuery),
+    request_body(content = String, description = "NDJSON link records, as from the links export", content_type = "application/x-ndjson"),
+    responses(
+        (status = 200, description = "Import summary", body = ImportResponse),
+        (status = 400, description = "Unreadable body", body = ApiError),
+        (status = 401, description = "Invalid or missing auth token", body = ApiError),
+        (status = 403, description = "Not the master AUTH_TOKEN", body = ApiError),
+    ),
+    security((), ("bearer_auth" = []))
+))]
+pub async fn import_links(
+    State(state): State<AppState>,
+    Query(query): Query<ImportQuery>,
+    headers: axum::http::HeaderMap,
+    body: Body,
+) -> Result<Json<ImportResponse>, ApiError> {
+    // Imports can overwrite any owner's links
+    require_admin(&state, &headers, "Import")?;
+
+    let summary = import_body(&state, query.on_conflict, body).await;
+    // Overwrites may have replaced cached links, even if the import stopped partway
+    forget_all_links(&state);
+    let summary = summary?;
+
+    info!(
+        "Imported {} links ({} skipped, {} failed)",
+        summary.imported, summary.skipped, summary.failed
+    );
+
+    Ok(Json(summary))
+}
+
+/// Streams `body` into an `Importer`, returning its summary
+///
+/// Chunks written before an error stay imported.
+async fn import_body(
+    state: &AppState,
+    on_conflict: ConflictMode,
+    body: Body,
+) -> Result<ImportResponse, ApiError> {
+    let mut importer = Importer::new(state, on_conflict);
+    let mut stream = body.into_data_stream();
+
+    while let Some(data) = stream.next().await {
+        let data = data.map_err(|e| {
+            ApiError::bad_request("invalid_body", format!("Failed to read body: {}", e))
+        })?;
+        importer.feed(&data).await.map_err(|e| {
+            ApiError::internal("internal_error", format!("Failed to import links: {}", e))
+        })?;
+    }
+
+    importer
+        .finish()
+        .await
+        .map_err(|e| ApiError::internal("internal_error", format!("Failed to import links: {}", e)))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{
+        blocklist::Blocklist,
+        models::{ApiToken, LinkRecord, RedirectStatus},
+        utils::{
+            DomainList, IpStorage, TtlLimits, UrlNormalization, BOT_USER_AGENT_PATTERNS,
+            DEFAULT_MAX_URL_LENGTH, DEFAULT_RESERVED_CODES,
+        },
+    };
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+        routing::get,
+        Router,
+    };
+    use sqlx::sqlite::SqlitePool;
+    use tower::ServiceExt;
+
+    /// An `AppState` with every option off, shared with router tests in `main.rs`
+    pub(crate) fn test_state(db: SqlitePool) -> AppState {
+        AppState {
+            db,
+            base_url: "http://localhost:3000".to_string(),
+            allowed_hosts: Vec::new(),
+            auth_token: None,
+            api_tokens: Vec::new(),
+            auth_header: header::AUTHORIZATION,
+            trust_proxy: false,
+            geoip: None,
+            ip_storage: IpStorage::Raw,
+            ip_hash_salt: "test-salt".to_string(),
+            dedup_urls: false,
+            url_normalization: UrlNormalization::Off,
+            redirect_status: RedirectStatus::Temporary,
+            ttl_limits: TtlLimits::default(),
+            max_batch_size: 500,
+            code_settings: CodeSettings::default(),
+            case_insensitive_codes: false,
+            reserved_codes: DEFAULT_RESERVED_CODES
+                .iter()
+                .map(|c| c.to_string())
+                .collect(),
+            preview_mode: false,
+            count_bots: false,
+            bot_user_agents: BOT_USER_AGENT_PATTERNS
+                .iter()
+                .map(|p| p.to_string())
+                .collect(),
+            redirect_cache_max_age: None,
+            metrics: None,
+            webhook: None,
+            blocklist: Blocklist::default(),
+            allowed_domains: DomainList::default(),
+            // Tests don't depend on a working resolver
+            resolver: None,
+            max_url_length: DEFAULT_MAX_URL_LENGTH,
+            visit_buffer: None,
+            link_cache: None,
+            redirect_counters: Default::default(),
+        }
+    }
+
+    /// Resolves `internal.example` to loopback, `metadata.example` to the cloud
+    /// metadata address, and every other host to a public address
+    pub(crate) struct FakeResolver;
+
+    impl crate::utils::HostResolver for FakeResolver {
+        fn lookup<'a>(
+            &'a self,
+            host: &'a str,
+            port: u16,
+        ) -> futures_util::future::BoxFuture<'a, std::io::Result<Vec<SocketAddr>>> {
+            let ip = match host {
+                "internal.example" => [127, 0, 0, 1],
+                "metadata.example" => [169, 254, 169, 254],
+                _ => [93, 184, 216, 34],
+            };
+            Box::pin(async move { Ok(vec![SocketAddr::from((ip, port))]) })
+        }
+    }
+
+    async fn setup_app() -> Router {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        crate::database::run_migrations(&pool).await.unwrap();
+
+        let state = test_state(pool);
+
+        Router::new()
+            .route("/{code}", get(redirect).delete(delete))
+            .route("/analytics/{code}", get(analytics))
+            .with_state(state)
+    }
+
+    #[tokio::test]
+    async fn test_analytics_not_found() {
+        let app = setup_app().await;
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/analytics/noexist")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_analytics_etag_not_modified() {
+        let pool = setup_pool_with_link("tagged").await;
+        let app = Router::new()
+            .route("/analytics/{code}", get(analytics))
+            .with_state(test_state(pool.clone()));
+        let request = |etag: Option<&str>| {
+            let mut builder = Request::builder().uri("/analytics/tagged?limit=5");
+            if let Some(etag) = etag {
+                builder = builder.header("if-none-match", etag);
+            }
+            builder.body(Body::empty()).unwrap()
+        };
+
+        let response = app.clone().oneshot(request(None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let etag = response.headers()["etag"].to_str().unwrap().to_string();
+        assert!(etag.starts_with("W/\""));
+
+        // The same ETag back means nothing changed
+        let response = app.clone().oneshot(request(Some(&etag))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()["etag"], etag.as_str());
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(body.is_empty());
+
+        // A new visit changes the ETag, so the old one gets the full response
+        insert_visit(
+            &pool,
+            &NewVisit {
+                code: "tagged".to_string(),
+                visited_at: now_unix(),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        let response = app.oneshot(request(Some(&etag))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_ne!(response.headers()["etag"], etag.as_str());
+    }
+
+    #[tokio::test]
+    async fn test_analytics_returns_counts() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        crate::database::run_migrations(&pool).await.unwrap();
+
+        // Create a link that expires far in the future
+        crate::database::insert_link(
+            &pool,
+            &NewLink {
+                code: "testcode",
+                original_url: "https://example.com",
+                expires_at: 9999999999,
+                created_at: 1000000000,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let state = test_state(pool);
+
+        let app = Router::new()
+            .route("/{code}", get(redirect))
+            .route("/analytics/{code}", get(analytics))
+            .with_state(state);
+
+        // Trigger two redirects to record visits
+        app.clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/testcode")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        app.clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/testcode")
+                    .header(
+                        "user-agent",
+                        "Mozilla/5.0 (iPhone; CPU iPhone OS 17_2 like Mac OS X)",
+                    )
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Call analytics endpoint
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/analytics/testcode")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(json["total_visits"], 2);
+        assert_eq!(json["unique_visitors"], 0);
+
+        let devices = json["devices"].as_array().unwrap();
+        assert_eq!(devices.len(), 2);
+        assert!(devices.contains(&serde_json::json!({ "value": "mobile", "count": 1 })));
+        assert!(devices.contains(&serde_json::json!({ "value": null, "count": 1 })));
+    }
+
+    #[tokio::test]
+    async fn test_delete_removes_link_and_visits() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        crate::database::run_migrations(&pool).await.unwrap();
+
+        crate::database::insert_link(
+            &pool,
+            &NewLink {
+                code: "gone",
+                original_url: "https://example.com",
+                expires_at: 9999999999,
+                created_at: 1000000000,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        crate::database::insert_visit(
+            &pool,
+            &NewVisit {
+                code: "gone".to_string(),
+                visited_at: 1000000001,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let state = test_state(pool.clone());
+
+        let app = Router::new()
+            .route("/{code}", get(redirect).delete(delete))
+            .with_state(state);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("DELETE")
+                    .uri("/gone")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["deleted"], true);
+
+        assert!(get_link(&pool, "gone").await.unwrap().is_none());
+        assert_eq!(count_visits(&pool, "gone").await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_delete_not_found() {
+        let app = setup_app().await;
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("DELETE")
+                    .uri("/noexist")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_delete_requires_auth() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        crate::database::run_migrations(&pool).await.unwrap();
+
+        crate::database::insert_link(
+            &pool,
+            &NewLink {
+                code: "keep",
+                original_url: "https://example.com",
+                expires_at: 9999999999,
+                created_at: 1000000000,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let mut state = test_state(pool.clone());
+        state.auth_token = Some("secret".to_string());
+
+        let app = Router::new()
+            .route("/{code}", get(redirect).delete(delete))
+            .with_state(state);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("DELETE")
+                    .uri("/keep")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(get_link(&pool, "keep").await.unwrap().is_some());
+    }
+
+    /// Sends `method uri` with `body` and the extra `headers`, returning the status
+    async fn send_with_headers(
+        app: Router,
+        method: &str,
+        uri: &str,
+        headers: &[(&str, &str)],
+        body: &str,
+    ) -> StatusCode {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json");
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+
+        app.oneshot(request.body(Body::from(body.to_string())).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn test_auth_header_accepts_api_key_and_bearer() {
+        let pool = setup_pool_with_link("keyed").await;
+        let mut state = test_state(pool);
+        state.auth_token = Some("secret".to_string());
+        state.auth_header = axum::http::HeaderName::from_static("x-api-key");
+        let app = Router::new()
+            .route("/shorten", axum::routing::post(shorten))
+            .route("/analytics/{code}", get(analytics))
+            .with_state(state);
+        let body = r#"{"url":"https://example.com"}"#;
+
+        for headers in [
+            [("x-api-key", "secret")],
+            [("authorization", "Bearer secret")],
+        ] {
+            let status = send_with_headers(app.clone(), "POST", "/shorten", &headers, body).await;
+            assert_eq!(status, StatusCode::OK);
+            let status =
+                send_with_headers(app.clone(), "GET", "/analytics/keyed", &headers, "").await;
+            assert_eq!(status, StatusCode::OK);
+        }
+
+        // Neither header carries a valid token
+        for headers in [
+            vec![],
+            vec![("x-api-key", "wrong")],
+            vec![("authorization", "secret")],
+            vec![("x-other-key", "secret")],
+        ] {
+            let status = send_with_headers(app.clone(), "POST", "/shorten", &headers, body).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+            let status =
+                send_with_headers(app.clone(), "GET", "/analytics/keyed", &headers, "").await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_api_key_accepted_without_auth_header() {
+        let pool = setup_pool_with_link("keyed").await;
+        let mut state = test_state(pool);
+        state.auth_token = Some("secret".to_string());
+        let app = Router::new()
+            .route("/analytics/{code}", get(analytics))
+            .with_state(state);
+
+        let status = send_with_headers(
+            app.clone(),
+            "GET",
+            "/analytics/keyed",
+            &[("x-api-key", "secret")],
+            "",
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        // Other headers only count once AUTH_HEADER names them
+        let status = send_with_headers(
+            app,
+            "GET",
+            "/analytics/keyed",
+            &[("x-gateway-token", "secret")],
+            "",
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    async fn patch_link(pool: SqlitePool, code: &str, body: &str) -> axum::response::Response {
+        let state = test_state(pool);
+
+        let app = Router::new()
+            .route("/{code}", get(redirect).patch(update))
+            .with_state(state);
+
+        app.oneshot(
+            Request::builder()
+                .method("PATCH")
+                .uri(format!("/{}", code))
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+    }
+
+    async fn get_qr(pool: SqlitePool, uri: &str) -> axum::response::Response {
+        let app = Router::new()
+            .route("/qr/{code}", get(qr))
+            .with_state(test_state(pool));
+
+        app.oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_qr_png() {
+        let pool = setup_pool_with_link("qrcode").await;
+
+        let response = get_qr(pool, "/qr/qrcode").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "image/png");
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(!body.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_qr_svg_with_size() {
+        let pool = setup_pool_with_link("qrcode").await;
+
+        let response = get_qr(pool, "/qr/qrcode?format=svg&size=5000").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "image/svg+xml");
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(!body.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_qr_not_found() {
+        let pool = setup_pool_with_link("qrcode").await;
+
+        let response = get_qr(pool, "/qr/missing").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    async fn setup_pool_with_link(code: &str) -> SqlitePool {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        crate::database::run_migrations(&pool).await.unwrap();
+        crate::database::insert_link(
+            &pool,
+            &NewLink {
+                code,
+                original_url: "https://example.com",
+                expires_at: 9999999999,
+                created_at: 1000000000,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        pool
+    }
+
+    #[tokio::test]
+    async fn test_update_url_only() {
+        let pool = setup_pool_with_link("upd").await;
+
+        let response = patch_link(pool.clone(), "upd", r#"{"url":"https://example.org"}"#).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let link = get_link(&pool, "upd").await.unwrap().unwrap();
+        assert_eq!(link.original_url, "https://example.org");
+        assert_eq!(link.expires_at, 9999999999);
+    }
+
+    #[tokio::test]
+    async fn test_update_ttl_only() {
+        let pool = setup_pool_with_link("upd").await;
+
+        let response = patch_link(pool.clone(), "upd", r#"{"ttl":"1h"}"#).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        let link = get_link(&pool, "upd").await.unwrap().unwrap();
+        assert_eq!(link.original_url, "https://example.com");
+        assert!(link.expires_at <= now_unix() + 3600);
+        assert_eq!(json["expires_at"], link.expires_at);
+    }
+
+    #[tokio::test]
+    async fn test_update_url_and_ttl() {
+        let pool = setup_pool_with_link("upd").await;
+
+        let response = patch_link(
+            pool.clone(),
+            "upd",
+            r#"{"url":"https://example.org","ttl":"2d"}"#,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let link = get_link(&pool, "upd").await.unwrap().unwrap();
+        assert_eq!(link.original_url, "https://example.org");
+        assert!(link.expires_at <= now_unix() + 2 * 86400);
+    }
+
+    #[tokio::test]
+    async fn test_update_invalid_input() {
+        let pool = setup_pool_with_link("upd").await;
+
+        let response = patch_link(pool.clone(), "upd", r#"{"url":"ftp://example.org"}"#).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = patch_link(pool.clone(), "upd", r#"{"ttl":"1s"}"#).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_update_not_found() {
+        let pool = setup_pool_with_link("upd").await;
+
+        let response = patch_link(pool, "missing", r#"{"url":"https://example.org"}"#).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    async fn post_shorten(app: Router, body: &str) -> serde_json::Value {
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/shorten")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    async fn setup_shorten_app(dedup_urls: bool) -> Router {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        crate::database::run_migrations(&pool).await.unwrap();
+
+        let mut state = test_state(pool);
+        state.dedup_urls = dedup_urls;
+
+        Router::new()
+            .route("/api/shorten", axum::routing::post(shorten_noauth))
+            .with_state(state)
+    }
+
+    #[tokio::test]
+    async fn test_shorten_dedup_returns_existing_code() {
+        let app = setup_shorten_app(true).await;
+
+        let first = post_shorten(app.clone(), r#"{"url":"https://example.com"}"#).await;
+        let second = post_shorten(app, r#"{"url":"https://example.com"}"#).await;
+
+        assert_eq!(first["code"], second["code"]);
+        assert_eq!(first["expires_at"], second["expires_at"]);
+    }
+
+    #[tokio::test]
+    async fn test_shorten_dedup_respects_ttl() {
+        let app = setup_shorten_app(true).await;
+
+        let day = post_shorten(app.clone(), r#"{"url":"https://example.com","ttl":"1d"}"#).await;
+        let again = post_shorten(app.clone(), r#"{"url":"https://example.com","ttl":"1d"}"#).await;
+        let week = post_shorten(app, r#"{"url":"https://example.com","ttl":"7d"}"#).await;
+
+        assert_eq!(day["code"], again["code"]);
+        assert_ne!(day["code"], week["code"]);
+        assert_eq!(
+            week["expires_at"].as_i64().unwrap() - day["expires_at"].as_i64().unwrap(),
+            6 * 86400
+        );
+    }
+
+    #[tokio::test]
+    async fn test_shorten_sends_created_webhook() {
+        use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        crate::database::run_migrations(&pool).await.unwrap();
+        let mut state = test_state(pool);
+        state.dedup_urls = true;
+        state.webhook = Some(crate::webhook::Webhook::new(server.uri()).unwrap());
+        let app = Router::new()
+            .route("/api/shorten", axum::routing::post(shorten_noauth))
+            .with_state(state);
+
+        let created = post_shorten(app.clone(), r#"{"url":"https://example.com"}"#).await;
+        // Reusing the existing link is not a new creation
+        post_shorten(app, r#"{"url":"https://example.com"}"#).await;
+
+        // Delivery happens in the background, after the response
+        let mut requests = Vec::new();
+        for _ in 0..100 {
+            requests = server.received_requests().await.unwrap();
+            if !requests.is_empty() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+
+        let payload: serde_json::Value = requests[0].body_json().unwrap();
+        assert_eq!(payload["event"], "created");
+        assert_eq!(payload["code"], created["code"]);
+        assert_eq!(payload["url"], "https://example.com");
+        assert_eq!(payload["expires_at"], created["expires_at"]);
+        assert!(payload["created_at"].is_i64());
+        assert_eq!(payload["timestamp"], payload["created_at"]);
+    }
+
+    #[tokio::test]
+    async fn test_shorten_dry_run_writes_nothing() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        crate::database::run_migrations(&pool).await.unwrap();
+        let app = Router::new()
+            .route("/api/shorten", axum::routing::post(shorten_noauth))
+            .with_state(test_state(pool.clone()));
+
+        let json = post_shorten(
+            app.clone(),
+            r#"{"url":"https://example.com","code":"maybe","dry_run":true}"#,
+        )
+        .await;
+        assert_eq!(json["code"], "maybe");
+        assert_eq!(json["short_url"], "http://localhost:3000/maybe");
+        assert!(json["expires_at"].as_i64().unwrap() > now_unix());
+        assert!(get_link(&pool, "maybe").await.unwrap().is_none());
+
+        let json = post_shorten(app, r#"{"url":"https://example.com","dry_run":true}"#).await;
+        assert!(json["code"].is_string());
+        assert_eq!(count_links(&pool, None, None).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_shorten_dry_run_still_checks_collisions() {
+        let pool = setup_pool_with_link("taken").await;
+        let app = Router::new()
+            .route("/api/shorten", axum::routing::post(shorten_noauth))
+            .with_state(test_state(pool));
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/shorten")
+                    .header("content-type", "application/json")
+                    .body(Body::from(
+                        r#"{"url":"https://example.com","code":"taken","dry_run":true}"#,
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn test_shorten_sliding_sets_idle_window_and_hard_limit() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        crate::database::run_migrations(&pool).await.unwrap();
+        let app = Router::new()
+            .route("/api/shorten", axum::routing::post(shorten_noauth))
+            .with_state(test_state(pool.clone()));
+
+        let json = post_shorten(
+            app.clone(),
+            r#"{"url":"https://example.com","code":"slide","sliding":true,"idle_ttl":"1h","ttl":"2d"}"#,
+        )
+        .await;
+        let link = get_link(&pool, "slide").await.unwrap().unwrap();
+        assert_eq!(link.idle_ttl, Some(3600));
+        assert_eq!(link.expires_at, link.created_at + 3600);
+        assert_eq!(link.max_expires_at, Some(link.created_at + 2 * 86400));
+        assert_eq!(json["expires_at"], link.expires_at);
+
+        // Without a ttl, the hard limit is MAX_TTL
+        post_shorten(
+            app.clone(),
+            r#"{"url":"https://example.com","code":"open","sliding":true}"#,
+        )
+        .await;
+        let link = get_link(&pool, "open").await.unwrap().unwrap();
+        let limits = TtlLimits::default();
+        assert_eq!(link.idle_ttl, Some(limits.default_seconds));
+        assert_eq!(
+            link.max_expires_at,
+            Some(link.created_at + limits.max_seconds)
+        );
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/shorten")
+                    .header("content-type", "application/json")
+                    .body(Body::from(
+                        r#"{"url":"https://example.com","idle_ttl":"1h"}"#,
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_redirect_pushes_sliding_expiry_forward() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        crate::database::run_migrations(&pool).await.unwrap();
+        let now = now_unix();
+        insert_link(
+            &pool,
+            &NewLink {
+                code: "slide",
+                original_url: "https://example.com",
+                expires_at: now + 10,
+                created_at: now - 1000,
+                idle_ttl: Some(3600),
+                max_expires_at: Some(now + 86400),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        insert_link(
+            &pool,
+            &NewLink {
+                code: "idle",
+                original_url: "https://example.com",
+                expires_at: now - 10,
+                created_at: now - 3610,
+                idle_ttl: Some(3600),
+                max_expires_at: Some(now + 86400),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let app = Router::new()
+            .route("/{code}", get(redirect))
+            .with_state(test_state(pool.clone()));
+        let response = get_uri(app, "/slide").await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+
+        let link = get_link(&pool, "slide").await.unwrap().unwrap();
+        assert!(link.expires_at >= now + 3600);
+
+        // A sliding link nobody visited still expires and gets swept
+        let expired = crate::database::delete_expired_links(&pool, now_unix())
+            .await
+            .unwrap();
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].code, "idle");
+        assert!(get_link(&pool, "slide").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_shorten_rejects_blocked_destination() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        crate::database::run_migrations(&pool).await.unwrap();
+        let mut state = test_state(pool);
+        state.blocklist = Blocklist::new(DomainList::parse("evil.com"), None);
+        let app = Router::new()
+            .route("/api/shorten", axum::routing::post(shorten_noauth))
+            .with_state(state);
+
+        for body in [
+            r#"{"url":"https://login.evil.com/"}"#,
+            r#"{"url":"https://example.com","rules":{"languages":{"fr":"https://evil.com/fr"}}}"#,
+        ] {
+            let response = app
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .method("POST")
+                        .uri("/api/shorten")
+                        .header("content-type", "application/json")
+                        .body(Body::from(body))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(json["error"], "Destination not allowed");
+            assert_eq!(json["code"], "destination_not_allowed");
+        }
+
+        post_shorten(app, r#"{"url":"https://notevil.com/"}"#).await;
+    }
+
+    #[tokio::test]
+    async fn test_shorten_allowed_domains() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        crate::database::run_migrations(&pool).await.unwrap();
+        let mut state = test_state(pool);
+        state.allowed_domains = DomainList::parse("corp.example");
+        let app = Router::new()
+            .route("/api/shorten", axum::routing::post(shorten_noauth))
+            .with_state(state);
+
+        post_shorten(app.clone(), r#"{"url":"https://corp.example/handbook"}"#).await;
+        post_shorten(app.clone(), r#"{"url":"https://wiki.corp.example/"}"#).await;
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/shorten")
+                    .header("content-type", "application/json")
+                    .body(Body::from(r#"{"url":"https://example.com/"}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_shorten_errors_carry_code() {
+        let pool = setup_pool_with_link("taken").await;
+        let app = Router::new()
+            .route("/api/shorten", axum::routing::post(shorten_noauth))
+            .with_state(test_state(pool));
+
+        for (body, status, code, error) in [
+            (
+                r#"{"url":"ftp://example.com"}"#,
+                StatusCode::BAD_REQUEST,
+                "invalid_url",
+                "Invalid URL: URL must start with http:// or https://",
+            ),
+            (
+                r#"{"url":"https://example.com","code":"taken"}"#,
+                StatusCode::CONFLICT,
+                "code_exists",
+                "Code 'taken' already exists",
+            ),
+        ] {
+            let response = app
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .method("POST")
+                        .uri("/api/shorten")
+                        .header("content-type", "application/json")
+                        .body(Body::from(body))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), status);
+
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(json["code"], code);
+            assert_eq!(json["error"], error);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shorten_dedup_with_custom_code_creates_new() {
+        let app = setup_shorten_app(true).await;
+
+        let first = post_shorten(app.clone(), r#"{"url":"https://example.com"}"#).await;
+        let second = post_shorten(app, r#"{"url":"https://example.com","code":"custom"}"#).await;
+
+        assert_ne!(first["code"], second["code"]);
+        assert_eq!(second["code"], "custom");
+    }
+
+    #[tokio::test]
+    async fn test_shorten_without_dedup_creates_new() {
+        let app = setup_shorten_app(false).await;
+
+        let first = post_shorten(app.clone(), r#"{"url":"https://example.com"}"#).await;
+        let second = post_shorten(app, r#"{"url":"https://example.com"}"#).await;
+
+        assert_ne!(first["code"], second["code"]);
+    }
+
+    async fn shorten_with_host(host: &str) -> serde_json::Value {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        crate::database::run_migrations(&pool).await.unwrap();
+
+        let mut state = test_state(pool);
+        state.base_url = "https://cutl.my.id".to_string();
+        state.allowed_hosts = vec!["go.example.com".to_string()];
+
+        let app = Router::new()
+            .route("/shorten", axum::routing::post(shorten))
+            .with_state(state);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/shorten")
+                    .header("host", host)
+                    .header("content-type", "application/json")
+                    .body(Body::from(r#"{"url":"https://example.com","code":"docs"}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_shorten_uses_allowed_host() {
+        let created = shorten_with_host("go.example.com").await;
+        assert_eq!(created["short_url"], "https://go.example.com/docs");
+    }
+
+    #[tokio::test]
+    async fn test_shorten_disallowed_host_falls_back_to_base_url() {
+        let created = shorten_with_host("evil.example.net").await;
+        assert_eq!(created["short_url"], "https://cutl.my.id/docs");
+    }
+
+    async fn setup_protected_link() -> (Router, SqlitePool) {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        crate::database::run_migrations(&pool).await.unwrap();
+
+        let app = Router::new()
+            .route("/api/shorten", axum::routing::post(shorten_noauth))
+            .route("/{code}", get(redirect).post(unlock))
+            .with_state(test_state(pool.clone()));
+
+        let created = post_shorten(
+            app.clone(),
+            r#"{"url":"https://example.com","code":"locked","password":"hunter2"}"#,
+        )
+        .await;
+        assert_eq!(created["code"], "locked");
+
+        (app, pool)
+    }
+
+    async fn get_uri(app: Router, uri: &str) -> axum::response::Response {
+        app.oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_protected_link_stores_hash() {
+        let (_app, pool) = setup_protected_link().await;
+
+        let link = get_link(&pool, "locked").await.unwrap().unwrap();
+        let hash = link.password_hash.unwrap();
+        assert_ne!(hash, "hunter2");
+        assert!(verify_password("hunter2", &hash));
+    }
+
+    #[tokio::test]
+    async fn test_protected_link_prompts_for_password() {
+        let (app, pool) = setup_protected_link().await;
+
+        let response = get_uri(app, "/locked").await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(response.headers()["content-type"]
+            .to_str()
+            .unwrap()
+            .starts_with("text/html"));
+        assert_eq!(count_visits(&pool, "locked").await.unwrap(), 0);
+    }
+
+    /// Submits `password` to `POST /{code}` the way the password form does
+    async fn post_password(app: Router, uri: &str, password: &str) -> axum::response::Response {
+        app.oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(uri)
+                .header("content-type", "application/x-www-form-urlencoded")
+                .header("x-forwarded-for", "203.0.113.9")
+                .body(Body::from(format!("password={}", password)))
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_protected_link_wrong_password() {
+        let (app, pool) = setup_protected_link().await;
+
+        let response = post_password(app, "/locked", "wrong").await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(String::from_utf8_lossy(&body).contains("Incorrect password"));
+        assert_eq!(count_visits(&pool, "locked").await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_protected_link_correct_password() {
+        let (app, pool) = setup_protected_link().await;
+
+        let response = post_password(app, "/locked", "hunter2").await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+        assert_eq!(response.headers()["location"], "https://example.com");
+        assert_eq!(count_visits(&pool, "locked").await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_protected_link_ignores_password_in_query() {
+        let (app, pool) = setup_protected_link().await;
+
+        let response = get_uri(app.clone(), "/locked?password=hunter2").await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(count_visits(&pool, "locked").await.unwrap(), 0);
+
+        // The form posts instead of putting the password in the URL
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(String::from_utf8_lossy(&body).contains(r#"<form method="post" action="/locked">"#));
+    }
+
+    #[tokio::test]
+    async fn test_password_attempts_are_rate_limited() {
+        use crate::middleware::{create_rate_limiter, RateLimitKey};
+
+        let pool = setup_pool_with_link("open").await;
+        let app = Router::new()
+            .route(
+                "/{code}",
+                axum::routing::post(unlock).layer(create_rate_limiter(
+                    1,
+                    1,
+                    RateLimitKey::Forwarded,
+                    &crate::utils::IpAllowlist::default(),
+                    true,
+                )),
+            )
+            .with_state(test_state(pool));
+
+        let response = post_password(app.clone(), "/open", "guess").await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+        let response = post_password(app, "/open", "guess").await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[tokio::test]
+    async fn test_shorten_empty_password_rejected() {
+        let app = setup_shorten_app(false).await;
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/shorten")
+                    .header("content-type", "application/json")
+                    .body(Body::from(r#"{"url":"https://example.com","password":""}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_shorten_dedup_skips_protected_links() {
+        let app = setup_shorten_app(true).await;
+
+        let protected = post_shorten(
+            app.clone(),
+            r#"{"url":"https://example.com","password":"hunter2"}"#,
+        )
+        .await;
+        let open = post_shorten(app, r#"{"url":"https://example.com"}"#).await;
+
+        assert_ne!(protected["code"], open["code"]);
+    }
+
+    #[tokio::test]
+    async fn test_max_visits_link_works_once() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        crate::database::run_migrations(&pool).await.unwrap();
+
+        let app = Router::new()
+            .route("/api/shorten", axum::routing::post(shorten_noauth))
+            .route("/{code}", get(redirect))
+            .with_state(test_state(pool.clone()));
+
+        post_shorten(
+            app.clone(),
+            r#"{"url":"https://example.com","code":"once","max_visits":1}"#,
+        )
+        .await;
+
+        let response = get_uri(app.clone(), "/once").await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+
+        let response = get_uri(app, "/once").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert!(get_link(&pool, "once").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_shorten_invalid_max_visits() {
+        let app = setup_shorten_app(false).await;
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/shorten")
+                    .header("content-type", "application/json")
+                    .body(Body::from(
+                        r#"{"url":"https://example.com","max_visits":0}"#,
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    async fn setup_preview_app() -> (Router, SqlitePool) {
+        let pool = setup_pool_with_link("peek").await;
+
+        let mut state = test_state(pool.clone());
+        state.preview_mode = true;
+
+        let app = Router::new()
+            .route("/{code}", get(redirect))
+            .with_state(state);
+
+        (app, pool)
+    }
+
+    #[tokio::test]
+    async fn test_preview_mode_renders_page_without_visit() {
+        let (app, pool) = setup_preview_app().await;
+
+        let response = get_uri(app, "/peek").await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let html = String::from_utf8_lossy(&body);
+        assert!(html.contains("https://example.com"));
+        assert!(html.contains("/peek?go=1"));
+        assert_eq!(count_visits(&pool, "peek").await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_preview_mode_go_redirects_and_records_visit() {
+        let (app, pool) = setup_preview_app().await;
+
+        let response = get_uri(app, "/peek?go=1").await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+        assert_eq!(response.headers()["location"], "https://example.com");
+        assert_eq!(count_visits(&pool, "peek").await.unwrap(), 1);
+    }
+
+    async fn public_stats_app(pool: SqlitePool) -> Router {
+        let mut state = test_state(pool);
+        state.auth_token = Some("secret".to_string());
+
+        Router::new()
+            .route("/{code}", get(redirect))
+            .route("/{code}/+", get(public_stats))
+            .with_state(state)
+    }
+
+    #[tokio::test]
+    async fn test_public_stats_without_token() {
+        let pool = setup_pool_with_link("pub").await;
+        insert_visit(
+            &pool,
+            &NewVisit {
+                code: "pub".to_string(),
+                visited_at: now_unix(),
+                ip: Some("203.0.113.7".to_string()),
+                country: Some("DE".to_string()),
+                user_agent: Some("Mozilla/5.0".to_string()),
+                device: Some("desktop".to_string()),
+                referer: Some("https://news.example".to_string()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let response = get_uri(public_stats_app(pool).await, "/pub/+").await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["code"], "pub");
+        assert_eq!(json["total_visits"], 1);
+        assert_eq!(json["daily"].as_array().unwrap().len(), 1);
+        assert_eq!(json["daily"][0]["count"], 1);
+
+        // Only aggregates are shared, never visitor details or the destination
+        let keys: Vec<&str> = json
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        assert_eq!(keys.len(), 3, "{:?}", keys);
+        let text = String::from_utf8(body.to_vec()).unwrap();
+        for private in ["203.0.113.7", "Mozilla", "news.example", "example.com"] {
+            assert!(!text.contains(private), "{}", private);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_public_stats_hides_missing_disabled_and_expired() {
+        let pool = setup_pool_with_link("off").await;
+        set_link_enabled(&pool, "off", false).await.unwrap();
+        insert_link(
+            &pool,
+            &NewLink {
+                code: "old",
+                original_url: "https://example.com",
+                expires_at: 1000000001,
+                created_at: 1000000000,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let app = public_stats_app(pool).await;
+        for uri in ["/nope/+", "/off/+", "/old/+"] {
+            let response = get_uri(app.clone(), uri).await;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND, "{}", uri);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_analytics_invalid_date_range() {
+        let pool = setup_pool_with_link("range").await;
+
+        let app = Router::new()
+            .route("/analytics/{code}", get(analytics))
+            .with_state(test_state(pool));
+
+        let response = get_uri(app.clone(), "/analytics/range?from=200&to=100").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = get_uri(app, "/analytics/range?from=100&to=200").await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    /// Places visitors from 203.0.113.0/24 in Germany and everyone else nowhere
+    struct FakeGeo;
+
+    impl crate::utils::GeoLookup for FakeGeo {
+        fn resolve(&self, ip: &str) -> (Option<String>, Option<String>) {
+            if ip.starts_with("203.0.113.") {
+                (Some("DE".to_string()), Some("Berlin".to_string()))
+            } else {
+                (None, None)
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_redirect_rules_with_geo_lookup() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        crate::database::run_migrations(&pool).await.unwrap();
+        let mut state = test_state(pool.clone());
+        state.geoip = Some(std::sync::Arc::new(FakeGeo));
+        state.trust_proxy = true;
+
+        let app = Router::new()
+            .route("/api/shorten", axum::routing::post(shorten_noauth))
+            .route("/{code}", get(redirect))
+            .with_state(state);
+
+        post_shorten(
+            app.clone(),
+            r#"{"url":"https://example.com","code":"geo","rules":{"countries":{"DE":"https://example.com/de"}}}"#,
+        )
+        .await;
+        post_shorten(
+            app.clone(),
+            r#"{"url":"https://example.com","code":"lang","rules":{"countries":{"DE":"https://example.com/de"},"languages":{"fr":"https://example.com/fr"}}}"#,
+        )
+        .await;
+
+        for (code, ip, accept_language, location, vary) in [
+            (
+                "geo",
+                "203.0.113.7",
+                "fr",
+                "https://example.com/de",
+                "accept",
+            ),
+            ("geo", "198.51.100.7", "fr", "https://example.com", "accept"),
+            (
+                "lang",
+                "203.0.113.7",
+                "fr",
+                "https://example.com/de",
+                "accept, accept-language",
+            ),
+            (
+                "lang",
+                "198.51.100.7",
+                "fr-CA",
+                "https://example.com/fr",
+                "accept, accept-language",
+            ),
+            (
+                "lang",
+                "198.51.100.7",
+                "en",
+                "https://example.com",
+                "accept, accept-language",
+            ),
+        ] {
+            let response = app
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .uri(format!("/{}", code))
+                        .header("x-forwarded-for", ip)
+                        .header("accept-language", accept_language)
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::FOUND);
+            assert_eq!(response.headers()[header::LOCATION], location);
+            assert_eq!(response.headers()[header::VARY], vary);
+        }
+
+        // The faked lookup also fills in the recorded visit
+        let visits = recent_visits(&pool, "geo", 10, 0).await.unwrap();
+        assert_eq!(visits.len(), 2);
+        assert!(visits
+            .iter()
+            .any(|v| v.country.as_deref() == Some("DE") && v.city.as_deref() == Some("Berlin")));
+    }
+
+    #[tokio::test]
+    async fn test_shorten_with_country_rules() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        crate::database::run_migrations(&pool).await.unwrap();
+
+        let app = Router::new()
+            .route("/api/shorten", axum::routing::post(shorten_noauth))
+            .route("/{code}", get(redirect))
+            .with_state(test_state(pool.clone()));
+
+        post_shorten(
+            app.clone(),
+            r#"{"url":"https://example.com","code":"geo","rules":{"countries":{"de":"https://example.com/de"}}}"#,
+        )
+        .await;
+
+        let rules = get_link(&pool, "geo")
+            .await
+            .unwrap()
+            .unwrap()
+            .rules()
+            .unwrap();
+        assert_eq!(
+            rules.destination_for(Some("DE"), None),
+            Some("https://example.com/de")
+        );
+
+        // Without GeoIP no visitor has a country, so everyone gets the default
+        let response = get_uri(app.clone(), "/geo").await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+        assert_eq!(response.headers()["location"], "https://example.com");
+
+        // Language rules still apply without GeoIP
+        post_shorten(
+            app.clone(),
+            r#"{"url":"https://example.com","code":"lang","rules":{"countries":{"DE":"https://example.com/de"},"languages":{"FR":"https://example.com/fr"}}}"#,
+        )
+        .await;
+        for (accept_language, location) in [
+            ("fr-CA,fr;q=0.9,en;q=0.8", "https://example.com/fr"),
+            ("de-DE", "https://example.com"),
+        ] {
+            let response = app
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .uri("/lang")
+                        .header("accept-language", accept_language)
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.headers()["location"], location);
+        }
+
+        for bad in [
+            r#"{"countries":{"Germany":"https://example.com/de"}}"#,
+            r#"{"countries":{"DE":"ftp://example.com/de"}}"#,
+            r#"{"languages":{"en-US":"https://example.com/en"}}"#,
+        ] {
+            let response = app
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .method("POST")
+                        .uri("/api/shorten")
+                        .header("content-type", "application/json")
+                        .body(Body::from(format!(
+                            r#"{{"url":"https://example.com","rules":{}}}"#,
+                            bad
+                        )))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", bad);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shorten_resolves_rule_destinations() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        crate::database::run_migrations(&pool).await.unwrap();
+        let mut state = test_state(pool);
+        state.resolver = Some(std::sync::Arc::new(FakeResolver));
+        let app = Router::new()
+            .route("/api/shorten", axum::routing::post(shorten_noauth))
+            .with_state(state);
+
+        for body in [
+            r#"{"url":"https://internal.example"}"#,
+            r#"{"url":"https://example.com","rules":{"countries":{"DE":"https://internal.example/de"}}}"#,
+            r#"{"url":"https://example.com","rules":{"languages":{"fr":"http://metadata.example/latest"}}}"#,
+        ] {
+            let response = app
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .method("POST")
+                        .uri("/api/shorten")
+                        .header("content-type", "application/json")
+                        .body(Body::from(body))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", body);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let error: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(error["code"], "invalid_url");
+        }
+
+        post_shorten(
+            app,
+            r#"{"url":"https://example.com","rules":{"countries":{"DE":"https://example.de"}}}"#,
+        )
+        .await;
+    }
+
+    async fn redirect_as(app: Router, user_agent: &str) -> StatusCode {
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/bots")
+                    .header("user-agent", user_agent)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        response.status()
+    }
+
+    #[tokio::test]
+    async fn test_redirect_skips_bot_visits() {
+        let pool = setup_pool_with_link("bots").await;
+
+        let app = Router::new()
+            .route("/{code}", get(redirect))
+            .with_state(test_state(pool.clone()));
+
+        let status = redirect_as(app.clone(), "Slackbot-LinkExpanding 1.0").await;
+        assert_eq!(status, StatusCode::FOUND);
+        assert_eq!(count_visits(&pool, "bots").await.unwrap(), 0);
+
+        redirect_as(
+            app,
+            "Mozilla/5.0 (X11; Linux x86_64; rv:121.0) Firefox/121.0",
+        )
+        .await;
+        assert_eq!(count_visits(&pool, "bots").await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_redirect_counts_bots_when_enabled() {
+        let pool = setup_pool_with_link("bots").await;
+
+        let mut state = test_state(pool.clone());
+        state.count_bots = true;
+
+        let app = Router::new()
+            .route("/{code}", get(redirect))
+            .with_state(state);
+
+        redirect_as(app, "Twitterbot/1.0").await;
+        assert_eq!(count_visits(&pool, "bots").await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_redirect_skips_configured_bot_user_agents() {
+        let pool = setup_pool_with_link("bots").await;
+
+        let mut state = test_state(pool.clone());
+        state.bot_user_agents.push("my-monitor/".to_string());
+
+        let app = Router::new()
+            .route("/{code}", get(redirect))
+            .with_state(state);
+
+        redirect_as(app, "My-Monitor/1.0 (+https://status.example)").await;
+        assert_eq!(count_visits(&pool, "bots").await.unwrap(), 0);
+    }
+
+    /// Visits `/ips` from 203.0.113.7 and returns what landed in the `ip` column
+    async fn stored_ip(ip_storage: IpStorage) -> Option<String> {
+        let pool = setup_pool_with_link("ips").await;
+
+        let mut state = test_state(pool.clone());
+        state.trust_proxy = true;
+        state.ip_storage = ip_storage;
+
+        let app = Router::new()
+            .route("/{code}", get(redirect))
+            .with_state(state);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/ips")
+                    .header("x-forwarded-for", "203.0.113.7")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FOUND);
+
+        let visits = recent_visits(&pool, "ips", 10, 0).await.unwrap();
+        assert_eq!(visits.len(), 1);
+        visits[0].ip.clone()
+    }
+
+    #[tokio::test]
+    async fn test_redirect_stores_raw_ip() {
+        assert_eq!(
+            stored_ip(IpStorage::Raw).await.as_deref(),
+            Some("203.0.113.7")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_redirect_stores_hashed_ip() {
+        let stored = stored_ip(IpStorage::Hashed).await.unwrap();
+        assert_eq!(stored, crate::utils::hash_ip("203.0.113.7", "test-salt"));
+        assert!(!stored.contains("203.0.113.7"));
+    }
+
+    #[tokio::test]
+    async fn test_redirect_discards_ip() {
+        assert_eq!(stored_ip(IpStorage::Discard).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_redirect_buffers_visits_until_flush() {
+        let pool = setup_pool_with_link("hot").await;
+
+        let buffer = crate::visits::VisitBuffer::default();
+        let mut state = test_state(pool.clone());
+        state.visit_buffer = Some(buffer.clone());
+
+        let app = Router::new()
+            .route("/{code}", get(redirect))
+            .with_state(state);
+
+        for _ in 0..3 {
+            let response = get_uri(app.clone(), "/hot").await;
+            assert_eq!(response.status(), StatusCode::FOUND);
+        }
+
+        // Redirects only queue their visits
+        assert_eq!(buffer.len().await, 3);
+        assert_eq!(count_visits(&pool, "hot").await.unwrap(), 0);
+
+        assert_eq!(buffer.flush(&pool).await.unwrap(), 3);
+        assert_eq!(count_visits(&pool, "hot").await.unwrap(), 3);
+    }
+
+    /// A router over `pool` whose redirects go through a link cache
+    fn cached_app(pool: SqlitePool) -> Router {
+        let mut state = test_state(pool);
+        state.link_cache = Some(crate::cache::LinkCache::new(100, 60));
+
+        Router::new()
+            .route("/{code}", get(redirect).delete(delete))
+            .route("/api/links/{code}/disable", axum::routing::post(disable))
+            .with_state(state)
+    }
+
+    #[tokio::test]
+    async fn test_cached_redirect_skips_database() {
+        let pool = setup_pool_with_link("hot").await;
+        let app = cached_app(pool.clone());
+
+        let response = get_uri(app.clone(), "/hot").await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+
+        // With the pool closed, only the cache can answer
+        pool.close().await;
+        let response = get_uri(app.clone(), "/hot").await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+        assert_eq!(response.headers()[header::LOCATION], "https://example.com");
+
+        // Codes that were never cached still need the database
+        let response = get_uri(app, "/cold").await;
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[tokio::test]
+    async fn test_cache_forgets_deleted_and_disabled_links() {
+        let pool = setup_pool_with_link("gone").await;
+        let app = cached_app(pool.clone());
+
+        let response = get_uri(app.clone(), "/gone").await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("DELETE")
+                    .uri("/gone")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = get_uri(app.clone(), "/gone").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let pool = setup_pool_with_link("off").await;
+        let app = cached_app(pool);
+
+        let response = get_uri(app.clone(), "/off").await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/links/off/disable")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = get_uri(app, "/off").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_info_does_not_record_visit() {
+        let pool = setup_pool_with_link("peek").await;
+
+        let app = Router::new()
+            .route("/api/info/{code}", get(info))
+            .with_state(test_state(pool.clone()));
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/info/peek")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(json["original_url"], "https://example.com");
+        assert_eq!(json["expired"], false);
+        assert_eq!(count_visits(&pool, "peek").await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_info_not_found() {
+        let pool = setup_pool_with_link("peek").await;
+
+        let app = Router::new()
+            .route("/api/info/{code}", get(info))
+            .with_state(test_state(pool));
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/info/missing")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    async fn redirect_status_for(redirect_status: RedirectStatus) -> StatusCode {
+        let pool = setup_pool_with_link("go").await;
+
+        let mut state = test_state(pool);
+        state.redirect_status = redirect_status;
+
+        let app = Router::new()
+            .route("/{code}", get(redirect))
+            .with_state(state);
+
+        let response = app
+            .oneshot(Request::builder().uri("/go").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(response.headers()["location"], "https://example.com");
+        response.status()
+    }
+
+    #[tokio::test]
+    async fn test_redirect_cache_control_no_store() {
+        let pool = setup_pool_with_link("go").await;
+
+        let app = Router::new()
+            .route("/{code}", get(redirect))
+            .with_state(test_state(pool));
+
+        let response = get_uri(app, "/go").await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+        assert_eq!(response.headers()["cache-control"], "no-store");
+    }
+
+    #[tokio::test]
+    async fn test_redirect_cache_control_max_age() {
+        let pool = setup_pool_with_link("go").await;
+
+        let mut state = test_state(pool);
+        state.redirect_cache_max_age = Some(300);
+
+        let app = Router::new()
+            .route("/{code}", get(redirect))
+            .with_state(state);
+
+        let response = get_uri(app, "/go").await;
+        assert_eq!(response.headers()["cache-control"], "private, max-age=300");
+        assert_eq!(response.headers()["location"], "https://example.com");
+    }
+
+    #[tokio::test]
+    async fn test_metrics_counts_redirects() {
+        let pool = setup_pool_with_link("go").await;
+        crate::database::insert_link(
+            &pool,
+            &NewLink {
+                code: "old",
+                original_url: "https://example.com",
+                expires_at: 1000000100,
+                created_at: 1000000000,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let mut state = test_state(pool);
+        state.metrics = Some(crate::metrics::test_handle());
+        state.link_cache = Some(crate::cache::LinkCache::new(100, 60));
+
+        let app = Router::new()
+            .route("/metrics", get(metrics))
+            .route("/{code}", get(redirect))
+            .with_state(state);
+
+        // The second visit is served from the link cache
+        get_uri(app.clone(), "/go").await;
+        get_uri(app.clone(), "/go").await;
+        get_uri(app.clone(), "/missing").await;
+        get_uri(app.clone(), "/old").await;
+
+        let response = get_uri(app, "/metrics").await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let output = String::from_utf8_lossy(&body);
+        assert!(output.contains(r#"cutl_redirects_total{result="hit"}"#));
+        assert!(output.contains(r#"cutl_redirects_total{result="miss"}"#));
+        assert!(output.contains(r#"cutl_redirects_total{result="expired"}"#));
+        assert!(output.contains(r#"cutl_link_cache_lookups_total{result="hit"}"#));
+        assert!(output.contains(r#"cutl_link_cache_lookups_total{result="miss"}"#));
+    }
+
+    #[tokio::test]
+    async fn test_healthz() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+
+        let app = Router::new()
+            .route("/healthz", get(healthz))
+            .with_state(test_state(pool.clone()));
+
+        let response = get_uri(app.clone(), "/healthz").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["status"], "ok");
+
+        // A closed pool stands in for an unreachable database
+        pool.close().await;
+        let response = get_uri(app, "/healthz").await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn test_metrics_disabled() {
+        let pool = setup_pool_with_link("go").await;
+
+        let app = Router::new()
+            .route("/metrics", get(metrics))
+            .with_state(test_state(pool));
+
+        let response = get_uri(app, "/metrics").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_redirect_status_temporary() {
+        assert_eq!(
+            redirect_status_for(RedirectStatus::Temporary).await,
+            StatusCode::FOUND
+        );
+    }
+
+    #[tokio::test]
+    async fn test_redirect_status_permanent() {
+        assert_eq!(
+            redirect_status_for(RedirectStatus::Permanent).await,
+            StatusCode::MOVED_PERMANENTLY
+        );
+    }
+
+    #[tokio::test]
+    async fn test_redirect_status_see_other() {
+        assert_eq!(
+            redirect_status_for(RedirectStatus::SeeOther).await,
+            StatusCode::SEE_OTHER
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rotate_moves_link_to_new_code() {
+        let pool = setup_pool_with_link("leaked").await;
+        insert_visit(
+            &pool,
+            &NewVisit {
+                code: "leaked".to_string(),
+                visited_at: 1000000050,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let mut state = test_state(pool.clone());
+        state.auth_token = Some("master".to_string());
+        let app = Router::new()
+            .route("/{code}", get(redirect))
+            .route("/api/links/{code}/rotate", axum::routing::post(rotate))
+            .with_state(state);
+
+        let (status, _) =
+            send_with_token(app.clone(), "POST", "/api/links/leaked/rotate", "nope", "").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (status, json) = send_with_token(
+            app.clone(),
+            "POST",
+            "/api/links/leaked/rotate",
+            "master",
+            "",
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let new_code = json["code"].as_str().unwrap().to_string();
+        assert_ne!(new_code, "leaked");
+        assert_eq!(
+            json["short_url"],
+            format!("http://localhost:3000/{}", new_code)
+        );
+
+        let response = get_uri(app.clone(), "/leaked").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = get_uri(app.clone(), &format!("/{}", new_code)).await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+        assert_eq!(response.headers()["location"], "https://example.com");
+
+        // The earlier visit moved along with the link, plus the one just made
+        assert_eq!(count_visits(&pool, &new_code).await.unwrap(), 2);
+
+        let (status, _) =
+            send_with_token(app, "POST", "/api/links/leaked/rotate", "master", "").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_rotate_can_drop_visits() {
+        let pool = setup_pool_with_link("leaked").await;
+        insert_visit(
+            &pool,
+            &NewVisit {
+                code: "leaked".to_string(),
+                visited_at: 1000000050,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let app = Router::new()
+            .route("/api/links/{code}/rotate", axum::routing::post(rotate))
+            .with_state(test_state(pool.clone()));
+
+        let (status, json) = send_with_token(
+            app,
+            "POST",
+            "/api/links/leaked/rotate?keep_visits=false",
+            "",
+            "",
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let new_code = json["code"].as_str().unwrap();
+        assert_eq!(count_visits(&pool, new_code).await.unwrap(), 0);
+    }
+
+    /// An app with `/analytics` and `/share` behind the master token `master`
+    async fn share_app() -> Router {
+        let pool = setup_pool_with_link("mine").await;
+        crate::database::insert_link(
+            &pool,
+            &NewLink {
+                code: "other",
+                original_url: "https://example.org",
+                expires_at: 9999999999,
+                created_at: 1000000000,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let mut state = test_state(pool);
+        state.auth_token = Some("master".to_string());
+        Router::new()
+            .route("/analytics/{code}", get(analytics))
+            .route("/api/links/{code}/share", axum::routing::post(share))
+            .with_state(state)
+    }
+
+    #[tokio::test]
+    async fn test_share_token_grants_analytics_access() {
+        let app = share_app().await;
+
+        let (status, _) =
+            send_with_token(app.clone(), "POST", "/api/links/mine/share", "nope", "").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (status, json) = send_with_token(
+            app.clone(),
+            "POST",
+            "/api/links/mine/share?ttl=1h",
+            "master",
+            "",
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["code"], "mine");
+        let expires_at = json["expires_at"].as_i64().unwrap();
+        assert!(expires_at > now_unix() && expires_at <= now_unix() + 3600);
+        let token = json["token"].as_str().unwrap().to_string();
+
+        let (status, json) =
+            send_with_token(app.clone(), "GET", "/analytics/mine", &token, "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["code"], "mine");
+
+        // The master token still works alongside it
+        let (status, _) =
+            send_with_token(app.clone(), "GET", "/analytics/mine", "master", "").await;
+        assert_eq!(status, StatusCode::OK);
+
+        // Read-only: it can't mint further tokens
+        let (status, _) = send_with_token(app, "POST", "/api/links/mine/share", &token, "").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_share_token_rejected_for_other_code() {
+        let app = share_app().await;
+
+        let (_, json) =
+            send_with_token(app.clone(), "POST", "/api/links/mine/share", "master", "").await;
+        let token = json["token"].as_str().unwrap().to_string();
+
+        let (status, json) =
+            send_with_token(app.clone(), "GET", "/analytics/other", &token, "").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(json["code"], "unauthorized");
+
+        let (status, _) =
+            send_with_token(app.clone(), "GET", "/analytics/mine", "not-a-token", "").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (status, json) =
+            send_with_token(app, "POST", "/api/links/mine/share?ttl=soon", "master", "").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(json["code"], "invalid_ttl");
+    }
+
+    #[tokio::test]
+    async fn test_link_title_and_description() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        crate::database::run_migrations(&pool).await.unwrap();
+
+        let app = Router::new()
+            .route("/api/shorten", axum::routing::post(shorten_noauth))
+            .route("/api/info/{code}", get(info))
+            .with_state(test_state(pool));
+
+        post_shorten(
+            app.clone(),
+            r#"{"url":"https://example.com","code":"docs","title":"  Team docs ","description":"Where we keep everything"}"#,
+        )
+        .await;
+        post_shorten(
+            app.clone(),
+            r#"{"url":"https://example.com","code":"bare","title":"   "}"#,
+        )
+        .await;
+
+        let response = get_uri(app.clone(), "/api/info/docs").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["title"], "Team docs");
+        assert_eq!(json["description"], "Where we keep everything");
+
+        // Blank text is stored as no title at all
+        let response = get_uri(app.clone(), "/api/info/bare").await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert!(json.get("title").is_none());
+        assert!(json.get("description").is_none());
+
+        let long_title = "x".repeat(MAX_TITLE_LENGTH + 1);
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/shorten")
+                    .header("content-type", "application/json")
+                    .body(Body::from(
+                        serde_json::json!({"url": "https://example.com", "title": long_title})
+                            .to_string(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_link_text_limits() {
+        assert_eq!(link_text(None, 5), Ok(None));
+        assert_eq!(link_text(Some(" \t"), 5), Ok(None));
+        assert_eq!(link_text(Some(" héllo "), 5), Ok(Some("héllo".to_string())));
+        assert!(link_text(Some("héllo!"), 5).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_per_link_redirect_status() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        crate::database::run_migrations(&pool).await.unwrap();
+
+        let mut state = test_state(pool);
+        state.redirect_status = RedirectStatus::SeeOther;
+
+        let app = Router::new()
+            .route("/api/shorten", axum::routing::post(shorten_noauth))
+            .route("/{code}", get(redirect).head(redirect_head))
+            .with_state(state);
+
+        post_shorten(
+            app.clone(),
+            r#"{"url":"https://example.com","code":"seo","redirect_status":301}"#,
+        )
+        .await;
+        post_shorten(
+            app.clone(),
+            r#"{"url":"https://example.com","code":"plain"}"#,
+        )
+        .await;
+
+        let response = get_uri(app.clone(), "/seo").await;
+        assert_eq!(response.status(), StatusCode::MOVED_PERMANENTLY);
+        assert_eq!(response.headers()["location"], "https://example.com");
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("HEAD")
+                    .uri("/seo")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::MOVED_PERMANENTLY);
+
+        // Links without an override fall back to REDIRECT_STATUS
+        let response = get_uri(app, "/plain").await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    }
+
+    #[tokio::test]
+    async fn test_shorten_rejects_invalid_redirect_status() {
+        let app = setup_shorten_app(false).await;
+
+        for status in ["200", "303", "404", "999"] {
+            let response = app
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .method("POST")
+                        .uri("/api/shorten")
+                        .header("content-type", "application/json")
+                        .body(Body::from(format!(
+                            r#"{{"url":"https://example.com","redirect_status":{}}}"#,
+                            status
+                        )))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", status);
+
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(json["code"], "invalid_redirect_status");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shorten_uses_configured_ttl_limits() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        crate::database::run_migrations(&pool).await.unwrap();
+
+        let mut state = test_state(pool);
+        state.ttl_limits = TtlLimits {
+            min_seconds: 60,
+            max_seconds: 365 * 24 * 60 * 60,
+            default_seconds: 24 * 60 * 60,
+        };
+
+        let app = Router::new()
+            .route("/api/shorten", axum::routing::post(shorten_noauth))
+            .with_state(state);
+
+        let before = now_unix();
+        let json = post_shorten(app.clone(), r#"{"url":"https://example.com"}"#).await;
+        let expires_at = json["expires_at"].as_i64().unwrap();
+        assert!(expires_at >= before + 24 * 60 * 60);
+        assert!(expires_at <= now_unix() + 24 * 60 * 60);
+
+        post_shorten(app.clone(), r#"{"url":"https://example.com","ttl":"52w"}"#).await;
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/shorten")
+                    .header("content-type", "application/json")
+                    .body(Body::from(r#"{"url":"https://example.com","ttl":"53w"}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    async fn post_batch(pool: SqlitePool, body: &str) -> (StatusCode, serde_json::Value) {
+        let mut state = test_state(pool);
+        state.max_batch_size = 3;
+
+        let app = Router::new()
+            .route("/api/shorten/batch", axum::routing::post(shorten_batch))
+            .with_state(state);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/shorten/batch")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_shorten_batch_partial_success() {
+        let pool = setup_pool_with_link("taken").await;
+
+        let (status, json) = post_batch(
+            pool.clone(),
+            r#"{"links":[
+                {"url":"https://example.com/a","code":"a"},
+                {"url":"ftp://example.com"},
+                {"url":"https://example.com/b","code":"taken"}
+            ]}"#,
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["created"], 1);
+        assert_eq!(json["results"][0]["code"], "a");
+        assert_eq!(json["results"][1]["index"], 1);
+        assert_eq!(json["results"][1]["code"], "invalid_url");
+        assert!(json["results"][1]["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("Invalid URL"));
+        assert_eq!(json["results"][2]["index"], 2);
+        assert_eq!(json["results"][2]["code"], "code_exists");
+        assert!(code_exists(&pool, "a", false).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_shorten_batch_atomic_rolls_back() {
+        let pool = setup_pool_with_link("taken").await;
+
+        let (status, json) = post_batch(
+            pool.clone(),
+            r#"{"atomic":true,"links":[
+                {"url":"https://example.com/a","code":"a"},
+                {"url":"https://example.com/b","code":"taken"}
+            ]}"#,
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["created"], 0);
+        assert_eq!(json["results"][0]["index"], 0);
+        assert_eq!(json["results"][1]["index"], 1);
+        assert!(!code_exists(&pool, "a", false).await.unwrap());
+
+        // An item failing validation stops the batch before anything is inserted
+        let (status, json) = post_batch(
+            pool.clone(),
+            r#"{"atomic":true,"links":[
+                {"url":"https://example.com/a","code":"a"},
+                {"url":"ftp://example.com"}
+            ]}"#,
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["created"], 0);
+        assert_eq!(json["results"][0]["code"], "batch_aborted");
+        assert!(json["results"][0]["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("Not created"));
+        assert!(json["results"][1]["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("Invalid URL"));
+        assert!(!code_exists(&pool, "a", false).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_shorten_batch_atomic_success() {
+        let pool = setup_pool_with_link("taken").await;
+
+        let (status, json) = post_batch(
+            pool.clone(),
+            r#"{"atomic":true,"links":[
+                {"url":"https://example.com/a","code":"a"},
+                {"url":"https://example.com/b"}
+            ]}"#,
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["created"], 2);
+        assert!(code_exists(&pool, "a", false).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_shorten_batch_size_limits() {
+        let pool = setup_pool_with_link("taken").await;
+
+        let (status, _) = post_batch(pool.clone(), r#"{"links":[]}"#).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _) = post_batch(
+            pool,
+            r#"{"links":[
+                {"url":"https://example.com/1"},
+                {"url":"https://example.com/2"},
+                {"url":"https://example.com/3"},
+                {"url":"https://example.com/4"}
+            ]}"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    async fn setup_owner_app() -> Router {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        crate::database::run_migrations(&pool).await.unwrap();
+
+        let mut state = test_state(pool);
+        state.auth_token = Some("master".to_string());
+        state.api_tokens = vec![
+            ApiToken {
+                owner: "alice".to_string(),
+                token: "alice-token".to_string(),
+            },
+            ApiToken {
+                owner: "bob".to_string(),
+                token: "bob-token".to_string(),
+            },
+        ];
+
+        Router::new()
+            .route("/shorten", axum::routing::post(shorten))
+            .route("/api/links", get(list_links))
+            .route("/{code}", axum::routing::patch(update).delete(delete))
+            .route("/api/info/{code}", get(info))
+            .route("/analytics/{code}", get(analytics))
+            .route("/analytics/{code}/export.csv", get(export_visits))
+            .route("/api/links/{code}/extend", axum::routing::post(extend))
+            .route("/api/links/{code}/disable", axum::routing::post(disable))
+            .route("/api/links/{code}/rotate", axum::routing::post(rotate))
+            .route("/api/links/{code}/share", axum::routing::post(share))
+            .with_state(state)
+    }
+
+    async fn send_with_token(
+        app: Router,
+        method: &str,
+        uri: &str,
+        token: &str,
+        body: &str,
+    ) -> (StatusCode, serde_json::Value) {
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method(method)
+                    .uri(uri)
+                    .header("authorization", format!("Bearer {}", token))
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    async fn listed_codes(app: Router, token: &str) -> Vec<String> {
+        let (status, body) = send_with_token(app, "GET", "/api/links", token, "").await;
+        assert_eq!(status, StatusCode::OK);
+
+        body["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["code"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_list_links_disjoint_per_token() {
+        let app = setup_owner_app().await;
+
+        for (token, code) in [
+            ("alice-token", "alice1"),
+            ("alice-token", "alice2"),
+            ("bob-token", "bob1"),
+            ("master", "admin1"),
+        ] {
+            let body = format!(r#"{{"url":"https://example.com","code":"{}"}}"#, code);
+            let (status, _) = send_with_token(app.clone(), "POST", "/shorten", token, &body).await;
+            assert_eq!(status, StatusCode::OK);
+        }
+
+        let mut alice = listed_codes(app.clone(), "alice-token").await;
+        alice.sort();
+        assert_eq!(alice, ["alice1", "alice2"]);
+        assert_eq!(listed_codes(app.clone(), "bob-token").await, ["bob1"]);
+        assert_eq!(listed_codes(app, "master").await, ["admin1"]);
+    }
+
+    #[tokio::test]
+    async fn test_tokens_cannot_touch_other_owners_links() {
+        let app = setup_owner_app().await;
+        let (status, _) = send_with_token(
+            app.clone(),
+            "POST",
+            "/shorten",
+            "alice-token",
+            r#"{"url":"https://example.com","code":"alice1"}"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        for (method, uri, body) in [
+            ("GET", "/api/info/alice1", ""),
+            ("GET", "/analytics/alice1", ""),
+            ("GET", "/analytics/alice1/export.csv", ""),
+            ("PATCH", "/alice1", r#"{"url":"https://example.org"}"#),
+            ("POST", "/api/links/alice1/extend", r#"{"ttl":"1d"}"#),
+            ("POST", "/api/links/alice1/disable", ""),
+            ("POST", "/api/links/alice1/rotate", ""),
+            ("POST", "/api/links/alice1/share", ""),
+            ("DELETE", "/alice1", ""),
+        ] {
+            let (status, _) = send_with_token(app.clone(), method, uri, "bob-token", body).await;
+            assert_eq!(status, StatusCode::NOT_FOUND, "{} {}", method, uri);
+        }
+
+        // The link is untouched, and still visible to its owner and the admin
+        for token in ["alice-token", "master"] {
+            let (status, json) =
+                send_with_token(app.clone(), "GET", "/api/info/alice1", token, "").await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(json["original_url"], "https://example.com");
+            assert_eq!(json["enabled"], true);
+        }
+
+        let (status, _) =
+            send_with_token(app.clone(), "DELETE", "/alice1", "alice-token", "").await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_stats_counts_links_and_visits() {
+        let pool = setup_pool_with_link("live").await;
+        for (code, expires_at) in [("later", 9999999999), ("gone", 1000000100)] {
+            insert_link(
+                &pool,
+                &NewLink {
+                    code,
+                    original_url: "https://example.com",
+                    expires_at,
+                    created_at: 1000000000,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        }
+        for code in ["live", "live", "gone"] {
+            insert_visit(
+                &pool,
+                &NewVisit {
+                    code: code.to_string(),
+                    visited_at: 1000000050,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        }
+
+        let mut state = test_state(pool);
+        state.auth_token = Some("master".to_string());
+        let app = Router::new()
+            .route("/api/stats", get(stats))
+            .with_state(state);
+
+        let (status, json) = send_with_token(app.clone(), "GET", "/api/stats", "master", "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "total_links": 3,
+                "active_links": 2,
+                "expired_links": 1,
+                "total_visits": 3,
+                "redirects": { "hit": 0, "miss": 0, "expired": 0 },
+            })
+        );
+
+        let (status, _) = send_with_token(app, "GET", "/api/stats", "nope", "").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_stats_counts_redirect_lookups() {
+        let pool = setup_pool_with_link("live").await;
+        insert_link(
+            &pool,
+            &NewLink {
+                code: "old",
+                original_url: "https://example.com",
+                expires_at: 1000000100,
+                created_at: 1000000000,
+                ..Default::default()
+            },
+        )
         .await
-        .map_err(|e| ApiError::internal(format!("Database error: {}", e)))?
-        .into_iter()
-        .map(|(value, count)| CountStat { value, count })
-        .collect();
+        .unwrap();
+
+        let app = Router::new()
+            .route("/{code}", get(redirect))
+            .route("/api/stats", get(stats))
+            .with_state(test_state(pool));
+
+        for (uri, status) in [
+            ("/live", StatusCode::FOUND),
+            ("/missing", StatusCode::NOT_FOUND),
+            ("/live", StatusCode::FOUND),
+            ("/old", StatusCode::NOT_FOUND),
+            // The expired link was deleted on the first visit
+            ("/old", StatusCode::NOT_FOUND),
+            ("/live", StatusCode::FOUND),
+        ] {
+            assert_eq!(get_uri(app.clone(), uri).await.status(), status, "{}", uri);
+        }
+
+        let response = get_uri(app, "/api/stats").await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            json["redirects"],
+            serde_json::json!({ "hit": 3, "miss": 2, "expired": 1 })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_list_links_rejects_unknown_token() {
+        let app = setup_owner_app().await;
+
+        let (status, _) = send_with_token(app, "GET", "/api/links", "nope", "").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_dedup_does_not_share_links_between_tokens() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        crate::database::run_migrations(&pool).await.unwrap();
+        let mut state = test_state(pool);
+        state.dedup_urls = true;
+
+        let request = || serde_json::from_str(r#"{"url":"https://example.com"}"#).unwrap();
+
+        let alice = create_link(&state, "http://localhost:3000", request(), Some("alice"))
+            .await
+            .unwrap();
+        let bob = create_link(&state, "http://localhost:3000", request(), Some("bob"))
+            .await
+            .unwrap();
+
+        assert_ne!(alice.code, bob.code);
+    }
+
+    #[tokio::test]
+    async fn test_list_links_search_and_total() {
+        let app = setup_owner_app().await;
+
+        for code in ["docs1", "docs2", "docs3", "blog1"] {
+            let url = if code.starts_with("docs") {
+                "https://docs.rs"
+            } else {
+                "https://example.com"
+            };
+            let body = format!(r#"{{"url":"{}","code":"{}"}}"#, url, code);
+            let (status, _) =
+                send_with_token(app.clone(), "POST", "/shorten", "alice-token", &body).await;
+            assert_eq!(status, StatusCode::OK);
+        }
+
+        let (status, body) = send_with_token(
+            app.clone(),
+            "GET",
+            "/api/links?q=docs.rs&limit=2",
+            "alice-token",
+            "",
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["items"].as_array().unwrap().len(), 2);
+        assert_eq!(body["total"], 3);
+
+        let (_, body) = send_with_token(
+            app.clone(),
+            "GET",
+            "/api/links?q=docs.rs&limit=2&offset=2",
+            "alice-token",
+            "",
+        )
+        .await;
+        assert_eq!(body["items"].as_array().unwrap().len(), 1);
+
+        // An empty query lists everything
+        let (_, body) = send_with_token(app, "GET", "/api/links?q=", "alice-token", "").await;
+        assert_eq!(body["total"], 4);
+    }
+
+    #[tokio::test]
+    async fn test_export_visits_csv() {
+        let pool = setup_pool_with_link("export").await;
+        crate::database::insert_visit(
+            &pool,
+            &NewVisit {
+                code: "export".to_string(),
+                visited_at: 1739000000,
+                ip: Some("1.2.3.4".to_string()),
+                country: Some("ID".to_string()),
+                city: Some("Jakarta".to_string()),
+                user_agent: Some("curl/8.0".to_string()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let app = Router::new()
+            .route("/analytics/{code}/export.csv", get(export_visits))
+            .with_state(test_state(pool));
+
+        let response = get_uri(app.clone(), "/analytics/export/export.csv").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()["content-type"],
+            "text/csv; charset=utf-8"
+        );
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let csv = String::from_utf8(body.to_vec()).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next(),
+            Some("visited_at,ip,country,city,user_agent,referer")
+        );
+        assert_eq!(
+            lines.next(),
+            Some("1739000000,1.2.3.4,ID,Jakarta,curl/8.0,")
+        );
+        assert_eq!(lines.next(), None);
+
+        let response = get_uri(app, "/analytics/missing/export.csv").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_redirect_appends_query() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        crate::database::run_migrations(&pool).await.unwrap();
+        let app = Router::new()
+            .route("/api/shorten", axum::routing::post(shorten_noauth))
+            .route("/{code}", get(redirect))
+            .with_state(test_state(pool));
+
+        post_shorten(
+            app.clone(),
+            r#"{"url":"https://example.com/page?ref=1#top","code":"promo","append_query":"?utm_source=flyer&utm_medium=print"}"#,
+        )
+        .await;
+
+        let response = get_uri(app, "/promo").await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+        assert_eq!(
+            response.headers()["location"],
+            "https://example.com/page?ref=1&utm_source=flyer&utm_medium=print#top"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_shorten_dedup_matches_normalized_urls() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        crate::database::run_migrations(&pool).await.unwrap();
+        let mut state = test_state(pool.clone());
+        state.dedup_urls = true;
+        state.url_normalization = UrlNormalization::Basic;
+
+        let request =
+            |url: &str| serde_json::from_value(serde_json::json!({ "url": url })).unwrap();
+
+        let first = create_link(
+            &state,
+            "http://localhost:3000",
+            request("https://Example.com:443/"),
+            None,
+        )
+        .await
+        .unwrap();
+        let second = create_link(
+            &state,
+            "http://localhost:3000",
+            request("https://example.com"),
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(first.code, second.code);
+
+        let link = get_link(&pool, &first.code).await.unwrap().unwrap();
+        assert_eq!(link.original_url, "https://example.com/");
+    }
+
+    #[tokio::test]
+    async fn test_shorten_uses_configured_code_settings() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        crate::database::run_migrations(&pool).await.unwrap();
+        let mut state = test_state(pool);
+        state.code_settings = CodeSettings {
+            min_length: 10,
+            max_length: 10,
+            alphabet: crate::utils::CodeAlphabet::Lowercase,
+            ..Default::default()
+        };
+
+        let request = || serde_json::from_str(r#"{"url":"https://example.com"}"#).unwrap();
+        let response = create_link(&state, "http://localhost:3000", request(), None)
+            .await
+            .unwrap();
+
+        assert_eq!(response.code.len(), 10);
+        assert!(response
+            .code
+            .chars()
+            .all(|c| c.is_ascii_digit() || c.is_ascii_lowercase()));
+    }
+
+    #[tokio::test]
+    async fn test_generate_unique_code_falls_back_to_longer_code() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        crate::database::run_migrations(&pool).await.unwrap();
+
+        // Occupy every one-character lowercase code so each attempt collides
+        let settings = CodeSettings {
+            min_length: 1,
+            max_length: 1,
+            alphabet: crate::utils::CodeAlphabet::Lowercase,
+            max_attempts: 5,
+            ..Default::default()
+        };
+        for &c in settings.alphabet.chars() {
+            crate::database::insert_link(
+                &pool,
+                &NewLink {
+                    code: &(c as char).to_string(),
+                    original_url: "https://example.com",
+                    expires_at: 9999999999,
+                    created_at: 1000000000,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        }
+
+        let mut conn = pool.acquire().await.unwrap();
+        let code = generate_unique_code(&mut conn, &settings, &[], false)
+            .await
+            .unwrap();
+        assert_eq!(code.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_generate_unique_code_exhausted_is_retriable() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        crate::database::run_migrations(&pool).await.unwrap();
+
+        // Codes can't grow past the maximum length, so exhaustion surfaces as 503
+        let settings = CodeSettings {
+            min_length: MAX_CODE_LENGTH,
+            max_length: MAX_CODE_LENGTH,
+            max_attempts: 0,
+            ..Default::default()
+        };
+
+        let mut conn = pool.acquire().await.unwrap();
+        let err = generate_unique_code(&mut conn, &settings, &[], false)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status, StatusCode::SERVICE_UNAVAILABLE);
+        assert!(err.message.contains("retry"));
+    }
+
+    async fn setup_case_app(case_insensitive_codes: bool) -> Router {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        crate::database::run_migrations(&pool).await.unwrap();
+        crate::database::enforce_code_case(&pool, case_insensitive_codes)
+            .await
+            .unwrap();
+        let mut state = test_state(pool);
+        state.case_insensitive_codes = case_insensitive_codes;
+
+        Router::new()
+            .route("/api/shorten", axum::routing::post(shorten_noauth))
+            .route("/{code}", get(redirect))
+            .with_state(state)
+    }
+
+    #[tokio::test]
+    async fn test_case_insensitive_codes_redirect() {
+        let app = setup_case_app(true).await;
+        post_shorten(
+            app.clone(),
+            r#"{"url":"https://example.com","code":"Docs"}"#,
+        )
+        .await;
+
+        for uri in ["/Docs", "/docs", "/DOCS"] {
+            let response = get_uri(app.clone(), uri).await;
+            assert_eq!(response.status(), StatusCode::FOUND, "{}", uri);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_case_insensitive_codes_prevent_collisions() {
+        let app = setup_case_app(true).await;
+        post_shorten(
+            app.clone(),
+            r#"{"url":"https://example.com","code":"Docs"}"#,
+        )
+        .await;
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/shorten")
+                    .header("content-type", "application/json")
+                    .body(Body::from(r#"{"url":"https://example.com","code":"docs"}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn test_shorten_maps_code_race_to_conflict() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        crate::database::run_migrations(&pool).await.unwrap();
+        crate::database::enforce_code_case(&pool, true)
+            .await
+            .unwrap();
+        // With the handler's own check case-sensitive, only the database sees the
+        // clash, as when two requests race past the check together
+        let app = Router::new()
+            .route("/api/shorten", axum::routing::post(shorten_noauth))
+            .with_state(test_state(pool));
+        post_shorten(
+            app.clone(),
+            r#"{"url":"https://example.com","code":"Docs"}"#,
+        )
+        .await;
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/shorten")
+                    .header("content-type", "application/json")
+                    .body(Body::from(r#"{"url":"https://example.com","code":"docs"}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let error: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(error["code"], "code_exists");
+    }
+
+    #[tokio::test]
+    async fn test_codes_case_sensitive_by_default() {
+        let app = setup_case_app(false).await;
+        post_shorten(
+            app.clone(),
+            r#"{"url":"https://example.com","code":"Docs"}"#,
+        )
+        .await;
+        post_shorten(
+            app.clone(),
+            r#"{"url":"https://example.org","code":"docs"}"#,
+        )
+        .await;
+
+        let response = get_uri(app.clone(), "/DOCS").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = get_uri(app, "/docs").await;
+        assert_eq!(response.headers()["location"], "https://example.org");
+    }
+
+    #[tokio::test]
+    async fn test_shorten_rejects_reserved_codes() {
+        let app = setup_shorten_app(false).await;
+
+        for code in ["api", "Metrics", "shorten"] {
+            let body = format!(r#"{{"url":"https://example.com","code":"{}"}}"#, code);
+            let response = app
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .method("POST")
+                        .uri("/api/shorten")
+                        .header("content-type", "application/json")
+                        .body(Body::from(body))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", code);
+        }
+
+        let created = post_shorten(app, r#"{"url":"https://example.com","code":"apis"}"#).await;
+        assert_eq!(created["code"], "apis");
+    }
+
+    async fn post_uri(app: Router, uri: &str) -> axum::response::Response {
+        app.oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(uri)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_disable_and_enable_link() {
+        let pool = setup_pool_with_link("paused").await;
+
+        let app = Router::new()
+            .route("/{code}", get(redirect))
+            .route("/api/links/{code}/disable", axum::routing::post(disable))
+            .route("/api/links/{code}/enable", axum::routing::post(enable))
+            .with_state(test_state(pool.clone()));
+
+        let response = post_uri(app.clone(), "/api/links/paused/disable").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["enabled"], false);
+
+        // A disabled link looks missing and records no visit
+        let response = get_uri(app.clone(), "/paused").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(count_visits(&pool, "paused").await.unwrap(), 0);
+
+        let response = post_uri(app.clone(), "/api/links/paused/enable").await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = get_uri(app, "/paused").await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+        assert_eq!(count_visits(&pool, "paused").await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_disable_not_found() {
+        let app = Router::new()
+            .route("/api/links/{code}/disable", axum::routing::post(disable))
+            .with_state(test_state(setup_pool_with_link("other").await));
+
+        let response = post_uri(app, "/api/links/missing/disable").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_disable_requires_auth() {
+        let pool = setup_pool_with_link("guarded").await;
+        let mut state = test_state(pool.clone());
+        state.auth_token = Some("secret".to_string());
+
+        let app = Router::new()
+            .route("/api/links/{code}/disable", axum::routing::post(disable))
+            .with_state(state);
+
+        let response = post_uri(app, "/api/links/guarded/disable").await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(get_link(&pool, "guarded").await.unwrap().unwrap().enabled);
+    }
+
+    #[tokio::test]
+    async fn test_head_redirect_does_not_record_visit() {
+        let pool = setup_pool_with_link("probe").await;
+
+        let app = Router::new()
+            .route("/{code}", get(redirect).head(redirect_head))
+            .with_state(test_state(pool.clone()));
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("HEAD")
+                    .uri("/probe")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::FOUND);
+        assert_eq!(
+            response.headers().get(header::LOCATION).unwrap(),
+            "https://example.com"
+        );
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(body.is_empty());
+        assert_eq!(count_visits(&pool, "probe").await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_head_redirect_keeps_remaining_visits() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        crate::database::run_migrations(&pool).await.unwrap();
+        crate::database::insert_link(
+            &pool,
+            &NewLink {
+                code: "once",
+                original_url: "https://example.com",
+                expires_at: 9999999999,
+                created_at: 1000000000,
+                max_visits: Some(1),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let app = Router::new()
+            .route("/{code}", get(redirect).head(redirect_head))
+            .with_state(test_state(pool.clone()));
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("HEAD")
+                    .uri("/once")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
 
-    let referers = visits_by_referer(&state.db, &code)
-        .await
-        .map_err(|e| ApiError::internal(format!("Database error: {}", e)))?
-        .into_iter()
-        .map(|(value, count)| CountStat { value, count })
-        .collect();
+        assert_eq!(response.status(), StatusCode::FOUND);
+        let link = get_link(&pool, "once").await.unwrap().unwrap();
+        assert_eq!(link.remaining_visits, Some(1));
+    }
 
-    let daily = visits_daily(&state.db, &code)
+    async fn get_with_accept(app: Router, uri: &str, accept: &str) -> axum::response::Response {
+        app.oneshot(
+            Request::builder()
+                .uri(uri)
+                .header(header::ACCEPT, accept)
+                .body(Body::empty())
+                .unwrap(),
+        )
         .await
-        .map_err(|e| ApiError::internal(format!("Database error: {}", e)))?
-        .into_iter()
-        .map(|(date, count)| DailyStat { date, count })
-        .collect();
+        .unwrap()
+    }
 
-    let recent = recent_visits(&state.db, &code)
-        .await
-        .map_err(|e| ApiError::internal(format!("Database error: {}", e)))?;
+    #[tokio::test]
+    async fn test_redirect_json_returns_metadata_without_visit() {
+        let pool = setup_pool_with_link("meta").await;
 
-    Ok(Json(AnalyticsResponse {
-        code: link.code,
-        original_url: link.original_url,
-        created_at: link.created_at,
-        expires_at: link.expires_at,
-        total_visits,
-        countries,
-        referers,
-        daily,
-        recent_visits: recent,
-    }))
-}
+        let app = Router::new()
+            .route("/{code}", get(redirect))
+            .with_state(test_state(pool.clone()));
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use axum::{
-        body::Body,
-        http::{Request, StatusCode},
-        routing::get,
-        Router,
-    };
-    use sqlx::sqlite::SqlitePool;
-    use tower::ServiceExt;
+        let response = get_with_accept(app, "/meta", "application/json").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get(header::LOCATION).is_none());
 
-    async fn setup_app() -> Router {
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["code"], "meta");
+        assert_eq!(json["original_url"], "https://example.com");
+        assert_eq!(json["expired"], false);
+
+        assert_eq!(count_visits(&pool, "meta").await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_redirect_browser_accept_records_visit() {
+        let pool = setup_pool_with_link("page").await;
+
+        let app = Router::new()
+            .route("/{code}", get(redirect))
+            .with_state(test_state(pool.clone()));
+
+        let response = get_with_accept(
+            app,
+            "/page",
+            "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8",
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+        assert_eq!(
+            response.headers().get(header::LOCATION).unwrap(),
+            "https://example.com"
+        );
+        assert_eq!(response.headers().get(header::VARY).unwrap(), "accept");
+
+        assert_eq!(count_visits(&pool, "page").await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_redirect_json_protected_link_hides_destination() {
+        let (app, _pool) = setup_protected_link().await;
+
+        let response = get_with_accept(app, "/locked", "application/json").await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(!String::from_utf8_lossy(&body).contains("example.com"));
+    }
+
+    async fn setup_cleanup_pool() -> SqlitePool {
         let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
         crate::database::run_migrations(&pool).await.unwrap();
 
-        let state = AppState {
-            db: pool,
-            base_url: "http://localhost:3000".to_string(),
-            auth_token: None,
-            geoip: None,
+        let now = now_unix();
+        let links = [
+            ("expired", now - 100, now - 1000),
+            ("unused", now + 10000, now - 40 * SECONDS_PER_DAY),
+            ("visited", now + 10000, now - 40 * SECONDS_PER_DAY),
+            ("recent", now + 10000, now - SECONDS_PER_DAY),
+        ];
+        for (code, expires_at, created_at) in links {
+            crate::database::insert_link(
+                &pool,
+                &NewLink {
+                    code,
+                    original_url: "https://example.com",
+                    expires_at,
+                    created_at,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        }
+        crate::database::insert_visit(
+            &pool,
+            &NewVisit {
+                code: "visited".to_string(),
+                visited_at: now,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        pool
+    }
+
+    /// A state whose master token is `master`, for the admin-only routes
+    fn admin_state(pool: SqlitePool) -> AppState {
+        let mut state = test_state(pool);
+        state.auth_token = Some("master".to_string());
+        state
+    }
+
+    async fn cleanup_json(app: Router, uri: &str) -> serde_json::Value {
+        let (status, json) = send_with_token(app, "POST", uri, "master", "").await;
+        assert_eq!(status, StatusCode::OK);
+        json
+    }
+
+    #[tokio::test]
+    async fn test_admin_cleanup_expired_only() {
+        let pool = setup_cleanup_pool().await;
+        let app = Router::new()
+            .route("/api/admin/cleanup", axum::routing::post(admin_cleanup))
+            .with_state(admin_state(pool.clone()));
+
+        let json = cleanup_json(app, "/api/admin/cleanup").await;
+        assert_eq!(json["expired_deleted"], 1);
+        assert_eq!(json["unused_deleted"], 0);
+
+        assert!(get_link(&pool, "expired").await.unwrap().is_none());
+        assert!(get_link(&pool, "unused").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_admin_cleanup_unused_links() {
+        let pool = setup_cleanup_pool().await;
+        let app = Router::new()
+            .route("/api/admin/cleanup", axum::routing::post(admin_cleanup))
+            .with_state(admin_state(pool.clone()));
+
+        let json = cleanup_json(app, "/api/admin/cleanup?unused_days=30").await;
+        assert_eq!(json["expired_deleted"], 1);
+        assert_eq!(json["unused_deleted"], 1);
+
+        assert!(get_link(&pool, "unused").await.unwrap().is_none());
+        assert!(get_link(&pool, "visited").await.unwrap().is_some());
+        assert!(get_link(&pool, "recent").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_admin_cleanup_requires_admin_token() {
+        let pool = setup_cleanup_pool().await;
+        let mut state = test_state(pool.clone());
+        state.auth_token = Some("secret".to_string());
+        state.api_tokens = vec![ApiToken {
+            owner: "alice".to_string(),
+            token: "alice-token".to_string(),
+        }];
+
+        let app = Router::new()
+            .route("/api/admin/cleanup", axum::routing::post(admin_cleanup))
+            .with_state(state);
+
+        let cleanup_as = |token: Option<&'static str>| {
+            let app = app.clone();
+            async move {
+                let mut request = Request::builder().method("POST").uri("/api/admin/cleanup");
+                if let Some(token) = token {
+                    request = request.header("authorization", format!("Bearer {}", token));
+                }
+                app.oneshot(request.body(Body::empty()).unwrap())
+                    .await
+                    .unwrap()
+                    .status()
+            }
         };
 
-        Router::new()
-            .route("/{code}", get(redirect))
-            .route("/analytics/{code}", get(analytics))
-            .with_state(state)
+        assert_eq!(cleanup_as(None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(cleanup_as(Some("alice-token")).await, StatusCode::FORBIDDEN);
+        assert!(get_link(&pool, "expired").await.unwrap().is_some());
+
+        assert_eq!(cleanup_as(Some("secret")).await, StatusCode::OK);
+        assert!(get_link(&pool, "expired").await.unwrap().is_none());
     }
 
     #[tokio::test]
-    async fn test_analytics_not_found() {
-        let app = setup_app().await;
+    async fn test_admin_routes_closed_without_auth() {
+        let pool = setup_cleanup_pool().await;
+        let app = Router::new()
+            .route("/api/admin/cleanup", axum::routing::post(admin_cleanup))
+            .route("/api/export/links.ndjson", get(export_links))
+            .route("/api/import/links", axum::routing::post(import_links))
+            .with_state(test_state(pool.clone()));
+
+        for (method, uri) in [
+            ("POST", "/api/admin/cleanup?unused_days=0"),
+            ("GET", "/api/export/links.ndjson"),
+            ("POST", "/api/import/links"),
+        ] {
+            let (status, json) = send_with_token(app.clone(), method, uri, "", "").await;
+            assert_eq!(status, StatusCode::FORBIDDEN, "{} {}", method, uri);
+            assert_eq!(json["code"], "admin_required");
+        }
+        assert!(get_link(&pool, "unused").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_export_links_ndjson() {
+        let pool = setup_cleanup_pool().await;
+        let mut state = test_state(pool);
+        state.auth_token = Some("secret".to_string());
+        state.api_tokens = vec![ApiToken {
+            owner: "alice".to_string(),
+            token: "alice-token".to_string(),
+        }];
+
+        let app = Router::new()
+            .route("/api/export/links.ndjson", get(export_links))
+            .with_state(state);
+
+        let export_as = |token: &'static str| {
+            let request = Request::builder()
+                .uri("/api/export/links.ndjson")
+                .header("authorization", format!("Bearer {}", token))
+                .body(Body::empty())
+                .unwrap();
+            app.clone().oneshot(request)
+        };
+
+        let response = export_as("alice-token").await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let response = export_as("secret").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "application/x-ndjson");
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.ends_with('\n'));
+
+        // Every line is one link object, expired links included, in code order
+        let links: Vec<LinkRecord> = body
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let codes: Vec<&str> = links.iter().map(|l| l.code.as_str()).collect();
+        assert_eq!(codes, ["expired", "recent", "unused", "visited"]);
+        assert!(links
+            .iter()
+            .all(|l| l.original_url == "https://example.com" && l.created_at > 0));
+    }
 
+    const IMPORT_BODY: &str = concat!(
+        r#"{"code":"docs","original_url":"https://docs.rs","expires_at":9999999999,"created_at":1739000000}"#,
+        "\n",
+        r#"{"code":"blog","original_url":"https://example.com/blog","expires_at":9999999999,"created_at":1739000001}"#,
+        "\n\n",
+        r#"{"code":"bad code","original_url":"https://example.com","expires_at":9999999999,"created_at":1739000002}"#,
+        "\n",
+    );
+
+    async fn import_json(app: Router, uri: &str) -> serde_json::Value {
         let response = app
             .oneshot(
                 Request::builder()
-                    .uri("/analytics/noexist")
-                    .body(Body::empty())
+                    .method("POST")
+                    .uri(uri)
+                    .header("authorization", "Bearer master")
+                    .header("content-type", "application/x-ndjson")
+                    .body(Body::from(IMPORT_BODY))
                     .unwrap(),
             )
             .await
             .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
 
-        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&body).unwrap()
     }
 
-    #[tokio::test]
-    async fn test_analytics_returns_counts() {
+    async fn import_app() -> (Router, SqlitePool) {
         let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
         crate::database::run_migrations(&pool).await.unwrap();
 
-        // Create a link that expires far in the future
+        let app = Router::new()
+            .route("/api/import/links", axum::routing::post(import_links))
+            .with_state(admin_state(pool.clone()));
+
+        (app, pool)
+    }
+
+    #[tokio::test]
+    async fn test_import_links_clean() {
+        let (app, pool) = import_app().await;
+
+        let json = import_json(app, "/api/import/links").await;
+        assert_eq!(json["imported"], 2);
+        assert_eq!(json["skipped"], 0);
+        assert_eq!(json["failed"], 1);
+        assert_eq!(json["errors"][0]["line"], 4);
+        assert!(json["errors"][0]["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("Invalid code"));
+
+        let docs = get_link(&pool, "docs").await.unwrap().unwrap();
+        assert_eq!(docs.original_url, "https://docs.rs");
+        assert_eq!(docs.created_at, 1739000000);
+        assert!(get_link(&pool, "blog").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_import_links_conflict_skip() {
+        let (app, pool) = import_app().await;
         crate::database::insert_link(
             &pool,
-            "testcode",
-            "https://example.com",
-            9999999999,
-            1000000000,
+            &NewLink {
+                code: "docs",
+                original_url: "https://example.com/kept",
+                expires_at: 9999999999,
+                created_at: 1000000000,
+                ..Default::default()
+            },
         )
         .await
         .unwrap();
 
-        let state = AppState {
-            db: pool,
-            base_url: "http://localhost:3000".to_string(),
-            auth_token: None,
-            geoip: None,
-        };
+        let json = import_json(app, "/api/import/links?on_conflict=skip").await;
+        assert_eq!(json["imported"], 1);
+        assert_eq!(json["skipped"], 1);
+        assert_eq!(json["skipped_codes"], serde_json::json!(["docs"]));
+
+        let docs = get_link(&pool, "docs").await.unwrap().unwrap();
+        assert_eq!(docs.original_url, "https://example.com/kept");
+    }
+
+    #[tokio::test]
+    async fn test_import_links_conflict_overwrite() {
+        let (app, pool) = import_app().await;
+        crate::database::insert_link(
+            &pool,
+            &NewLink {
+                code: "docs",
+                original_url: "https://example.com/replaced",
+                expires_at: 1000000100,
+                created_at: 1000000000,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let json = import_json(app, "/api/import/links?on_conflict=overwrite").await;
+        assert_eq!(json["imported"], 2);
+        assert_eq!(json["skipped"], 0);
+
+        let docs = get_link(&pool, "docs").await.unwrap().unwrap();
+        assert_eq!(docs.original_url, "https://docs.rs");
+        assert_eq!(docs.expires_at, 9999999999);
+        assert_eq!(docs.created_at, 1739000000);
+    }
 
+    #[tokio::test]
+    async fn test_import_links_failure_clears_cache() {
+        let pool = setup_pool_with_link("docs").await;
+        let mut state = admin_state(pool.clone());
+        state.link_cache = Some(crate::cache::LinkCache::new(100, 60));
         let app = Router::new()
             .route("/{code}", get(redirect))
-            .route("/analytics/{code}", get(analytics))
+            .route("/api/import/links", axum::routing::post(import_links))
             .with_state(state);
 
-        // Trigger two redirects to record visits
-        app.clone()
+        let response = get_uri(app.clone(), "/docs").await;
+        assert_eq!(response.headers()[header::LOCATION], "https://example.com");
+
+        // A full chunk, overwriting the cached link, is written before the body breaks
+        let mut chunk = String::new();
+        for i in 0..crate::import::IMPORT_CHUNK_SIZE {
+            let code = if i == 0 {
+                "docs".to_string()
+            } else {
+                format!("link{}", i)
+            };
+            chunk.push_str(&format!(
+                r#"{{"code":"{}","original_url":"https://docs.rs","expires_at":9999999999,"created_at":1}}"#,
+                code
+            ));
+            chunk.push('\n');
+        }
+        let pieces: Vec<Result<String, std::io::Error>> =
+            vec![Ok(chunk), Err(std::io::Error::other("connection reset"))];
+        let response = app
+            .clone()
             .oneshot(
                 Request::builder()
-                    .uri("/testcode")
-                    .body(Body::empty())
+                    .method("POST")
+                    .uri("/api/import/links?on_conflict=overwrite")
+                    .header("authorization", "Bearer master")
+                    .body(Body::from_stream(futures_util::stream::iter(pieces)))
                     .unwrap(),
             )
             .await
             .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
 
-        app.clone()
-            .oneshot(
-                Request::builder()
-                    .uri("/testcode")
-                    .body(Body::empty())
-                    .unwrap(),
-            )
+        let response = get_uri(app, "/docs").await;
+        assert_eq!(response.headers()[header::LOCATION], "https://docs.rs");
+    }
+
+    #[tokio::test]
+    async fn test_import_links_checks_blocklist() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        crate::database::run_migrations(&pool).await.unwrap();
+        let mut state = admin_state(pool.clone());
+        state.blocklist = Blocklist::new(DomainList::parse("docs.rs"), None);
+        let app = Router::new()
+            .route("/api/import/links", axum::routing::post(import_links))
+            .with_state(state);
+
+        let json = import_json(app, "/api/import/links").await;
+        assert_eq!(json["imported"], 1);
+        assert_eq!(json["failed"], 2);
+        assert_eq!(json["errors"][0]["line"], 1);
+        assert_eq!(json["errors"][0]["error"], "Destination not allowed");
+        assert!(get_link(&pool, "docs").await.unwrap().is_none());
+        assert!(get_link(&pool, "blog").await.unwrap().is_some());
+    }
+
+    async fn extend_app(expires_in: i64) -> (Router, SqlitePool) {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        crate::database::run_migrations(&pool).await.unwrap();
+        crate::database::insert_link(
+            &pool,
+            &NewLink {
+                code: "campaign",
+                original_url: "https://example.com",
+                expires_at: now_unix() + expires_in,
+                created_at: 1000000000,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let app = Router::new()
+            .route("/api/links/{code}/extend", axum::routing::post(extend))
+            .with_state(test_state(pool.clone()));
+
+        (app, pool)
+    }
+
+    async fn post_extend(app: Router, body: &str) -> axum::response::Response {
+        app.oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/api/links/campaign/extend")
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_extend_set_mode() {
+        let (app, pool) = extend_app(3600).await;
+
+        let before = now_unix();
+        let response = post_extend(app, r#"{"ttl":"7d"}"#).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
             .await
             .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let expires_at = json["expires_at"].as_i64().unwrap();
+        assert!(expires_at >= before + 7 * 86400 && expires_at <= now_unix() + 7 * 86400);
 
-        // Call analytics endpoint
-        let response = app
-            .oneshot(
-                Request::builder()
-                    .uri("/analytics/testcode")
-                    .body(Body::empty())
-                    .unwrap(),
-            )
+        let link = get_link(&pool, "campaign").await.unwrap().unwrap();
+        assert_eq!(link.expires_at, expires_at);
+        assert_eq!(link.original_url, "https://example.com");
+    }
+
+    #[tokio::test]
+    async fn test_extend_add_mode() {
+        let (app, pool) = extend_app(86400).await;
+        let original = get_link(&pool, "campaign")
             .await
-            .unwrap();
+            .unwrap()
+            .unwrap()
+            .expires_at;
 
+        let response = post_extend(app, r#"{"ttl":"2d","mode":"add"}"#).await;
         assert_eq!(response.status(), StatusCode::OK);
 
+        let link = get_link(&pool, "campaign").await.unwrap().unwrap();
+        assert_eq!(link.expires_at, original + 2 * 86400);
+    }
+
+    #[tokio::test]
+    async fn test_extend_rejects_past_max_ttl() {
+        // 20 days left plus 20 more would pass the 30-day maximum
+        let (app, pool) = extend_app(20 * 86400).await;
+        let original = get_link(&pool, "campaign")
+            .await
+            .unwrap()
+            .unwrap()
+            .expires_at;
+
+        let response = post_extend(app.clone(), r#"{"ttl":"20d","mode":"add"}"#).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = post_extend(app, r#"{"ttl":"31d"}"#).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let link = get_link(&pool, "campaign").await.unwrap().unwrap();
+        assert_eq!(link.expires_at, original);
+    }
+
+    #[tokio::test]
+    async fn test_robots_txt_disallows_crawling() {
+        let app = Router::new()
+            .route("/robots.txt", get(robots))
+            .route("/{code}", get(redirect))
+            .with_state(test_state(setup_pool_with_link("robots").await));
+
+        let response = get_uri(app, "/robots.txt").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers()[header::CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .starts_with("text/plain"));
+
         let body = axum::body::to_bytes(response.into_body(), usize::MAX)
             .await
             .unwrap();
-        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains("User-agent: *"));
+        assert!(body.contains("Disallow: /\n"));
+    }
 
-        assert_eq!(json["total_visits"], 2);
+    #[tokio::test]
+    async fn test_favicon_returns_no_content() {
+        let app = Router::new()
+            .route("/favicon.ico", get(favicon))
+            .route("/{code}", get(redirect))
+            .with_state(test_state(setup_pool_with_link("favicon").await));
+
+        let response = get_uri(app, "/favicon.ico").await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    }
+
+    #[tokio::test]
+    async fn test_redirect_rejects_malformed_code_without_db() {
+        let pool = setup_pool_with_link("valid_code").await;
+        let app = Router::new()
+            .route("/{code}", get(redirect))
+            .with_state(test_state(pool.clone()));
+
+        // A valid code still redirects
+        let response = get_uri(app.clone(), "/valid_code").await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+
+        // With the pool closed, any lookup would fail with a 500
+        pool.close().await;
+
+        let response = get_uri(app.clone(), "/some@weird").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = get_uri(app, "/valid_code").await;
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
     }
 }
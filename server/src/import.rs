@@ -0,0 +1,329 @@
+//! Restoring links from an NDJSON export
+//!
+//! The request body is fed in as it arrives and written in chunks, each in its
+//! own transaction, so large imports neither buffer the whole file nor hold a
+//! single long write lock.
+
+use crate::{
+    blocklist::{destination_allowed, Blocklist},
+    database::{code_exists, upsert_link},
+    models::{AppState, ConflictMode, ImportLineError, ImportResponse, LinkRecord},
+    utils::{is_reserved_code, resolves_to_blocked_ip, validate_code, validate_url, DomainList},
+};
+use anyhow::Result;
+
+/// Links written per transaction
+pub const IMPORT_CHUNK_SIZE: usize = 500;
+
+/// Most skipped codes and line errors listed in the summary; counts stay exact
+pub const MAX_REPORTED_ITEMS: usize = 1000;
+
+/// Imports NDJSON link records fed to it piece by piece
+///
+/// Each non-blank line must be a `LinkRecord`. Lines that don't parse or
+/// validate are counted as failed and never stop the import.
+pub struct Importer<'a> {
+    /// Database, destination checks, and code rules the import follows
+    state: &'a AppState,
+    on_conflict: ConflictMode,
+    /// Bytes of a line that hasn't ended yet
+    buffer: Vec<u8>,
+    /// Number of lines seen so far
+    line: usize,
+    /// Valid links waiting for the next chunk write
+    pending: Vec<LinkRecord>,
+    summary: ImportResponse,
+}
+
+impl<'a> Importer<'a> {
+    /// Creates an importer writing to `state.db`
+    pub fn new(state: &'a AppState, on_conflict: ConflictMode) -> Self {
+        Self {
+            state,
+            on_conflict,
+            buffer: Vec::new(),
+            line: 0,
+            pending: Vec::new(),
+            summary: ImportResponse::default(),
+        }
+    }
+
+    /// Consumes the next piece of the body, which may end mid-line
+    ///
+    /// # Errors
+    /// Returns an error if writing a chunk fails; earlier chunks stay imported.
+    pub async fn feed(&mut self, data: &[u8]) -> Result<()> {
+        let mut buffer = std::mem::take(&mut self.buffer);
+        buffer.extend_from_slice(data);
+
+        let mut start = 0;
+        while let Some(offset) = buffer[start..].iter().position(|&b| b == b'\n') {
+            let end = start + offset;
+            self.push_line(&buffer[start..end]).await?;
+            start = end + 1;
+        }
+
+        buffer.drain(..start);
+        self.buffer = buffer;
+        Ok(())
+    }
+
+    /// Imports the final line and any pending links, returning the summary
+    pub async fn finish(mut self) -> Result<ImportResponse> {
+        let rest = std::mem::take(&mut self.buffer);
+        if !rest.is_empty() {
+            self.push_line(&rest).await?;
+        }
+        self.flush().await?;
+
+        Ok(self.summary)
+    }
+
+    /// Parses and validates one line, writing a chunk once enough links are pending
+    async fn push_line(&mut self, bytes: &[u8]) -> Result<()> {
+        self.line += 1;
+
+        let bytes = bytes.trim_ascii();
+        if bytes.is_empty() {
+            return Ok(());
+        }
+
+        let mut parsed = parse_record(
+            bytes,
+            &self.state.reserved_codes,
+            self.state.max_url_length,
+            &self.state.blocklist,
+            &self.state.allowed_domains,
+        );
+        if let (Ok(link), Some(resolver)) = (&parsed, &self.state.resolver) {
+            if resolves_to_blocked_ip(resolver.as_ref(), &link.original_url).await {
+                parsed = Err(
+                    "Invalid URL: URL cannot point to localhost or a private network address"
+                        .to_string(),
+                );
+            }
+        }
+
+        match parsed {
+            Ok(link) => self.pending.push(link),
+            Err(error) => {
+                self.summary.failed += 1;
+                if self.summary.errors.len() < MAX_REPORTED_ITEMS {
+                    self.summary.errors.push(ImportLineError {
+                        line: self.line,
+                        error,
+                    });
+                }
+            }
+        }
+
+        if self.pending.len() >= IMPORT_CHUNK_SIZE {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+
+    /// Writes the pending links in one transaction
+    async fn flush(&mut self) -> Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+
+        let overwrite = self.on_conflict == ConflictMode::Overwrite;
+        let case_insensitive = self.state.case_insensitive_codes;
+        let mut tx = self.state.db.begin().await?;
+
+        for link in self.pending.drain(..) {
+            // With CASE_INSENSITIVE_CODES, a stored code differing only in case
+            // belongs to another link, so it is skipped even when overwriting
+            let clashes = case_insensitive
+                && code_exists(&mut *tx, &link.code, true).await?
+                && !(overwrite && code_exists(&mut *tx, &link.code, false).await?);
+
+            if !clashes && upsert_link(&mut *tx, &link, overwrite).await? {
+                self.summary.imported += 1;
+            } else {
+                self.summary.skipped += 1;
+                if self.summary.skipped_codes.len() < MAX_REPORTED_ITEMS {
+                    self.summary.skipped_codes.push(link.code);
+                }
+            }
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+/// Parses one NDJSON line, checking the code and URL like `POST /shorten` would
+fn parse_record(
+    bytes: &[u8],
+    reserved_codes: &[String],
+    max_url_length: usize,
+    blocklist: &Blocklist,
+    allowed_domains: &DomainList,
+) -> Result<LinkRecord, String> {
+    let link: LinkRecord =
+        serde_json::from_slice(bytes).map_err(|e| format!("Invalid link record: {}", e))?;
+
+    validate_code(&link.code).map_err(|e| format!("Invalid code: {}", e))?;
+    if is_reserved_code(&link.code, reserved_codes) {
+        return Err(format!("Code '{}' is reserved", link.code));
+    }
+    validate_url(&link.original_url, max_url_length).map_err(|e| format!("Invalid URL: {}", e))?;
+    if !destination_allowed(blocklist, allowed_domains, &link.original_url) {
+        return Err("Destination not allowed".to_string());
+    }
+
+    Ok(link)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        database::{get_link, run_migrations},
+        handlers::tests::{test_state, FakeResolver},
+        utils::DEFAULT_MAX_URL_LENGTH,
+    };
+    use sqlx::SqlitePool;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_parse_record() {
+        let reserved = vec!["api".to_string()];
+        let blocklist = Blocklist::default();
+        let anywhere = DomainList::default();
+
+        let link = parse_record(
+            br#"{"code":"abc","original_url":"https://example.com","expires_at":2,"created_at":1}"#,
+            &reserved,
+            DEFAULT_MAX_URL_LENGTH,
+            &blocklist,
+            &anywhere,
+        )
+        .unwrap();
+        assert_eq!(link.code, "abc");
+
+        for bad in [
+            &br#"{"code":"abc"}"#[..],
+            br#"not json"#,
+            br#"{"code":"a b","original_url":"https://example.com","expires_at":2,"created_at":1}"#,
+            br#"{"code":"API","original_url":"https://example.com","expires_at":2,"created_at":1}"#,
+            br#"{"code":"abc","original_url":"ftp://example.com","expires_at":2,"created_at":1}"#,
+        ] {
+            assert!(parse_record(
+                bad,
+                &reserved,
+                DEFAULT_MAX_URL_LENGTH,
+                &blocklist,
+                &anywhere
+            )
+            .is_err());
+        }
+    }
+
+    #[test]
+    fn test_parse_record_checks_destination() {
+        let blocklist = Blocklist::new(DomainList::parse("evil.com"), None);
+        let allowed = DomainList::parse("example.com, evil.com");
+        let parse = |url: &str| {
+            let line = format!(
+                r#"{{"code":"abc","original_url":"{}","expires_at":2,"created_at":1}}"#,
+                url
+            );
+            parse_record(
+                line.as_bytes(),
+                &[],
+                DEFAULT_MAX_URL_LENGTH,
+                &blocklist,
+                &allowed,
+            )
+        };
+
+        assert!(parse("https://www.example.com/page").is_ok());
+        assert_eq!(
+            parse("https://login.evil.com").unwrap_err(),
+            "Destination not allowed"
+        );
+        assert_eq!(
+            parse("https://other.org").unwrap_err(),
+            "Destination not allowed"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_import_resolves_destinations() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        run_migrations(&pool).await.unwrap();
+
+        let mut state = test_state(pool.clone());
+        state.resolver = Some(Arc::new(FakeResolver));
+        let mut importer = Importer::new(&state, ConflictMode::Skip);
+        importer
+            .feed(
+                br#"{"code":"inside","original_url":"http://metadata.example/latest","expires_at":2,"created_at":1}
+{"code":"outside","original_url":"https://example.com","expires_at":2,"created_at":1}
+"#,
+            )
+            .await
+            .unwrap();
+        let summary = importer.finish().await.unwrap();
+
+        assert_eq!(summary.imported, 1);
+        assert_eq!(summary.failed, 1);
+        assert_eq!(summary.errors[0].line, 1);
+        assert!(summary.errors[0].error.contains("private network"));
+        assert!(get_link(&pool, "inside").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_feed_splits_lines_across_pieces() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        run_migrations(&pool).await.unwrap();
+
+        let state = test_state(pool.clone());
+        let mut importer = Importer::new(&state, ConflictMode::Skip);
+        importer
+            .feed(br#"{"code":"one","original_url":"https://example.com/1","expi"#)
+            .await
+            .unwrap();
+        importer
+            .feed(b"res_at\":2,\"created_at\":1}\r\n\n{\"code\":\"two\",")
+            .await
+            .unwrap();
+        importer
+            .feed(br#""original_url":"https://example.com/2","expires_at":2,"created_at":1}"#)
+            .await
+            .unwrap();
+        let summary = importer.finish().await.unwrap();
+
+        assert_eq!(summary.imported, 2);
+        assert_eq!(summary.failed, 0);
+        assert!(get_link(&pool, "one").await.unwrap().is_some());
+        assert!(get_link(&pool, "two").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_import_case_insensitive_codes() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        run_migrations(&pool).await.unwrap();
+        let mut state = test_state(pool.clone());
+        state.case_insensitive_codes = true;
+
+        let lines = br#"{"code":"docs","original_url":"https://example.com/old","expires_at":2,"created_at":1}
+{"code":"Docs","original_url":"https://example.com/new","expires_at":2,"created_at":1}
+"#;
+        for on_conflict in [ConflictMode::Skip, ConflictMode::Overwrite] {
+            let mut importer = Importer::new(&state, on_conflict);
+            importer.feed(lines).await.unwrap();
+            let summary = importer.finish().await.unwrap();
+            assert_eq!(summary.skipped_codes, vec!["Docs".to_string()]);
+        }
+
+        assert!(get_link(&pool, "Docs").await.unwrap().is_none());
+        // Overwriting the exact spelling still works
+        let link = get_link(&pool, "docs").await.unwrap().unwrap();
+        assert_eq!(link.original_url, "https://example.com/old");
+    }
+}
@@ -7,59 +7,211 @@
 //! - Set expiration times (TTL)
 //! - Redirect short links to original URLs
 //! - Automatic cleanup of expired links
+//! - Graceful shutdown that drains in-flight requests
 
+mod auth;
+mod blocklist;
+mod cache;
+mod cleanup;
 mod config;
 mod database;
+mod export;
 mod handlers;
+mod import;
+mod metrics;
 mod middleware;
 mod models;
+#[cfg(feature = "openapi")]
+mod openapi;
+mod pages;
+mod qr;
+mod shutdown;
 mod utils;
+mod visits;
+mod webhook;
 
 use crate::{
-    config::Config, database::delete_expired_links, middleware::create_rate_limiter,
-    models::AppState, utils::now_unix,
+    config::{Config, LogFormat},
+    middleware::{access_log, create_optional_rate_limiter, create_rate_limiter},
+    models::AppState,
+    utils::{
+        random_ip_salt, CodeSettings, GeoLookup, HostResolver, IpStorage, SystemResolver, TtlLimits,
+    },
 };
 use axum::{
+    extract::DefaultBodyLimit,
     routing::{get, post},
     Router,
 };
-use std::time::Duration;
-use tokio::time::interval;
+use std::{net::SocketAddr, time::Duration};
+use tokio::sync::watch;
 use tower_http::{cors::CorsLayer, trace::TraceLayer};
-use tracing::info;
+use tracing::{info, warn};
 use tracing_subscriber::prelude::*;
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    // Initialize tracing
-    tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "cutl_server=debug,tower_http=debug,axum=debug".into()),
+/// Routes for creating links, which share the shortening rate limit
+///
+/// The unauthenticated `/api/shorten` is left out unless `public_shorten` is set.
+/// Single-link bodies over `max_body_bytes` are rejected with 413 before being
+/// buffered; batches keep axum's default limit.
+fn shorten_routes(public_shorten: bool, max_body_bytes: usize) -> Router<AppState> {
+    let body_limit = DefaultBodyLimit::max(max_body_bytes);
+    let routes = Router::new()
+        .route("/shorten", post(handlers::shorten).layer(body_limit))
+        .route("/api/shorten/batch", post(handlers::shorten_batch));
+
+    if public_shorten {
+        routes.route(
+            "/api/shorten",
+            post(handlers::shorten_noauth).layer(body_limit),
         )
-        .with(tracing_subscriber::fmt::layer())
-        .init();
+    } else {
+        routes
+    }
+}
 
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
     // Load .env file if present
     dotenv::dotenv().ok();
 
     // Load configuration from environment
     let config = Config::from_env()?;
 
+    // Initialize tracing in the configured format
+    let fmt_layer = match config.log_format {
+        LogFormat::Pretty => tracing_subscriber::fmt::layer().boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer().json().boxed(),
+    };
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| {
+                "cutl_server=debug,access_log=info,tower_http=debug,axum=debug".into()
+            }),
+        )
+        .with(fmt_layer)
+        .init();
+
     info!("Starting cutl server");
     info!("Database: {}", config.database_url);
+    info!(
+        "SQLite: {:?} journal, {}s busy timeout, {}-{} connections",
+        config.sqlite_journal_mode,
+        config.sqlite_busy_timeout_seconds,
+        config.db_min_connections,
+        config.db_max_connections
+    );
     info!("Base URL: {}", config.base_url);
+    if !config.allowed_hosts.is_empty() {
+        info!("Allowed hosts: {}", config.allowed_hosts.join(", "));
+    }
     info!("Bind address: {}", config.bind_address);
+    info!("Trust proxy headers: {}", config.trust_proxy);
+    info!("Rate limit key: {:?}", config.rate_limit_key);
+    info!("Visitor IP storage: {:?}", config.ip_storage);
+    info!("Deduplicate URLs: {}", config.dedup_urls);
+    info!("URL normalization: {:?}", config.url_normalization);
+    info!("Case-insensitive codes: {}", config.case_insensitive_codes);
+    info!("Reserved codes: {}", config.reserved_codes.join(", "));
+    info!(
+        "Generated codes: {}-{} characters, {:?} alphabet",
+        config.code_min_length, config.code_max_length, config.code_alphabet
+    );
+    info!("Redirect status: {:?}", config.redirect_status);
+    info!("Preview mode: {}", config.preview_mode);
+    info!("Count bot visits: {}", config.count_bots);
+    info!("Bot User-Agent patterns: {}", config.bot_user_agents.len());
+    info!("Public /api/shorten: {}", config.public_shorten);
+    info!("Max shorten body: {} bytes", config.max_body_bytes);
+    info!("Max URL length: {} bytes", config.max_url_length);
+    info!("API tokens: {}", config.api_tokens.len());
+    info!("Auth header: {}", config.auth_header);
+    info!(
+        "Redirect cache max-age: {}",
+        config
+            .redirect_cache_max_age
+            .map_or_else(|| "no-store".to_string(), |s| format!("{}s", s))
+    );
+    info!(
+        "Cleanup interval: {}",
+        match config.cleanup_interval_seconds {
+            0 => "disabled".to_string(),
+            s => format!("{}s", s),
+        }
+    );
+    info!(
+        "Visit retention: {}",
+        config
+            .visit_retention_days
+            .map_or_else(|| "forever".to_string(), |d| format!("{} days", d))
+    );
+    info!(
+        "PII retention: {}",
+        config
+            .pii_retention_days
+            .map_or_else(|| "forever".to_string(), |d| format!("{} days", d))
+    );
+    info!(
+        "Visit writes: {}",
+        match config.visit_flush_interval_seconds {
+            0 => "immediate".to_string(),
+            s => format!("buffered, flushed every {}s", s),
+        }
+    );
+    if config.link_cache_size > 0 {
+        info!(
+            "Link cache: {} links, {}s TTL",
+            config.link_cache_size, config.link_cache_ttl_seconds
+        );
+    }
+    if let Some(ref url) = config.webhook_url {
+        info!("Webhook: {}", url);
+    }
+    info!("Shutdown timeout: {}s", config.shutdown_timeout_seconds);
+    info!(
+        "TTL: min {}s, max {}s, default {}s",
+        config.min_ttl_seconds, config.max_ttl_seconds, config.default_ttl_seconds
+    );
     info!(
         "Rate limit: {} requests/minute (burst: {})",
         config.rate_limit, config.rate_limit_burst
     );
+    if config.redirect_rate_limit > 0 {
+        info!(
+            "Redirect rate limit: {} requests/minute (burst: {})",
+            config.redirect_rate_limit, config.redirect_rate_limit_burst
+        );
+    }
+    info!(
+        "Password rate limit: {} attempts/minute (burst: {})",
+        config.password_rate_limit, config.password_rate_limit_burst
+    );
+    if !config.blocklist_domains.is_empty() {
+        info!(
+            "Blocked destination domains: {}",
+            config.blocklist_domains.len()
+        );
+    }
+    if !config.allowed_domains.is_empty() {
+        info!(
+            "Allowed destination domains: {}",
+            config.allowed_domains.len()
+        );
+    }
+    info!("Resolve destinations: {}", config.resolve_destinations);
+    if !config.rate_limit_allowlist.is_empty() {
+        info!(
+            "Rate limit allowlist: {} networks",
+            config.rate_limit_allowlist.len()
+        );
+    }
 
     // Create database connection pool
-    let db = database::create_pool(&config.database_url).await?;
+    let db = database::create_pool(&config.database_url, &config.pool_settings()).await?;
 
     // Run migrations automatically
     database::run_migrations(&db).await?;
+    database::enforce_code_case(&db, config.case_insensitive_codes).await?;
 
     // Initialize GeoIP reader if configured
     let geoip =
@@ -67,7 +219,7 @@ async fn main() -> anyhow::Result<()> {
             match maxminddb::Reader::open_readfile(path) {
                 Ok(r) => {
                     info!("GeoIP database loaded from {}", path);
-                    Some(std::sync::Arc::new(r))
+                    Some(std::sync::Arc::new(r) as std::sync::Arc<dyn GeoLookup>)
                 }
                 Err(e) => {
                     tracing::warn!("Could not load GeoIP database: {}", e);
@@ -76,67 +228,327 @@ async fn main() -> anyhow::Result<()> {
             }
         });
 
+    // A missing or unreadable BLOCKLIST_FILE stops startup; later reloads only warn
+    let blocklist = blocklist::Blocklist::new(
+        config.blocklist_domains.clone(),
+        config.blocklist_file.as_ref().map(std::path::PathBuf::from),
+    );
+    let blocked_from_file = blocklist.reload().await?;
+    if let Some(ref path) = config.blocklist_file {
+        info!("Blocklist file {}: {} domains", path, blocked_from_file);
+    }
+
+    // Without a fixed salt, hashed IPs only stay comparable until the next restart
+    let ip_hash_salt = match config.ip_hash_salt {
+        Some(salt) => salt,
+        None => {
+            if config.ip_storage == IpStorage::Hashed {
+                warn!("IP_HASH_SALT not set; using a random salt, so IP hashes change on restart");
+            }
+            random_ip_salt()
+        }
+    };
+
+    // Visits recorded under an earlier IP_STORAGE=raw still hold raw addresses
+    let rewritten = database::apply_ip_storage(&db, config.ip_storage, &ip_hash_salt).await?;
+    if rewritten > 0 {
+        info!(
+            "Rewrote stored IPs of {} visits to match IP_STORAGE",
+            rewritten
+        );
+    }
+
+    let webhook = config
+        .webhook_url
+        .as_deref()
+        .map(webhook::Webhook::new)
+        .transpose()?;
+
     // Create application state
     let state = AppState {
         db,
         base_url: config.base_url,
+        allowed_hosts: config.allowed_hosts,
         auth_token: config.auth_token,
+        api_tokens: config.api_tokens,
+        auth_header: config.auth_header,
+        trust_proxy: config.trust_proxy,
         geoip,
+        ip_storage: config.ip_storage,
+        ip_hash_salt,
+        dedup_urls: config.dedup_urls,
+        url_normalization: config.url_normalization,
+        redirect_status: config.redirect_status,
+        ttl_limits: TtlLimits {
+            min_seconds: config.min_ttl_seconds,
+            max_seconds: config.max_ttl_seconds,
+            default_seconds: config.default_ttl_seconds,
+        },
+        max_batch_size: config.max_batch_size,
+        code_settings: CodeSettings {
+            min_length: config.code_min_length,
+            max_length: config.code_max_length,
+            alphabet: config.code_alphabet,
+            max_attempts: config.code_max_attempts,
+            filter_bad_codes: config.filter_bad_codes,
+        },
+        case_insensitive_codes: config.case_insensitive_codes,
+        reserved_codes: config.reserved_codes,
+        preview_mode: config.preview_mode,
+        count_bots: config.count_bots,
+        bot_user_agents: config.bot_user_agents,
+        redirect_cache_max_age: config.redirect_cache_max_age,
+        metrics: Some(metrics::install()?),
+        webhook,
+        blocklist,
+        allowed_domains: config.allowed_domains,
+        resolver: config
+            .resolve_destinations
+            .then(|| std::sync::Arc::new(SystemResolver) as std::sync::Arc<dyn HostResolver>),
+        max_url_length: config.max_url_length,
+        visit_buffer: (config.visit_flush_interval_seconds > 0).then(visits::VisitBuffer::default),
+        redirect_counters: Default::default(),
+        link_cache: (config.link_cache_size > 0)
+            .then(|| cache::LinkCache::new(config.link_cache_size, config.link_cache_ttl_seconds)),
     };
 
+    // Keep handles on the pool and visit buffer for the final flush and close
+    let db = state.db.clone();
+    let visit_buffer = state.visit_buffer.clone();
+
+    // Background tasks watch this channel and stop once it flips to true
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+
     // Spawn background task for cleanup
-    let cleanup_state = state.clone();
-    tokio::spawn(async move {
-        cleanup_task(cleanup_state).await;
+    let cleanup = tokio::spawn(cleanup::cleanup_task(
+        state.db.clone(),
+        config.cleanup_interval_seconds,
+        config.visit_retention_days,
+        state.webhook.clone(),
+        shutdown_rx.clone(),
+    ));
+
+    // Spawn background task for scrubbing IPs and user agents from old visits
+    let anonymize = tokio::spawn(cleanup::anonymize_task(
+        state.db.clone(),
+        config.pii_scrub_interval_seconds,
+        config.pii_retention_days,
+        state.ip_hash_salt.clone(),
+        shutdown_rx.clone(),
+    ));
+
+    // Spawn background task for writing buffered visits
+    let visit_flush = state.visit_buffer.clone().map(|buffer| {
+        tokio::spawn(visits::flush_task(
+            buffer,
+            state.db.clone(),
+            config.visit_flush_interval_seconds as u64,
+            shutdown_rx.clone(),
+        ))
     });
 
+    // Spawn background task for re-reading BLOCKLIST_FILE
+    let blocklist_reload = tokio::spawn(blocklist::reload_task(
+        state.blocklist.clone(),
+        shutdown_rx.clone(),
+    ));
+
     // Create rate limiter
-    let rate_limiter = create_rate_limiter(config.rate_limit, config.rate_limit_burst);
+    let rate_limiter = create_rate_limiter(
+        config.rate_limit,
+        config.rate_limit_burst,
+        config.rate_limit_key,
+        &config.rate_limit_allowlist,
+        config.trust_proxy,
+    );
+
+    // Redirects get their own, optional limit; HEAD, PATCH, and DELETE stay unlimited
+    let redirect_route = match create_optional_rate_limiter(
+        config.redirect_rate_limit,
+        config.redirect_rate_limit_burst,
+        config.rate_limit_key,
+        &config.rate_limit_allowlist,
+        config.trust_proxy,
+    ) {
+        Some(limiter) => get(handlers::redirect).layer(limiter),
+        None => get(handlers::redirect),
+    };
+
+    // Password attempts are always limited, since each one costs an Argon2 hash
+    let unlock_route = post(handlers::unlock).layer(create_rate_limiter(
+        config.password_rate_limit,
+        config.password_rate_limit_burst,
+        config.rate_limit_key,
+        &config.rate_limit_allowlist,
+        config.trust_proxy,
+    ));
 
     // Configure CORS to allow frontend requests
     let cors = CorsLayer::permissive();
 
     // Build the router
-    let app = Router::new()
-        // Rate-limited routes for shortening
-        .route("/shorten", post(handlers::shorten))
-        .route("/api/shorten", post(handlers::shorten_noauth))
+    let routes = shorten_routes(config.public_shorten, config.max_body_bytes)
         .layer(rate_limiter)
-        // Public redirect and analytics (no rate limit)
-        .route("/{code}", get(handlers::redirect))
+        // Liveness probe for load balancers and `cutl health`
+        .route("/healthz", get(handlers::healthz))
+        // Crawler noise, answered before it reaches the short link lookup. Fixed
+        // paths always take priority over `/{code}`; any that could also be a
+        // valid code belong in DEFAULT_RESERVED_CODES
+        .route("/robots.txt", get(handlers::robots))
+        .route("/favicon.ico", get(handlers::favicon))
+        // Public redirect, authenticated update/delete, and analytics
+        .route(
+            "/{code}",
+            redirect_route
+                .merge(unlock_route)
+                .head(handlers::redirect_head)
+                .patch(handlers::update)
+                .delete(handlers::delete),
+        )
+        .route("/{code}/+", get(handlers::public_stats))
         .route("/analytics/{code}", get(handlers::analytics))
+        .route("/analytics/{code}/export.csv", get(handlers::export_visits))
+        .route("/api/info/{code}", get(handlers::info))
+        .route("/api/links", get(handlers::list_links))
+        .route("/api/stats", get(handlers::stats))
+        .route("/api/links/{code}/extend", post(handlers::extend))
+        .route("/api/links/{code}/disable", post(handlers::disable))
+        .route("/api/links/{code}/enable", post(handlers::enable))
+        .route("/api/links/{code}/rotate", post(handlers::rotate))
+        .route("/api/links/{code}/share", post(handlers::share))
+        .route("/api/admin/cleanup", post(handlers::admin_cleanup))
+        .route("/api/export/links.ndjson", get(handlers::export_links))
+        .route("/api/import/links", post(handlers::import_links))
+        .route("/qr/{code}", get(handlers::qr))
+        .route("/metrics", get(handlers::metrics));
+
+    #[cfg(feature = "openapi")]
+    let routes = routes.route("/openapi.json", get(openapi::spec));
+
+    let app = routes
         .layer(cors)
+        .layer(axum::middleware::from_fn_with_state(
+            config.trust_proxy,
+            access_log,
+        ))
         .layer(TraceLayer::new_for_http())
         .with_state(state);
 
     // Start the server
     let listener = tokio::net::TcpListener::bind(&config.bind_address).await?;
     info!("Server listening on {}", config.bind_address);
-    axum::serve(listener, app).await?;
+    let server = axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown::notify_on(shutdown::os_signal(), shutdown_tx));
+
+    // Once a signal arrives, give in-flight requests a bounded time to finish
+    let drain_timeout = Duration::from_secs(config.shutdown_timeout_seconds as u64);
+    let mut drain_rx = shutdown_rx;
+    let drain_deadline = async move {
+        shutdown::requested(&mut drain_rx).await;
+        tokio::time::sleep(drain_timeout).await;
+    };
+
+    tokio::select! {
+        result = server => result?,
+        _ = drain_deadline => {
+            warn!(
+                "In-flight requests did not finish within {}s, shutting down anyway",
+                drain_timeout.as_secs()
+            );
+        }
+    }
+
+    if let Err(e) = cleanup.await {
+        tracing::error!("Cleanup task failed: {}", e);
+    }
+    if let Err(e) = anonymize.await {
+        tracing::error!("Anonymization task failed: {}", e);
+    }
+    if let Err(e) = blocklist_reload.await {
+        tracing::error!("Blocklist reload task failed: {}", e);
+    }
+    if let Some(visit_flush) = visit_flush {
+        if let Err(e) = visit_flush.await {
+            tracing::error!("Visit flush task failed: {}", e);
+        }
+    }
+
+    // No more requests can arrive, so this catches every buffered visit
+    if let Some(buffer) = visit_buffer {
+        match buffer.flush(&db).await {
+            Ok(count) => info!("Wrote {} buffered visits", count),
+            Err(e) => tracing::error!("{:#}", e),
+        }
+    }
+    db.close().await;
+    info!("Server stopped");
 
     Ok(())
 }
 
-/// Background task that periodically deletes expired links
-///
-/// Runs every 60 seconds and cleans up any links that have expired.
-async fn cleanup_task(state: AppState) {
-    let mut timer = interval(Duration::from_secs(60));
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+    };
+    use sqlx::SqlitePool;
+    use tower::ServiceExt;
 
-    loop {
-        timer.tick().await;
+    async fn post_status(public_shorten: bool, uri: &str) -> StatusCode {
+        post_body(
+            public_shorten,
+            uri,
+            r#"{"url":"https://example.com"}"#.to_string(),
+        )
+        .await
+    }
 
-        let now = now_unix();
+    async fn post_body(public_shorten: bool, uri: &str, body: String) -> StatusCode {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        database::run_migrations(&pool).await.unwrap();
+        let app = shorten_routes(public_shorten, config::DEFAULT_MAX_BODY_BYTES)
+            .with_state(handlers::tests::test_state(pool));
 
-        match delete_expired_links(&state.db, now).await {
-            Ok(count) => {
-                if count > 0 {
-                    info!("Cleaned up {} expired links", count);
-                }
-            }
-            Err(e) => {
-                tracing::error!("Failed to cleanup expired links: {}", e);
-            }
+        app.oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(uri)
+                .header("content-type", "application/json")
+                .body(Body::from(body))
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+        .status()
+    }
+
+    #[tokio::test]
+    async fn test_public_shorten_route_registered_by_flag() {
+        assert_eq!(post_status(true, "/api/shorten").await, StatusCode::OK);
+        assert_eq!(
+            post_status(false, "/api/shorten").await,
+            StatusCode::NOT_FOUND
+        );
+
+        // The authenticated route stays either way
+        assert_eq!(post_status(false, "/shorten").await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_shorten_rejects_oversized_body() {
+        let padding = "a".repeat(config::DEFAULT_MAX_BODY_BYTES);
+        let body = format!(r#"{{"url":"https://example.com/?q={}"}}"#, padding);
+
+        for uri in ["/shorten", "/api/shorten"] {
+            assert_eq!(
+                post_body(true, uri, body.clone()).await,
+                StatusCode::PAYLOAD_TOO_LARGE
+            );
         }
     }
 }
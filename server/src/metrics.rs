@@ -0,0 +1,153 @@
+//! Prometheus metrics for the cutl server
+//!
+//! Counters are recorded through the `metrics` facade and rendered in
+//! Prometheus text format by `GET /metrics`.
+
+use crate::models::{ApiError, RedirectCounts};
+use metrics::counter;
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Counter of link creation requests, labelled by `endpoint`
+pub const SHORTEN_REQUESTS: &str = "cutl_shorten_requests_total";
+
+/// Counter of redirect lookups, labelled by `result` (`hit`, `miss`, or `expired`)
+pub const REDIRECTS: &str = "cutl_redirects_total";
+
+/// Counter of link cache lookups, labelled by `result` (`hit` or `miss`)
+pub const LINK_CACHE_LOOKUPS: &str = "cutl_link_cache_lookups_total";
+
+/// Counter of analytics requests
+pub const ANALYTICS_REQUESTS: &str = "cutl_analytics_requests_total";
+
+/// Counter of error responses, labelled by `handler` and `status`
+pub const ERRORS: &str = "cutl_errors_total";
+
+/// Installs the global Prometheus recorder
+///
+/// # Errors
+/// Returns an error if a recorder was already installed.
+pub fn install() -> anyhow::Result<PrometheusHandle> {
+    Ok(PrometheusBuilder::new().install_recorder()?)
+}
+
+/// Counts a request to one of the shorten endpoints
+pub fn record_shorten(endpoint: &'static str) {
+    counter!(SHORTEN_REQUESTS, "endpoint" => endpoint).increment(1);
+}
+
+/// Counts a redirect lookup; called through `RedirectCounters` so both agree
+fn record_redirect(result: &'static str) {
+    counter!(REDIRECTS, "result" => result).increment(1);
+}
+
+/// Counts a link cache lookup as a hit (served from memory) or a miss
+pub fn record_link_cache(hit: bool) {
+    let result = if hit { "hit" } else { "miss" };
+    counter!(LINK_CACHE_LOOKUPS, "result" => result).increment(1);
+}
+
+/// Counts a request to the analytics endpoint
+pub fn record_analytics() {
+    counter!(ANALYTICS_REQUESTS).increment(1);
+}
+
+/// Counts an error response from `handler` by status code
+pub fn record_error(handler: &'static str, error: &ApiError) {
+    counter!(
+        ERRORS,
+        "handler" => handler,
+        "status" => error.status.as_u16().to_string()
+    )
+    .increment(1);
+}
+
+/// Outcomes of redirect link lookups since the server started
+///
+/// Kept in `AppState` so `GET /api/stats` can report them without a
+/// Prometheus scrape. Each increment also counts `cutl_redirects_total`, so
+/// the two always use the same definitions. Increments are lock-free.
+#[derive(Debug, Default)]
+pub struct RedirectCounters {
+    hit: AtomicU64,
+    miss: AtomicU64,
+    expired: AtomicU64,
+}
+
+impl RedirectCounters {
+    /// Counts a lookup that found an enabled, unexpired link
+    pub fn record_hit(&self) {
+        self.hit.fetch_add(1, Ordering::Relaxed);
+        record_redirect("hit");
+    }
+
+    /// Counts a lookup for a code with no link, or a disabled one
+    pub fn record_miss(&self) {
+        self.miss.fetch_add(1, Ordering::Relaxed);
+        record_redirect("miss");
+    }
+
+    /// Counts a lookup that found a link past its expiry
+    pub fn record_expired(&self) {
+        self.expired.fetch_add(1, Ordering::Relaxed);
+        record_redirect("expired");
+    }
+
+    /// Current values of all three counters
+    pub fn snapshot(&self) -> RedirectCounts {
+        RedirectCounts {
+            hit: self.hit.load(Ordering::Relaxed),
+            miss: self.miss.load(Ordering::Relaxed),
+            expired: self.expired.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Returns a handle to a recorder shared by all tests in the process
+///
+/// Only one global recorder can be installed, so tests reuse this one.
+#[cfg(test)]
+pub fn test_handle() -> PrometheusHandle {
+    use std::sync::OnceLock;
+
+    static HANDLE: OnceLock<PrometheusHandle> = OnceLock::new();
+
+    HANDLE
+        .get_or_init(|| install().expect("failed to install test metrics recorder"))
+        .clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redirect_counters_snapshot() {
+        let counters = RedirectCounters::default();
+        counters.record_hit();
+        counters.record_hit();
+        counters.record_miss();
+        counters.record_expired();
+
+        assert_eq!(
+            counters.snapshot(),
+            RedirectCounts {
+                hit: 2,
+                miss: 1,
+                expired: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_record_error_renders_status_label() {
+        let handle = test_handle();
+
+        record_error("test_handler", &ApiError::conflict("code_exists", "taken"));
+
+        let output = handle.render();
+        assert!(output.contains(ERRORS));
+        assert!(output.contains(r#"handler="test_handler""#));
+        assert!(output.contains(r#"status="409""#));
+    }
+}
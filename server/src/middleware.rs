@@ -1,40 +1,697 @@
-//! Rate limiting middleware
+//! HTTP middleware: rate limiting and access logging
 
-use axum::body::Body;
+use crate::{
+    models::ApiError,
+    utils::{client_ip, IpAllowlist},
+};
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, MatchedPath, Request, State},
+    http::{header, HeaderName, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use futures_util::future::BoxFuture;
 use governor::clock::QuantaInstant;
 use governor::middleware::NoOpMiddleware;
+use std::{
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Instant,
+};
+use tower::{Layer, Service};
 use tower_governor::{
-    governor::GovernorConfigBuilder, key_extractor::SmartIpKeyExtractor, GovernorLayer,
+    governor::{Governor, GovernorConfigBuilder},
+    key_extractor::{KeyExtractor, PeerIpKeyExtractor, SmartIpKeyExtractor},
+    GovernorError, GovernorLayer,
 };
 
+/// Milliseconds in one minute, used to convert requests/minute into a replenish interval
+const MILLIS_PER_MINUTE: u64 = 60_000;
+
+/// Requests allowed per minute, sent with 429 responses (IETF RateLimit header fields)
+const RATELIMIT_LIMIT: HeaderName = HeaderName::from_static("ratelimit-limit");
+
+/// Requests left in the current window; always 0 on a 429
+const RATELIMIT_REMAINING: HeaderName = HeaderName::from_static("ratelimit-remaining");
+
+/// Seconds until another request will be accepted
+const RATELIMIT_RESET: HeaderName = HeaderName::from_static("ratelimit-reset");
+
+/// Where rate limits find the client IP they count requests against
+///
+/// `Forwarded` believes whatever the headers say, so it's only safe behind a
+/// proxy that overwrites them; otherwise clients can dodge limits by sending
+/// a different address with each request.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RateLimitKey {
+    /// The socket peer address
+    #[default]
+    Socket,
+    /// `X-Forwarded-For`, `X-Real-IP`, or `Forwarded`, falling back to the socket peer
+    Forwarded,
+}
+
+impl FromStr for RateLimitKey {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "socket" => Ok(Self::Socket),
+            "forwarded" => Ok(Self::Forwarded),
+            other => Err(anyhow::anyhow!(
+                "Invalid rate limit key: {}. Use socket or forwarded",
+                other
+            )),
+        }
+    }
+}
+
+/// Keys rate limits by client IP, found as `RateLimitKey` says, and knows
+/// which clients `RATE_LIMIT_ALLOWLIST` exempts
+#[derive(Debug, Clone, Default)]
+pub struct AllowlistKeyExtractor {
+    key: RateLimitKey,
+    allowlist: Arc<IpAllowlist>,
+    /// Whether the allowlist may take the client IP from forwarded headers
+    trust_proxy: bool,
+}
+
+impl AllowlistKeyExtractor {
+    /// Creates an extractor keyed by `key`, exempting the clients in `allowlist`
+    ///
+    /// The allowlist only believes forwarded headers with `trust_proxy`
+    /// (`TRUST_PROXY`); otherwise it checks the socket peer, whatever `key` is.
+    pub fn new(key: RateLimitKey, allowlist: IpAllowlist, trust_proxy: bool) -> Self {
+        Self {
+            key,
+            allowlist: Arc::new(allowlist),
+            trust_proxy,
+        }
+    }
+
+    /// Checks whether the request comes from an allowlisted client
+    pub fn is_allowlisted<T>(&self, req: &Request<T>) -> bool {
+        if self.allowlist.is_empty() {
+            return false;
+        }
+
+        let ip = match (self.key, self.trust_proxy) {
+            (RateLimitKey::Forwarded, true) => SmartIpKeyExtractor.extract(req),
+            _ => PeerIpKeyExtractor.extract(req),
+        };
+        ip.is_ok_and(|ip| self.allowlist.contains(ip))
+    }
+}
+
+impl KeyExtractor for AllowlistKeyExtractor {
+    type Key = IpAddr;
+
+    fn extract<T>(&self, req: &Request<T>) -> Result<Self::Key, GovernorError> {
+        match self.key {
+            RateLimitKey::Socket => PeerIpKeyExtractor.extract(req),
+            RateLimitKey::Forwarded => SmartIpKeyExtractor.extract(req),
+        }
+    }
+}
+
+/// Rate limits requests, except from clients allowlisted by the key extractor
+///
+/// Built by `create_rate_limiter`. Allowlisted requests skip the governor
+/// entirely, so they neither count against a limit nor can be rejected.
+#[derive(Clone)]
+pub struct RateLimitLayer {
+    governor: GovernorLayer<AllowlistKeyExtractor, NoOpMiddleware<QuantaInstant>, Body>,
+    key_extractor: AllowlistKeyExtractor,
+}
+
+impl<S: Clone> Layer<S> for RateLimitLayer {
+    type Service = RateLimit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimit {
+            limited: self.governor.layer(inner.clone()),
+            inner,
+            key_extractor: self.key_extractor.clone(),
+        }
+    }
+}
+
+/// Service produced by `RateLimitLayer`
+#[derive(Clone)]
+pub struct RateLimit<S> {
+    inner: S,
+    limited: Governor<AllowlistKeyExtractor, NoOpMiddleware<QuantaInstant>, S, Body>,
+    key_extractor: AllowlistKeyExtractor,
+}
+
+impl<S> Service<Request> for RateLimit<S>
+where
+    S: Service<Request, Response = Response> + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Response, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match self.inner.poll_ready(cx) {
+            Poll::Ready(Ok(())) => self.limited.poll_ready(cx),
+            other => other,
+        }
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        if self.key_extractor.is_allowlisted(&req) {
+            Box::pin(self.inner.call(req))
+        } else {
+            Box::pin(self.limited.call(req))
+        }
+    }
+}
+
 /// Creates the rate limiter middleware layer
 ///
-/// Uses SmartIpKeyExtractor which automatically extracts the client IP from:
+/// Uses `AllowlistKeyExtractor`, which with `RateLimitKey::Forwarded` takes the
+/// client IP from:
 /// - X-Forwarded-For header (first IP)
 /// - X-Real-IP header
 /// - Forwarded header
 /// - Connection IP (fallback)
 ///
+/// and with `RateLimitKey::Socket` from the connection IP only.
+///
 /// # Arguments
-/// * `rate_limit` - Maximum requests per minute
-/// * `burst_size` - How many requests can happen in quick succession
+/// * `rate_limit` - Maximum requests per minute (0 is treated as 1)
+/// * `burst_size` - How many requests can happen in quick succession (0 is treated as 1)
+/// * `key` - Where the client IP comes from (`RATE_LIMIT_KEY`)
+/// * `allowlist` - Clients that are never limited (`RATE_LIMIT_ALLOWLIST`)
+/// * `trust_proxy` - Whether the allowlist may believe forwarded headers (`TRUST_PROXY`)
 ///
 /// # Returns
-/// A GovernorLayer that can be used with `.layer()`
+/// A layer that can be used with `.layer()`. Each call returns a layer with its
+/// own counters, so routes limited separately don't share a budget.
+/// Rejected requests get a JSON 429 from `rate_limited_response`.
 pub fn create_rate_limiter(
     rate_limit: u32,
     burst_size: u32,
-) -> GovernorLayer<SmartIpKeyExtractor, NoOpMiddleware<QuantaInstant>, Body> {
-    // Build governor configuration
-    // Use per_second to calculate the rate: 60 seconds / rate_limit
-    let seconds_per_request = 60u64 / rate_limit as u64;
+    key: RateLimitKey,
+    allowlist: &IpAllowlist,
+    trust_proxy: bool,
+) -> RateLimitLayer {
+    let key_extractor = AllowlistKeyExtractor::new(key, allowlist.clone(), trust_proxy);
 
     let config = GovernorConfigBuilder::default()
-        .key_extractor(SmartIpKeyExtractor)
-        .per_second(seconds_per_request)
-        .burst_size(burst_size)
+        .key_extractor(key_extractor.clone())
+        .per_millisecond(replenish_interval_ms(rate_limit))
+        .burst_size(burst_size.max(1))
         .finish()
+        .expect("rate limiter period and burst size are non-zero");
+
+    let governor = GovernorLayer::new(config).error_handler(move |error| match error {
+        GovernorError::TooManyRequests { wait_time, .. } => {
+            rate_limited_response(rate_limit.max(1), wait_time)
+        }
+        other => other.into(),
+    });
+
+    RateLimitLayer {
+        governor,
+        key_extractor,
+    }
+}
+
+/// Builds the 429 sent to a client over its rate limit
+///
+/// Carries `Retry-After` and `RateLimit-Reset` (seconds to wait, at least 1),
+/// `RateLimit-Limit` (the configured requests per minute), and
+/// `RateLimit-Remaining: 0`, so clients can back off instead of retrying blindly.
+fn rate_limited_response(rate_limit: u32, wait_seconds: u64) -> Response {
+    let wait_seconds = wait_seconds.max(1);
+    let error = ApiError::new(
+        StatusCode::TOO_MANY_REQUESTS,
+        "rate_limited",
+        format!("Too many requests, retry in {}s", wait_seconds),
+    );
+
+    (
+        [
+            (header::RETRY_AFTER, wait_seconds.to_string()),
+            (RATELIMIT_LIMIT, rate_limit.to_string()),
+            (RATELIMIT_REMAINING, "0".to_string()),
+            (RATELIMIT_RESET, wait_seconds.to_string()),
+        ],
+        error,
+    )
+        .into_response()
+}
+
+/// Creates a rate limiter for an optional limit, such as `REDIRECT_RATE_LIMIT`
+///
+/// A `rate_limit` of 0 means unlimited and returns `None`, so no layer is added;
+/// otherwise this behaves like `create_rate_limiter`.
+pub fn create_optional_rate_limiter(
+    rate_limit: u32,
+    burst_size: u32,
+    key: RateLimitKey,
+    allowlist: &IpAllowlist,
+    trust_proxy: bool,
+) -> Option<RateLimitLayer> {
+    (rate_limit > 0)
+        .then(|| create_rate_limiter(rate_limit, burst_size, key, allowlist, trust_proxy))
+}
+
+/// Converts a requests-per-minute limit into the interval (in milliseconds)
+/// after which one request is replenished.
+///
+/// Never returns 0, so the governor configuration is always valid.
+fn replenish_interval_ms(rate_limit: u32) -> u64 {
+    (MILLIS_PER_MINUTE / u64::from(rate_limit.max(1))).max(1)
+}
+
+/// Logs one `access_log` event per request
+///
+/// Fields: `method`, `path`, `status`, `latency_ms`, `client_ip` (the peer
+/// address, or proxy headers when the `TRUST_PROXY` state is true), and `code`
+/// when the matched route has a `{code}` segment.
+pub async fn access_log(State(trust_proxy): State<bool>, request: Request, next: Next) -> Response {
+    let start = Instant::now();
+    let method = request.method().clone();
+    let path = request.uri().path().to_owned();
+
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+    let client_ip = client_ip(request.headers(), peer, trust_proxy);
+    let code = request
+        .extensions()
+        .get::<MatchedPath>()
+        .and_then(|matched| short_code(matched.as_str(), &path))
+        .map(str::to_owned);
+
+    let response = next.run(request).await;
+
+    tracing::info!(
+        target: "access_log",
+        method = %method,
+        path = %path,
+        status = response.status().as_u16(),
+        latency_ms = start.elapsed().as_millis() as u64,
+        client_ip = client_ip.as_deref(),
+        code = code.as_deref(),
+        "request"
+    );
+
+    response
+}
+
+/// Returns the segment of `path` that matched `{code}` in the route pattern
+fn short_code<'a>(matched_path: &str, path: &'a str) -> Option<&'a str> {
+    matched_path
+        .split('/')
+        .zip(path.split('/'))
+        .find(|(pattern, _)| *pattern == "{code}")
+        .map(|(_, segment)| segment)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{http::StatusCode, routing::get, Router};
+    use std::{
+        io,
+        sync::{Arc, Mutex},
+    };
+    use tower::ServiceExt;
+
+    /// Writer that appends log output to a shared buffer
+    #[derive(Clone)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_short_code() {
+        assert_eq!(short_code("/{code}", "/abc"), Some("abc"));
+        assert_eq!(
+            short_code("/analytics/{code}", "/analytics/docs"),
+            Some("docs")
+        );
+        assert_eq!(short_code("/metrics", "/metrics"), None);
+        assert_eq!(short_code("/api/shorten", "/api/shorten"), None);
+    }
+
+    /// Sends one request through `access_log` and returns the logged JSON line
+    async fn log_request(trust_proxy: bool, forwarded_for: &str) -> serde_json::Value {
+        let buf = SharedBuf(Arc::new(Mutex::new(Vec::new())));
+        let writer = buf.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .json()
+            .with_writer(move || writer.clone())
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let app = Router::new()
+            .route("/{code}", get(|| async { StatusCode::NO_CONTENT }))
+            .layer(axum::middleware::from_fn_with_state(
+                trust_proxy,
+                access_log,
+            ));
+
+        app.oneshot(
+            axum::http::Request::builder()
+                .uri("/abc")
+                .header("x-forwarded-for", forwarded_for)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
         .unwrap();
 
-    GovernorLayer::new(config)
+        let output = String::from_utf8(buf.0.lock().unwrap().clone()).unwrap();
+        serde_json::from_str(output.lines().last().unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_access_log_records_ip_and_code() {
+        let line = log_request(true, "203.0.113.7").await;
+
+        assert_eq!(line["target"], "access_log");
+        assert_eq!(line["fields"]["client_ip"], "203.0.113.7");
+        assert_eq!(line["fields"]["code"], "abc");
+        assert_eq!(line["fields"]["method"], "GET");
+        assert_eq!(line["fields"]["status"], 204);
+    }
+
+    #[tokio::test]
+    async fn test_access_log_ignores_untrusted_proxy_headers() {
+        // No socket address in a oneshot test, and the forged header is ignored
+        let line = log_request(false, "203.0.113.7").await;
+        assert!(line["fields"].get("client_ip").is_none());
+    }
+
+    /// Builds a request from socket peer 192.0.2.1, optionally with `X-Forwarded-For`
+    fn request_from_peer(forwarded_for: Option<&str>) -> Request {
+        let mut builder = Request::builder().uri("/shorten");
+        if let Some(ip) = forwarded_for {
+            builder = builder.header("x-forwarded-for", ip);
+        }
+
+        let mut request = builder.body(Body::empty()).unwrap();
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from(([192, 0, 2, 1], 4000))));
+        request
+    }
+
+    #[test]
+    fn test_key_extractor_socket_ignores_headers() {
+        let extractor =
+            AllowlistKeyExtractor::new(RateLimitKey::Socket, IpAllowlist::default(), false);
+        let peer: IpAddr = "192.0.2.1".parse().unwrap();
+
+        let request = request_from_peer(Some("203.0.113.7"));
+        assert_eq!(extractor.extract(&request).unwrap(), peer);
+        assert_eq!(extractor.extract(&request_from_peer(None)).unwrap(), peer);
+
+        // Without a socket address there is nothing to key on
+        let request = Request::builder()
+            .header("x-forwarded-for", "203.0.113.7")
+            .body(Body::empty())
+            .unwrap();
+        assert!(extractor.extract(&request).is_err());
+    }
+
+    #[test]
+    fn test_key_extractor_forwarded_prefers_headers() {
+        let extractor =
+            AllowlistKeyExtractor::new(RateLimitKey::Forwarded, IpAllowlist::default(), true);
+
+        let request = request_from_peer(Some("203.0.113.7"));
+        assert_eq!(
+            extractor.extract(&request).unwrap(),
+            "203.0.113.7".parse::<IpAddr>().unwrap()
+        );
+
+        // Falls back to the socket peer when no header is sent
+        assert_eq!(
+            extractor.extract(&request_from_peer(None)).unwrap(),
+            "192.0.2.1".parse::<IpAddr>().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_allowlist_uses_configured_key() {
+        let allowlist = IpAllowlist::parse("203.0.113.0/24").unwrap();
+        let request = request_from_peer(Some("203.0.113.7"));
+
+        // A forged header only helps when forwarded headers are trusted
+        let socket = AllowlistKeyExtractor::new(RateLimitKey::Socket, allowlist.clone(), true);
+        assert!(!socket.is_allowlisted(&request));
+
+        let untrusted =
+            AllowlistKeyExtractor::new(RateLimitKey::Forwarded, allowlist.clone(), false);
+        assert!(!untrusted.is_allowlisted(&request));
+
+        let forwarded = AllowlistKeyExtractor::new(RateLimitKey::Forwarded, allowlist, true);
+        assert!(forwarded.is_allowlisted(&request));
+
+        // Without TRUST_PROXY the socket peer is what gets checked
+        let peers = IpAllowlist::parse("192.0.2.0/24").unwrap();
+        let untrusted = AllowlistKeyExtractor::new(RateLimitKey::Forwarded, peers, false);
+        assert!(untrusted.is_allowlisted(&request));
+    }
+
+    #[test]
+    fn test_rate_limit_key_from_str() {
+        assert_eq!(
+            "socket".parse::<RateLimitKey>().unwrap(),
+            RateLimitKey::Socket
+        );
+        assert_eq!(
+            " Forwarded ".parse::<RateLimitKey>().unwrap(),
+            RateLimitKey::Forwarded
+        );
+        assert!("header".parse::<RateLimitKey>().is_err());
+    }
+
+    #[test]
+    fn test_replenish_interval_ms() {
+        assert_eq!(replenish_interval_ms(1), 60_000);
+        assert_eq!(replenish_interval_ms(25), 2_400);
+        assert_eq!(replenish_interval_ms(60), 1_000);
+        assert_eq!(replenish_interval_ms(120), 500);
+        assert_eq!(replenish_interval_ms(0), 60_000);
+        assert_eq!(replenish_interval_ms(u32::MAX), 1);
+    }
+
+    #[test]
+    fn test_create_rate_limiter_does_not_panic() {
+        for rate_limit in [1, 25, 60, 120, 0] {
+            let _ = create_rate_limiter(
+                rate_limit,
+                2,
+                RateLimitKey::Forwarded,
+                &IpAllowlist::default(),
+                true,
+            );
+        }
+        let _ = create_rate_limiter(
+            10,
+            0,
+            RateLimitKey::Forwarded,
+            &IpAllowlist::default(),
+            true,
+        );
+    }
+
+    #[test]
+    fn test_create_optional_rate_limiter() {
+        assert!(create_optional_rate_limiter(
+            0,
+            10,
+            RateLimitKey::Forwarded,
+            &IpAllowlist::default(),
+            true
+        )
+        .is_none());
+        assert!(create_optional_rate_limiter(
+            0,
+            0,
+            RateLimitKey::Forwarded,
+            &IpAllowlist::default(),
+            true
+        )
+        .is_none());
+        assert!(create_optional_rate_limiter(
+            120,
+            10,
+            RateLimitKey::Forwarded,
+            &IpAllowlist::default(),
+            true
+        )
+        .is_some());
+        assert!(create_optional_rate_limiter(
+            1,
+            0,
+            RateLimitKey::Forwarded,
+            &IpAllowlist::default(),
+            true
+        )
+        .is_some());
+    }
+
+    #[tokio::test]
+    async fn test_rate_limiters_are_independent() {
+        use axum::{routing::get, Router};
+        use tower::ServiceExt;
+
+        let app = Router::new()
+            .route("/shorten", get(|| async { "ok" }))
+            .layer(create_rate_limiter(
+                1,
+                1,
+                RateLimitKey::Forwarded,
+                &IpAllowlist::default(),
+                true,
+            ))
+            .route(
+                "/{code}",
+                get(|| async { "ok" }).layer(
+                    create_optional_rate_limiter(
+                        1,
+                        1,
+                        RateLimitKey::Forwarded,
+                        &IpAllowlist::default(),
+                        true,
+                    )
+                    .unwrap(),
+                ),
+            );
+
+        let call = |uri: &'static str| {
+            let app = app.clone();
+            async move {
+                let request = Request::builder()
+                    .uri(uri)
+                    .header("x-forwarded-for", "203.0.113.7")
+                    .body(Body::empty())
+                    .unwrap();
+                app.oneshot(request).await.unwrap().status()
+            }
+        };
+
+        // Each route gets its own burst of one before answering 429
+        assert_eq!(call("/shorten").await, axum::http::StatusCode::OK);
+        assert_eq!(call("/abc").await, axum::http::StatusCode::OK);
+        assert_eq!(
+            call("/shorten").await,
+            axum::http::StatusCode::TOO_MANY_REQUESTS
+        );
+        assert_eq!(
+            call("/abc").await,
+            axum::http::StatusCode::TOO_MANY_REQUESTS
+        );
+    }
+
+    #[tokio::test]
+    async fn test_over_limit_response_has_rate_limit_headers() {
+        use axum::{routing::post, Router};
+        use tower::ServiceExt;
+
+        let app = Router::new()
+            .route("/shorten", post(|| async { "ok" }))
+            .layer(create_rate_limiter(
+                6,
+                1,
+                RateLimitKey::Forwarded,
+                &IpAllowlist::default(),
+                true,
+            ));
+
+        let call = || {
+            let request = Request::builder()
+                .method("POST")
+                .uri("/shorten")
+                .header("x-forwarded-for", "198.51.100.4")
+                .body(Body::empty())
+                .unwrap();
+            app.clone().oneshot(request)
+        };
+
+        let response = call().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = call().await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        // One request every 10 seconds, so the client waits up to 10s
+        let headers = response.headers();
+        let retry_after: u64 = headers[header::RETRY_AFTER]
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!((1..=10).contains(&retry_after));
+        assert_eq!(headers["ratelimit-limit"], "6");
+        assert_eq!(headers["ratelimit-remaining"], "0");
+        assert_eq!(headers["ratelimit-reset"], headers[header::RETRY_AFTER]);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert!(json["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("Too many requests"));
+        assert_eq!(json["code"], "rate_limited");
+    }
+
+    #[tokio::test]
+    async fn test_allowlisted_clients_bypass_rate_limit() {
+        use axum::{routing::get, Router};
+        use tower::ServiceExt;
+
+        let allowlist = IpAllowlist::parse("10.0.0.0/8, 2001:db8::1").unwrap();
+        let app = Router::new()
+            .route("/shorten", get(|| async { "ok" }))
+            .layer(create_rate_limiter(
+                1,
+                1,
+                RateLimitKey::Forwarded,
+                &allowlist,
+                true,
+            ));
+
+        let call = |ip: &'static str| {
+            let request = Request::builder()
+                .uri("/shorten")
+                .header("x-forwarded-for", ip)
+                .body(Body::empty())
+                .unwrap();
+            let app = app.clone();
+            async move { app.oneshot(request).await.unwrap().status() }
+        };
+
+        for _ in 0..5 {
+            assert_eq!(call("10.1.2.3").await, StatusCode::OK);
+            assert_eq!(call("2001:db8::1").await, StatusCode::OK);
+        }
+
+        // Everyone else still gets a burst of one
+        assert_eq!(call("203.0.113.7").await, StatusCode::OK);
+        assert_eq!(call("203.0.113.7").await, StatusCode::TOO_MANY_REQUESTS);
+    }
 }
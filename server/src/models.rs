@@ -2,11 +2,24 @@
 //!
 //! Defines request/response types and domain models.
 
+use crate::{
+    blocklist::Blocklist,
+    cache::LinkCache,
+    metrics::RedirectCounters,
+    utils::{
+        format_rfc3339, CodeSettings, DomainList, GeoLookup, HostResolver, IpStorage, TtlLimits,
+        UrlNormalization,
+    },
+    visits::VisitBuffer,
+    webhook::Webhook,
+};
 use axum::{
     http::StatusCode,
     response::{IntoResponse, Json},
 };
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::str::FromStr;
 use std::sync::Arc;
 
 /// Application state shared across all request handlers
@@ -14,13 +27,125 @@ use std::sync::Arc;
 pub struct AppState {
     pub db: sqlx::Pool<sqlx::Sqlite>,
     pub base_url: String,
+    /// Hosts whose requests get short links on that host instead of `base_url`
+    pub allowed_hosts: Vec<String>,
     pub auth_token: Option<String>,
-    /// Optional GeoIP reader. None when GEOIP_DB_PATH is not configured.
-    pub geoip: Option<Arc<maxminddb::Reader<Vec<u8>>>>,
+    /// Additional bearer tokens, each labelled with the owner of the links it creates
+    pub api_tokens: Vec<ApiToken>,
+    /// Header accepted beside `Authorization: Bearer` and `X-API-Key`, from `AUTH_HEADER`
+    pub auth_header: axum::http::HeaderName,
+    /// Take visitor IPs from proxy headers instead of the socket address
+    pub trust_proxy: bool,
+    /// Optional GeoIP lookup. None when GEOIP_DB_PATH is not configured.
+    pub geoip: Option<Arc<dyn GeoLookup>>,
+    /// What recorded visits keep of the visitor's IP
+    pub ip_storage: IpStorage,
+    /// Salt mixed into IPs before hashing when `ip_storage` is `Hashed`
+    pub ip_hash_salt: String,
+    /// Reuse an existing non-expired code when the same URL is shortened again
+    pub dedup_urls: bool,
+    /// How submitted URLs are normalized before storing and deduplicating
+    pub url_normalization: UrlNormalization,
+    /// HTTP status used when redirecting short links
+    pub redirect_status: RedirectStatus,
+    /// Minimum, maximum, and default TTL for new links
+    pub ttl_limits: TtlLimits,
+    /// Maximum number of links accepted by a single batch request
+    pub max_batch_size: usize,
+    /// Length range and alphabet for auto-generated codes
+    pub code_settings: CodeSettings,
+    /// Match codes ignoring ASCII case, so `Docs` and `docs` are the same link
+    pub case_insensitive_codes: bool,
+    /// Custom codes rejected because they shadow routes or are otherwise reserved
+    pub reserved_codes: Vec<String>,
+    /// Render an interstitial page instead of redirecting immediately
+    pub preview_mode: bool,
+    /// Record visits from crawlers and link-preview bots
+    pub count_bots: bool,
+    /// Lowercase User-Agent substrings that mark a visitor as a bot
+    pub bot_user_agents: Vec<String>,
+    /// `max-age` for redirect responses in seconds; `None` sends `no-store`
+    pub redirect_cache_max_age: Option<i64>,
+    /// Prometheus handle used to render `GET /metrics`; None disables the endpoint
+    pub metrics: Option<metrics_exporter_prometheus::PrometheusHandle>,
+    /// Receives link created/expired events; None when WEBHOOK_URL is unset
+    pub webhook: Option<Webhook>,
+    /// Destination domains that links may not point to
+    pub blocklist: Blocklist,
+    /// When non-empty, the only destination domains links may point to
+    pub allowed_domains: DomainList,
+    /// Rejects destinations whose hostname resolves to a private address;
+    /// None when RESOLVE_DESTINATIONS is off
+    pub resolver: Option<Arc<dyn HostResolver>>,
+    /// Longest destination URL accepted, in bytes
+    pub max_url_length: usize,
+    /// Queues visits for batched writes; None writes each visit immediately
+    pub visit_buffer: Option<VisitBuffer>,
+    /// Recently used links for the redirect path; None reads every link from the database
+    pub link_cache: Option<LinkCache>,
+    /// Hit, miss, and expired counts for redirect lookups, reported by `GET /api/stats`
+    pub redirect_counters: Arc<RedirectCounters>,
+}
+
+/// A named API token from `API_TOKENS`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiToken {
+    /// Label recorded as the `owner` of links created with this token
+    pub owner: String,
+    pub token: String,
+}
+
+/// Redirect behavior for `GET /{code}`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RedirectStatus {
+    /// 301 Moved Permanently — cached by browsers, repeat visits may skip the server
+    Permanent,
+    /// 302 Found — every visit reaches the server
+    #[default]
+    Temporary,
+    /// 303 See Other
+    SeeOther,
+}
+
+impl RedirectStatus {
+    /// HTTP status code sent with the redirect
+    pub fn status_code(self) -> StatusCode {
+        match self {
+            Self::Permanent => StatusCode::MOVED_PERMANENTLY,
+            Self::Temporary => StatusCode::FOUND,
+            Self::SeeOther => StatusCode::SEE_OTHER,
+        }
+    }
+}
+
+impl FromStr for RedirectStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "permanent" => Ok(Self::Permanent),
+            "temporary" => Ok(Self::Temporary),
+            "see_other" => Ok(Self::SeeOther),
+            other => Err(anyhow::anyhow!(
+                "Invalid redirect status: {}. Use permanent, temporary, or see_other",
+                other
+            )),
+        }
+    }
 }
 
+/// Statuses a link may override the global `REDIRECT_STATUS` with
+pub const LINK_REDIRECT_STATUSES: [u16; 4] = [301, 302, 307, 308];
+
+/// Longest link title accepted, in characters
+pub const MAX_TITLE_LENGTH: usize = 200;
+
+/// Longest link description accepted, in characters
+pub const MAX_DESCRIPTION_LENGTH: usize = 1000;
+
 /// Request body for creating a shortened URL
 #[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct ShortenRequest {
     /// Original URL to shorten
     pub url: String,
@@ -30,10 +155,206 @@ pub struct ShortenRequest {
 
     /// Optional TTL (e.g., "5m", "1h", "3d", "30d")
     pub ttl: Option<String>,
+
+    /// Optional password visitors must enter before being redirected
+    pub password: Option<String>,
+
+    /// Optional number of redirects after which the link stops working
+    pub max_visits: Option<i64>,
+
+    /// Optional query string merged into the destination on redirect (e.g. "utm_source=flyer")
+    pub append_query: Option<String>,
+
+    /// Optional per-visitor destinations that take precedence over `url`
+    pub rules: Option<LinkRules>,
+
+    /// Expire after `idle_ttl` without visits instead of at a fixed time (default: false)
+    #[serde(default)]
+    pub sliding: bool,
+
+    /// Idle window for sliding links (e.g. "7d"; default: the server's default TTL)
+    pub idle_ttl: Option<String>,
+
+    /// Redirect status for this link only: 301, 302, 307, or 308 (default: `REDIRECT_STATUS`)
+    pub redirect_status: Option<u16>,
+
+    /// Optional: Human-readable label for the link (at most 200 characters)
+    pub title: Option<String>,
+
+    /// Optional: Longer notes about the link (at most 1000 characters)
+    pub description: Option<String>,
+
+    /// Validate and return what would be created, without saving (default: false)
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// Alternative destinations for a link, chosen per visitor
+///
+/// Stored as JSON in `links.rules`. A country rule wins over a language rule,
+/// and visitors matching neither go to the link's `original_url`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct LinkRules {
+    /// Destination per visitor country, keyed by ISO 3166-1 alpha-2 code (e.g. "DE");
+    /// needs `GEOIP_DB_PATH`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub countries: BTreeMap<String, String>,
+
+    /// Destination per preferred language from `Accept-Language`, keyed by
+    /// primary language subtag (e.g. "fr" also matches "fr-CA")
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub languages: BTreeMap<String, String>,
+}
+
+impl LinkRules {
+    /// Returns true if no rule is set
+    pub fn is_empty(&self) -> bool {
+        self.countries.is_empty() && self.languages.is_empty()
+    }
+
+    /// Returns the destination for a visitor from `country` preferring `language`,
+    /// if a rule matches
+    pub fn destination_for(&self, country: Option<&str>, language: Option<&str>) -> Option<&str> {
+        let by_country = country.and_then(|c| self.countries.get(&c.to_ascii_uppercase()));
+        let by_language = || language.and_then(|l| self.languages.get(&l.to_ascii_lowercase()));
+
+        by_country.or_else(by_language).map(String::as_str)
+    }
+}
+
+/// Request body for creating several short links at once
+#[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BatchShortenRequest {
+    /// Links to create, each with the same shape as a single shorten request
+    pub links: Vec<ShortenRequest>,
+
+    /// If true, either every link is created or none are (default: false)
+    #[serde(default)]
+    pub atomic: bool,
+}
+
+/// Request body for updating an existing short link
+#[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UpdateRequest {
+    /// Optional new target URL
+    pub url: Option<String>,
+
+    /// Optional new TTL, counted from now (e.g., "5m", "1h", "3d", "30d")
+    pub ttl: Option<String>,
+}
+
+/// Request body for extending a short link's lifetime
+#[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ExtendRequest {
+    /// TTL to apply (e.g., "1h", "7d")
+    pub ttl: String,
+
+    /// Whether the TTL replaces or adds to the current expiry (default: set)
+    #[serde(default)]
+    pub mode: ExtendMode,
+}
+
+/// How `ExtendRequest::ttl` changes a link's expiry
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum ExtendMode {
+    /// The link expires `ttl` from now
+    #[default]
+    Set,
+    /// The link expires `ttl` after its current expiry
+    Add,
+}
+
+/// Query parameters for `GET /{code}`
+#[derive(Debug, Deserialize)]
+pub struct RedirectQuery {
+    /// `1` to skip the preview page and redirect (used when `PREVIEW_MODE` is on)
+    pub go: Option<u8>,
+}
+
+/// Form body of `POST /{code}`, sent by the password prompt
+#[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PasswordForm {
+    /// Password for the protected link
+    pub password: String,
+}
+
+/// Query parameters for `GET /analytics/{code}`
+#[derive(Debug, Deserialize)]
+#[cfg_attr(
+    feature = "openapi",
+    derive(utoipa::IntoParams),
+    into_params(parameter_in = Query)
+)]
+pub struct AnalyticsQuery {
+    /// Maximum number of recent visits to return (default: 20, max: 500)
+    pub limit: Option<u32>,
+
+    /// Number of recent visits to skip, for paging (default: 0)
+    pub offset: Option<u32>,
+
+    /// Only count visits at or after this UNIX timestamp
+    pub from: Option<i64>,
+
+    /// Only count visits at or before this UNIX timestamp
+    pub to: Option<i64>,
+
+    /// Number of countries and referers to list before folding the rest
+    /// into an "others" entry; 0 lists all (default: 10)
+    pub top: Option<u32>,
+
+    /// Group referers by full URL or by host (default: full)
+    #[serde(default)]
+    pub group_by: RefererGrouping,
+}
+
+/// How `referers` are grouped in analytics
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum RefererGrouping {
+    /// One entry per distinct referer URL
+    #[default]
+    Full,
+    /// One entry per referer host, e.g. `twitter.com`
+    Host,
+}
+
+/// Image format of a QR code
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum QrFormat {
+    #[default]
+    Png,
+    Svg,
+}
+
+/// Query parameters for `GET /qr/{code}`
+#[derive(Debug, Deserialize)]
+#[cfg_attr(
+    feature = "openapi",
+    derive(utoipa::IntoParams),
+    into_params(parameter_in = Query)
+)]
+pub struct QrQuery {
+    /// Output format (default: png)
+    #[serde(default)]
+    pub format: QrFormat,
+
+    /// Image size in pixels, clamped to a sane range
+    pub size: Option<u32>,
 }
 
 /// Response after successfully creating a short link
 #[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct ShortenResponse {
     /// The short code
     pub code: String,
@@ -43,47 +364,329 @@ pub struct ShortenResponse {
 
     /// Expiration timestamp (UNIX seconds)
     pub expires_at: i64,
+
+    /// Expiration time as an RFC 3339 UTC string
+    pub expires_at_iso: String,
+}
+
+impl ShortenResponse {
+    /// Builds a response, deriving `expires_at_iso` from `expires_at`
+    pub fn new(code: String, short_url: String, expires_at: i64) -> Self {
+        Self {
+            code,
+            short_url,
+            expires_at,
+            expires_at_iso: format_rfc3339(expires_at),
+        }
+    }
+}
+
+/// Per-item outcome of a batch shorten request
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(untagged)]
+pub enum BatchItemResult {
+    Created(ShortenResponse),
+    Failed(BatchItemError),
+}
+
+/// Error for a single item of a batch shorten request
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BatchItemError {
+    /// Position of the item in the request's `links` array
+    pub index: usize,
+    /// Machine-readable error code, as in single-link error responses
+    pub code: &'static str,
+    pub error: String,
+}
+
+/// Response for a batch shorten request
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BatchShortenResponse {
+    /// Number of links created (or reused when dedup is enabled)
+    pub created: usize,
+    /// One entry per requested link, in request order
+    pub results: Vec<BatchItemResult>,
+}
+
+/// Link metadata returned without redirecting
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct LinkInfoResponse {
+    pub code: String,
+    pub original_url: String,
+    pub created_at: i64,
+    pub expires_at: i64,
+    /// Whether the link is past its expiration time
+    pub expired: bool,
+    /// Whether the link redirects; disabled links respond 404
+    pub enabled: bool,
+    /// Per-visitor destinations, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rules: Option<LinkRules>,
+    /// Idle window in seconds, for sliding links
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub idle_ttl: Option<i64>,
+    /// Latest expiry a sliding link can be pushed to (UNIX seconds)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_expires_at: Option<i64>,
+    /// Redirect status overriding the server default, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redirect_status: Option<u16>,
+    /// Human-readable label, if one was set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Longer notes about the link, if any were set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+/// Query parameters for `GET /api/links`
+#[derive(Debug, Default, Deserialize)]
+#[cfg_attr(
+    feature = "openapi",
+    derive(utoipa::IntoParams),
+    into_params(parameter_in = Query)
+)]
+pub struct LinksQuery {
+    /// Page size (default 20, max 500)
+    pub limit: Option<u32>,
+    /// Number of links to skip (default 0)
+    pub offset: Option<u32>,
+    /// Only return links whose URL or code contains this text
+    pub q: Option<String>,
+}
+
+/// A page of links returned by `GET /api/links`
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct LinksResponse {
+    pub items: Vec<LinkInfoResponse>,
+    /// Number of matching links across all pages
+    pub total: i64,
+}
+
+/// Server-wide counts returned by `GET /api/stats`
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct StatsResponse {
+    pub total_links: i64,
+    /// Links that haven't expired yet
+    pub active_links: i64,
+    /// Links past their expiry that cleanup hasn't removed yet
+    pub expired_links: i64,
+    pub total_visits: i64,
+    /// Redirect lookups since this server process started
+    pub redirects: RedirectCounts,
+}
+
+/// Redirect lookup outcomes, counted per server process
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RedirectCounts {
+    /// Lookups that found an enabled, unexpired link
+    pub hit: u64,
+    /// Lookups for a code with no link, or a disabled one
+    pub miss: u64,
+    /// Lookups that found a link past its expiry
+    pub expired: u64,
+}
+
+/// Public visit counts returned by `GET /{code}/+`
+///
+/// Holds aggregates only, so it can be shown without a token.
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PublicStatsResponse {
+    pub code: String,
+    pub total_visits: i64,
+    /// Visits per day over the last 30 days
+    pub daily: Vec<DailyStat>,
+}
+
+/// Body of a healthy `GET /healthz` response
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct HealthResponse {
+    /// Always "ok"; failures are reported as a 503 `ApiError`
+    pub status: &'static str,
+}
+
+/// Response after deleting a short link
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DeleteResponse {
+    /// Whether the link was deleted
+    pub deleted: bool,
+}
+
+/// Query parameters for `POST /api/admin/cleanup`
+#[derive(Debug, Default, Deserialize)]
+#[cfg_attr(
+    feature = "openapi",
+    derive(utoipa::IntoParams),
+    into_params(parameter_in = Query)
+)]
+pub struct CleanupQuery {
+    /// Also delete links with no visits created more than this many days ago
+    pub unused_days: Option<u32>,
+}
+
+/// Query parameters for `POST /api/links/{code}/rotate`
+#[derive(Debug, Default, Deserialize)]
+#[cfg_attr(
+    feature = "openapi",
+    derive(utoipa::IntoParams),
+    into_params(parameter_in = Query)
+)]
+pub struct RotateQuery {
+    /// Move the link's visit history to the new code (default: true); with
+    /// `false` it is deleted, as visits can't outlive their link
+    pub keep_visits: Option<bool>,
+}
+
+/// Query parameters for `POST /api/links/{code}/share`
+#[derive(Debug, Default, Deserialize)]
+#[cfg_attr(
+    feature = "openapi",
+    derive(utoipa::IntoParams),
+    into_params(parameter_in = Query)
+)]
+pub struct ShareQuery {
+    /// How long the token works (e.g., "1h", "7d"; default 7 days); never past
+    /// the link's own expiry
+    pub ttl: Option<String>,
+}
+
+/// Response after minting a share token
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ShareResponse {
+    /// Bearer token for `GET /analytics/{code}`; only shown once
+    pub token: String,
+    /// The code the token grants access to
+    pub code: String,
+    /// UNIX timestamp after which the token is rejected
+    pub expires_at: i64,
+}
+
+/// Response after a manual cleanup
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CleanupResponse {
+    /// Expired links deleted
+    pub expired_deleted: u64,
+    /// Never-visited links deleted (0 unless `unused_days` was given)
+    pub unused_deleted: u64,
+}
+
+/// Query parameters for `POST /api/import/links`
+#[derive(Debug, Default, Deserialize)]
+#[cfg_attr(
+    feature = "openapi",
+    derive(utoipa::IntoParams),
+    into_params(parameter_in = Query)
+)]
+pub struct ImportQuery {
+    /// What to do with links whose code already exists (default: skip)
+    #[serde(default)]
+    pub on_conflict: ConflictMode,
+}
+
+/// How an import treats codes that already exist
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictMode {
+    /// Keep the existing link and report the code as skipped
+    #[default]
+    Skip,
+    /// Replace the existing link, resetting settings the export doesn't carry
+    Overwrite,
+}
+
+/// Summary of a links import
+#[derive(Debug, Default, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ImportResponse {
+    /// Links created or overwritten
+    pub imported: u64,
+    /// Links left alone because their code already exists
+    pub skipped: u64,
+    /// Lines that couldn't be imported
+    pub failed: u64,
+    /// Codes of the skipped links
+    pub skipped_codes: Vec<String>,
+    /// Why each failed line was rejected
+    pub errors: Vec<ImportLineError>,
+}
+
+/// A line rejected by an import
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ImportLineError {
+    /// 1-based line number in the request body
+    pub line: usize,
+    pub error: String,
 }
 
 /// Error response type
+///
+/// Responds with `{"error": "...", "code": "..."}`, where `error` is a
+/// human-readable message and `code` a stable, machine-readable identifier
+/// such as `invalid_url` or `code_exists` for clients to branch on.
 #[derive(Debug)]
 pub struct ApiError {
     pub status: StatusCode,
+    pub code: &'static str,
     pub message: String,
 }
 
 impl ApiError {
     /// Create a new API error
-    pub fn new(status: StatusCode, message: impl Into<String>) -> Self {
+    pub fn new(status: StatusCode, code: &'static str, message: impl Into<String>) -> Self {
         Self {
             status,
+            code,
             message: message.into(),
         }
     }
 
     /// Bad request (400)
-    pub fn bad_request(message: impl Into<String>) -> Self {
-        Self::new(StatusCode::BAD_REQUEST, message)
+    pub fn bad_request(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, code, message)
     }
 
     /// Unauthorized (401)
-    pub fn unauthorized(message: impl Into<String>) -> Self {
-        Self::new(StatusCode::UNAUTHORIZED, message)
+    pub fn unauthorized(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(StatusCode::UNAUTHORIZED, code, message)
+    }
+
+    /// Forbidden (403)
+    pub fn forbidden(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(StatusCode::FORBIDDEN, code, message)
     }
 
     /// Not found (404)
-    pub fn not_found(message: impl Into<String>) -> Self {
-        Self::new(StatusCode::NOT_FOUND, message)
+    pub fn not_found(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, code, message)
     }
 
     /// Conflict (409)
-    pub fn conflict(message: impl Into<String>) -> Self {
-        Self::new(StatusCode::CONFLICT, message)
+    pub fn conflict(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(StatusCode::CONFLICT, code, message)
     }
 
     /// Internal server error (500)
-    pub fn internal(message: impl Into<String>) -> Self {
-        Self::new(StatusCode::INTERNAL_SERVER_ERROR, message)
+    pub fn internal(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, code, message)
+    }
+
+    /// Service unavailable (503), for temporary failures the client may retry
+    pub fn service_unavailable(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(StatusCode::SERVICE_UNAVAILABLE, code, message)
     }
 }
 
@@ -91,44 +694,156 @@ impl IntoResponse for ApiError {
     fn into_response(self) -> axum::response::Response {
         (
             self.status,
-            Json(serde_json::json!({"error": self.message})),
+            Json(serde_json::json!({"error": self.message, "code": self.code})),
         )
             .into_response()
     }
 }
 
+/// Documents the `{"error": "...", "code": "..."}` body every `ApiError` responds with
+#[cfg(feature = "openapi")]
+impl utoipa::PartialSchema for ApiError {
+    fn schema() -> utoipa::openapi::RefOr<utoipa::openapi::schema::Schema> {
+        use utoipa::openapi::schema::{ObjectBuilder, Type};
+
+        ObjectBuilder::new()
+            .property(
+                "error",
+                ObjectBuilder::new()
+                    .schema_type(Type::String)
+                    .description(Some("Human-readable error message")),
+            )
+            .property(
+                "code",
+                ObjectBuilder::new()
+                    .schema_type(Type::String)
+                    .description(Some("Machine-readable error code, e.g. `invalid_url`")),
+            )
+            .required("error")
+            .required("code")
+            .into()
+    }
+}
+
+#[cfg(feature = "openapi")]
+impl utoipa::ToSchema for ApiError {}
+
 impl From<anyhow::Error> for ApiError {
     fn from(err: anyhow::Error) -> Self {
-        Self::internal(format!("Database error: {}", err))
+        Self::internal("database_error", format!("Database error: {}", err))
     }
 }
 
 /// Database record for a shortened link
-#[derive(Debug)]
+#[derive(Debug, Clone, sqlx::FromRow)]
 #[allow(dead_code)]
 pub struct Link {
     pub code: String,
     pub original_url: String,
     pub expires_at: i64,
     pub created_at: i64,
+    /// Argon2 hash of the link's password, if it is protected
+    pub password_hash: Option<String>,
+    /// Visit limit the link was created with, if any
+    pub max_visits: Option<i64>,
+    /// Visits left before a visit-limited link stops redirecting
+    pub remaining_visits: Option<i64>,
+    /// Label of the API token that created the link, if auth was used
+    pub owner: Option<String>,
+    /// Query parameters merged into `original_url` on redirect
+    pub append_query: Option<String>,
+    /// Disabled links respond 404 but keep their analytics
+    pub enabled: bool,
+    /// JSON-encoded `LinkRules`, if the link has any
+    pub rules: Option<String>,
+    /// Seconds a sliding link survives without a visit; None for fixed expiry
+    pub idle_ttl: Option<i64>,
+    /// Hard limit on a sliding link's expiry, however often it's visited
+    pub max_expires_at: Option<i64>,
+    /// Redirect status used instead of the global `REDIRECT_STATUS`
+    pub redirect_status: Option<u16>,
+    /// Human-readable label shown on the preview page
+    pub title: Option<String>,
+    /// Longer notes about the link, shown on the preview page
+    pub description: Option<String>,
+}
+
+impl Link {
+    /// Decodes the link's rules; unreadable rules are ignored
+    pub fn rules(&self) -> Option<LinkRules> {
+        serde_json::from_str(self.rules.as_deref()?).ok()
+    }
+}
+
+/// Fields for a link about to be inserted
+#[derive(Debug, Default)]
+pub struct NewLink<'a> {
+    pub code: &'a str,
+    pub original_url: &'a str,
+    pub expires_at: i64,
+    pub created_at: i64,
+    /// Argon2 hash of the link's password; plaintext passwords are never stored
+    pub password_hash: Option<&'a str>,
+    /// Visit limit; the link starts with this many remaining visits
+    pub max_visits: Option<i64>,
+    /// Label of the API token creating the link
+    pub owner: Option<&'a str>,
+    /// Query parameters merged into `original_url` on redirect
+    pub append_query: Option<&'a str>,
+    /// JSON-encoded `LinkRules`
+    pub rules: Option<&'a str>,
+    /// Idle window in seconds, for sliding links
+    pub idle_ttl: Option<i64>,
+    /// Hard limit on a sliding link's expiry
+    pub max_expires_at: Option<i64>,
+    /// Redirect status used instead of the global `REDIRECT_STATUS`
+    pub redirect_status: Option<u16>,
+    /// Human-readable label
+    pub title: Option<&'a str>,
+    /// Longer notes about the link
+    pub description: Option<&'a str>,
+}
+
+/// Fields for a visit about to be recorded
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NewVisit {
+    pub code: String,
+    pub visited_at: i64,
+    /// Visitor IP as stored under `IP_STORAGE`
+    pub ip: Option<String>,
+    pub country: Option<String>,
+    pub city: Option<String>,
+    pub user_agent: Option<String>,
+    pub device: Option<String>,
+    pub referer: Option<String>,
 }
 
 /// Analytics response for a short link
 #[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct AnalyticsResponse {
     pub code: String,
     pub original_url: String,
     pub created_at: i64,
+    /// `created_at` as an RFC 3339 UTC string
+    pub created_at_iso: String,
     pub expires_at: i64,
+    /// `expires_at` as an RFC 3339 UTC string
+    pub expires_at_iso: String,
     pub total_visits: i64,
+    /// Distinct visitor IPs; visits without a recorded IP are excluded
+    pub unique_visitors: i64,
     pub countries: Vec<CountStat>,
     pub referers: Vec<CountStat>,
+    /// Visits by device type: `desktop`, `mobile`, `bot`, or null when no User-Agent was sent
+    pub devices: Vec<CountStat>,
     pub daily: Vec<DailyStat>,
     pub recent_visits: Vec<VisitRow>,
 }
 
-/// A count grouped by a string value (used for countries and referers)
+/// A count grouped by a string value (used for countries, referers, and devices)
 #[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct CountStat {
     pub value: Option<String>,
     pub count: i64,
@@ -136,14 +851,30 @@ pub struct CountStat {
 
 /// Daily visit count
 #[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct DailyStat {
     /// Date in "YYYY-MM-DD" format
     pub date: String,
     pub count: i64,
 }
 
+/// One line of the links export, as NDJSON
+///
+/// Carries only the code, destination and timestamps. Passwords, visit limits,
+/// owners, rules and the other per-link settings are not exported, and links
+/// imported from this format start with all of them unset.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, sqlx::FromRow)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct LinkRecord {
+    pub code: String,
+    pub original_url: String,
+    pub expires_at: i64,
+    pub created_at: i64,
+}
+
 /// A single visit record
 #[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct VisitRow {
     pub visited_at: i64,
     pub ip: Option<String>,
@@ -159,58 +890,74 @@ mod tests {
 
     #[test]
     fn test_api_error_new() {
-        let error = ApiError::new(StatusCode::BAD_REQUEST, "Test error");
+        let error = ApiError::new(StatusCode::BAD_REQUEST, "test_error", "Test error");
         assert_eq!(error.status, StatusCode::BAD_REQUEST);
+        assert_eq!(error.code, "test_error");
         assert_eq!(error.message, "Test error");
     }
 
     #[test]
     fn test_api_error_bad_request() {
-        let error = ApiError::bad_request("Invalid input");
+        let error = ApiError::bad_request("invalid_input", "Invalid input");
         assert_eq!(error.status, StatusCode::BAD_REQUEST);
         assert_eq!(error.message, "Invalid input");
     }
 
     #[test]
     fn test_api_error_unauthorized() {
-        let error = ApiError::unauthorized("Missing token");
+        let error = ApiError::unauthorized("unauthorized", "Missing token");
         assert_eq!(error.status, StatusCode::UNAUTHORIZED);
         assert_eq!(error.message, "Missing token");
     }
 
     #[test]
     fn test_api_error_not_found() {
-        let error = ApiError::not_found("Resource not found");
+        let error = ApiError::not_found("not_found", "Resource not found");
         assert_eq!(error.status, StatusCode::NOT_FOUND);
         assert_eq!(error.message, "Resource not found");
     }
 
     #[test]
     fn test_api_error_conflict() {
-        let error = ApiError::conflict("Duplicate entry");
+        let error = ApiError::conflict("code_exists", "Duplicate entry");
         assert_eq!(error.status, StatusCode::CONFLICT);
         assert_eq!(error.message, "Duplicate entry");
     }
 
     #[test]
     fn test_api_error_internal() {
-        let error = ApiError::internal("Database failure");
+        let error = ApiError::internal("database_error", "Database failure");
         assert_eq!(error.status, StatusCode::INTERNAL_SERVER_ERROR);
         assert_eq!(error.message, "Database failure");
     }
 
     #[test]
-    fn test_api_error_into_response() {
-        let error = ApiError::bad_request("Test error");
-        let response = error.into_response();
+    fn test_api_error_service_unavailable() {
+        let error = ApiError::service_unavailable("retry", "Try again");
+        assert_eq!(error.status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(error.message, "Try again");
+    }
 
+    #[tokio::test]
+    async fn test_api_error_into_response() {
+        let error = ApiError::bad_request("invalid_url", "Invalid URL: missing host");
+        let response = error.into_response();
         assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"error": "Invalid URL: missing host", "code": "invalid_url"})
+        );
     }
 
     #[test]
     fn test_api_error_message_types() {
-        let error1 = ApiError::bad_request(String::from("String message"));
-        let error2 = ApiError::bad_request("&str message");
+        let error1 = ApiError::bad_request("test_error", String::from("String message"));
+        let error2 = ApiError::bad_request("test_error", "&str message");
 
         assert_eq!(error1.message, "String message");
         assert_eq!(error2.message, "&str message");
@@ -236,16 +983,42 @@ mod tests {
 
     #[test]
     fn test_shorten_response_serialize() {
-        let response = ShortenResponse {
-            code: "abc123".to_string(),
-            short_url: "http://localhost:3000/abc123".to_string(),
-            expires_at: 1234567890,
-        };
+        let response = ShortenResponse::new(
+            "abc123".to_string(),
+            "http://localhost:3000/abc123".to_string(),
+            1234567890,
+        );
 
         let json = serde_json::to_string(&response).unwrap();
         assert!(json.contains("\"code\":\"abc123\""));
         assert!(json.contains("\"short_url\":\"http://localhost:3000/abc123\""));
         assert!(json.contains("\"expires_at\":1234567890"));
+        assert!(json.contains("\"expires_at_iso\":\"2009-02-13T23:31:30Z\""));
+    }
+
+    #[test]
+    fn test_analytics_response_serializes_iso_timestamps() {
+        let response = AnalyticsResponse {
+            code: "abc123".to_string(),
+            original_url: "https://example.com".to_string(),
+            created_at: 0,
+            created_at_iso: format_rfc3339(0),
+            expires_at: 1234567890,
+            expires_at_iso: format_rfc3339(1234567890),
+            total_visits: 0,
+            unique_visitors: 0,
+            countries: Vec::new(),
+            referers: Vec::new(),
+            devices: Vec::new(),
+            daily: Vec::new(),
+            recent_visits: Vec::new(),
+        };
+
+        let json = serde_json::to_value(&response).unwrap();
+        assert_eq!(json["created_at"], 0);
+        assert_eq!(json["created_at_iso"], "1970-01-01T00:00:00Z");
+        assert_eq!(json["expires_at"], 1234567890);
+        assert_eq!(json["expires_at_iso"], "2009-02-13T23:31:30Z");
     }
 
     #[test]
@@ -255,10 +1028,169 @@ mod tests {
             original_url: "https://example.com".to_string(),
             expires_at: 1234567890,
             created_at: 1234567800,
+            password_hash: None,
+            max_visits: None,
+            remaining_visits: None,
+            owner: None,
+            append_query: None,
+            enabled: true,
+            rules: None,
+            idle_ttl: None,
+            max_expires_at: None,
+            redirect_status: None,
+            title: None,
+            description: None,
         };
 
         let debug_str = format!("{:?}", link);
         assert!(debug_str.contains("test"));
         assert!(debug_str.contains("https://example.com"));
     }
+
+    #[test]
+    fn test_link_rules_destination_for_country() {
+        let rules = LinkRules {
+            countries: BTreeMap::from([
+                ("DE".to_string(), "https://example.com/de".to_string()),
+                ("FR".to_string(), "https://example.com/fr".to_string()),
+            ]),
+            ..Default::default()
+        };
+
+        // Countries as a GeoIP lookup would report them
+        assert_eq!(
+            rules.destination_for(Some("DE"), None),
+            Some("https://example.com/de")
+        );
+        assert_eq!(
+            rules.destination_for(Some("fr"), None),
+            Some("https://example.com/fr")
+        );
+
+        // Unmatched or unknown countries fall back to the link's own URL
+        assert_eq!(rules.destination_for(Some("US"), None), None);
+        assert_eq!(rules.destination_for(None, None), None);
+        assert_eq!(LinkRules::default().destination_for(Some("DE"), None), None);
+    }
+
+    #[test]
+    fn test_link_rules_precedence() {
+        let rules = LinkRules {
+            countries: BTreeMap::from([("CA".to_string(), "https://example.com/ca".to_string())]),
+            languages: BTreeMap::from([("fr".to_string(), "https://example.com/fr".to_string())]),
+        };
+
+        // Geo rule > language rule > default
+        assert_eq!(
+            rules.destination_for(Some("CA"), Some("fr")),
+            Some("https://example.com/ca")
+        );
+        assert_eq!(
+            rules.destination_for(Some("BE"), Some("fr")),
+            Some("https://example.com/fr")
+        );
+        assert_eq!(
+            rules.destination_for(None, Some("FR")),
+            Some("https://example.com/fr")
+        );
+        assert_eq!(rules.destination_for(Some("BE"), Some("nl")), None);
+    }
+
+    #[test]
+    fn test_link_rules_from_stored_json() {
+        let mut link = Link {
+            code: "geo".to_string(),
+            original_url: "https://example.com".to_string(),
+            expires_at: 1234567890,
+            created_at: 1234567800,
+            password_hash: None,
+            max_visits: None,
+            remaining_visits: None,
+            owner: None,
+            append_query: None,
+            enabled: true,
+            rules: Some(r#"{"countries":{"ID":"https://example.id"}}"#.to_string()),
+            idle_ttl: None,
+            max_expires_at: None,
+            redirect_status: None,
+            title: None,
+            description: None,
+        };
+        let rules = link.rules().unwrap();
+        assert_eq!(
+            rules.destination_for(Some("ID"), None),
+            Some("https://example.id")
+        );
+
+        link.rules = Some("not json".to_string());
+        assert!(link.rules().is_none());
+        link.rules = None;
+        assert!(link.rules().is_none());
+    }
+
+    #[test]
+    fn test_redirect_status_from_str() {
+        assert_eq!(
+            "permanent".parse::<RedirectStatus>().unwrap(),
+            RedirectStatus::Permanent
+        );
+        assert_eq!(
+            "Temporary".parse::<RedirectStatus>().unwrap(),
+            RedirectStatus::Temporary
+        );
+        assert_eq!(
+            "see_other".parse::<RedirectStatus>().unwrap(),
+            RedirectStatus::SeeOther
+        );
+        assert!("moved".parse::<RedirectStatus>().is_err());
+    }
+
+    #[test]
+    fn test_redirect_status_default() {
+        assert_eq!(RedirectStatus::default(), RedirectStatus::Temporary);
+    }
+
+    #[test]
+    fn test_redirect_status_code() {
+        assert_eq!(
+            RedirectStatus::Permanent.status_code(),
+            StatusCode::MOVED_PERMANENTLY
+        );
+        assert_eq!(RedirectStatus::Temporary.status_code(), StatusCode::FOUND);
+        assert_eq!(
+            RedirectStatus::SeeOther.status_code(),
+            StatusCode::SEE_OTHER
+        );
+    }
+
+    #[test]
+    fn test_batch_shorten_request_deserialize() {
+        let json = r#"{"links":[{"url":"https://example.com"},{"url":"https://example.org","code":"org"}]}"#;
+        let request: BatchShortenRequest = serde_json::from_str(json).unwrap();
+        assert_eq!(request.links.len(), 2);
+        assert_eq!(request.links[1].code, Some("org".to_string()));
+        assert!(!request.atomic);
+    }
+
+    #[test]
+    fn test_batch_item_result_serialize() {
+        let results = vec![
+            BatchItemResult::Created(ShortenResponse::new(
+                "abc".to_string(),
+                "http://localhost:3000/abc".to_string(),
+                1234567890,
+            )),
+            BatchItemResult::Failed(BatchItemError {
+                index: 1,
+                code: "invalid_url",
+                error: "Invalid URL".to_string(),
+            }),
+        ];
+
+        let json = serde_json::to_value(&results).unwrap();
+        assert_eq!(json[0]["code"], "abc");
+        assert_eq!(json[1]["index"], 1);
+        assert_eq!(json[1]["code"], "invalid_url");
+        assert_eq!(json[1]["error"], "Invalid URL");
+    }
 }
@@ -0,0 +1,102 @@
+//! OpenAPI description of the HTTP API
+//!
+//! The spec is generated from `utoipa` annotations on the handlers and models
+//! and served at `GET /openapi.json`. Only built with the `openapi` feature,
+//! which is on by default.
+
+use crate::{handlers, models::ApiError};
+use axum::Json;
+use utoipa::{
+    openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+    Modify, OpenApi,
+};
+
+/// Every documented route, plus the schemas they reference
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "cutl", description = "Self-hosted URL shortener API"),
+    paths(
+        handlers::shorten,
+        handlers::shorten_noauth,
+        handlers::shorten_batch,
+        handlers::redirect,
+        handlers::redirect_head,
+        handlers::unlock,
+        handlers::update,
+        handlers::delete,
+        handlers::extend,
+        handlers::disable,
+        handlers::enable,
+        handlers::rotate,
+        handlers::share,
+        handlers::admin_cleanup,
+        handlers::info,
+        handlers::list_links,
+        handlers::stats,
+        handlers::qr,
+        handlers::metrics,
+        handlers::healthz,
+        handlers::robots,
+        handlers::favicon,
+        handlers::analytics,
+        handlers::public_stats,
+        handlers::export_visits,
+        handlers::export_links,
+        handlers::import_links,
+    ),
+    components(schemas(ApiError)),
+    modifiers(&BearerAuth)
+)]
+pub struct ApiDoc;
+
+/// Registers the `bearer_auth` scheme referenced by authenticated routes
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer_auth",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+    }
+}
+
+/// GET /openapi.json - Returns the OpenAPI 3.1 spec for this server
+pub async fn spec() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request, routing::get, Router};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_openapi_json_lists_routes() {
+        let app = Router::new().route("/openapi.json", get(spec));
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/openapi.json")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        assert!(json["paths"]["/shorten"]["post"].is_object());
+        assert!(json["paths"]["/{code}"]["get"].is_object());
+        assert!(json["components"]["schemas"]["ShortenResponse"].is_object());
+        assert!(json["components"]["schemas"]["ApiError"].is_object());
+        assert!(json["components"]["securitySchemes"]["bearer_auth"].is_object());
+    }
+}
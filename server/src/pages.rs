@@ -0,0 +1,144 @@
+//! HTML pages served to visitors of short links
+//!
+//! Pages are small, self-contained documents built from string templates.
+
+/// Escapes text for safe inclusion in HTML content and attribute values
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// Wraps `body` in a minimal HTML document
+fn page(title: &str, body: &str) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<meta name="robots" content="noindex">
+<title>{}</title>
+</head>
+<body>
+{}
+</body>
+</html>
+"#,
+        escape_html(title),
+        body
+    )
+}
+
+/// Renders the password prompt for a protected link
+///
+/// The form posts the password to `/{code}`, keeping it out of the URL.
+pub fn password_form(code: &str, wrong_password: bool) -> String {
+    let error = if wrong_password {
+        "<p><strong>Incorrect password.</strong></p>\n"
+    } else {
+        ""
+    };
+
+    page(
+        "Password required",
+        &format!(
+            r#"<h1>Password required</h1>
+<p>This link is protected. Enter the password to continue.</p>
+{}<form method="post" action="/{}">
+<input type="password" name="password" autofocus required>
+<button type="submit">Continue</button>
+</form>"#,
+            error,
+            escape_html(code)
+        ),
+    )
+}
+
+/// Renders the interstitial page that shows where a link leads
+///
+/// The link's title and description, if it has them, are shown above the
+/// destination. The "Continue" button points at `/{code}?go=1`, which performs
+/// the redirect.
+pub fn preview_page(
+    code: &str,
+    original_url: &str,
+    title: Option<&str>,
+    description: Option<&str>,
+) -> String {
+    let mut about = String::new();
+    if let Some(title) = title {
+        about.push_str(&format!("<h2>{}</h2>\n", escape_html(title)));
+    }
+    if let Some(description) = description {
+        about.push_str(&format!("<p>{}</p>\n", escape_html(description)));
+    }
+
+    page(
+        "Link preview",
+        &format!(
+            r#"<h1>You are about to leave</h1>
+{}<p>This short link leads to:</p>
+<p><code>{}</code></p>
+<p><a href="/{}?go=1">Continue</a></p>"#,
+            about,
+            escape_html(original_url),
+            escape_html(code)
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_html() {
+        assert_eq!(
+            escape_html(r#"<a href="x">'&'</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;&#39;&amp;&#39;&lt;/a&gt;"
+        );
+        assert_eq!(escape_html("plain"), "plain");
+    }
+
+    #[test]
+    fn test_password_form() {
+        let html = password_form("secret", false);
+        assert!(html.contains(r#"action="/secret""#));
+        assert!(!html.contains("Incorrect password"));
+
+        let html = password_form("secret", true);
+        assert!(html.contains("Incorrect password"));
+    }
+
+    #[test]
+    fn test_preview_page_escapes_destination() {
+        let html = preview_page("docs", "https://example.com/?a=1&b=<2>", None, None);
+        assert!(html.contains("https://example.com/?a=1&amp;b=&lt;2&gt;"));
+        assert!(html.contains(r#"href="/docs?go=1""#));
+        assert!(!html.contains("<h2>"));
+    }
+
+    #[test]
+    fn test_preview_page_shows_title_and_description() {
+        let html = preview_page(
+            "docs",
+            "https://example.com",
+            Some("Team <docs>"),
+            Some("Where we keep everything"),
+        );
+        assert!(html.contains("<h2>Team &lt;docs&gt;</h2>"));
+        assert!(html.contains("<p>Where we keep everything</p>"));
+    }
+}
@@ -0,0 +1,72 @@
+//! QR code rendering for short links
+//!
+//! Encodes a short URL as a QR code and renders it as PNG or SVG.
+
+use image::{DynamicImage, ImageFormat, Luma};
+use qrcode::{render::svg, QrCode};
+use std::io::Cursor;
+
+/// Default QR image size in pixels
+pub const DEFAULT_QR_SIZE: u32 = 256;
+
+/// Smallest QR image size that can be requested
+pub const MIN_QR_SIZE: u32 = 64;
+
+/// Largest QR image size that can be requested
+pub const MAX_QR_SIZE: u32 = 1024;
+
+/// Clamps a requested image size to `MIN_QR_SIZE..=MAX_QR_SIZE`
+///
+/// Falls back to `DEFAULT_QR_SIZE` when no size was requested.
+pub fn clamp_size(size: Option<u32>) -> u32 {
+    size.unwrap_or(DEFAULT_QR_SIZE)
+        .clamp(MIN_QR_SIZE, MAX_QR_SIZE)
+}
+
+/// Renders `data` as a PNG-encoded QR code at least `size` pixels wide
+pub fn render_png(data: &str, size: u32) -> anyhow::Result<Vec<u8>> {
+    let code = QrCode::new(data.as_bytes())?;
+    let image = code.render::<Luma<u8>>().min_dimensions(size, size).build();
+
+    let mut png = Vec::new();
+    DynamicImage::ImageLuma8(image).write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+
+    Ok(png)
+}
+
+/// Renders `data` as an SVG QR code at least `size` pixels wide
+pub fn render_svg(data: &str, size: u32) -> anyhow::Result<String> {
+    let code = QrCode::new(data.as_bytes())?;
+
+    Ok(code
+        .render::<svg::Color>()
+        .min_dimensions(size, size)
+        .build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+    #[test]
+    fn test_clamp_size() {
+        assert_eq!(clamp_size(None), DEFAULT_QR_SIZE);
+        assert_eq!(clamp_size(Some(300)), 300);
+        assert_eq!(clamp_size(Some(1)), MIN_QR_SIZE);
+        assert_eq!(clamp_size(Some(100_000)), MAX_QR_SIZE);
+    }
+
+    #[test]
+    fn test_render_png() {
+        let png = render_png("https://cutl.my.id/abc123", DEFAULT_QR_SIZE).unwrap();
+        assert!(png.starts_with(PNG_SIGNATURE));
+    }
+
+    #[test]
+    fn test_render_svg() {
+        let svg = render_svg("https://cutl.my.id/abc123", DEFAULT_QR_SIZE).unwrap();
+        assert!(svg.contains("<svg"));
+    }
+}
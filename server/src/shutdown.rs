@@ -0,0 +1,99 @@
+//! Graceful shutdown on SIGINT/SIGTERM
+//!
+//! The server stops accepting connections when a signal arrives, and
+//! background tasks watch a shared channel to know when to stop.
+
+use std::future::Future;
+use tokio::sync::watch;
+use tracing::info;
+
+/// Resolves when the process receives Ctrl+C (SIGINT) or, on Unix, SIGTERM
+pub async fn os_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("Failed to listen for Ctrl+C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+/// Waits for `signal`, then tells every `watch` subscriber to shut down
+///
+/// Pass the result to `axum::serve(...).with_graceful_shutdown` so the server
+/// stops accepting connections at the same moment.
+pub async fn notify_on(signal: impl Future<Output = ()>, shutdown: watch::Sender<bool>) {
+    signal.await;
+    info!("Shutdown signal received, draining connections");
+    shutdown.send_replace(true);
+}
+
+/// Resolves once `notify_on` has fired (or the sender was dropped)
+pub async fn requested(shutdown: &mut watch::Receiver<bool>) {
+    // An error means the sender is gone, which also means shutting down
+    let _ = shutdown.wait_for(|&stop| stop).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::sync::oneshot;
+
+    #[tokio::test]
+    async fn test_notify_on_resolves_on_trigger() {
+        let (trigger_tx, trigger_rx) = oneshot::channel::<()>();
+        let (shutdown_tx, mut shutdown_rx) = watch::channel(false);
+
+        let notify = tokio::spawn(notify_on(
+            async {
+                trigger_rx.await.ok();
+            },
+            shutdown_tx,
+        ));
+
+        // Nothing happens until the trigger fires
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!*shutdown_rx.borrow());
+
+        trigger_tx.send(()).unwrap();
+        tokio::time::timeout(Duration::from_secs(1), notify)
+            .await
+            .expect("notify_on should resolve after the trigger")
+            .unwrap();
+
+        tokio::time::timeout(Duration::from_secs(1), requested(&mut shutdown_rx))
+            .await
+            .expect("subscribers should see the shutdown");
+    }
+
+    #[tokio::test]
+    async fn test_requested_resolves_when_sender_dropped() {
+        let (shutdown_tx, mut shutdown_rx) = watch::channel(false);
+        drop(shutdown_tx);
+
+        tokio::time::timeout(Duration::from_secs(1), requested(&mut shutdown_rx))
+            .await
+            .expect("a dropped sender counts as shutdown");
+    }
+}
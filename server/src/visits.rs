@@ -0,0 +1,284 @@
+//! Buffered visit writes for busy links
+//!
+//! With `VISIT_FLUSH_INTERVAL` set, redirects append their visit to an
+//! in-memory buffer instead of writing it straight away. A background task
+//! writes the buffer in one transaction per interval, and whatever is left at
+//! shutdown is written once the server has stopped taking requests. Analytics
+//! lag by up to one interval.
+//!
+//! The buffer holds at most `MAX_BUFFERED_VISITS`. A failed write puts its
+//! visits back for the next flush, and if the database stays unreachable long
+//! enough to fill the buffer, the oldest visits are dropped and counted.
+
+use crate::{database::insert_visits, models::NewVisit, shutdown};
+use anyhow::{Context, Result};
+use sqlx::{Pool, Sqlite};
+use std::{collections::VecDeque, sync::Arc, time::Duration};
+use tokio::sync::{watch, Mutex};
+use tracing::{debug, error, warn};
+
+/// Most visits held in memory before the oldest are dropped
+pub const MAX_BUFFERED_VISITS: usize = 100_000;
+
+/// Visits waiting to be written, shared by every request handler
+#[derive(Debug, Clone)]
+pub struct VisitBuffer {
+    pending: Arc<Mutex<Pending>>,
+    capacity: usize,
+}
+
+/// The queued visits, oldest first, and how many were dropped since the last flush
+#[derive(Debug, Default)]
+struct Pending {
+    visits: VecDeque<NewVisit>,
+    dropped: u64,
+}
+
+impl Pending {
+    /// Drops the oldest visits until at most `capacity` are left
+    fn truncate_oldest(&mut self, capacity: usize) {
+        let excess = self.visits.len().saturating_sub(capacity);
+        self.visits.drain(..excess);
+        self.dropped += excess as u64;
+    }
+}
+
+impl Default for VisitBuffer {
+    fn default() -> Self {
+        Self::new(MAX_BUFFERED_VISITS)
+    }
+}
+
+impl VisitBuffer {
+    /// Creates a buffer holding at most `capacity` visits
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            pending: Arc::default(),
+            capacity,
+        }
+    }
+
+    /// Queues a visit for the next flush, dropping the oldest if the buffer is full
+    pub async fn push(&self, visit: NewVisit) {
+        let mut pending = self.pending.lock().await;
+        pending.visits.push_back(visit);
+        pending.truncate_oldest(self.capacity);
+    }
+
+    /// Number of visits waiting to be written
+    #[cfg(test)]
+    pub async fn len(&self) -> usize {
+        self.pending.lock().await.visits.len()
+    }
+
+    /// Writes every queued visit in one transaction, returning how many were written
+    ///
+    /// A warning is logged for visits dropped because the buffer was full.
+    ///
+    /// # Errors
+    /// Returns an error if the write fails; the visits are put back in front of
+    /// any queued since, to be retried on the next flush.
+    pub async fn flush(&self, db: &Pool<Sqlite>) -> Result<usize> {
+        // Swap the buffer out so redirects aren't held up by the write
+        let (mut visits, dropped) = {
+            let mut pending = self.pending.lock().await;
+            let dropped = std::mem::take(&mut pending.dropped);
+            (std::mem::take(&mut pending.visits), dropped)
+        };
+        if dropped > 0 {
+            warn!(
+                "Dropped {} visits because the visit buffer was full",
+                dropped
+            );
+        }
+        if visits.is_empty() {
+            return Ok(0);
+        }
+
+        let count = visits.len();
+        if let Err(e) = insert_visits(db, visits.make_contiguous()).await {
+            self.requeue(visits).await;
+            return Err(e).with_context(|| format!("Failed to write {} buffered visits", count));
+        }
+
+        Ok(count)
+    }
+
+    /// Puts visits from a failed write back in front of those queued since
+    async fn requeue(&self, mut visits: VecDeque<NewVisit>) {
+        let mut pending = self.pending.lock().await;
+        visits.append(&mut pending.visits);
+        pending.visits = visits;
+        pending.truncate_oldest(self.capacity);
+    }
+}
+
+/// Background task that flushes `buffer` every `interval_seconds`
+///
+/// Stops once a shutdown is requested; the final flush is left to the caller
+/// so visits from requests still draining are not lost.
+pub async fn flush_task(
+    buffer: VisitBuffer,
+    db: Pool<Sqlite>,
+    interval_seconds: u64,
+    mut shutdown_rx: watch::Receiver<bool>,
+) {
+    let mut interval = tokio::time::interval(Duration::from_secs(interval_seconds));
+    // The first tick completes immediately
+    interval.tick().await;
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = shutdown::requested(&mut shutdown_rx) => break,
+        }
+
+        match buffer.flush(&db).await {
+            Ok(0) => {}
+            Ok(count) => debug!("Wrote {} buffered visits", count),
+            Err(e) => error!("{:#}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        database::{count_visits, insert_link, run_migrations},
+        models::NewLink,
+    };
+    use sqlx::SqlitePool;
+
+    fn visit(code: &str, visited_at: i64) -> NewVisit {
+        NewVisit {
+            code: code.to_string(),
+            visited_at,
+            ..Default::default()
+        }
+    }
+
+    /// A database holding the links `hot` and `cold`
+    async fn setup_pool() -> SqlitePool {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        run_migrations(&pool).await.unwrap();
+
+        for code in ["hot", "cold"] {
+            insert_link(
+                &pool,
+                &NewLink {
+                    code,
+                    original_url: "https://example.com",
+                    expires_at: 9999999999,
+                    created_at: 1000000000,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        }
+        pool
+    }
+
+    #[tokio::test]
+    async fn test_flush_writes_and_empties_buffer() {
+        let pool = setup_pool().await;
+        let buffer = VisitBuffer::default();
+
+        buffer.push(visit("hot", 1000)).await;
+        buffer.push(visit("hot", 1001)).await;
+        buffer.push(visit("cold", 1002)).await;
+        assert_eq!(buffer.len().await, 3);
+        assert_eq!(count_visits(&pool, "hot").await.unwrap(), 0);
+
+        assert_eq!(buffer.flush(&pool).await.unwrap(), 3);
+        assert_eq!(buffer.len().await, 0);
+        assert_eq!(count_visits(&pool, "hot").await.unwrap(), 2);
+        assert_eq!(count_visits(&pool, "cold").await.unwrap(), 1);
+
+        // Nothing left to write
+        assert_eq!(buffer.flush(&pool).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_flush_skips_visits_to_deleted_links() {
+        let pool = setup_pool().await;
+        let buffer = VisitBuffer::default();
+
+        buffer.push(visit("gone", 1000)).await;
+        buffer.push(visit("hot", 1001)).await;
+        buffer.flush(&pool).await.unwrap();
+
+        assert_eq!(count_visits(&pool, "hot").await.unwrap(), 1);
+        assert_eq!(count_visits(&pool, "gone").await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_full_buffer_drops_oldest_visits() {
+        let pool = setup_pool().await;
+        let buffer = VisitBuffer::new(2);
+
+        for visited_at in [1000, 1001, 1002] {
+            buffer.push(visit("hot", visited_at)).await;
+        }
+        assert_eq!(buffer.len().await, 2);
+        assert_eq!(buffer.pending.lock().await.dropped, 1);
+
+        assert_eq!(buffer.flush(&pool).await.unwrap(), 2);
+        assert_eq!(buffer.pending.lock().await.dropped, 0);
+        let visits = crate::database::recent_visits(&pool, "hot", 10, 0)
+            .await
+            .unwrap();
+        let times: Vec<i64> = visits.iter().map(|v| v.visited_at).collect();
+        assert_eq!(times, vec![1002, 1001]);
+    }
+
+    #[tokio::test]
+    async fn test_failed_flush_keeps_visits() {
+        let pool = setup_pool().await;
+        let buffer = VisitBuffer::new(3);
+        buffer.push(visit("hot", 1000)).await;
+        buffer.push(visit("hot", 1001)).await;
+
+        let closed = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        closed.close().await;
+        assert!(buffer.flush(&closed).await.is_err());
+        assert_eq!(buffer.len().await, 2);
+
+        // Requeued visits count against the cap, oldest dropped first
+        buffer.push(visit("hot", 1002)).await;
+        buffer.push(visit("hot", 1003)).await;
+        assert_eq!(buffer.len().await, 3);
+
+        assert_eq!(buffer.flush(&pool).await.unwrap(), 3);
+        let visits = crate::database::recent_visits(&pool, "hot", 10, 0)
+            .await
+            .unwrap();
+        let times: Vec<i64> = visits.iter().map(|v| v.visited_at).collect();
+        assert_eq!(times, vec![1003, 1002, 1001]);
+    }
+
+    #[tokio::test]
+    async fn test_flush_task_eventually_persists_visits() {
+        let pool = setup_pool().await;
+        let buffer = VisitBuffer::default();
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+
+        let task = tokio::spawn(flush_task(buffer.clone(), pool.clone(), 1, shutdown_rx));
+        buffer.push(visit("hot", 1000)).await;
+        buffer.push(visit("hot", 1001)).await;
+
+        let mut counted = 0;
+        for _ in 0..50 {
+            counted = count_visits(&pool, "hot").await.unwrap();
+            if counted == 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert_eq!(counted, 2);
+
+        shutdown_tx.send(true).unwrap();
+        task.await.unwrap();
+    }
+}
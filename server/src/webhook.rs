@@ -0,0 +1,256 @@
+//! Best-effort webhook notifications for link events
+//!
+//! When `WEBHOOK_URL` is set, every link creation and expiry is POSTed there as
+//! JSON. Deliveries run on their own task with a short timeout, so a slow or
+//! unreachable receiver never holds up a response or a cleanup sweep, and
+//! failures are only logged. At most `WEBHOOK_MAX_IN_FLIGHT` deliveries run at
+//! once; events beyond that are dropped with a warning rather than piling up
+//! tasks while the receiver is down.
+
+use crate::models::Link;
+use anyhow::Result;
+use serde::Serialize;
+use std::{sync::Arc, time::Duration};
+use tokio::sync::Semaphore;
+use tracing::warn;
+
+/// Time allowed for a webhook delivery, connection included
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Most deliveries allowed to run at the same time
+pub const WEBHOOK_MAX_IN_FLIGHT: usize = 32;
+
+/// What happened to a link
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookEvent {
+    Created,
+    Expired,
+}
+
+/// JSON body of a webhook delivery
+///
+/// ```json
+/// {
+///   "event": "created",
+///   "code": "abc123",
+///   "url": "https://example.com",
+///   "created_at": 1739000000,
+///   "expires_at": 1760000000,
+///   "timestamp": 1739000000
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct WebhookPayload {
+    pub event: WebhookEvent,
+    pub code: String,
+    pub url: String,
+    pub created_at: i64,
+    pub expires_at: i64,
+    /// When the event happened (UNIX seconds)
+    pub timestamp: i64,
+}
+
+impl WebhookPayload {
+    /// Builds the `created` event for a link just inserted
+    pub fn created(code: &str, url: &str, created_at: i64, expires_at: i64) -> Self {
+        Self {
+            event: WebhookEvent::Created,
+            code: code.to_string(),
+            url: url.to_string(),
+            created_at,
+            expires_at,
+            timestamp: created_at,
+        }
+    }
+
+    /// Builds the `expired` event for a link deleted at `now`
+    pub fn expired(link: &Link, now: i64) -> Self {
+        Self {
+            event: WebhookEvent::Expired,
+            code: link.code.clone(),
+            url: link.original_url.clone(),
+            created_at: link.created_at,
+            expires_at: link.expires_at,
+            timestamp: now,
+        }
+    }
+}
+
+/// Sends link events to `WEBHOOK_URL`
+#[derive(Debug, Clone)]
+pub struct Webhook {
+    client: reqwest::Client,
+    url: String,
+    /// One permit per delivery in flight, shared by every clone
+    in_flight: Arc<Semaphore>,
+}
+
+impl Webhook {
+    /// Creates a webhook posting to `url`
+    pub fn new(url: impl Into<String>) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(WEBHOOK_TIMEOUT)
+            .build()?;
+
+        Ok(Self {
+            client,
+            url: url.into(),
+            in_flight: Arc::new(Semaphore::new(WEBHOOK_MAX_IN_FLIGHT)),
+        })
+    }
+
+    /// Delivers `payload` in the background, logging a warning if it fails
+    ///
+    /// The event is dropped, with a warning, if `WEBHOOK_MAX_IN_FLIGHT`
+    /// deliveries are already running.
+    pub fn send(&self, payload: WebhookPayload) {
+        let Ok(permit) = Arc::clone(&self.in_flight).try_acquire_owned() else {
+            warn!(
+                "Webhook deliveries backed up; dropped {:?} event on {}",
+                payload.event, payload.code
+            );
+            return;
+        };
+        let webhook = self.clone();
+
+        tokio::spawn(async move {
+            if let Err(e) = webhook.deliver(&payload).await {
+                warn!(
+                    "Webhook delivery failed for {:?} event on {}: {}",
+                    payload.event, payload.code, e
+                );
+            }
+            drop(permit);
+        });
+    }
+
+    /// Posts `payload` and waits for a successful status
+    async fn deliver(&self, payload: &WebhookPayload) -> Result<()> {
+        self.client
+            .post(&self.url)
+            .json(payload)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::{
+        matchers::{method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    fn payload() -> WebhookPayload {
+        WebhookPayload {
+            event: WebhookEvent::Created,
+            code: "abc123".to_string(),
+            url: "https://example.com".to_string(),
+            created_at: 1739000000,
+            expires_at: 1760000000,
+            timestamp: 1739000000,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_deliver_posts_json_payload() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/hook"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let webhook = Webhook::new(format!("{}/hook", server.uri())).unwrap();
+        webhook.deliver(&payload()).await.unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        let json: serde_json::Value = requests[0].body_json().unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "event": "created",
+                "code": "abc123",
+                "url": "https://example.com",
+                "created_at": 1739000000,
+                "expires_at": 1760000000,
+                "timestamp": 1739000000,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send_drops_events_when_backed_up() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let webhook = Webhook::new(server.uri()).unwrap();
+        let busy = Arc::clone(&webhook.in_flight)
+            .try_acquire_many_owned(WEBHOOK_MAX_IN_FLIGHT as u32)
+            .unwrap();
+        webhook.send(payload());
+        assert_eq!(webhook.in_flight.available_permits(), 0);
+
+        // Once deliveries finish, events go out again
+        drop(busy);
+        webhook.send(payload());
+        for _ in 0..50 {
+            if webhook.in_flight.available_permits() == WEBHOOK_MAX_IN_FLIGHT {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_deliver_reports_error_status() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&server)
+            .await;
+
+        let webhook = Webhook::new(server.uri()).unwrap();
+        assert!(webhook.deliver(&payload()).await.is_err());
+    }
+
+    #[test]
+    fn test_expired_payload() {
+        let link = Link {
+            code: "old".to_string(),
+            original_url: "https://example.org".to_string(),
+            expires_at: 1700000000,
+            created_at: 1690000000,
+            password_hash: None,
+            max_visits: None,
+            remaining_visits: None,
+            owner: None,
+            append_query: None,
+            enabled: true,
+            rules: None,
+            idle_ttl: None,
+            max_expires_at: None,
+            redirect_status: None,
+            title: None,
+            description: None,
+        };
+
+        let payload = WebhookPayload::expired(&link, 1700000060);
+        assert_eq!(payload.event, WebhookEvent::Expired);
+        assert_eq!(payload.code, "old");
+        assert_eq!(payload.created_at, 1690000000);
+        assert_eq!(payload.expires_at, 1700000000);
+        assert_eq!(payload.timestamp, 1700000060);
+    }
+}